@@ -0,0 +1,79 @@
+//! Parses a manifest of 20,000 files and upgrades a folder that already matches it, which hashes every file and compares it to its [green_lib::File::sha].
+//! Also prints how many allocations each of them makes, digests used to be hex strings allocated for every file parsed and every file hashed.
+//! Run with `cargo bench --bench digests`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use green_lib::Directory;
+use sha2::{Digest, Sha256};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const FILES: usize = 20_000;
+
+/// The system allocator, counting allocations.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		// SAFETY: forwarded as is, the caller upholds alloc's contract
+		unsafe { System.alloc(layout) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		// SAFETY: forwarded as is, the caller upholds dealloc's contract
+		unsafe { System.dealloc(ptr, layout) }
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations<T>(run: impl FnOnce() -> T) -> usize {
+	let before = ALLOCATIONS.load(Ordering::Relaxed);
+	drop(run());
+	ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// Small files spread over 100 folders, written to `root`, and the manifest that describes them.
+fn pack(root: &Path) -> Vec<u8> {
+	let mut folders = vec![];
+	for folder in 0..100 {
+		let mut files = vec![];
+		for index in (folder..FILES).step_by(100) {
+			let contents = format!("file {index}\n").repeat(1 + index % 20);
+			std::fs::create_dir_all(root.join(folder.to_string())).unwrap();
+			std::fs::write(root.join(folder.to_string()).join(format!("{index}.txt")), &contents).unwrap();
+			let sha: String = Sha256::digest(&contents).iter().map(|byte| format!("{byte:02x}")).collect();
+			files.push(format!(r#""{index}.txt":{{"sha":"{sha}","url":"http://127.0.0.1:9/{folder}/{index}.txt"}}"#));
+		}
+		folders.push(format!(r#""{folder}":{{"files":{{{}}},"children":{{}}}}"#, files.join(",")));
+	}
+	format!(r#"{{"files":{{}},"children":{{{}}}}}"#, folders.join(",")).into_bytes()
+}
+
+fn digests(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let root = std::env::temp_dir().join(format!("green-lib-digests-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&root);
+	let manifest = pack(&root);
+	let directory = Directory::from_slice(&manifest).unwrap();
+	let upgrade = || runtime.block_on(directory.upgrade_game_folder(&root, None)).unwrap();
+	upgrade();
+
+	println!("parse: {} allocations for {FILES} files", allocations(|| Directory::from_slice(&manifest).unwrap()));
+	println!("up to date upgrade: {} allocations for {FILES} files", allocations(upgrade));
+
+	let mut group = c.benchmark_group("digests");
+	group.sample_size(10);
+	group.bench_function("parse", |b| b.iter(|| Directory::from_slice(&manifest).unwrap()));
+	group.bench_function("up to date upgrade", |b| b.iter(upgrade));
+	group.finish();
+	let _ = std::fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, digests);
+criterion_main!(benches);
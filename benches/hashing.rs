@@ -0,0 +1,42 @@
+//! Verifies a folder of 50,000 small files against its manifest, which hashes every one of them, once a file at a time and once with a hashing task per CPU.
+//! Run it with and without the `rayon` feature to compare hashing on the blocking pool to hashing on rayon's:
+//! `cargo bench --features test-util --bench hashing` and `cargo bench --features test-util,rayon --bench hashing`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::UpgradeOptions;
+
+const FILES: usize = 50_000;
+
+fn hashing(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let root = std::env::temp_dir().join(format!("green-lib-hashing-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&root);
+
+	// inlined so the folder can be filled without a server, verifying hashes inline files like any other
+	let directory = (0..FILES).fold(DirectoryBuilder::new(), |builder, index| {
+		let contents: Vec<u8> = format!("file {index}\n").bytes().cycle().take(256 + index * 13 % 3840).collect();
+		builder.inline_file(&format!("{}/{index}.txt", index % 250), contents)
+	}).build("http://127.0.0.1:9/");
+	std::fs::create_dir_all(&root).unwrap();
+	runtime.block_on(directory.upgrade_game_folder(&root, None)).unwrap();
+
+	let verify = |hash_threads| {
+		let options = UpgradeOptions {
+			hash_threads,
+			..Default::default()
+		};
+		let report = runtime.block_on(directory.verify_folder(&root, &options, None));
+		assert_eq!(report.matched.len(), FILES);
+	};
+
+	let mut group = c.benchmark_group(if cfg!(feature = "rayon") { "hashing/rayon" } else { "hashing/spawn_blocking" });
+	group.sample_size(10);
+	group.bench_function("sequential", |b| b.iter(|| verify(Some(1))));
+	group.bench_function("parallel", |b| b.iter(|| verify(None)));
+	group.finish();
+	let _ = std::fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, hashing);
+criterion_main!(benches);
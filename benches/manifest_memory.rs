@@ -0,0 +1,92 @@
+//! Fetches a manifest of 200,000 files (about 32 MB of JSON) from a local [FixtureServer] and prints the most memory it took at once.
+//! Once the way [Directory::try_from_url] does it, parsing the body while it streams in, and once the way it used to, buffering the whole text and parsing that.
+//! Run with `cargo bench --features test-util --bench manifest_memory`.
+
+use green_lib::test_util::FixtureServer;
+use green_lib::Directory;
+use sha2::{Digest, Sha256};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const FILES: usize = 200_000;
+
+/// The system allocator, keeping track of how much is allocated and the most that ever was.
+struct Peak;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Peak {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+		PEAK.fetch_max(current, Ordering::Relaxed);
+		// SAFETY: forwarded as is, the caller upholds alloc's contract
+		unsafe { System.alloc(layout) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+		// SAFETY: forwarded as is, the caller upholds dealloc's contract
+		unsafe { System.dealloc(ptr, layout) }
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: Peak = Peak;
+
+/// The most memory allocated at once while `run` ran and what it still held at the end, above what was allocated before it.
+fn measure<T>(run: impl FnOnce() -> T) -> (T, usize, usize) {
+	let before = CURRENT.load(Ordering::Relaxed);
+	PEAK.store(before, Ordering::Relaxed);
+	let result = run();
+	(result, PEAK.load(Ordering::Relaxed) - before, CURRENT.load(Ordering::Relaxed).saturating_sub(before))
+}
+
+/// Files spread over 200 folders, each with a sha and a url like a real pack's.
+fn manifest() -> Vec<u8> {
+	let mut folders = vec![];
+	for folder in 0..200 {
+		let mut files = vec![];
+		for index in (folder..FILES).step_by(200) {
+			let sha: String = Sha256::digest(index.to_le_bytes()).iter().map(|byte| format!("{byte:02x}")).collect();
+			files.push(format!(r#""file-{index}.jar":{{"sha":"{sha}","url":"https://cdn.example.com/packs/big/mods/{folder}/file-{index}.jar"}}"#));
+		}
+		folders.push(format!(r#""{folder}":{{"files":{{{}}},"children":{{}}}}"#, files.join(",")));
+	}
+	format!(r#"{{"files":{{}},"children":{{{}}}}}"#, folders.join(",")).into_bytes()
+}
+
+/// What fetching a manifest did before it was streamed: every chunk of the body appended to one buffer, which is parsed once it's complete.
+async fn buffered(url: &str) -> Directory {
+	let mut response = reqwest::get(url).await.unwrap();
+	let mut body = vec![];
+	while let Some(chunk) = response.chunk().await.unwrap() {
+		body.extend_from_slice(&chunk);
+	}
+	Directory::from_slice(&body).unwrap()
+}
+
+fn mb(bytes: usize) -> String {
+	format!("{:.1} MB", bytes as f64 / 1e6)
+}
+
+fn main() {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let server = runtime.block_on(FixtureServer::start()).unwrap();
+	let manifest = manifest();
+	let length = manifest.len();
+	server.insert("manifest.json", manifest);
+	let url = server.url("manifest.json");
+
+	let (tree, _, retained) = measure(|| runtime.block_on(Directory::try_from_url(&url)).unwrap());
+	println!("{FILES} files, {} of JSON, the parsed tree takes {}", mb(length), mb(retained));
+	drop(tree);
+
+	for _ in 0..3 {
+		let (streamed, streamed_peak, _) = measure(|| runtime.block_on(Directory::try_from_url(&url)).unwrap());
+		drop(streamed);
+		let (buffered, buffered_peak, _) = measure(|| runtime.block_on(buffered(&url)));
+		drop(buffered);
+		println!("peak while fetching: buffered {}, streamed {}", mb(buffered_peak), mb(streamed_peak));
+	}
+}
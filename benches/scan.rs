@@ -0,0 +1,63 @@
+//! Upgrades a folder to a pack of 2,000 files served by a local [FixtureServer]: into an empty folder, into one that already matches it, and into one that has every other file.
+//! The last one is where hashing the local files overlaps with downloading the missing ones.
+//! Also verifies a wide pack of 400 sibling directories with a handful of small files each, where reading the directories one by one adds up.
+//! Run with `cargo bench --features test-util --bench scan`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use std::path::Path;
+
+const FILES: usize = 2_000;
+
+/// Files of a few dozen KiB spread over 20 folders, like a pack of mods and their configs.
+fn pack() -> DirectoryBuilder {
+	(0..FILES).fold(DirectoryBuilder::new(), |builder, index| {
+		let contents: Vec<u8> = format!("file {index}\n").bytes().cycle().take(8192 + index * 37 % 57344).collect();
+		builder.file(&format!("mods/{}/{index}.jar", index % 20), contents)
+	})
+}
+
+const SIBLINGS: usize = 400;
+
+/// A few small files in each of many directories next to each other, like a pack of many mods with a config folder each.
+fn wide_pack() -> DirectoryBuilder {
+	(0..SIBLINGS * 5).fold(DirectoryBuilder::new(), |builder, index| builder.file(&format!("{}/{index}.toml", index % SIBLINGS), format!("config {index}\n").repeat(8)))
+}
+
+/// Removes every other file of the pack from `root`.
+fn remove_half(root: &Path) {
+	for index in (0..FILES).step_by(2) {
+		let _ = std::fs::remove_file(root.join(format!("mods/{}/{index}.jar", index % 20)));
+	}
+}
+
+fn scan(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let server = runtime.block_on(FixtureServer::start()).unwrap();
+	let directory = server.serve("pack", &pack());
+	let root = std::env::temp_dir().join(format!("green-lib-scan-{}", std::process::id()));
+	let upgrade = || runtime.block_on(directory.upgrade_game_folder(&root, None)).unwrap();
+
+	let mut group = c.benchmark_group("scan");
+	group.sample_size(10);
+	group.bench_function("cold install", |b| b.iter_batched(|| {
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(&root).unwrap();
+	}, |_| upgrade(), BatchSize::PerIteration));
+	group.bench_function("warm verify", |b| b.iter(upgrade));
+	group.bench_function("half installed", |b| b.iter_batched(|| remove_half(&root), |_| upgrade(), BatchSize::PerIteration));
+
+	let wide = server.serve("wide", &wide_pack());
+	let wide_root = root.with_extension("wide");
+	let _ = std::fs::remove_dir_all(&wide_root);
+	std::fs::create_dir_all(&wide_root).unwrap();
+	let upgrade_wide = || runtime.block_on(wide.upgrade_game_folder(&wide_root, None)).unwrap();
+	upgrade_wide();
+	group.bench_function("wide warm verify", |b| b.iter(upgrade_wide));
+	group.finish();
+	let _ = std::fs::remove_dir_all(&root);
+	let _ = std::fs::remove_dir_all(&wide_root);
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
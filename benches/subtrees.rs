@@ -0,0 +1,60 @@
+//! Upgrades and verifies a folder that already matches a pack of 10,000 files in 40 directories, with [UpgradeOptions::hash_cache] warmed up by a first upgrade.
+//! Once with a manifest whose children have a [Directory::subtree_hash], so the 39 that were applied before aren't scanned again, and once with the same manifest without them, which hashes nothing either but still reads every directory and looks at every file.
+//! The paranoid runs scan and hash everything for comparison.
+//! Run with `cargo bench --features test-util --bench subtrees`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{Directory, UpgradeOptions};
+
+const CHILDREN: usize = 40;
+const FILES: usize = 10_000;
+
+/// Small files spread over directories like resourcepacks and libraries, that hardly ever change.
+fn pack() -> DirectoryBuilder {
+	(0..FILES).fold(DirectoryBuilder::new(), |builder, index| {
+		let contents: Vec<u8> = format!("file {index}\n").bytes().cycle().take(256 + index * 37 % 2048).collect();
+		builder.file(&format!("{}/{index}.dat", index % CHILDREN), contents)
+	})
+}
+
+fn subtrees(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let server = runtime.block_on(FixtureServer::start()).unwrap();
+	let without = server.serve("pack", &pack());
+	let mut with = without.clone();
+	with.set_subtree_hashes();
+	// one directory was edited by hand since, it's always scanned
+	with.children.get_mut("0").unwrap().subtree_hash = None;
+
+	let root = std::env::temp_dir().join(format!("green-lib-subtrees-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&root);
+	std::fs::create_dir_all(&root).unwrap();
+	let options = UpgradeOptions {
+		hash_cache: true,
+		..Default::default()
+	};
+	let paranoid = UpgradeOptions {
+		paranoid: true,
+		..options.clone()
+	};
+	let upgrade = |directory: &Directory, options: &UpgradeOptions| runtime.block_on(directory.upgrade_game_folder_with_options(&root, options, None)).unwrap();
+	let verify = |directory: &Directory, options: &UpgradeOptions| assert!(runtime.block_on(directory.verify_folder(&root, options, None)).is_ok());
+	upgrade(&with, &options);
+
+	let mut group = c.benchmark_group("subtrees");
+	group.sample_size(10);
+	group.bench_function("warm upgrade with subtree hashes", |b| b.iter(|| upgrade(&with, &options)));
+	group.bench_function("warm upgrade without subtree hashes", |b| b.iter(|| upgrade(&without, &options)));
+	group.bench_function("paranoid upgrade", |b| b.iter(|| upgrade(&with, &paranoid)));
+	// the upgrade before each of these left the subtrees it applied behind
+	upgrade(&with, &options);
+	group.bench_function("warm verify with subtree hashes", |b| b.iter(|| verify(&with, &options)));
+	group.bench_function("warm verify without subtree hashes", |b| b.iter(|| verify(&without, &options)));
+	group.bench_function("paranoid verify", |b| b.iter(|| verify(&with, &paranoid)));
+	group.finish();
+	let _ = std::fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, subtrees);
+criterion_main!(benches);
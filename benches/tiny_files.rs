@@ -0,0 +1,50 @@
+//! Installs a manifest of 10,000 tiny files into a fresh folder a few times and prints how long it took.
+//! Once with every file served by a local [FixtureServer] and once with all of them inlined, which leaves out the requests and mostly measures writing them.
+//! Run with `cargo bench --features test-util --bench tiny_files`, with `TMPDIR=/dev/shm` or another tmpfs to leave the disk out of it, whose journal makes the numbers far noisier.
+
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{Directory, UpgradeOptions};
+use std::path::Path;
+use std::time::Instant;
+
+const FILES: usize = 10_000;
+const RUNS: usize = 7;
+
+/// Sub-kilobyte files spread over a few folders, like a datapack or a folder of scripts.
+fn tiny_files(inline: bool) -> DirectoryBuilder {
+	(0..FILES).fold(DirectoryBuilder::new(), |builder, index| {
+		let path = format!("scripts/{}/{index}.js", index % 100);
+		let contents: Vec<u8> = format!("// script {index}\n").bytes().cycle().take(64 + index * 7 % 900).collect();
+		match inline {
+			true => builder.inline_file(&path, contents),
+			false => builder.file(&path, contents)
+		}
+	})
+}
+
+async fn bench(name: &str, directory: &Directory, root: &Path) {
+	let mut times = vec![];
+	for _ in 0..RUNS {
+		let _ = std::fs::remove_dir_all(root);
+		std::fs::create_dir_all(root).unwrap();
+		let started = Instant::now();
+		directory.upgrade_game_folder_with_options(root, &UpgradeOptions::default(), None).await.unwrap();
+		times.push(started.elapsed());
+	}
+	let _ = std::fs::remove_dir_all(root);
+
+	times.sort();
+	let median = times[RUNS / 2];
+	println!("{name}: {FILES} tiny files in {median:?} (median of {RUNS}, fastest {:?}), {:.0} files/s", times[0], FILES as f64 / median.as_secs_f64());
+}
+
+fn main() {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	runtime.block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let root = std::env::temp_dir().join(format!("green-lib-tiny-files-{}", std::process::id()));
+
+		bench("served", &server.serve("pack", &tiny_files(false)), &root).await;
+		bench("inline", &tiny_files(true).build(&server.url("")), &root).await;
+	});
+}
@@ -0,0 +1,146 @@
+use base64::Engine;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::hash::Sha256Hash;
+use crate::{Directory, ExportError, Progress, UpgradeStatus};
+
+/// Whether fewer than half of the files in `directory` exist under `path`, which is when a bootstrap archive is worth it.
+pub(crate) async fn mostly_missing(directory: &Directory, path: &Path) -> bool {
+	let mut present = 0;
+	let mut total = 0;
+	let mut stack = vec![(directory, path.to_owned())];
+
+	while let Some((directory, path)) = stack.pop() {
+		for name in directory.files.keys() {
+			total += 1;
+			if tokio::fs::symlink_metadata(path.join(name)).await.is_ok() {
+				present += 1;
+			}
+		}
+
+		for (name, child) in &directory.children {
+			stack.push((child, path.join(name)));
+		}
+	}
+
+	present * 2 < total
+}
+
+/// Extracts an archive into `root`, blocking.
+/// Entries that would end up outside of `root`, symlinks, and entries that would be written through an existing symlink are skipped.
+pub(crate) fn extract<R: std::io::Read + std::io::Seek>(reader: R, root: &Path, progress: &Progress) -> zip::result::ZipResult<()> {
+	let mut archive = zip::ZipArchive::new(reader)?;
+	let runtime = tokio::runtime::Handle::current();
+
+	for index in 0..archive.len() {
+		let mut entry = archive.by_index(index)?;
+		let Some(relative) = entry.enclosed_name() else {
+			continue;
+		};
+
+		if entry.is_symlink() || through_symlink(root, &relative) {
+			continue;
+		}
+
+		let local_path = root.join(&relative);
+		if entry.is_dir() {
+			std::fs::create_dir_all(&local_path)?;
+			continue;
+		}
+
+		if let Some(parent) = local_path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+		// removed first so a file that's hardlinked somewhere else is replaced rather than changed
+		match std::fs::remove_file(&local_path) {
+			Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+			_ => {}
+		}
+		std::io::copy(&mut entry, &mut std::fs::File::create(&local_path)?)?;
+		runtime.block_on(progress.emit(UpgradeStatus::Extracted(relative)));
+	}
+
+	Ok(())
+}
+
+fn through_symlink(root: &Path, relative: &Path) -> bool {
+	let mut path = PathBuf::from(root);
+	relative.components().any(|component| {
+		path.push(component);
+		std::fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+	})
+}
+
+/// Writes every file of `directory` from `source` into a zip at `zip_path`, checking each one against the manifest, blocking.
+/// The archive is written to a temporary file first so a failed export never leaves a partial zip behind.
+pub(crate) fn export(directory: &Directory, source: &Path, zip_path: &Path, manifest_name: Option<&str>) -> Result<(), ExportError> {
+	let temp_path = crate::manifest::temp_path(zip_path).map_err(ExportError::Io)?;
+	let result = (|| {
+		let mut zip = zip::ZipWriter::new(std::fs::File::create(&temp_path).map_err(ExportError::Io)?);
+		let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+		if let Some(manifest_name) = manifest_name {
+			zip.start_file(manifest_name, options).map_err(ExportError::Zip)?;
+			crate::manifest::to_writer(directory, &mut zip, true).map_err(|error| ExportError::Io(error.into()))?;
+		}
+
+		add(&mut zip, options, directory, source, Path::new(""))?;
+		zip.finish().map_err(ExportError::Zip)?;
+		std::fs::rename(&temp_path, zip_path).map_err(ExportError::Io)
+	})();
+
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temp_path);
+	}
+	result
+}
+
+fn add(zip: &mut zip::ZipWriter<std::fs::File>, options: zip::write::SimpleFileOptions, directory: &Directory, source: &Path, relative: &Path) -> Result<(), ExportError> {
+	let name = |relative: &Path| crate::hash_cache::key(relative);
+
+	let mut files: Vec<_> = directory.files.iter().collect();
+	files.sort_by_key(|(name, _)| *name);
+
+	for (file_name, file) in files {
+		let relative = relative.join(file_name);
+		let contents = match &file.content_b64 {
+			Some(content) => base64::engine::general_purpose::STANDARD.decode(content).map_err(|_| ExportError::InvalidContent {
+				path: relative.clone()
+			})?,
+			None => std::fs::read(source.join(&relative)).map_err(|error| match error.kind() {
+				std::io::ErrorKind::NotFound => ExportError::Missing {
+					path: relative.clone()
+				},
+				_ => ExportError::Io(error)
+			})?
+		};
+
+		let found = Sha256Hash::digest(&contents);
+		if found != file.sha {
+			return Err(ExportError::ShaMismatch {
+				path: relative,
+				expected: file.sha,
+				found
+			});
+		}
+
+		zip.start_file(name(&relative), options).map_err(ExportError::Zip)?;
+		zip.write_all(&contents).map_err(ExportError::Io)?;
+	}
+
+	let mut links: Vec<_> = directory.links.iter().collect();
+	links.sort_by_key(|(name, _)| *name);
+	for (link_name, target) in links {
+		zip.add_symlink(name(&relative.join(link_name)), target, options).map_err(ExportError::Zip)?;
+	}
+
+	let mut children: Vec<_> = directory.children.iter().collect();
+	children.sort_by_key(|(name, _)| *name);
+	for (child_name, child) in children {
+		let relative = relative.join(child_name);
+		zip.add_directory(format!("{}/", name(&relative)), options).map_err(ExportError::Zip)?;
+		add(zip, options, child, source, &relative)?;
+	}
+
+	Ok(())
+}
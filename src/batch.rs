@@ -0,0 +1,117 @@
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+/// Files up to this size are written by [write], bigger ones are worth their own hops to the blocking threads.
+pub(crate) const TINY: usize = 16 << 10;
+/// The most blocking threads writing tiny files at once.
+const WRITERS: usize = 4;
+/// The most files a writer takes off the queue at once, so the others still get some when thousands are waiting.
+const BATCH: usize = 64;
+/// How long a writer waits for more files when fewer than a batch are queued, downloaded files come in one by one and every hop to a writer costs more than writing one.
+const GATHER: std::time::Duration = std::time::Duration::from_millis(2);
+
+/// A file to write to `temp_path` and rename to `local_path`.
+struct Job {
+	temp_path: PathBuf,
+	local_path: PathBuf,
+	contents: Bytes,
+	mtime: Option<i64>,
+	done: oneshot::Sender<std::io::Result<std::fs::Metadata>>
+}
+
+#[derive(Default)]
+struct Queue {
+	jobs: VecDeque<Job>,
+	writers: usize
+}
+
+/// Shared by every upgrade, so the writers are kept busy no matter how many files each of them has.
+static QUEUE: Lazy<Mutex<Queue>> = Lazy::new(Mutex::default);
+
+/// Writes `contents` to `temp_path`, sets its modification time and renames it to `local_path` like writing it with [tokio::fs] would, returning its metadata from before the rename.
+/// Instead of awaiting every step on its own a few blocking threads write the queued files in batches, creating, writing and renaming thousands of tiny files costs far fewer hops that way.
+/// The temp file is left behind on errors, for the caller to remove.
+pub(crate) async fn write(temp_path: PathBuf, local_path: PathBuf, contents: Bytes, mtime: Option<i64>) -> std::io::Result<std::fs::Metadata> {
+	let (done, written) = oneshot::channel();
+	let spawn = {
+		let mut queue = QUEUE.lock().unwrap();
+		queue.jobs.push_back(Job {
+			temp_path,
+			local_path,
+			contents,
+			mtime,
+			done
+		});
+		let spawn = queue.writers < WRITERS;
+		queue.writers += spawn as usize;
+		spawn
+	};
+	if spawn {
+		let writer = Writer { counted: true };
+		tokio::task::spawn_blocking(move || writer.drain());
+	}
+	written.await.unwrap_or_else(|_| Err(std::io::Error::other("the writer stopped before writing the file")))
+}
+
+/// One of the [Queue::writers], leaving when it's dropped even if its runtime shut down before it ran, so another one is spawned for what's left.
+struct Writer {
+	/// Still counted in [Queue::writers], it stops being once it saw the queue empty without letting go of the lock, so nothing is queued without a writer left to see it.
+	counted: bool
+}
+
+impl Writer {
+	/// Writes batches off the queue until it stays empty while waiting for more.
+	fn drain(mut self) {
+		let mut waited = false;
+		loop {
+			let jobs = {
+				let mut queue = QUEUE.lock().unwrap();
+				if queue.jobs.is_empty() && waited {
+					queue.writers -= 1;
+					self.counted = false;
+					return;
+				}
+				if queue.jobs.len() < BATCH && !waited {
+					drop(queue);
+					std::thread::sleep(GATHER);
+					waited = true;
+					continue;
+				}
+				waited = false;
+				let count = queue.jobs.len().min(BATCH);
+				queue.jobs.drain(..count).collect::<Vec<_>>()
+			};
+			// nothing waits for the files of an upgrade that was dropped, like nothing would write them without the queue
+			for job in jobs.into_iter().filter(|job| !job.done.is_closed()) {
+				let written = write_one(&job);
+				let _ = job.done.send(written);
+			}
+		}
+	}
+}
+
+impl Drop for Writer {
+	fn drop(&mut self) {
+		if self.counted {
+			QUEUE.lock().unwrap().writers -= 1;
+		}
+	}
+}
+
+fn write_one(job: &Job) -> std::io::Result<std::fs::Metadata> {
+	use std::io::Write;
+
+	let mut file = std::fs::File::create(&job.temp_path)?;
+	file.write_all(&job.contents)?;
+	if let Some(mtime) = job.mtime {
+		crate::mtime::set_file(&file, mtime)?;
+	}
+	let metadata = file.metadata()?;
+	drop(file);
+	std::fs::rename(&job.temp_path, &job.local_path)?;
+	Ok(metadata)
+}
@@ -0,0 +1,393 @@
+//! Syncs, verifies and generates manifests from the command line, built with the `cli` feature.
+//!
+//! ```text
+//! green-sync sync <manifest-url> <path> [--dry-run] [--concurrency <n>] [--protect <glob>]... [--no-delete] [--paranoid]
+//! green-sync verify <manifest-url> <path> [--paranoid]
+//! green-sync generate <folder> --base-url <url> [--out <file>] [--subtree-hashes] [--exclude <pattern>]... [--scan-cache <file> [--paranoid]]
+//! ```
+//!
+//! `--protect` globs are matched against `/` separated paths relative to `<path>`, `*` and `?` stay within a component and `**` matches any number of them.
+//! Files of the manifest they match are left out of the sync and local entries they match are never deleted.
+//! `--exclude` patterns are gitignore-style and come after the `.greenignore` of `<folder>`, what they left out is listed on stderr.
+//! With `--scan-cache` only files that changed since the last generate are hashed, the cache is kept in that file and chunks of unchanged files come from the `--out` manifest, `--paranoid` hashes everything again.
+//!
+//! Local files are remembered in the hash cache of `<path>`, so unchanged files and subtrees aren't read again, `--paranoid` hashes and scans everything.
+//!
+//! Progress goes to stderr when it's a terminal, what was done is printed to stdout when it's done.
+//! The exit code is [UP_TO_DATE] when nothing had to change, [CHANGED] when the folder was changed (or would be with `--dry-run`, or doesn't match for `verify`), [USAGE] for bad arguments and [FAILED] for anything else.
+
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use green_lib::{Deletions, Directory, DownloadFilter, DownloadLimiter, ExcludeSource, FromPathOptions, ProtectFilter, ScanCache, UpgradeObserver, UpgradeOptions, UpgradeStatus, VerificationReport};
+
+const UP_TO_DATE: u8 = 0;
+const FAILED: u8 = 1;
+const USAGE: u8 = 2;
+const CHANGED: u8 = 3;
+
+const HELP: &str = "usage:
+  green-sync sync <manifest-url> <path> [--dry-run] [--concurrency <n>] [--protect <glob>]... [--no-delete] [--paranoid]
+  green-sync verify <manifest-url> <path> [--paranoid]
+  green-sync generate <folder> --base-url <url> [--out <file>] [--subtree-hashes] [--exclude <pattern>]... [--scan-cache <file> [--paranoid]]
+
+exits with 0 when nothing changed, 3 when something changed (or would, or doesn't match), 2 for bad arguments and 1 when it failed";
+
+enum Command {
+	Sync {
+		url: String,
+		path: PathBuf,
+		dry_run: bool,
+		concurrency: Option<usize>,
+		protect: Vec<String>,
+		no_delete: bool,
+		paranoid: bool
+	},
+	Verify {
+		url: String,
+		path: PathBuf,
+		paranoid: bool
+	},
+	Generate {
+		folder: PathBuf,
+		base_url: String,
+		out: Option<PathBuf>,
+		subtree_hashes: bool,
+		exclude: Vec<String>,
+		scan_cache: Option<PathBuf>,
+		paranoid: bool
+	}
+}
+
+/// The value of a flag that takes one.
+fn value(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<String, String> {
+	args.next().ok_or_else(|| format!("{flag} needs a value"))
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+	let command = args.next().ok_or("missing the command")?;
+	let (mut positional, mut dry_run, mut concurrency, mut protect, mut no_delete, mut base_url, mut out, mut subtree_hashes, mut paranoid, mut exclude, mut scan_cache) = (vec![], false, None, vec![], false, None, None, false, false, vec![], None);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--dry-run" if command == "sync" => dry_run = true,
+			"--no-delete" if command == "sync" => no_delete = true,
+			"--paranoid" => paranoid = true,
+			"--protect" if command == "sync" => protect.push(value(&arg, &mut args)?),
+			"--concurrency" if command == "sync" => concurrency = Some(value(&arg, &mut args)?.parse::<usize>().ok().filter(|n| *n > 0).ok_or("--concurrency needs a positive number")?),
+			"--base-url" if command == "generate" => base_url = Some(value(&arg, &mut args)?),
+			"--out" if command == "generate" => out = Some(PathBuf::from(value(&arg, &mut args)?)),
+			"--subtree-hashes" if command == "generate" => subtree_hashes = true,
+			"--exclude" if command == "generate" => exclude.push(value(&arg, &mut args)?),
+			"--scan-cache" if command == "generate" => scan_cache = Some(PathBuf::from(value(&arg, &mut args)?)),
+			flag if flag.starts_with("--") => return Err(format!("unknown flag {flag} for {command}")),
+			_ => positional.push(arg)
+		}
+	}
+
+	let mut positional = positional.into_iter();
+	let (first, second, rest) = (positional.next(), positional.next(), positional.next());
+	if rest.is_some() {
+		return Err(format!("too many arguments for {command}"));
+	}
+	match (command.as_str(), first, second) {
+		("sync", Some(url), Some(path)) => Ok(Command::Sync {
+			url,
+			path: path.into(),
+			dry_run,
+			concurrency,
+			protect,
+			no_delete,
+			paranoid
+		}),
+		("verify", Some(url), Some(path)) => Ok(Command::Verify {
+			url,
+			path: path.into(),
+			paranoid
+		}),
+		("generate", Some(folder), None) => Ok(Command::Generate {
+			folder: folder.into(),
+			base_url: base_url.ok_or("generate needs --base-url")?,
+			out,
+			subtree_hashes,
+			exclude,
+			scan_cache,
+			paranoid
+		}),
+		("sync" | "verify" | "generate", _, _) => Err(format!("wrong arguments for {command}")),
+		_ => Err(format!("unknown command {command}"))
+	}
+}
+
+/// Whether `path` matches `pattern` component by component, `**` matches any number of components.
+fn glob_matches(pattern: &[&str], path: &[&str]) -> bool {
+	match pattern.split_first() {
+		None => path.is_empty(),
+		Some((&"**", rest)) => (0..=path.len()).any(|skipped| glob_matches(rest, &path[skipped..])),
+		Some((component, rest)) => path.split_first().is_some_and(|(name, path)| component_matches(component.as_bytes(), name.as_bytes()) && glob_matches(rest, path))
+	}
+}
+
+fn component_matches(pattern: &[u8], name: &[u8]) -> bool {
+	match pattern.split_first() {
+		None => name.is_empty(),
+		Some((b'*', rest)) => (0..=name.len()).any(|skipped| component_matches(rest, &name[skipped..])),
+		Some((b'?', rest)) => !name.is_empty() && component_matches(rest, &name[1..]),
+		Some((byte, rest)) => name.first() == Some(byte) && component_matches(rest, &name[1..])
+	}
+}
+
+fn protected(protect: &[String], relative: &Path) -> bool {
+	let components: Vec<_> = relative.iter().map(|component| component.to_string_lossy()).collect();
+	let components: Vec<&str> = components.iter().map(|component| component.as_ref()).collect();
+	protect.iter().any(|pattern| glob_matches(&pattern.split('/').filter(|component| !component.is_empty()).collect::<Vec<_>>(), &components))
+}
+
+#[derive(Default)]
+struct State {
+	total: usize,
+	done: usize,
+	line: bool
+}
+
+/// Draws a progress line on a terminal.
+struct Reporter {
+	tty: bool,
+	state: Mutex<State>
+}
+
+impl Reporter {
+	fn new() -> Arc<Self> {
+		Arc::new(Self {
+			tty: std::io::stderr().is_terminal(),
+			state: Mutex::new(State::default())
+		})
+	}
+
+	fn draw(&self, state: &mut State, path: Option<&Path>) {
+		if !self.tty {
+			return;
+		}
+		let path = path.map(|path| format!(" {}", path.display())).unwrap_or_default();
+		eprint!("\r\x1b[2K[{}/{}]{path}", state.done, state.total);
+		let _ = std::io::stderr().flush();
+		state.line = true;
+	}
+
+	/// Prints a line to stderr above the progress line.
+	fn message(&self, message: &str) {
+		let mut state = self.state.lock().unwrap();
+		match state.line {
+			true => eprint!("\r\x1b[2K{message}\n"),
+			false => eprintln!("{message}")
+		}
+		state.line = false;
+	}
+
+	fn clear(&self) {
+		let mut state = self.state.lock().unwrap();
+		if state.line {
+			eprint!("\r\x1b[2K");
+			state.line = false;
+		}
+	}
+}
+
+impl UpgradeObserver for Reporter {
+	fn status(&self, status: &UpgradeStatus) {
+		match status {
+			UpgradeStatus::Length(length) => {
+				let mut state = self.state.lock().unwrap();
+				state.total = *length;
+				self.draw(&mut state, None);
+			},
+			UpgradeStatus::Tick => {
+				let mut state = self.state.lock().unwrap();
+				state.done += 1;
+				self.draw(&mut state, None);
+			},
+			UpgradeStatus::Started(path) | UpgradeStatus::Deleted(path) => {
+				let mut state = self.state.lock().unwrap();
+				self.draw(&mut state, Some(path));
+			},
+			UpgradeStatus::Failed { path, reason, attempts, .. } => self.message(&format!("failed {} after {attempts} attempts: {reason}", path.display())),
+			UpgradeStatus::Warning { path, reason } => self.message(&format!("warning: {}: {reason}", path.display())),
+			_ => {}
+		}
+	}
+}
+
+async fn manifest(url: &str) -> Result<Directory, u8> {
+	Directory::try_from_url(url).await.map_err(|error| {
+		eprintln!("couldn't get the manifest: {error}");
+		FAILED
+	})
+}
+
+/// Prints what `report` says a sync would do, returning whether it would change anything.
+fn print_plan(report: &VerificationReport, deletions: Deletions) -> bool {
+	let mut changed = false;
+	let mut print = |action: &str, path: &Path| {
+		println!("{action} {}", path.display());
+		changed |= action != "keep";
+	};
+	report.missing.iter().for_each(|path| print("download", path));
+	report.mismatched.iter().for_each(|mismatch| print("replace", &mismatch.path));
+	report.wrong_links.iter().for_each(|path| print("link", path));
+	for path in &report.unknown {
+		print(match deletions {
+			Deletions::Enabled => "delete",
+			Deletions::Disabled => "keep"
+		}, path);
+	}
+	report.protected.iter().for_each(|path| print("keep", path));
+	for warning in &report.unreadable {
+		eprintln!("warning: {}: {}", warning.path.display(), warning.error);
+	}
+	changed
+}
+
+fn print_verification(report: &VerificationReport) {
+	report.missing.iter().for_each(|path| println!("missing {}", path.display()));
+	report.mismatched.iter().for_each(|mismatch| println!("mismatched {}", mismatch.path.display()));
+	report.wrong_links.iter().for_each(|path| println!("wrong link {}", path.display()));
+	report.unknown.iter().for_each(|path| println!("unknown {}", path.display()));
+	for warning in &report.unreadable {
+		eprintln!("warning: {}: {}", warning.path.display(), warning.error);
+	}
+}
+
+async fn run(command: Command) -> Result<u8, u8> {
+	match command {
+		Command::Sync { url, path, dry_run, concurrency, protect, no_delete, paranoid } => {
+			let directory = manifest(&url).await?;
+			let mut options = UpgradeOptions {
+				deletions: match no_delete {
+					true => Deletions::Disabled,
+					false => Deletions::Enabled
+				},
+				download_limiter: concurrency.map(DownloadLimiter::new),
+				hash_cache: true,
+				paranoid,
+				..Default::default()
+			};
+			if !protect.is_empty() {
+				let patterns = protect.clone();
+				options.filter = Some(DownloadFilter::new(move |relative, _| !protected(&patterns, relative)));
+				options.protect = Some(ProtectFilter::new(move |relative| protected(&protect, relative)));
+			}
+
+			let reporter = Reporter::new();
+			let observer: Arc<dyn UpgradeObserver> = reporter.clone();
+			// the upgrade always rewrites the top level, so whether anything changes is decided by verifying first
+			let report = directory.verify_folder(&path, &options, observer.clone()).await;
+			reporter.clear();
+			if !print_plan(&report, options.deletions) {
+				println!("{} is up to date", path.display());
+				return Ok(UP_TO_DATE);
+			}
+			if dry_run {
+				return Ok(CHANGED);
+			}
+
+			let result = directory.upgrade_game_folder_with_options(&path, &options, observer).await;
+			reporter.clear();
+			match result {
+				Ok(report) => {
+					for warning in &report.warnings {
+						eprintln!("warning: {}: {}", warning.path.display(), warning.error);
+					}
+					println!("synced {}", path.display());
+					Ok(CHANGED)
+				},
+				Err(error) => {
+					eprintln!("sync failed: {error}");
+					Err(FAILED)
+				}
+			}
+		},
+		Command::Verify { url, path, paranoid } => {
+			let options = UpgradeOptions {
+				hash_cache: true,
+				paranoid,
+				..Default::default()
+			};
+			let directory = manifest(&url).await?;
+			let reporter = Reporter::new();
+			let observer: Arc<dyn UpgradeObserver> = reporter.clone();
+			let report = directory.verify_folder(&path, &options, observer).await;
+			reporter.clear();
+			print_verification(&report);
+			Ok(match report.is_ok() {
+				true => UP_TO_DATE,
+				false => CHANGED
+			})
+		},
+		Command::Generate { folder, base_url, out, subtree_hashes, exclude, scan_cache, paranoid } => {
+			let options = FromPathOptions {
+				base_url,
+				subtree_hashes,
+				exclude,
+				..Default::default()
+			};
+			let generated = match &scan_cache {
+				Some(scan_cache) => {
+					let (previous, cache) = match (&out, paranoid) {
+						(Some(out), false) => (Directory::from_file(out).await.unwrap_or_default(), ScanCache::load(scan_cache).await),
+						_ => (Directory::default(), ScanCache::default())
+					};
+					Directory::from_path_incremental(&folder, &options, &previous, &cache).await.map(|(directory, report, cache)| (directory, report, Some(cache)))
+				},
+				None => Directory::from_path_with_report(&folder, &options).await.map(|(directory, report)| (directory, report, None))
+			};
+			let (directory, report, cache) = generated.map_err(|error| {
+				eprintln!("couldn't generate a manifest of {}: {error}", folder.display());
+				FAILED
+			})?;
+			for excluded in &report.excluded {
+				match excluded.source {
+					ExcludeSource::Options => eprintln!("excluded {} (--exclude {})", excluded.path.display(), excluded.pattern),
+					ExcludeSource::IgnoreFile { line } => eprintln!("excluded {} ({} line {line}: {})", excluded.path.display(), green_lib::IGNORE_FILE, excluded.pattern)
+				}
+			}
+			let written = match &out {
+				Some(out) => directory.to_file(out, true).await,
+				None => serde_json::to_string_pretty(&directory).map_err(std::io::Error::from).and_then(|json| writeln!(std::io::stdout(), "{json}"))
+			};
+			written.map_err(|error| {
+				eprintln!("couldn't write the manifest: {error}");
+				FAILED
+			})?;
+
+			if let (Some(cache), Some(scan_cache)) = (cache, &scan_cache) {
+				eprintln!("{} unchanged files weren't hashed again", report.reused);
+				cache.save(scan_cache).await.map_err(|error| {
+					eprintln!("couldn't write the scan cache: {error}");
+					FAILED
+				})?;
+			}
+			Ok(UP_TO_DATE)
+		}
+	}
+}
+
+fn main() -> ExitCode {
+	let args: Vec<_> = std::env::args().skip(1).collect();
+	if args.first().is_none_or(|arg| matches!(arg.as_str(), "-h" | "--help" | "help")) {
+		println!("{HELP}");
+		return ExitCode::from(match args.is_empty() {
+			true => USAGE,
+			false => UP_TO_DATE
+		});
+	}
+
+	let command = match parse(args.into_iter()) {
+		Ok(command) => command,
+		Err(error) => {
+			eprintln!("{error}\n\n{HELP}");
+			return ExitCode::from(USAGE);
+		}
+	};
+	let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("couldn't start the runtime");
+	ExitCode::from(runtime.block_on(run(command)).unwrap_or_else(|code| code))
+}
@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::hash::Sha256Hash;
+use crate::{CacheStats, CacheVerifyReport, Directory, GcPolicy, GcReport, Materialize, ObjectCache, Side};
+
+/// An object of the cache as it was listed.
+struct Object {
+	sha: Sha256Hash,
+	path: PathBuf,
+	size: u64,
+	accessed: SystemTime
+}
+
+/// Where the object with a sha is kept, fanned out by the first byte so no directory gets huge.
+pub(crate) fn object_path(cache: &ObjectCache, sha: &Sha256Hash) -> PathBuf {
+	let sha = sha.to_string();
+	cache.path.join("objects").join(&sha[..2]).join(sha)
+}
+
+/// Whether the cache has an object for every file of `directory` for `side` that would be downloaded, without checking that the objects are intact.
+pub(crate) async fn covers(cache: &ObjectCache, directory: &Directory, side: &Side) -> bool {
+	let mut stack = vec![directory];
+	while let Some(directory) = stack.pop() {
+		for file in directory.files.values().filter(|file| file.content_b64.is_none() && file.side.includes(side) && file.applies_here()) {
+			if !tokio::fs::try_exists(object_path(cache, &file.sha)).await.unwrap_or(false) {
+				return false;
+			}
+		}
+		stack.extend(directory.children.values().filter(|child| child.side.includes(side)));
+	}
+	true
+}
+
+/// Returns the object for a file if the cache has an intact copy, one that doesn't match anymore (like a hardlinked file that was edited in place) is removed.
+pub(crate) async fn hit(cache: &ObjectCache, sha: Sha256Hash, size: Option<u64>) -> Option<PathBuf> {
+	let object = object_path(cache, &sha);
+	let metadata = tokio::fs::metadata(&object).await.ok()?;
+	if size.is_some_and(|size| size != metadata.len()) {
+		let _ = tokio::fs::remove_file(&object).await;
+		return None;
+	}
+
+	let hashed = {
+		let object = object.clone();
+		tokio::task::spawn_blocking(move || -> io::Result<Sha256Hash> {
+			let mut hasher = sha2::Sha256::default();
+			io::copy(&mut std::fs::File::open(object)?, &mut hasher)?;
+			Ok(Sha256Hash(sha2::Digest::finalize(hasher).into()))
+		}).await.unwrap()
+	};
+
+	match hashed {
+		Ok(found) if found == sha => {
+			touch(&object);
+			Some(object)
+		},
+		Ok(_) => {
+			let _ = tokio::fs::remove_file(&object).await;
+			None
+		},
+		Err(_) => None
+	}
+}
+
+/// Adds a file that was just written and verified to the cache, by linking it when allowed and otherwise by copying it, blocking.
+pub(crate) fn insert(cache: &ObjectCache, sha: &Sha256Hash, local_path: &Path) -> io::Result<()> {
+	let object = object_path(cache, sha);
+	if object.exists() {
+		return Ok(());
+	}
+
+	std::fs::create_dir_all(object.parent().unwrap())?;
+	let temp_path = crate::manifest::temp_path(&object)?;
+	let _ = std::fs::remove_file(&temp_path);
+
+	let linked = cache.materialize == Materialize::Link && (reflink_copy::reflink(local_path, &temp_path).is_ok() || std::fs::hard_link(local_path, &temp_path).is_ok());
+	let result = match linked {
+		true => Ok(()),
+		false => std::fs::copy(local_path, &temp_path).map(drop)
+	}.and_then(|()| std::fs::rename(&temp_path, &object));
+
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temp_path);
+	}
+	result
+}
+
+/// Puts a copy of `object` at `local_path` through a temporary file, so whatever was there (maybe a hardlink to another object) is replaced instead of written to, blocking.
+/// Returns whether the copy shares its data with the cache instead of taking up space of its own.
+pub(crate) fn materialize(object: &Path, local_path: &Path, materialize: Materialize) -> io::Result<bool> {
+	let temp_path = crate::manifest::temp_path(local_path)?;
+	let _ = std::fs::remove_file(&temp_path);
+
+	let result = (|| {
+		let linked = match materialize {
+			Materialize::Link => reflink_copy::reflink(object, &temp_path).is_ok() || std::fs::hard_link(object, &temp_path).is_ok(),
+			Materialize::Copy => false
+		};
+		if !linked {
+			std::fs::copy(object, &temp_path)?;
+		}
+
+		std::fs::rename(&temp_path, local_path)?;
+		Ok(linked)
+	})();
+
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temp_path);
+	}
+	result
+}
+
+/// Records that an object was used, the filesystem may not do it itself (like with `noatime`) and [crate::GcPolicy] goes by it.
+fn touch(object: &Path) {
+	let times = std::fs::FileTimes::new().set_accessed(SystemTime::now());
+	let _ = std::fs::OpenOptions::new().write(true).open(object).and_then(|file| file.set_times(times));
+}
+
+/// Locks the whole cache, blocking until it can. Upgrades hold a shared lock while they use the cache and [ObjectCache::gc] an exclusive one, so nothing is removed while an upgrade could be linking it.
+/// The lock is released when the file is dropped.
+pub(crate) fn lock(cache: &ObjectCache, exclusive: bool) -> io::Result<std::fs::File> {
+	std::fs::create_dir_all(&cache.path)?;
+	let file = std::fs::OpenOptions::new().create(true).truncate(false).read(true).write(true).open(cache.path.join("lock"))?;
+	match exclusive {
+		true => file.lock()?,
+		false => file.lock_shared()?
+	}
+	Ok(file)
+}
+
+/// Every object of the cache, files that aren't named like one (like temporary files of a write that's in progress) are left out, blocking.
+fn objects(cache: &ObjectCache) -> io::Result<Vec<Object>> {
+	let mut objects = vec![];
+	let fans = match std::fs::read_dir(cache.path.join("objects")) {
+		Ok(fans) => fans,
+		Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(objects),
+		Err(error) => return Err(error)
+	};
+
+	for fan in fans {
+		let fan = fan?;
+		if !fan.file_type()?.is_dir() {
+			continue;
+		}
+		for entry in std::fs::read_dir(fan.path())? {
+			let entry = entry?;
+			let Some(sha) = entry.file_name().to_str().and_then(|name| name.parse::<Sha256Hash>().ok()) else {
+				continue;
+			};
+			let metadata = entry.metadata()?;
+			if !metadata.is_file() {
+				continue;
+			}
+			objects.push(Object {
+				sha,
+				path: entry.path(),
+				size: metadata.len(),
+				accessed: metadata.accessed().or_else(|_| metadata.modified()).unwrap_or(SystemTime::UNIX_EPOCH)
+			});
+		}
+	}
+	Ok(objects)
+}
+
+/// Blocking.
+pub(crate) fn stats(cache: &ObjectCache) -> io::Result<CacheStats> {
+	let objects = objects(cache)?;
+	Ok(CacheStats {
+		blobs: objects.len(),
+		bytes: objects.iter().map(|object| object.size).sum(),
+		oldest_access: objects.iter().map(|object| object.accessed).min(),
+		newest_access: objects.iter().map(|object| object.accessed).max()
+	})
+}
+
+/// Blocking, waits for upgrades that use the cache to finish.
+pub(crate) fn gc(cache: &ObjectCache, policy: &GcPolicy) -> io::Result<GcReport> {
+	let _lock = lock(cache, true)?;
+	let referenced = policy.referenced_by.as_ref().map(|directories| {
+		let mut referenced = HashSet::new();
+		let mut stack: Vec<_> = directories.iter().collect();
+		while let Some(directory) = stack.pop() {
+			referenced.extend(directory.files.values().map(|file| file.sha));
+			stack.extend(directory.children.values());
+		}
+		referenced
+	});
+
+	let now = SystemTime::now();
+	let (mut kept, mut removing): (Vec<_>, Vec<_>) = objects(cache)?.into_iter().partition(|object| {
+		let unreferenced = referenced.as_ref().is_some_and(|referenced| !referenced.contains(&object.sha));
+		let expired = policy.max_age.is_some_and(|max_age| now.duration_since(object.accessed).is_ok_and(|age| age > max_age));
+		!unreferenced && !expired
+	});
+
+	if let Some(max_bytes) = policy.max_bytes {
+		// least recently used first
+		kept.sort_by_key(|object| std::cmp::Reverse(object.accessed));
+		let mut bytes = kept.iter().map(|object| object.size).sum::<u64>();
+		while bytes > max_bytes {
+			let object = kept.pop().unwrap();
+			bytes -= object.size;
+			removing.push(object);
+		}
+	}
+
+	let mut report = GcReport {
+		kept: kept.len(),
+		kept_bytes: kept.iter().map(|object| object.size).sum(),
+		..Default::default()
+	};
+	for object in removing {
+		match std::fs::remove_file(&object.path) {
+			Ok(()) => {
+				report.removed_bytes += object.size;
+				report.removed.push(object.sha);
+			},
+			Err(error) if error.kind() == io::ErrorKind::NotFound => {},
+			Err(error) => return Err(error)
+		}
+	}
+	report.removed.sort();
+	Ok(report)
+}
+
+/// Blocking. Corrupt objects are removed the same way [hit] removes them, so no lock is needed.
+pub(crate) fn verify(cache: &ObjectCache) -> io::Result<CacheVerifyReport> {
+	let mut report = CacheVerifyReport::default();
+	for object in objects(cache)? {
+		let mut hasher = sha2::Sha256::default();
+		let found = match std::fs::File::open(&object.path).and_then(|mut file| io::copy(&mut file, &mut hasher)) {
+			Ok(_) => Sha256Hash(sha2::Digest::finalize(hasher).into()),
+			// removed since it was listed
+			Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+			Err(error) => return Err(error)
+		};
+
+		report.checked += 1;
+		if found != object.sha {
+			match std::fs::remove_file(&object.path) {
+				Ok(()) => report.evicted.push(object.sha),
+				Err(error) if error.kind() == io::ErrorKind::NotFound => {},
+				Err(error) => return Err(error)
+			}
+		}
+	}
+	report.evicted.sort();
+	Ok(report)
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::{Directory, DirectoryDiff, File, Sha256SumsError};
+
+/// Quotes a name like GNU sha256sum does, names with a backslash or line break are escaped and their line starts with a backslash.
+fn escape(name: &str) -> (bool, String) {
+	if !name.contains(['\\', '\n', '\r']) {
+		return (false, name.to_owned());
+	}
+
+	(true, name.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r"))
+}
+
+fn unescape(name: &str) -> Option<String> {
+	let mut unescaped = String::with_capacity(name.len());
+	let mut chars = name.chars();
+
+	while let Some(c) = chars.next() {
+		unescaped.push(match c {
+			'\\' => match chars.next()? {
+				'\\' => '\\',
+				'n' => '\n',
+				'r' => '\r',
+				_ => return None
+			},
+			c => c
+		});
+	}
+	Some(unescaped)
+}
+
+/// Formats `<sha256>  <path>` lines sorted by path.
+pub(crate) fn format<'a>(entries: impl IntoIterator<Item = (String, &'a crate::hash::Sha256Hash)>) -> String {
+	let mut entries: Vec<_> = entries.into_iter().collect();
+	entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+	let mut out = String::new();
+	for (path, sha) in entries {
+		let (escaped, path) = escape(&path);
+		if escaped {
+			out.push('\\');
+		}
+		out.push_str(&format!("{sha}  {path}\n"));
+	}
+	out
+}
+
+/// A relative path with `/` separators, whatever the platform.
+pub(crate) fn key(relative: &Path) -> String {
+	relative.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+/// Every file of `directory` with its `/` separated path.
+pub(crate) fn files<'a>(directory: &'a Directory, relative: &Path, out: &mut Vec<(String, &'a crate::hash::Sha256Hash)>) {
+	for (name, file) in &directory.files {
+		out.push((key(&relative.join(name)), &file.sha));
+	}
+
+	for (name, child) in &directory.children {
+		files(child, &relative.join(name), out);
+	}
+}
+
+pub(crate) fn parse(text: &str, url_for: impl Fn(&Path) -> String) -> Result<Directory, Sha256SumsError> {
+	let mut directory = Directory::default();
+
+	for (index, line) in text.lines().enumerate() {
+		let error = || Sha256SumsError {
+			line: index + 1,
+			content: line.to_owned()
+		};
+
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let (escaped, line_rest) = match line.strip_prefix('\\') {
+			Some(rest) => (true, rest),
+			None => (false, line)
+		};
+
+		let (sha, rest) = line_rest.split_once(' ').ok_or_else(error)?;
+		let sha = sha.parse().map_err(|_| error())?;
+		// the second separator is a space for text mode and `*` for binary mode, both mean the same thing here
+		let name = rest.strip_prefix([' ', '*']).ok_or_else(error)?;
+		let name = match escaped {
+			true => unescape(name).ok_or_else(error)?,
+			false => name.to_owned()
+		};
+
+		let components: Vec<_> = name.split('/').filter(|component| !component.is_empty() && *component != ".").collect();
+		if name.starts_with('/') || components.is_empty() || components.contains(&"..") {
+			return Err(error());
+		}
+
+		let relative: PathBuf = components.iter().collect();
+		let (file_name, parents) = components.split_last().unwrap();
+		let mut parent = &mut directory;
+		for component in parents {
+			if parent.files.contains_key(*component) {
+				return Err(error());
+			}
+			parent = parent.children.entry(component.to_string()).or_default();
+		}
+
+		if parent.children.contains_key(*file_name) {
+			return Err(error());
+		}
+
+		let file = File {
+			sha,
+			size: None,
+			url: Some(url_for(&relative)),
+			content_b64: None,
+			mtime: None,
+			side: Default::default(),
+			os: vec![],
+			arch: vec![],
+			headers: Default::default(),
+			query: vec![],
+			chunks: None,
+			extra: Default::default()
+		};
+		if parent.files.insert(file_name.to_string(), file).is_some() {
+			return Err(error());
+		}
+	}
+
+	Ok(directory)
+}
+
+/// Written first by [content_hash], changing how entries are encoded means a new version here.
+const CONTENT_HASH_VERSION: &[u8] = b"green-lib content hash v1\0";
+
+/// One thing [content_hash] covers, by `/` separated path.
+#[derive(PartialEq, Eq)]
+enum Entry<'a> {
+	Directory,
+	File(&'a crate::hash::Sha256Hash),
+	Link(&'a str),
+	/// A child that's still a reference, by the sha of its sub-manifest.
+	Ref(&'a str)
+}
+
+fn entries<'a>(directory: &'a Directory, prefix: &str, out: &mut Vec<(String, Entry<'a>)>) {
+	let join = |name: &str| match prefix.is_empty() {
+		true => name.to_owned(),
+		false => format!("{prefix}/{name}")
+	};
+
+	out.extend(directory.files.iter().map(|(name, file)| (join(name), Entry::File(&file.sha))));
+	out.extend(directory.links.iter().map(|(name, target)| (join(name), Entry::Link(target))));
+	for (name, child) in &directory.children {
+		let path = join(name);
+		match &child.reference {
+			Some(reference) => out.push((path, Entry::Ref(&reference.sha))),
+			None => {
+				entries(child, &path, out);
+				out.push((path, Entry::Directory));
+			}
+		}
+	}
+}
+
+/// Hashes the paths, shas and link targets of a tree sorted by path, every field is length prefixed so two different trees can't encode the same.
+pub(crate) fn content_hash(directory: &Directory) -> [u8; 32] {
+	use sha2::Digest;
+
+	let mut all = vec![];
+	entries(directory, "", &mut all);
+	let tag = |entry: &Entry| match entry {
+		Entry::Directory => b'd',
+		Entry::File(_) => b'f',
+		Entry::Link(_) => b'l',
+		Entry::Ref(_) => b'r'
+	};
+	all.sort_by(|(a, a_entry), (b, b_entry)| a.cmp(b).then(tag(a_entry).cmp(&tag(b_entry))));
+
+	let mut hasher = sha2::Sha256::new();
+	let mut field = |bytes: &[u8]| {
+		hasher.update((bytes.len() as u64).to_le_bytes());
+		hasher.update(bytes);
+	};
+	field(CONTENT_HASH_VERSION);
+	for (path, entry) in &all {
+		field(&[tag(entry)]);
+		field(path.as_bytes());
+		match entry {
+			Entry::Directory => {},
+			Entry::File(sha) => field(&sha.0),
+			Entry::Link(target) | Entry::Ref(target) => field(target.as_bytes())
+		}
+	}
+	hasher.finalize().into()
+}
+
+/// Gives every child below `directory` its [Directory::subtree_hash], returns whether `directory` has no references below it.
+pub(crate) fn set_subtree_hashes(directory: &mut Directory) -> bool {
+	let mut resolved = true;
+	for child in directory.children.values_mut() {
+		let child_resolved = child.reference.is_none() && set_subtree_hashes(child);
+		child.subtree_hash = child_resolved.then(|| crate::hash::Sha256Hash(content_hash(child)));
+		resolved &= child_resolved;
+	}
+	resolved
+}
+
+/// Compares the files and links of two trees by path, directories only matter through what's in them.
+/// Unresolved references count as one entry each, changed when their sha is.
+pub(crate) fn diff(old: &Directory, new: &Directory) -> DirectoryDiff {
+	let collect = |directory| {
+		let mut all = vec![];
+		entries(directory, "", &mut all);
+		all.into_iter().filter(|(_, entry)| *entry != Entry::Directory).collect::<HashMap<_, _>>()
+	};
+	let (old, new) = (collect(old), collect(new));
+
+	let mut diff = DirectoryDiff::default();
+	for (path, entry) in &new {
+		match old.get(path) {
+			None => diff.added.push(path.clone()),
+			Some(old_entry) if old_entry != entry => diff.changed.push(path.clone()),
+			Some(_) => {}
+		}
+	}
+	diff.removed.extend(old.keys().filter(|path| !new.contains_key(*path)).cloned());
+
+	diff.added.sort();
+	diff.removed.sort();
+	diff.changed.sort();
+	diff
+}
@@ -0,0 +1,32 @@
+use std::io::Read;
+use std::path::Path;
+use crate::hash::Sha256Hash;
+use crate::Chunks;
+
+/// The indexes of the chunks of a local file that don't match, [None] when `chunks` doesn't cover its length.
+/// It's read one chunk at a time so a huge file isn't held in memory.
+pub(crate) async fn damaged_in(path: &Path, chunks: &Chunks) -> std::io::Result<Option<Vec<usize>>> {
+	let (path, chunks) = (path.to_owned(), chunks.clone());
+	tokio::task::spawn_blocking(move || damaged(&path, &chunks)).await.unwrap()
+}
+
+/// Same as [damaged_in] on the current thread.
+pub(crate) fn damaged(path: &Path, chunks: &Chunks) -> std::io::Result<Option<Vec<usize>>> {
+	let mut file = std::fs::File::open(path)?;
+	let length = file.metadata()?.len();
+	if !chunks.fits(length) {
+		return Ok(None);
+	}
+
+	let mut damaged = vec![];
+	let mut buffer = vec![];
+	for (index, sha) in chunks.shas.iter().enumerate() {
+		let range = chunks.range(index, length);
+		buffer.resize((range.end - range.start) as usize, 0);
+		file.read_exact(&mut buffer)?;
+		if Sha256Hash::digest(&buffer) != *sha {
+			damaged.push(index);
+		}
+	}
+	Ok(Some(damaged))
+}
@@ -0,0 +1,149 @@
+use crate::hash::Sha256Hash;
+use crate::{DeltaError, Directory, ManifestDelta};
+
+/// A directory's own fields, without what's in it.
+fn fields(directory: &Directory) -> Directory {
+	Directory {
+		url_template: directory.url_template.clone(),
+		min_green_lib: directory.min_green_lib.clone(),
+		side: directory.side.clone(),
+		reference: directory.reference.clone(),
+		subtree_hash: directory.subtree_hash,
+		extra: directory.extra.clone(),
+		..Default::default()
+	}
+}
+
+fn join(prefix: &str, name: &str) -> String {
+	match prefix.is_empty() {
+		true => name.to_owned(),
+		false => format!("{prefix}/{name}")
+	}
+}
+
+/// Adds what turns `old` into `new` to `delta`, `old` is [None] for a directory only `new` has.
+fn collect(old: Option<&Directory>, new: &Directory, prefix: &str, delta: &mut ManifestDelta) {
+	if old.is_none_or(|old| fields(old) != fields(new)) {
+		delta.directories.insert(prefix.to_owned(), fields(new));
+	}
+
+	if let Some(old) = old {
+		let removed = old.files.keys().filter(|name| !new.files.contains_key(*name))
+			.chain(old.links.keys().filter(|name| !new.links.contains_key(*name)))
+			.chain(old.children.keys().filter(|name| !new.children.contains_key(*name)));
+		delta.removed.extend(removed.map(|name| join(prefix, name)));
+	}
+
+	for (name, file) in &new.files {
+		if old.and_then(|old| old.files.get(name)) != Some(file) {
+			delta.files.insert(join(prefix, name), file.clone());
+		}
+	}
+	for (name, target) in &new.links {
+		if old.and_then(|old| old.links.get(name)) != Some(target) {
+			delta.links.insert(join(prefix, name), target.clone());
+		}
+	}
+	for (name, child) in &new.children {
+		collect(old.and_then(|old| old.children.get(name)), child, &join(prefix, name), delta);
+	}
+}
+
+/// The delta from `old` to `new`, see [Directory::delta_to].
+pub(crate) fn delta(old: &Directory, new: &Directory) -> ManifestDelta {
+	let mut delta = ManifestDelta {
+		base: Sha256Hash(old.content_hash()),
+		target: Sha256Hash(new.content_hash()),
+		directories: Default::default(),
+		files: Default::default(),
+		links: Default::default(),
+		removed: vec![],
+		extra: Default::default()
+	};
+	collect(Some(old), new, "", &mut delta);
+	delta.removed.sort();
+	delta
+}
+
+/// The directory a `/` separated path is in and the last component, missing directories are created when `create` is set.
+fn parent_mut<'a, 'p>(directory: &'a mut Directory, path: &'p str, create: bool) -> Result<(&'a mut Directory, &'p str), DeltaError> {
+	let mut components: Vec<_> = path.split('/').collect();
+	let name = components.pop().unwrap();
+	if components.iter().chain([&name]).any(|component| matches!(*component, "" | "." | "..")) {
+		return Err(DeltaError::InvalidPath(path.to_owned()));
+	}
+
+	let mut parent = directory;
+	for component in components {
+		parent = match create {
+			true => {
+				parent.files.remove(component);
+				parent.links.remove(component);
+				parent.children.entry(component.to_owned()).or_default()
+			},
+			false => parent.children.get_mut(component).ok_or_else(|| DeltaError::InvalidPath(path.to_owned()))?
+		};
+	}
+	Ok((parent, name))
+}
+
+/// Applies `delta` to `base`, see [Directory::apply_delta].
+pub(crate) fn apply(base: &Directory, delta: &ManifestDelta) -> Result<Directory, DeltaError> {
+	let found = Sha256Hash(base.content_hash());
+	if found != delta.base {
+		return Err(DeltaError::WrongBase {
+			expected: delta.base,
+			found
+		});
+	}
+
+	let mut directory = base.clone();
+	for path in &delta.removed {
+		let (parent, name) = parent_mut(&mut directory, path, false)?;
+		parent.files.remove(name);
+		parent.links.remove(name);
+		parent.children.remove(name);
+	}
+
+	// sorted, so a directory's parent has its fields before it does
+	for (path, fields) in &delta.directories {
+		let target = match path.is_empty() {
+			true => &mut directory,
+			false => {
+				let (parent, name) = parent_mut(&mut directory, path, true)?;
+				parent.files.remove(name);
+				parent.links.remove(name);
+				parent.children.entry(name.to_owned()).or_default()
+			}
+		};
+		*target = Directory {
+			files: std::mem::take(&mut target.files),
+			children: std::mem::take(&mut target.children),
+			links: std::mem::take(&mut target.links),
+			..fields.clone()
+		};
+	}
+
+	for (path, file) in &delta.files {
+		let (parent, name) = parent_mut(&mut directory, path, true)?;
+		parent.children.remove(name);
+		parent.links.remove(name);
+		parent.files.insert(name.to_owned(), file.clone());
+	}
+	for (path, target) in &delta.links {
+		let (parent, name) = parent_mut(&mut directory, path, true)?;
+		parent.children.remove(name);
+		parent.files.remove(name);
+		parent.links.insert(name.to_owned(), target.clone());
+	}
+
+	let found = Sha256Hash(directory.content_hash());
+	if found != delta.target {
+		return Err(DeltaError::WrongTarget {
+			expected: delta.target,
+			found
+		});
+	}
+	directory.validate().map_err(DeltaError::Invalid)?;
+	Ok(directory)
+}
@@ -0,0 +1,1300 @@
+use base64::Engine;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::hash::Sha256Hash;
+use crate::hash_cache::{self, HashCache};
+use crate::metrics::Recorder;
+use crate::{batch, http, DownloadError, FileOperation, Progress, RetryReason, UpgradeError, UpgradeStatus};
+
+pub(crate) const MAX_ATTEMPTS: u64 = 5;
+
+enum FetchFailure {
+	/// Worth another attempt, the error is what gets reported if this was the last one.
+	Retry(RetryReason, DownloadError),
+	Fatal(DownloadError),
+	/// The upgrade was paused while receiving, what arrived is kept and the next request continues after it without counting as an attempt.
+	Paused
+}
+
+impl FetchFailure {
+	fn retry(reason: RetryReason) -> Self {
+		FetchFailure::Retry(reason.clone(), DownloadError::Exhausted(reason))
+	}
+}
+
+impl From<reqwest::Error> for FetchFailure {
+	fn from(error: reqwest::Error) -> Self {
+		if error.is_timeout() {
+			FetchFailure::retry(RetryReason::Timeout)
+		} else if error.is_connect() || error.is_request() || error.is_body() {
+			FetchFailure::retry(RetryReason::Connection)
+		} else {
+			FetchFailure::Fatal(DownloadError::Request(format!("{error}")))
+		}
+	}
+}
+
+impl From<http::GetError> for FetchFailure {
+	fn from(error: http::GetError) -> Self {
+		match error {
+			http::GetError::Request(error, _) => error.into(),
+			http::GetError::Redirect(error) => FetchFailure::Fatal(DownloadError::Redirect(error)),
+			http::GetError::Token(reason) => FetchFailure::Fatal(DownloadError::Token(reason))
+		}
+	}
+}
+
+/// The [crate::File::headers] of a file for its download, ones that aren't valid headers can't get past validation and are left out.
+pub(crate) fn file_headers(file: &crate::File) -> reqwest::header::HeaderMap {
+	file.headers.iter().filter_map(|(name, value)| Some((
+		reqwest::header::HeaderName::from_bytes(name.as_bytes()).ok()?,
+		reqwest::header::HeaderValue::from_str(value).ok()?
+	))).collect()
+}
+
+/// `url` with the [crate::File::query] of a file added, a url that doesn't parse is left for the request to fail on.
+fn with_query(url: String, query: &[(String, String)]) -> String {
+	if query.is_empty() {
+		return url;
+	}
+	match reqwest::Url::parse(&url) {
+		Ok(mut parsed) => {
+			parsed.query_pairs_mut().extend_pairs(query);
+			parsed.into()
+		},
+		Err(_) => url
+	}
+}
+
+/// Limits how many bytes of downloaded bodies are held in memory at once across every download of an upgrade.
+/// Bodies are streamed to a temp file, so what counts is a download that's receiving (its [STREAM_WINDOW]), tiny bodies that are kept in memory and the chunks a repair downloads.
+/// Permits are KiB so budgets bigger than 4 GiB still fit in a semaphore.
+pub(crate) struct Budget {
+	semaphore: Arc<Semaphore>,
+	permits: u32,
+	held: AtomicU64,
+	peak: AtomicU64
+}
+
+/// Holds part of the [Budget] until the body it was admitted for has been written out.
+pub(crate) struct BufferPermit {
+	_permit: OwnedSemaphorePermit,
+	bytes: u64,
+	budget: Arc<Budget>
+}
+
+impl Budget {
+	pub(crate) fn new(bytes: u64) -> Self {
+		let permits = bytes.div_ceil(1024).clamp(1, u32::MAX as u64) as u32;
+		Self {
+			semaphore: Arc::new(Semaphore::new(permits as usize)),
+			permits,
+			held: AtomicU64::new(0),
+			peak: AtomicU64::new(0)
+		}
+	}
+
+	/// Waits until `length` bytes fit, [None] or more than the whole budget waits for the entire budget so it's admitted alone.
+	async fn admit(self: &Arc<Self>, length: Option<u64>) -> BufferPermit {
+		let permits = length.map_or(self.permits, |length| length.div_ceil(1024).min(self.permits as u64) as u32);
+		BufferPermit {
+			_permit: self.semaphore.clone().acquire_many_owned(permits).await.unwrap(),
+			bytes: 0,
+			budget: self.clone()
+		}
+	}
+
+	/// The most bytes that were buffered at once.
+	pub(crate) fn peak(&self) -> u64 {
+		self.peak.load(Ordering::Relaxed)
+	}
+}
+
+impl BufferPermit {
+	fn hold(&mut self, bytes: u64) {
+		self.bytes += bytes;
+		let held = self.budget.held.fetch_add(bytes, Ordering::Relaxed) + bytes;
+		self.budget.peak.fetch_max(held, Ordering::Relaxed);
+	}
+
+	/// Lowers what's held to `bytes` once the rest was written out.
+	fn keep(&mut self, bytes: u64) {
+		let released = self.bytes.saturating_sub(bytes);
+		self.bytes -= released;
+		self.budget.held.fetch_sub(released, Ordering::Relaxed);
+	}
+}
+
+impl Drop for BufferPermit {
+	fn drop(&mut self) {
+		self.budget.held.fetch_sub(self.bytes, Ordering::Relaxed);
+	}
+}
+
+/// The host of `url` as [crate::HostLimits] matches it, empty when it has none.
+pub(crate) fn host(url: &str) -> String {
+	reqwest::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_ascii_lowercase)).unwrap_or_default()
+}
+
+/// What a download holds while it's requested and received, see [crate::DownloadLimiter].
+pub(crate) struct Slot {
+	pub(crate) _host: Option<OwnedSemaphorePermit>,
+	pub(crate) _slot: OwnedSemaphorePermit
+}
+
+/// The [crate::HostLimits] of a [crate::DownloadLimiter] and the hosts that asked to be left alone for a while.
+#[derive(Debug)]
+pub(crate) struct Hosts {
+	limits: crate::HostLimits,
+	hosts: Mutex<std::collections::HashMap<String, Host>>
+}
+
+#[derive(Debug)]
+struct Host {
+	/// [None] when the host isn't capped.
+	slots: Option<Arc<Semaphore>>,
+	cool_down_until: Option<tokio::time::Instant>
+}
+
+impl Hosts {
+	pub(crate) fn new(limits: crate::HostLimits) -> Self {
+		Self {
+			limits: crate::HostLimits {
+				hosts: limits.hosts.into_iter().map(|(host, limit)| (host.to_ascii_lowercase(), limit)).collect(),
+				..limits
+			},
+			hosts: Mutex::default()
+		}
+	}
+
+	fn with_host<T>(&self, host: &str, f: impl FnOnce(&mut Host) -> T) -> T {
+		let mut hosts = self.hosts.lock().unwrap();
+		let entry = hosts.entry(host.to_owned()).or_insert_with(|| Host {
+			slots: self.limits.hosts.get(host).copied().or(self.limits.per_host).filter(|_| !host.is_empty()).map(|limit| Arc::new(Semaphore::new(limit.max(1)))),
+			cool_down_until: None
+		});
+		f(entry)
+	}
+
+	fn cooling_down(&self, host: &str) -> Option<tokio::time::Instant> {
+		self.with_host(host, |host| host.cool_down_until).filter(|until| *until > tokio::time::Instant::now())
+	}
+
+	/// Waits until `host` isn't cooling down anymore and has a slot free, ones without a cap don't need one.
+	pub(crate) async fn acquire(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+		loop {
+			if let Some(until) = self.cooling_down(host) {
+				tokio::time::sleep_until(until).await;
+			}
+			let permit = match self.with_host(host, |host| host.slots.clone()) {
+				Some(slots) => Some(slots.acquire_owned().await.unwrap()),
+				None => None
+			};
+			// the host may have started cooling down while this waited for its slot
+			if self.cooling_down(host).is_none() {
+				return permit;
+			}
+		}
+	}
+
+	pub(crate) fn cool_down(&self, host: &str, duration: Duration) {
+		let until = tokio::time::Instant::now() + duration.min(crate::fetch::MAX_RETRY_AFTER);
+		self.with_host(host, |host| host.cool_down_until = host.cool_down_until.max(Some(until)));
+	}
+}
+
+/// How many connection failures in a row, before anything connected, mean the network is down.
+const OFFLINE_FAILURES: u32 = 8;
+/// How quickly those failures have to happen, a network that fails slowly is left to the retries.
+const OFFLINE_WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Notices when every download of an upgrade fails to connect, so the rest give up instead of going through their retries.
+#[derive(Default)]
+pub(crate) struct Connectivity {
+	state: Mutex<ConnectivityState>
+}
+
+#[derive(Default)]
+struct ConnectivityState {
+	/// Something answered, after which the network is never considered down.
+	connected: bool,
+	failures: u32,
+	first_failure: Option<std::time::Instant>,
+	/// The URL of the failure that settled it.
+	offline: Option<String>
+}
+
+impl Connectivity {
+	fn connected(&self) {
+		self.state.lock().unwrap().connected = true;
+	}
+
+	fn failed(&self, url: &str) {
+		let mut state = self.state.lock().unwrap();
+		if state.connected || state.offline.is_some() {
+			return;
+		}
+
+		let now = std::time::Instant::now();
+		match state.first_failure {
+			Some(first) if now.duration_since(first) <= OFFLINE_WINDOW => state.failures += 1,
+			_ => {
+				state.first_failure = Some(now);
+				state.failures = 1;
+			}
+		}
+		if state.failures >= OFFLINE_FAILURES {
+			state.offline = Some(url.to_owned());
+		}
+	}
+
+	/// The URL that showed the network is down, if it is.
+	pub(crate) fn offline(&self) -> Option<String> {
+		self.state.lock().unwrap().offline.clone()
+	}
+}
+
+/// Enforces the [crate::SizeLimits] of an upgrade.
+pub(crate) struct Limits {
+	per_file: Option<u64>,
+	total: Option<u64>,
+	received: AtomicU64
+}
+
+impl Limits {
+	pub(crate) fn new(limits: &crate::SizeLimits) -> Self {
+		Self {
+			per_file: limits.per_file,
+			total: limits.total,
+			received: AtomicU64::new(0)
+		}
+	}
+
+	/// Counts received bytes against the total limit.
+	fn receive(&self, bytes: u64) -> Result<(), DownloadError> {
+		let received = self.received.fetch_add(bytes, Ordering::Relaxed) + bytes;
+		match self.total {
+			Some(limit) if received > limit => Err(DownloadError::TotalTooLarge {
+				limit
+			}),
+			_ => Ok(())
+		}
+	}
+}
+
+/// What the server said about a body, for the [crate::BodyDiagnostics] of a mismatch.
+struct Served {
+	url: String,
+	content_type: Option<String>,
+	content_length: Option<u64>,
+	redirects: usize
+}
+
+impl Served {
+	/// `redact` leaves the query out of the url.
+	fn of(response: &reqwest::Response, redact: bool) -> Self {
+		let url = response.url().as_str();
+		Self {
+			url: match redact {
+				true => http::redact(url),
+				false => url.to_owned()
+			},
+			content_type: response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(str::to_owned),
+			content_length: response.content_length(),
+			redirects: http::redirects(response).len()
+		}
+	}
+}
+
+/// How many bytes of a body are looked at to tell what it is.
+const SNIFF_LENGTH: usize = 512;
+
+/// What the start of `body` looks like.
+fn body_kind(body: &[u8]) -> crate::BodyKind {
+	let head = &body[..body.len().min(SNIFF_LENGTH)];
+	// a multibyte character may be cut off at the end
+	let text = match std::str::from_utf8(head) {
+		Ok(text) => Some(text),
+		Err(error) if error.error_len().is_none() => std::str::from_utf8(&head[..error.valid_up_to()]).ok(),
+		Err(_) => None
+	};
+	match text {
+		_ if body.is_empty() => crate::BodyKind::Empty,
+		Some(text) if text.trim_start_matches('\u{feff}').trim_start().starts_with('<') && {
+			let lower = text.to_ascii_lowercase();
+			lower.contains("<html") || lower.contains("<!doctype html") || lower.contains("<head") || lower.contains("<body")
+		} => crate::BodyKind::Html,
+		Some(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => crate::BodyKind::Text,
+		_ => crate::BodyKind::Binary
+	}
+}
+
+fn diagnostics(served: Option<&Served>, body: &[u8], expected_size: Option<u64>) -> Box<crate::BodyDiagnostics> {
+	Box::new(crate::BodyDiagnostics {
+		expected_size,
+		content_type: served.and_then(|served| served.content_type.clone()),
+		content_length: served.and_then(|served| served.content_length),
+		kind: body_kind(body),
+		url: served.map(|served| served.url.clone()),
+		redirects: served.map_or(0, |served| served.redirects)
+	})
+}
+
+/// How much of the [Budget] a download holds while it receives a body: the chunk of the response that's on its way to the file, or a tiny body that's kept in memory.
+const STREAM_WINDOW: u64 = 512 << 10;
+
+/// A file next to the one being downloaded that the body is written to as it arrives, it's removed when it's dropped before [TempFile::persist].
+pub(crate) struct TempFile {
+	path: PathBuf,
+	file: Option<tokio::fs::File>
+}
+
+impl TempFile {
+	async fn create(path: PathBuf) -> std::io::Result<Self> {
+		let file = tokio::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).await?;
+		Ok(Self {
+			path,
+			file: Some(file)
+		})
+	}
+
+	/// A copy of `from` to change in place.
+	async fn copy(from: &std::path::Path, path: PathBuf) -> std::io::Result<Self> {
+		let opened = match tokio::fs::copy(from, &path).await {
+			Ok(_) => tokio::fs::OpenOptions::new().read(true).write(true).open(&path).await,
+			Err(error) => Err(error)
+		};
+		match opened {
+			Ok(file) => Ok(Self {
+				path,
+				file: Some(file)
+			}),
+			Err(error) => {
+				let _ = tokio::fs::remove_file(&path).await;
+				Err(error)
+			}
+		}
+	}
+
+	fn file(&mut self) -> &mut tokio::fs::File {
+		self.file.as_mut().unwrap()
+	}
+
+	/// Up to `length` bytes from `offset` on, writing continues at the end afterwards.
+	async fn read_at(&mut self, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
+		let file = self.file();
+		file.flush().await?;
+		file.seek(std::io::SeekFrom::Start(offset)).await?;
+		let mut bytes = vec![];
+		(&mut *file).take(length as u64).read_to_end(&mut bytes).await?;
+		file.seek(std::io::SeekFrom::End(0)).await?;
+		Ok(bytes)
+	}
+
+	/// Cuts the file down to `length` bytes, writing continues after them.
+	async fn truncate(&mut self, length: u64) -> std::io::Result<()> {
+		let file = self.file();
+		file.flush().await?;
+		file.set_len(length).await?;
+		file.seek(std::io::SeekFrom::Start(length)).await.map(drop)
+	}
+
+	/// The file was renamed into place, so it isn't removed anymore.
+	fn persist(mut self) {
+		self.file = None;
+	}
+}
+
+impl Drop for TempFile {
+	fn drop(&mut self) {
+		if let Some(file) = self.file.take() {
+			// closed first, an open file can't be removed on Windows
+			drop(file);
+			let _ = std::fs::remove_file(&self.path);
+		}
+	}
+}
+
+/// A verified body, tiny ones are kept in memory for [batch::write] and the rest is in a [TempFile] already.
+pub(crate) enum Body {
+	Memory(bytes::Bytes),
+	File {
+		temp: TempFile,
+		length: u64
+	}
+}
+
+impl Body {
+	fn len(&self) -> u64 {
+		match self {
+			Body::Memory(contents) => contents.len() as u64,
+			Body::File { length, .. } => *length
+		}
+	}
+
+	/// The start of the body, to tell what it is.
+	async fn sniff(&mut self) -> Vec<u8> {
+		match self {
+			Body::Memory(contents) => contents[..contents.len().min(SNIFF_LENGTH)].to_vec(),
+			Body::File { temp, .. } => temp.read_at(0, SNIFF_LENGTH).await.unwrap_or_default()
+		}
+	}
+
+	/// Extracts the body as an archive into `root`, blocking.
+	#[cfg(feature = "zip")]
+	pub(crate) fn extract(self, root: &std::path::Path, progress: &Progress) -> zip::result::ZipResult<()> {
+		match self {
+			Body::Memory(contents) => crate::archive::extract(std::io::Cursor::new(contents), root, progress),
+			// removed once it's extracted
+			Body::File { temp, .. } => crate::archive::extract(std::fs::File::open(&temp.path)?, root, progress)
+		}
+	}
+}
+
+/// Where what arrived of a body is kept.
+enum Sink {
+	/// A tiny body, it's moved to a [TempFile] when it outgrows [batch::TINY].
+	Memory(bytes::BytesMut),
+	File(TempFile)
+}
+
+/// How far a body got, kept between attempts so a retry only asks for the rest.
+/// Only the shas are in memory, what arrived was written to the [TempFile] at `temp_path` except for a tiny body.
+struct Partial {
+	temp_path: PathBuf,
+	sink: Option<Sink>,
+	/// How many bytes are in `sink`, a range request continues after them.
+	received: u64,
+	/// How many of them are made of chunks that matched, the rest belongs to the one that's still being received.
+	verified: u64,
+	/// The sha of the whole file so far.
+	hasher: sha2::Sha256,
+	/// `hasher` as it was at `verified`, it goes back to that when a chunk doesn't match.
+	checkpoint: sha2::Sha256,
+	/// The sha of what arrived of the current chunk.
+	chunk: sha2::Sha256
+}
+
+/// Why [Partial::hash] stopped.
+enum Unmatched {
+	Chunk {
+		index: usize,
+		expected: Sha256Hash,
+		found: Sha256Hash,
+		expected_size: Option<u64>
+	},
+	/// The body is longer than the chunks cover.
+	TooLong {
+		limit: u64
+	}
+}
+
+impl Partial {
+	fn new(temp_path: PathBuf) -> Self {
+		Self {
+			temp_path,
+			sink: None,
+			received: 0,
+			verified: 0,
+			hasher: Default::default(),
+			checkpoint: Default::default(),
+			chunk: Default::default()
+		}
+	}
+
+	/// Forgets everything, the temp file goes with it.
+	fn restart(&mut self) {
+		self.sink = None;
+		self.received = 0;
+		self.verified = 0;
+		self.hasher = Default::default();
+		self.checkpoint = Default::default();
+		self.chunk = Default::default();
+	}
+
+	/// Goes back to the end of the last chunk that matched, for the next attempt to continue from.
+	async fn rewind(&mut self) {
+		let truncated = match &mut self.sink {
+			Some(Sink::Memory(buffer)) => {
+				buffer.truncate(self.verified as usize);
+				Ok(())
+			},
+			Some(Sink::File(temp)) => temp.truncate(self.verified).await,
+			None => Ok(())
+		};
+		match truncated {
+			Ok(()) => {
+				self.received = self.verified;
+				self.hasher = self.checkpoint.clone();
+				self.chunk = Default::default();
+			},
+			Err(_) => self.restart()
+		}
+	}
+
+	/// How many bytes of the body are held in memory.
+	fn resident(&self) -> u64 {
+		match &self.sink {
+			Some(Sink::Memory(buffer)) => buffer.len() as u64,
+			_ => 0
+		}
+	}
+
+	/// Adds `bytes` to the body, in memory while it's `tiny` and fits in [batch::TINY].
+	/// The sink is left in a state that's only good for [Partial::restart] when this fails.
+	async fn write(&mut self, bytes: &[u8], tiny: bool) -> std::io::Result<()> {
+		if self.sink.is_none() {
+			self.sink = Some(match tiny {
+				true => Sink::Memory(bytes::BytesMut::new()),
+				false => Sink::File(TempFile::create(self.temp_path.clone()).await?)
+			});
+		}
+		if let Some(Sink::Memory(buffer)) = &self.sink {
+			if buffer.len() + bytes.len() > batch::TINY {
+				let mut temp = TempFile::create(self.temp_path.clone()).await?;
+				temp.file().write_all(buffer).await?;
+				self.sink = Some(Sink::File(temp));
+			}
+		}
+		match self.sink.as_mut().unwrap() {
+			Sink::Memory(buffer) => buffer.extend_from_slice(bytes),
+			Sink::File(temp) => temp.file().write_all(bytes).await?
+		}
+		self.received += bytes.len() as u64;
+		Ok(())
+	}
+
+	/// Up to `length` bytes of the body from `offset` on, empty when they can't be read.
+	async fn read(&mut self, offset: u64, length: usize) -> Vec<u8> {
+		match &mut self.sink {
+			Some(Sink::Memory(buffer)) => buffer.get(offset as usize..).map_or(vec![], |rest| rest[..rest.len().min(length)].to_vec()),
+			Some(Sink::File(temp)) => temp.read_at(offset, length).await.unwrap_or_default(),
+			None => vec![]
+		}
+	}
+
+	/// Feeds what was just written to the shas, with `chunks` every one of them is checked as soon as it's complete, `end` also checks a shorter last one. `size` is the [crate::File::size].
+	fn hash(&mut self, mut bytes: &[u8], chunks: Option<&crate::Chunks>, end: bool, size: Option<u64>) -> Result<(), Unmatched> {
+		let Some(chunks) = chunks else {
+			sha2::Digest::update(&mut self.hasher, bytes);
+			return Ok(());
+		};
+
+		let mut position = self.received - bytes.len() as u64;
+		loop {
+			let index = (self.verified / chunks.size) as usize;
+			let chunk_end = self.verified + chunks.size;
+			let (piece, rest) = bytes.split_at((chunk_end - position).min(bytes.len() as u64) as usize);
+			if !piece.is_empty() && index >= chunks.shas.len() {
+				return Err(Unmatched::TooLong {
+					limit: chunks.size.saturating_mul(chunks.shas.len() as u64)
+				});
+			}
+			sha2::Digest::update(&mut self.hasher, piece);
+			sha2::Digest::update(&mut self.chunk, piece);
+			position += piece.len() as u64;
+			bytes = rest;
+
+			if position < chunk_end && !(end && position > self.verified) {
+				return Ok(());
+			}
+			let found = Sha256Hash(sha2::Digest::finalize_reset(&mut self.chunk).into());
+			if found != chunks.shas[index] {
+				return Err(Unmatched::Chunk {
+					index,
+					expected: chunks.shas[index],
+					found,
+					expected_size: size.map(|size| chunks.range(index, size)).map(|range| range.end - range.start)
+				});
+			}
+			self.verified = position;
+			self.checkpoint = self.hasher.clone();
+		}
+	}
+
+	/// The failure for what [Partial::hash] ran into.
+	async fn unmatched(&mut self, unmatched: Unmatched, served: &Served) -> FetchFailure {
+		match unmatched {
+			Unmatched::Chunk { index, expected, found, expected_size } => {
+				// what arrived from the chunk on tells more about an error page than the chunk alone
+				let head = self.read(self.verified, SNIFF_LENGTH).await;
+				FetchFailure::Retry(RetryReason::ShaMismatch, DownloadError::ChunkMismatch {
+					chunk: index,
+					expected,
+					found,
+					diagnostics: diagnostics(Some(served), &head, expected_size)
+				})
+			},
+			Unmatched::TooLong { limit } => FetchFailure::Fatal(DownloadError::TooLarge {
+				limit,
+				received: self.received
+			})
+		}
+	}
+
+	/// Hands over the whole body along with its sha and starts over.
+	async fn finish(&mut self) -> std::io::Result<(Body, Sha256Hash)> {
+		let body = match self.sink.take() {
+			Some(Sink::Memory(buffer)) => Body::Memory(buffer.freeze()),
+			Some(Sink::File(mut temp)) => {
+				temp.file().flush().await?;
+				Body::File {
+					temp,
+					length: self.received
+				}
+			},
+			None => Body::Memory(bytes::Bytes::new())
+		};
+		let sha = Sha256Hash(sha2::Digest::finalize(std::mem::take(&mut self.hasher)).into());
+		self.restart();
+		Ok((body, sha))
+	}
+}
+
+/// The sha of a file, read a piece at a time, blocking.
+fn sha_of(path: &std::path::Path) -> std::io::Result<Sha256Hash> {
+	let mut hasher = sha2::Sha256::default();
+	std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+	Ok(Sha256Hash(sha2::Digest::finalize(hasher).into()))
+}
+
+/// Where the body of a `206 Partial Content` response starts.
+fn range_start(response: &reqwest::Response) -> Option<u64> {
+	let range = response.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+	range.strip_prefix("bytes ")?.split('-').next()?.trim().parse().ok()
+}
+
+/// How long the body of an unsuccessful response gets to arrive for [crate::DownloadFailure::snippet].
+const SNIPPET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The start of the body of an unsuccessful response, [None] when it's empty.
+async fn error_snippet(mut response: reqwest::Response) -> Option<String> {
+	let mut head = vec![];
+	let read = async {
+		while head.len() < crate::fetch::HEAD_LENGTH {
+			match response.chunk().await {
+				Ok(Some(chunk)) => head.extend_from_slice(&chunk),
+				_ => break
+			}
+		}
+	};
+	let _ = tokio::time::timeout(SNIPPET_TIMEOUT, read).await;
+	head.truncate(crate::fetch::HEAD_LENGTH);
+	(!head.is_empty()).then(|| crate::fetch::snippet(&head))
+}
+
+/// Receives a body into the [TempFile] of `partial`, hashing it as the chunks arrive so it's verified as soon as the last one lands, a tiny body is kept in memory instead.
+/// While receiving the download holds [STREAM_WINDOW] of `budget` at most.
+/// Nothing is hashed when `hash` is off, and [None] is returned instead.
+/// With `chunks` each one is checked as soon as it's complete, and what was verified is kept in `partial` when receiving fails, for a range request to continue from.
+/// Receiving stops with [FetchFailure::Paused] as soon as `pause` is paused, dropping the connection and keeping everything in `partial`.
+#[allow(clippy::too_many_arguments)]
+async fn fetch(mut response: reqwest::Response, budget: &Arc<Budget>, limits: &Limits, metrics: &Recorder, size: Option<u64>, hash: bool, stall_timeout: Option<Duration>, chunks: Option<&crate::Chunks>, served: &Served, partial: &mut Partial, pause: &mut crate::pause::Pause) -> Result<(Body, Option<Sha256Hash>, BufferPermit), FetchFailure> {
+	// unsuccessful statuses were already handled by the caller
+	let status = response.status();
+	let chunks = chunks.filter(|chunks| hash && chunks.size > 0);
+	match status == reqwest::StatusCode::PARTIAL_CONTENT {
+		true if partial.received > 0 && range_start(&response) == Some(partial.received) => {},
+		// not the part that was asked for, the next attempt asks for the whole file
+		true => {
+			partial.restart();
+			return Err(FetchFailure::Fatal(DownloadError::Status(status.as_u16())));
+		},
+		false => partial.restart()
+	}
+
+	let limit = match (limits.per_file, size) {
+		(Some(per_file), Some(size)) => Some(per_file.min(size)),
+		(limit, size) => limit.or(size)
+	};
+	let too_large = |received| FetchFailure::Fatal(DownloadError::TooLarge {
+		limit: limit.unwrap_or(u64::MAX),
+		received
+	});
+
+	let offset = partial.received;
+	let length = response.content_length().map(|length| offset + length);
+	if length.zip(limit).is_some_and(|(length, limit)| length > limit) {
+		partial.restart();
+		return Err(too_large(0));
+	}
+
+	let tiny = length.is_some_and(|length| length <= batch::TINY as u64);
+	let mut permit = budget.admit(Some(length.unwrap_or(STREAM_WINDOW).min(STREAM_WINDOW))).await;
+	permit.hold(partial.resident());
+
+	let received = async {
+		loop {
+			let chunk = async {
+				match stall_timeout {
+					Some(stall_timeout) => tokio::time::timeout(stall_timeout, response.chunk()).await.map_err(|_| FetchFailure::retry(RetryReason::Stalled))?.map_err(FetchFailure::from),
+					None => response.chunk().await.map_err(FetchFailure::from)
+				}
+			};
+			let chunk = pause.unless_paused(chunk).await.ok_or(FetchFailure::Paused)??;
+			let Some(chunk) = chunk else {
+				break;
+			};
+
+			metrics.received(chunk.len() as u64);
+			limits.receive(chunk.len() as u64).map_err(FetchFailure::Fatal)?;
+			permit.hold(chunk.len() as u64);
+			partial.write(&chunk, tiny).await.map_err(|error| FetchFailure::Fatal(DownloadError::Write(error)))?;
+			// only a tiny body stays in memory once it's written
+			permit.keep(partial.resident());
+
+			if limit.is_some_and(|limit| partial.received > limit) {
+				return Err(too_large(partial.received));
+			}
+			if hash {
+				if let Err(unmatched) = partial.hash(&chunk, chunks, false, size) {
+					return Err(partial.unmatched(unmatched, served).await);
+				}
+			}
+		}
+		if let Err(unmatched) = partial.hash(&[], chunks, true, size) {
+			return Err(partial.unmatched(unmatched, served).await);
+		}
+		Ok(())
+	}.await;
+
+	if let Err(failure) = received {
+		match (&failure, chunks) {
+			// nothing went wrong with what arrived, unverified chunks and the sha so far included
+			(FetchFailure::Paused, _) => {},
+			(FetchFailure::Fatal(DownloadError::Write(_)), _) | (_, None) => partial.restart(),
+			(_, Some(_)) => partial.rewind().await
+		}
+		return Err(failure);
+	}
+
+	match partial.finish().await {
+		Ok((body, sha)) => Ok((body, hash.then_some(sha), permit)),
+		Err(error) => {
+			partial.restart();
+			Err(FetchFailure::Fatal(DownloadError::Write(error)))
+		}
+	}
+}
+
+/// Everything needed to download a single file of an upgrade.
+pub(crate) struct Download {
+	pub(crate) http: Arc<http::Http>,
+	pub(crate) progress: Progress,
+	pub(crate) relative: PathBuf,
+	pub(crate) url: Option<String>,
+	pub(crate) content_b64: Option<String>,
+	pub(crate) sha: Sha256Hash,
+	pub(crate) size: Option<u64>,
+	/// See [crate::File::mtime].
+	pub(crate) mtime: Option<i64>,
+	pub(crate) local_path: PathBuf,
+	pub(crate) hash_cache: Option<Arc<Mutex<HashCache>>>,
+	pub(crate) budget: Arc<Budget>,
+	pub(crate) rewrite_url: Option<crate::RewriteUrl>,
+	pub(crate) refresh_url: Option<crate::RefreshUrl>,
+	/// See [crate::File::headers].
+	pub(crate) headers: reqwest::header::HeaderMap,
+	/// See [crate::File::query].
+	pub(crate) query: Vec<(String, String)>,
+	/// See [crate::File::chunks].
+	pub(crate) chunks: Option<crate::Chunks>,
+	pub(crate) limits: Arc<Limits>,
+	pub(crate) stall_timeout: Option<Duration>,
+	/// Accept downloads without checking them, see [crate::UpgradeOptions::dangerously_skip_hash_verification].
+	pub(crate) skip_verification: bool,
+	pub(crate) metrics: Arc<Recorder>,
+	pub(crate) limiter: crate::DownloadLimiter,
+	pub(crate) cache: Option<crate::ObjectCache>,
+	pub(crate) source: crate::FileSource,
+	/// The redirects the last attempt followed, reported along with its error.
+	pub(crate) redirects: Mutex<Vec<String>>,
+	/// What the attempts so far ran into, reported along with the error.
+	pub(crate) failure: Mutex<crate::DownloadFailure>,
+	pub(crate) connectivity: Arc<Connectivity>,
+	pub(crate) locks: Arc<crate::in_use::Locks>,
+	pub(crate) pause: crate::pause::Pause
+}
+
+impl Download {
+	pub(crate) async fn run(self) -> Result<(), UpgradeError> {
+		self.progress.emit(UpgradeStatus::Started(self.relative.clone())).await;
+		let started = self.metrics.started();
+		let started_at = std::time::Instant::now();
+
+		match self.download().await {
+			Ok(bytes) => {
+				self.metrics.finished(started, Some(self.relative.clone()), bytes);
+				self.progress.emit(UpgradeStatus::Finished(self.relative)).await;
+				self.progress.emit(UpgradeStatus::Tick).await;
+				Ok(())
+			},
+			Err((error, attempts)) => {
+				self.metrics.finished(started, None, 0);
+				let failure = Box::new(crate::DownloadFailure {
+					attempts,
+					elapsed: started_at.elapsed(),
+					..std::mem::take(&mut *self.failure.lock().unwrap())
+				});
+				self.progress.emit(UpgradeStatus::Failed {
+					path: self.relative.clone(),
+					reason: error.to_string(),
+					attempts,
+					failure: failure.clone()
+				}).await;
+
+				if let DownloadError::Write(error) = &error {
+					if let Some(retry) = self.locks.exhausted(&self.relative) {
+						return Err(UpgradeError::Locked {
+							path: self.relative,
+							operation: retry.operation,
+							error: retry.error,
+							retries: retry.retries
+						});
+					}
+					if crate::in_use::is_in_use(error) {
+						return Err(UpgradeError::InUse { path: self.relative });
+					}
+				}
+
+				Err(UpgradeError::Download {
+					path: self.relative,
+					url: self.url.as_deref().map(http::without_userinfo),
+					attempts,
+					redirects: self.redirects.into_inner().unwrap(),
+					error,
+					failure
+				})
+			}
+		}
+	}
+
+	/// Produces the verified body of the file and how many attempts it took, the permit has to be kept until the body is dropped.
+	pub(crate) async fn contents(&self) -> Result<(Body, u64, Option<BufferPermit>), (DownloadError, u64)> {
+		match (&self.content_b64, &self.url) {
+			(Some(content), _) => Ok((self.inline(content).await.map_err(|error| (error, 1))?, 1, None)),
+			(None, Some(url)) => {
+				let (body, attempt, permit) = self.fetch(url).await?;
+				Ok((body, attempt, Some(permit)))
+			},
+			(None, None) => Err((DownloadError::NoSource, 1))
+		}
+	}
+
+	/// Writes the verified file and returns its size.
+	async fn download(&self) -> Result<u64, (DownloadError, u64)> {
+		if self.source != crate::FileSource::Network {
+			if let Some(size) = self.materialize_cached().await {
+				return Ok(size);
+			}
+		}
+		if self.source == crate::FileSource::CacheOnly && self.content_b64.is_none() {
+			return Err((DownloadError::NotCached, 0));
+		}
+
+		let (body, attempt, _permit) = match self.repair().await {
+			Some(body) => (body, 1, None),
+			None => self.contents().await?
+		};
+
+		let writing = std::time::Instant::now();
+		let metadata = match body {
+			Body::Memory(contents) => self.write(contents).await,
+			Body::File { mut temp, .. } => {
+				let write = async {
+					if let Some(mtime) = self.mtime {
+						crate::mtime::set(&temp.path, mtime).await?;
+					}
+					let metadata = temp.file().metadata().await?;
+					// a scanner that opened the fresh file stops this until it's done with it
+					self.locks.retry(&self.relative, FileOperation::Rename, || tokio::fs::rename(&temp.path, &self.local_path)).await?;
+					Ok(metadata)
+				};
+				let written = write.await;
+				if written.is_ok() {
+					temp.persist();
+				}
+				written
+			}
+		}.map_err(|error| (DownloadError::Write(error), attempt))?;
+		self.metrics.wrote(writing.elapsed());
+
+		if let Some(cache) = &self.hash_cache {
+			cache.lock().unwrap().insert(hash_cache::key(&self.relative), &metadata, self.sha);
+		}
+
+		if let Some(cache) = self.cache.clone() {
+			let (sha, local_path) = (self.sha, self.local_path.clone());
+			// the file is already where it belongs, so a cache that can't be written to isn't worth failing over
+			let _ = tokio::task::spawn_blocking(move || crate::cache::insert(&cache, &sha, &local_path)).await;
+		}
+		Ok(metadata.len())
+	}
+
+	/// Writes a body that's in memory next to the file and renames it over it, so a file that's hardlinked somewhere else is replaced rather than changed.
+	async fn write(&self, contents: bytes::Bytes) -> std::io::Result<std::fs::Metadata> {
+		let temp_path = crate::manifest::temp_path(&self.local_path)?;
+		let write = async {
+			if contents.len() <= batch::TINY {
+				match batch::write(temp_path.clone(), self.local_path.clone(), contents.clone(), self.mtime).await {
+					// the slow way retries what's locked
+					Err(error) if crate::in_use::is_locked(&error) => {},
+					written => return written
+				}
+			}
+			let mut local_file = self.locks.retry(&self.relative, FileOperation::Create, || tokio::fs::File::create(&temp_path)).await?;
+			local_file.write_all(&contents).await?;
+			local_file.flush().await?;
+			if let Some(mtime) = self.mtime {
+				crate::mtime::set(&temp_path, mtime).await?;
+			}
+			let metadata = local_file.metadata().await?;
+			// a scanner that opened the fresh file stops this until it's done with it
+			self.locks.retry(&self.relative, FileOperation::Rename, || tokio::fs::rename(&temp_path, &self.local_path)).await?;
+			Ok(metadata)
+		};
+		let written = write.await;
+		if written.is_err() {
+			let _ = tokio::fs::remove_file(&temp_path).await;
+		}
+		written
+	}
+
+	/// Downloads only the [crate::File::chunks] of the local copy that don't match, with a range request each, into a copy of it that's returned.
+	/// [None] when the whole file has to be downloaded instead: there's no local copy of the right size, most of it is damaged or the server doesn't answer range requests.
+	async fn repair(&self) -> Option<Body> {
+		let (Some(url), Some(chunks), Some(size), false) = (&self.url, &self.chunks, self.size, self.skip_verification) else {
+			return None;
+		};
+		if !tokio::fs::symlink_metadata(&self.local_path).await.is_ok_and(|metadata| metadata.is_file() && metadata.len() == size) {
+			return None;
+		}
+
+		let damaged = crate::chunks::damaged_in(&self.local_path, chunks).await.ok()??;
+		// when everything matches the whole sha didn't, so the chunks can't be trusted
+		if damaged.is_empty() || damaged.len() * 2 > chunks.shas.len() {
+			return None;
+		}
+
+		let url = match &self.rewrite_url {
+			Some(rewrite_url) => rewrite_url.rewrite(url),
+			None => url.clone()
+		};
+		let url = with_query(url, &self.query);
+		let mut temp = TempFile::copy(&self.local_path, crate::manifest::temp_path(&self.local_path).ok()?).await.ok()?;
+		for index in damaged {
+			let range = chunks.range(index, size);
+			let mut headers = self.headers.clone();
+			headers.insert(reqwest::header::RANGE, format!("bytes={}-{}", range.start, range.end - 1).parse().unwrap());
+
+			let mut permit = self.budget.admit(Some(range.end - range.start)).await;
+			let slot = self.limiter.acquire(&url).await;
+			let response = self.http.get_with_credentials(&url, &headers).await.ok()?;
+			if response.status() != reqwest::StatusCode::PARTIAL_CONTENT || range_start(&response) != Some(range.start) {
+				return None;
+			}
+			let piece = response.bytes().await.ok()?;
+			drop(slot);
+			permit.hold(piece.len() as u64);
+			self.metrics.received(piece.len() as u64);
+			self.limits.receive(piece.len() as u64).ok()?;
+			if piece.len() as u64 != range.end - range.start || Sha256Hash::digest(&piece) != chunks.shas[index] {
+				return None;
+			}
+			let file = temp.file();
+			file.seek(std::io::SeekFrom::Start(range.start)).await.ok()?;
+			file.write_all(&piece).await.ok()?;
+		}
+
+		temp.file().flush().await.ok()?;
+		let sha = {
+			let path = temp.path.clone();
+			tokio::task::spawn_blocking(move || sha_of(&path)).await.unwrap().ok()?
+		};
+		self.verify(Body::File {
+			temp,
+			length: size
+		}, Some(sha), None).await.ok()
+	}
+
+	/// Puts the file in place from the [crate::ObjectCache] if it has it, returns its size.
+	async fn materialize_cached(&self) -> Option<u64> {
+		let cache = self.cache.as_ref()?;
+		let object = crate::cache::hit(cache, self.sha, self.size).await?;
+
+		let writing = std::time::Instant::now();
+		let (local_path, materialize) = (self.local_path.clone(), cache.materialize);
+		let linked = tokio::task::spawn_blocking(move || crate::cache::materialize(&object, &local_path, materialize)).await.unwrap().ok()?;
+		if let Some(mtime) = self.mtime {
+			crate::mtime::set(&self.local_path, mtime).await.ok()?;
+		}
+		let metadata = tokio::fs::metadata(&self.local_path).await.ok()?;
+		self.metrics.wrote(writing.elapsed());
+		self.metrics.cached(metadata.len(), linked);
+
+		if let Some(cache) = &self.hash_cache {
+			cache.lock().unwrap().insert(hash_cache::key(&self.relative), &metadata, self.sha);
+		}
+		Some(metadata.len())
+	}
+
+	/// Decodes inline content, nothing is retried since there's no transfer that could have gone wrong.
+	async fn inline(&self, content: &str) -> Result<Body, DownloadError> {
+		let contents = base64::engine::general_purpose::STANDARD.decode(content).map_err(|_| DownloadError::InvalidContent)?;
+		let sha = Sha256Hash::digest(&contents);
+		match self.verify(Body::Memory(contents.into()), Some(sha), None).await {
+			Ok(body) => Ok(body),
+			Err(FetchFailure::Retry(_, error) | FetchFailure::Fatal(error)) => Err(error),
+			Err(FetchFailure::Paused) => unreachable!("checking a sha never pauses")
+		}
+	}
+
+	/// Downloads and verifies the file, retrying failures that might go away, returns the body and how many attempts it took.
+	async fn fetch(&self, manifest_url: &str) -> Result<(Body, u64, BufferPermit), (DownloadError, u64)> {
+		let url = match &self.rewrite_url {
+			Some(rewrite_url) => rewrite_url.rewrite(manifest_url),
+			None => manifest_url.to_owned()
+		};
+		let mut url = with_query(url, &self.query);
+
+		let mut attempt = 1;
+		let mut refreshes = 0;
+		let mut partial = Partial::new(crate::manifest::temp_path(&self.local_path).map_err(|error| (DownloadError::Write(error), 1))?);
+		let mut pause = self.pause.clone();
+		loop {
+			if self.connectivity.offline().is_some() {
+				return Err((DownloadError::Offline, attempt - 1));
+			}
+
+			// pausing while waiting for a slot leaves it to downloads that aren't paused
+			let slot = loop {
+				if !pause.resumed().await {
+					return Err((DownloadError::Cancelled, attempt - 1));
+				}
+				if let Some(slot) = pause.unless_paused(self.limiter.acquire(&url)).await {
+					break slot;
+				}
+			};
+			// refreshed urls and file queries usually have credentials in them
+			let redact = refreshes > 0 || !self.query.is_empty();
+			let shown_url = http::redact(&url);
+			{
+				let mut failure = self.failure.lock().unwrap();
+				let mut tried_urls = std::mem::take(&mut failure.tried_urls);
+				if tried_urls.last() != Some(&shown_url) {
+					tried_urls.push(shown_url.clone());
+				}
+				*failure = crate::DownloadFailure {
+					final_url: Some(shown_url),
+					tried_urls,
+					..Default::default()
+				};
+			}
+			let mut retry_after = None;
+			let headers = match partial.received {
+				0 => std::borrow::Cow::Borrowed(&self.headers),
+				received => {
+					let mut headers = self.headers.clone();
+					headers.insert(reqwest::header::RANGE, format!("bytes={received}-").parse().unwrap());
+					std::borrow::Cow::Owned(headers)
+				}
+			};
+			let result = match self.http.get_with_credentials(&url, &headers).await {
+				Ok(response) => {
+					self.connectivity.connected();
+					*self.redirects.lock().unwrap() = match redact {
+						false => http::redirects(&response).to_vec(),
+						true => http::redirects(&response).iter().map(|url| http::redact(url)).collect()
+					};
+					let served = Served::of(&response, redact);
+					self.responded(&response, &served);
+					let status = response.status();
+					match status.is_success() {
+						true => match fetch(response, &self.budget, &self.limits, &self.metrics, self.size, !self.skip_verification, self.stall_timeout, self.chunks.as_ref(), &served, &mut partial, &mut pause).await {
+							Ok((body, sha, permit)) => self.verify(body, sha, Some(&served)).await.map(|body| (body, permit)),
+							Err(failure) => Err(failure)
+						},
+						false => {
+							retry_after = crate::fetch::retry_after(&response);
+							// before the slot is given to another download from the host
+							if let Some(retry_after) = retry_after {
+								self.limiter.cool_down(&url, retry_after);
+							}
+							let snippet = error_snippet(response).await;
+							self.failure.lock().unwrap().snippet = snippet;
+							Err(match status {
+								_ if status.is_server_error() => FetchFailure::retry(RetryReason::ServerError(status.as_u16())),
+								reqwest::StatusCode::TOO_MANY_REQUESTS => FetchFailure::retry(RetryReason::RateLimited),
+								_ => FetchFailure::Fatal(DownloadError::Status(status.as_u16()))
+							})
+						}
+					}
+				},
+				Err(error) => {
+					match &error {
+						http::GetError::Request(error, _) if error.is_connect() && redact => self.connectivity.failed(&http::redact(&url)),
+						http::GetError::Request(error, _) if error.is_connect() => self.connectivity.failed(&http::without_userinfo(&url)),
+						_ => self.connectivity.connected()
+					}
+					let error = match redact {
+						false => error,
+						true => error.redacted()
+					};
+					*self.redirects.lock().unwrap() = error.redirects().to_vec();
+					if let Some(last) = error.redirects().last() {
+						self.failure.lock().unwrap().final_url = Some(http::redact(last));
+					}
+					Err(error.into())
+				}
+			};
+			drop(slot);
+
+			let (reason, error) = match result {
+				Ok((body, permit)) => return Ok((body, attempt, permit)),
+				Err(FetchFailure::Paused) => continue,
+				Err(FetchFailure::Retry(reason, error)) => (reason, error),
+				Err(FetchFailure::Fatal(DownloadError::Status(status @ (401 | 403)))) if self.refresh_url.is_some() && refreshes < crate::MAX_URL_REFRESHES => {
+					match self.refresh_url.as_ref().unwrap().refresh(manifest_url).await {
+						Ok(refreshed) => {
+							url = with_query(refreshed, &self.query);
+							refreshes += 1;
+							(RetryReason::Refreshed(status), DownloadError::Status(status))
+						},
+						Err(error) => return Err((DownloadError::RefreshFailed {
+							status,
+							reason: error.to_string()
+						}, attempt))
+					}
+				},
+				Err(FetchFailure::Fatal(error)) => return Err((error, attempt))
+			};
+
+			if attempt >= MAX_ATTEMPTS {
+				return Err((error, attempt));
+			}
+
+			let delay = match reason {
+				RetryReason::Refreshed(_) => Duration::ZERO,
+				_ => tokio::time::Duration::from_millis(attempt * 250)
+			};
+			let delay = match retry_after {
+				Some(retry_after) => delay.max(retry_after.min(crate::fetch::MAX_RETRY_AFTER)),
+				None => delay
+			};
+			attempt += 1;
+			self.metrics.retried(&reason);
+			self.progress.emit(UpgradeStatus::Retrying {
+				path: self.relative.clone(),
+				attempt,
+				max_attempts: MAX_ATTEMPTS,
+				delay,
+				reason
+			}).await;
+			// cut short when pausing or stopping, both are waited out at the start of the next attempt
+			pause.unless_paused(tokio::time::sleep(delay)).await;
+		}
+	}
+
+	/// Keeps what the response of an attempt said for [crate::DownloadFailure].
+	fn responded(&self, response: &reqwest::Response, served: &Served) {
+		let header = |name| response.headers().get(name).and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok()).map(str::to_owned);
+		let mut failure = self.failure.lock().unwrap();
+		failure.final_url = Some(http::redact(&served.url));
+		failure.status = Some(response.status().as_u16());
+		failure.content_type = served.content_type.clone();
+		failure.content_length = served.content_length;
+		failure.server = header(reqwest::header::SERVER);
+	}
+
+	/// Checks the sha of the downloaded bytes against the manifest, a mismatch is retried since the transfer may have been corrupted.
+	/// Without a sha the bytes are accepted as they are. `served` is [None] for inline content.
+	async fn verify(&self, mut body: Body, downloaded_sha: Option<Sha256Hash>, served: Option<&Served>) -> Result<Body, FetchFailure> {
+		self.progress.emit(UpgradeStatus::Progressed(self.relative.clone(), body.len())).await;
+
+		if let Some(downloaded_sha) = downloaded_sha.filter(|downloaded_sha| *downloaded_sha != self.sha) {
+			return Err(FetchFailure::Retry(RetryReason::ShaMismatch, DownloadError::ShaMismatch {
+				expected: self.sha,
+				found: downloaded_sha,
+				bytes: body.len(),
+				diagnostics: diagnostics(served, &body.sniff().await, self.size)
+			}));
+		}
+
+		Ok(body)
+	}
+}
+
+/// Downloads the file at `/` separated `relative` in `directory` to the same path under `root`, see [crate::Directory::download_single].
+pub(crate) async fn download_single(directory: &crate::Directory, relative: &str, root: &std::path::Path, options: &crate::UpgradeOptions, progress: Progress) -> Result<(), UpgradeError> {
+	let http = crate::upgrade::http_for(options).map_err(UpgradeError::Client)?;
+	let directory = &*crate::upgrade::prepared(directory, &http, options.separators).await?;
+
+	let relative_path = PathBuf::from(relative);
+	let mut components: Vec<_> = relative.split('/').collect();
+	let name = components.pop().unwrap();
+	let not_in_manifest = || UpgradeError::NotInManifest(relative_path.clone());
+	if components.iter().chain([&name]).any(|component| matches!(*component, "" | "." | "..")) {
+		return Err(not_in_manifest());
+	}
+
+	let mut parent = directory;
+	let mut url_template = directory.url_template.as_deref();
+	for component in components {
+		parent = parent.children.get(component).ok_or_else(not_in_manifest)?;
+		url_template = parent.url_template.as_deref().or(url_template);
+	}
+	let file = match parent.files.get(name) {
+		Some(file) => crate::upgrade::to_fetch(file, url_template),
+		None if parent.children.contains_key(name) || parent.links.contains_key(name) => return Err(UpgradeError::NotAFile(relative_path)),
+		None => return Err(not_in_manifest())
+	};
+
+	download_to(&file, &relative_path, &root.join(&relative_path), options, progress).await
+}
+
+/// Downloads a single file to `dest` with the same [Download] an upgrade uses, `relative` is its path in events and errors, see [crate::File::download_to].
+pub(crate) async fn download_to(file: &crate::File, relative: &std::path::Path, dest: &std::path::Path, options: &crate::UpgradeOptions, progress: Progress) -> Result<(), UpgradeError> {
+	if let Some(parent) = dest.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+		tokio::fs::create_dir_all(parent).await.map_err(|error| UpgradeError::Local {
+			path: parent.to_owned(),
+			error
+		})?;
+	}
+
+	let (pause, _running) = crate::pause::new(options.pause.as_ref(), &progress);
+	let download = Download {
+		http: crate::upgrade::http_for(options).map_err(UpgradeError::Client)?,
+		progress,
+		relative: relative.to_owned(),
+		url: file.url.clone(),
+		content_b64: file.content_b64.clone(),
+		sha: file.sha,
+		size: file.size,
+		mtime: file.mtime,
+		local_path: dest.to_owned(),
+		hash_cache: None,
+		budget: Arc::new(Budget::new(options.buffer_budget.unwrap_or(crate::DEFAULT_BUFFER_BUDGET))),
+		rewrite_url: options.rewrite_url.clone(),
+		refresh_url: options.refresh_url.clone(),
+		headers: file_headers(file),
+		query: file.query.clone(),
+		chunks: file.chunks.clone(),
+		limits: Arc::new(Limits::new(&options.limits)),
+		stall_timeout: options.stall_timeout,
+		skip_verification: options.dangerously_skip_hash_verification,
+		metrics: Arc::new(Recorder::new()),
+		limiter: options.download_limiter.clone().unwrap_or_else(|| crate::DownloadLimiter::new(crate::DEFAULT_CONCURRENT_DOWNLOADS)),
+		cache: options.cache.clone(),
+		source: options.source,
+		redirects: Default::default(),
+		failure: Default::default(),
+		connectivity: Arc::default(),
+		locks: Arc::new(crate::in_use::Locks::new(options.lock_retries)),
+		pause
+	};
+	download.run().await
+}
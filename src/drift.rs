@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+use notify::Watcher;
+use crate::hash_cache;
+use crate::upgrade::LocalHasher;
+use crate::{Directory, DriftEvent, DriftOptions, FolderWatcher, UpgradeOptions};
+
+/// What a scan saw of a local entry, enough to tell that it changed without reading it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Stat {
+	is_dir: bool,
+	len: u64,
+	modified: Option<SystemTime>
+}
+
+/// Local entries by `/` separated relative path.
+type Snapshot = HashMap<String, Stat>;
+
+/// Watches `path` with the notifications of the OS until the [FolderWatcher] is dropped, sending what settled changes mean for `directory`.
+/// Paths an event is about are looked at again once they had no events for [DriftOptions::debounce], an empty path stands for the whole folder.
+pub(crate) fn watch(directory: &Directory, path: &Path, options: &DriftOptions) -> std::io::Result<FolderWatcher> {
+	let root = std::fs::canonicalize(path)?;
+	let (notifications, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+	let mut watcher = notify::recommended_watcher(move |event| {
+		let _ = notifications.send(event);
+	}).map_err(io_error)?;
+	watcher.watch(&root, notify::RecursiveMode::Recursive).map_err(io_error)?;
+
+	let (directory, debounce) = (directory.clone(), options.debounce);
+	let (tx, events) = tokio::sync::mpsc::channel(64);
+	let task = tokio::spawn(async move {
+		let hasher = LocalHasher::new(&root, &UpgradeOptions::default()).await.0;
+		let mut snapshot = scan(&directory, &root, "").await;
+		let mut pending: HashMap<String, Instant> = HashMap::new();
+
+		loop {
+			let settles = pending.values().min().map(|last_change| *last_change + debounce);
+			let notification = match settles {
+				Some(settles) => tokio::time::timeout_at(settles.into(), receiver.recv()).await.ok(),
+				None => Some(receiver.recv().await)
+			};
+			match notification {
+				// the watcher only goes away with the FolderWatcher
+				Some(None) => return,
+				Some(Some(notification)) => {
+					for relative in changed(&directory, &root, notification) {
+						pending.insert(relative, Instant::now());
+					}
+					continue;
+				},
+				None => {}
+			}
+
+			let now = Instant::now();
+			let mut settled: Vec<_> = pending.iter().filter(|(_, last_change)| now.duration_since(**last_change) >= debounce).map(|(relative, _)| relative.clone()).collect();
+			settled.sort();
+			for relative in settled {
+				pending.remove(&relative);
+				for event in rescan(&directory, &root, &hasher, &mut snapshot, &relative).await {
+					if tx.send(event).await.is_err() {
+						return;
+					}
+				}
+			}
+		}
+	});
+
+	Ok(FolderWatcher {
+		receiver: events,
+		task,
+		_watcher: watcher
+	})
+}
+
+fn io_error(error: notify::Error) -> std::io::Error {
+	match error.kind {
+		notify::ErrorKind::Io(error) => error,
+		_ => std::io::Error::other(error)
+	}
+}
+
+/// The relative paths a notification is about that an upgrade would look at, the whole folder when the OS lost track of what changed.
+fn changed(directory: &Directory, root: &Path, notification: notify::Result<notify::Event>) -> Vec<String> {
+	let event = match notification {
+		Ok(event) if !event.need_rescan() => event,
+		_ => return vec![String::new()]
+	};
+	if event.kind.is_access() {
+		return vec![];
+	}
+
+	event.paths.iter().filter_map(|path| {
+		let relative: Vec<_> = path.strip_prefix(root).ok()?.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+		let top_level = relative.first()?;
+		let is_managed = directory.files.contains_key(&**top_level) || directory.links.contains_key(&**top_level) || directory.children.contains_key(&**top_level);
+		(is_managed && top_level != hash_cache::STATE_DIR).then(|| relative.join("/"))
+	}).collect()
+}
+
+/// Looks at `relative` and everything below it again, sending what changed since `snapshot` saw it and bringing `snapshot` up to date.
+async fn rescan(directory: &Directory, root: &Path, hasher: &LocalHasher, snapshot: &mut Snapshot, relative: &str) -> Vec<DriftEvent> {
+	let below = |path: &str| relative.is_empty() || path == relative || path.strip_prefix(relative).is_some_and(|rest| rest.starts_with('/'));
+	let next = scan(directory, root, relative).await;
+	let mut changed: Vec<_> = next.iter().filter(|(path, stat)| snapshot.get(*path) != Some(stat)).map(|(path, _)| path.clone())
+		.chain(snapshot.keys().filter(|path| below(path) && !next.contains_key(*path)).cloned())
+		.collect();
+	changed.sort();
+
+	let mut events = vec![];
+	for path in changed {
+		let (before, after) = (snapshot.get(&path).copied(), next.get(&path).copied());
+		events.extend(classify(directory, root, hasher, &path, before, after).await);
+	}
+	snapshot.retain(|path, _| !below(path));
+	snapshot.extend(next);
+	events
+}
+
+enum Managed<'a> {
+	File(&'a crate::File),
+	Link(&'a str),
+	Directory,
+	/// Not in the manifest, but in one of its directories so an upgrade would delete it.
+	Unknown,
+	/// Below something unknown or at the top level, where an upgrade doesn't look.
+	Ignored
+}
+
+fn managed<'a>(directory: &'a Directory, relative: &str) -> Managed<'a> {
+	let mut components: Vec<_> = relative.split('/').collect();
+	let name = components.pop().unwrap();
+	let mut parent = directory;
+	for component in &components {
+		match parent.children.get(*component) {
+			Some(child) => parent = child,
+			None => return Managed::Ignored
+		}
+	}
+
+	if let Some(file) = parent.files.get(name) {
+		Managed::File(file)
+	} else if let Some(target) = parent.links.get(name) {
+		Managed::Link(target)
+	} else if parent.children.contains_key(name) {
+		Managed::Directory
+	} else if components.is_empty() {
+		Managed::Ignored
+	} else {
+		Managed::Unknown
+	}
+}
+
+/// What a settled change means, managed files are only hashed here once they stopped changing.
+async fn classify(directory: &Directory, path: &Path, hasher: &LocalHasher, relative: &str, before: Option<Stat>, after: Option<Stat>) -> Option<DriftEvent> {
+	let local_path = path.join(relative);
+	let relative_path = PathBuf::from(relative);
+	match (managed(directory, relative), before, after) {
+		(Managed::File(_) | Managed::Link(_), Some(_), None) => Some(DriftEvent::Deleted(relative_path)),
+		(Managed::File(file), _, Some(stat)) => match stat.is_dir {
+			true => Some(DriftEvent::Modified(relative_path)),
+			false => match hasher.local_sha(&local_path, &relative_path).await {
+				Ok(sha) if sha == file.sha => None,
+				_ => Some(DriftEvent::Modified(relative_path))
+			}
+		},
+		(Managed::Link(target), _, Some(_)) => {
+			let target: PathBuf = target.split('/').filter(|component| !component.is_empty() && *component != ".").collect();
+			match tokio::fs::read_link(&local_path).await {
+				Ok(existing) if existing.components().eq(target.components()) => None,
+				_ => Some(DriftEvent::Modified(relative_path))
+			}
+		},
+		(Managed::Unknown, None, Some(_)) => Some(DriftEvent::Appeared(relative_path)),
+		_ => None
+	}
+}
+
+/// Everything an upgrade would look at at `relative` and below it: the manifest's entries at the top level and everything in its directories, without going into unknown directories.
+async fn scan(directory: &Directory, path: &Path, relative: &str) -> Snapshot {
+	let mut snapshot = Snapshot::new();
+	let mut stack = vec![];
+	match relative.is_empty() {
+		true => stack.push((directory, path.to_owned(), String::new())),
+		false => {
+			let local_path = path.join(relative);
+			let (managed, metadata) = (managed(directory, relative), tokio::fs::symlink_metadata(&local_path).await);
+			if let (false, Ok(metadata)) = (matches!(managed, Managed::Ignored), metadata) {
+				snapshot.insert(relative.to_owned(), stat(&metadata));
+				if let (true, Managed::Directory) = (metadata.is_dir(), managed) {
+					let child = relative.split('/').try_fold(directory, |parent, name| parent.children.get(name));
+					stack.extend(child.map(|child| (child, local_path, relative.to_owned())));
+				}
+			}
+		}
+	}
+
+	while let Some((directory, path, relative)) = stack.pop() {
+		let Ok(mut entries) = tokio::fs::read_dir(&path).await else {
+			continue;
+		};
+		while let Ok(Some(entry)) = entries.next_entry().await {
+			let name = entry.file_name().to_string_lossy().into_owned();
+			let top_level = relative.is_empty();
+			if top_level && (name == hash_cache::STATE_DIR || !(directory.files.contains_key(&name) || directory.links.contains_key(&name) || directory.children.contains_key(&name))) {
+				continue;
+			}
+			let Ok(metadata) = tokio::fs::symlink_metadata(entry.path()).await else {
+				continue;
+			};
+
+			let child_relative = match top_level {
+				true => name.clone(),
+				false => format!("{relative}/{name}")
+			};
+			snapshot.insert(child_relative.clone(), stat(&metadata));
+			if let (true, Some(child)) = (metadata.is_dir(), directory.children.get(&name)) {
+				stack.push((child, entry.path(), child_relative));
+			}
+		}
+	}
+	snapshot
+}
+
+fn stat(metadata: &std::fs::Metadata) -> Stat {
+	Stat {
+		is_dir: metadata.is_dir(),
+		len: metadata.len(),
+		modified: metadata.modified().ok()
+	}
+}
@@ -0,0 +1,123 @@
+use std::ffi::OsString;
+use std::fs::{FileType, Metadata};
+use std::path::{Path, PathBuf};
+use crate::{FromPathOptions, UpgradeOptions};
+#[cfg(feature = "runtime-agnostic")]
+use crate::Spawner;
+
+/// Where the blocking filesystem work of a scan or verification runs, tokio's blocking threads unless a [Spawner] was given.
+#[derive(Clone, Default)]
+pub(crate) enum Executor {
+	#[default]
+	Tokio,
+	#[cfg(feature = "runtime-agnostic")]
+	Spawner(Spawner)
+}
+
+/// An entry of a folder listed with [Executor::read_dir].
+pub(crate) struct Entry {
+	pub(crate) name: OsString,
+	pub(crate) path: PathBuf,
+	pub(crate) file_type: std::io::Result<FileType>
+}
+
+impl Executor {
+	/// The [UpgradeOptions::spawner] of `options`, tokio without one.
+	pub(crate) fn of(options: &UpgradeOptions) -> Self {
+		#[cfg(feature = "runtime-agnostic")]
+		if let Some(spawner) = &options.spawner {
+			return Executor::Spawner(spawner.clone());
+		}
+		#[cfg(not(feature = "runtime-agnostic"))]
+		let _ = options;
+		Executor::Tokio
+	}
+
+	/// The [FromPathOptions::spawner] of `options`, tokio without one.
+	pub(crate) fn of_scan(options: &FromPathOptions) -> Self {
+		#[cfg(feature = "runtime-agnostic")]
+		if let Some(spawner) = &options.spawner {
+			return Executor::Spawner(spawner.clone());
+		}
+		#[cfg(not(feature = "runtime-agnostic"))]
+		let _ = options;
+		Executor::Tokio
+	}
+
+	/// Whether this needs a tokio runtime to be awaited in.
+	pub(crate) fn is_tokio(&self) -> bool {
+		matches!(self, Executor::Tokio)
+	}
+
+	/// Runs `job` where blocking is fine, a panic in it is passed on.
+	pub(crate) async fn blocking<T: Send + 'static>(&self, job: impl FnOnce() -> T + Send + 'static) -> T {
+		match self {
+			Executor::Tokio => tokio::task::spawn_blocking(job).await.unwrap(),
+			#[cfg(feature = "runtime-agnostic")]
+			Executor::Spawner(spawner) => {
+				let (tx, rx) = futures_channel::oneshot::channel();
+				spawner.spawn(Box::new(move || {
+					let _ = tx.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)));
+				}));
+				match rx.await {
+					Ok(Ok(result)) => result,
+					Ok(Err(panic)) => std::panic::resume_unwind(panic),
+					Err(_) => panic!("the Spawner dropped a task without running it")
+				}
+			}
+		}
+	}
+
+	pub(crate) async fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+		let path = path.to_owned();
+		self.blocking(move || std::fs::metadata(path)).await
+	}
+
+	pub(crate) async fn symlink_metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+		let path = path.to_owned();
+		self.blocking(move || std::fs::symlink_metadata(path)).await
+	}
+
+	pub(crate) async fn read_link(&self, path: &Path) -> std::io::Result<PathBuf> {
+		let path = path.to_owned();
+		self.blocking(move || std::fs::read_link(path)).await
+	}
+
+	pub(crate) async fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+		let path = path.to_owned();
+		self.blocking(move || std::fs::read(path)).await
+	}
+
+	pub(crate) async fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+		let path = path.to_owned();
+		self.blocking(move || std::fs::read_to_string(path)).await
+	}
+
+	/// Writes `contents` to `path` through a temporary file next to it, creating the folder it's in.
+	pub(crate) async fn write_replacing(&self, path: &Path, contents: Vec<u8>) -> std::io::Result<()> {
+		let path = path.to_owned();
+		self.blocking(move || {
+			if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+				std::fs::create_dir_all(parent)?;
+			}
+			let temp_path = path.with_extension("tmp");
+			std::fs::write(&temp_path, contents)?;
+			std::fs::rename(temp_path, path)
+		}).await
+	}
+
+	/// Lists a whole folder at once, entries whose type can't be told have the error instead.
+	pub(crate) async fn read_dir(&self, path: &Path) -> std::io::Result<Vec<Entry>> {
+		let path = path.to_owned();
+		self.blocking(move || {
+			std::fs::read_dir(path)?.map(|entry| {
+				let entry = entry?;
+				Ok(Entry {
+					name: entry.file_name(),
+					path: entry.path(),
+					file_type: entry.file_type()
+				})
+			}).collect()
+		}).await
+	}
+}
@@ -0,0 +1,280 @@
+use std::time::Duration;
+use crate::{http, ManifestError, ParseError};
+use crate::hash::HashAlgorithm;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::hash::Hasher;
+use crate::manifest::{Manifest, validated};
+
+/// Feeds a response body to a parser on a blocking thread, hashing the bytes as they're read.
+#[cfg(not(target_arch = "wasm32"))]
+struct StreamReader {
+	receiver: tokio::sync::mpsc::Receiver<bytes::Bytes>,
+	chunk: bytes::Bytes,
+	hasher: Hasher,
+	locator: crate::manifest::Locator
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::io::Read for StreamReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		while self.chunk.is_empty() {
+			match self.receiver.blocking_recv() {
+				Some(chunk) => self.chunk = chunk,
+				None => return Ok(0)
+			}
+		}
+
+		let length = buf.len().min(self.chunk.len());
+		let bytes = self.chunk.split_to(length);
+		self.hasher.update(&bytes);
+		self.locator.push(&bytes);
+		buf[..length].copy_from_slice(&bytes);
+		Ok(length)
+	}
+}
+
+/// Fetches a manifest, parsing the body as it arrives instead of buffering the whole text first.
+/// When a digest is expected it's computed over the same bytes with its [HashAlgorithm] and the parsed manifest is only returned if it matches.
+pub(crate) async fn manifest<T: Manifest + Send + 'static, U: AsRef<str>>(url: U, expected_sha: Option<&str>) -> Result<T, ManifestError> {
+	manifest_with(&http::shared(), url, expected_sha).await
+}
+
+/// The longest a Retry-After header can make a manifest fetch wait.
+pub(crate) const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+enum FetchFailure {
+	/// Worth another attempt, after the server's Retry-After if it sent one.
+	Retry(ManifestError, Option<Duration>),
+	Fatal(ManifestError)
+}
+
+impl From<reqwest::Error> for FetchFailure {
+	fn from(error: reqwest::Error) -> Self {
+		let retry = error.is_timeout() || error.is_request() || error.is_body();
+		#[cfg(not(target_arch = "wasm32"))]
+		let retry = retry || error.is_connect();
+		match retry {
+			true => FetchFailure::Retry(ManifestError::Request(error), None),
+			false => FetchFailure::Fatal(ManifestError::Request(error))
+		}
+	}
+}
+
+impl From<http::GetError> for FetchFailure {
+	fn from(error: http::GetError) -> Self {
+		match error {
+			http::GetError::Request(error, _) => error.into(),
+			http::GetError::Redirect(error) => FetchFailure::Fatal(ManifestError::Redirect(error)),
+			http::GetError::Token(reason) => FetchFailure::Fatal(ManifestError::Token(reason))
+		}
+	}
+}
+
+/// Same as [manifest] with a specific client instead of the shared one.
+/// Connection errors, timeouts and server errors are retried up to [http::HttpConfig::manifest_attempts] times, except on wasm32 where there's no timer to wait between attempts with.
+pub(crate) async fn manifest_with<T: Manifest + Send + 'static, U: AsRef<str>>(http: &http::Http, url: U, expected_sha: Option<&str>) -> Result<T, ManifestError> {
+	let expected = expected_sha.map(|expected| HashAlgorithm::split(expected).map(|(algorithm, _)| (algorithm, expected))).transpose().map_err(ManifestError::UnsupportedHash)?;
+	#[cfg(not(target_arch = "wasm32"))]
+	let attempts = http.manifest_attempts().max(1);
+	#[cfg(target_arch = "wasm32")]
+	let attempts = 1;
+	let mut attempt = 1;
+	loop {
+		let (error, retry_after) = match fetch_once(http, url.as_ref(), expected).await {
+			Ok(manifest) => return Ok(manifest),
+			Err(FetchFailure::Fatal(error)) => return Err(error),
+			Err(FetchFailure::Retry(error, retry_after)) => (error, retry_after)
+		};
+
+		if attempt >= attempts {
+			return Err(match attempts {
+				1 => error,
+				_ => ManifestError::Exhausted {
+					attempts,
+					error: Box::new(error)
+				}
+			});
+		}
+
+		let delay = retry_after.map_or(Duration::from_millis(attempt * 250), |retry_after| retry_after.min(MAX_RETRY_AFTER));
+		attempt += 1;
+		tokio::time::sleep(delay).await;
+	}
+}
+
+/// A Retry-After header in seconds, the HTTP date form isn't worth a date parser here.
+pub(crate) fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+	let seconds = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+	Some(Duration::from_secs(seconds))
+}
+
+async fn fetch_once<T: Manifest + Send + 'static>(http: &http::Http, url: &str, expected: Option<(HashAlgorithm, &str)>) -> Result<T, FetchFailure> {
+	from_response(http, http.get(url).await?, expected).await
+}
+
+/// What the server said to a conditional request.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) enum Conditional<T> {
+	Modified(T, Validators),
+	NotModified
+}
+
+/// The `ETag` and `Last-Modified` of the last response, sent back as `If-None-Match` and `If-Modified-Since`.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub(crate) struct Validators {
+	etag: Option<reqwest::header::HeaderValue>,
+	last_modified: Option<reqwest::header::HeaderValue>
+}
+
+/// Fetches a manifest once unless it's unchanged since the response `validators` came from, for polling without downloading the same manifest over and over.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn manifest_if_changed<T: Manifest + Send + 'static>(http: &http::Http, url: &str, validators: &Validators) -> Result<Conditional<T>, ManifestError> {
+	use reqwest::header;
+
+	let mut headers = header::HeaderMap::new();
+	headers.extend(validators.etag.clone().map(|etag| (header::IF_NONE_MATCH, etag)));
+	headers.extend(validators.last_modified.clone().map(|last_modified| (header::IF_MODIFIED_SINCE, last_modified)));
+
+	let result = async {
+		let response = http.get_with_headers(url, &headers).await?;
+		if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+			return Ok(Conditional::NotModified);
+		}
+
+		let validators = Validators {
+			etag: response.headers().get(header::ETAG).cloned(),
+			last_modified: response.headers().get(header::LAST_MODIFIED).cloned()
+		};
+		Ok(Conditional::Modified(from_response(http, response, None).await?, validators))
+	};
+	result.await.map_err(|failure| match failure {
+		FetchFailure::Retry(error, _) | FetchFailure::Fatal(error) => error
+	})
+}
+
+/// Checks the status of a manifest response and parses its body.
+async fn from_response<T: Manifest + Send + 'static>(http: &http::Http, response: reqwest::Response, expected: Option<(HashAlgorithm, &str)>) -> Result<T, FetchFailure> {
+	let status = response.status();
+	if status.is_server_error() {
+		return Err(FetchFailure::Retry(ManifestError::Status(status.as_u16()), retry_after(&response)));
+	} else if !status.is_success() {
+		return Err(FetchFailure::Fatal(ManifestError::Status(status.as_u16())));
+	}
+
+	let content_type = response.headers().get(reqwest::header::CONTENT_TYPE).map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+	let algorithm = expected.map_or_else(HashAlgorithm::default, |(algorithm, _)| algorithm);
+	let Body { manifest, digest: found, head } = parse_body::<T>(response, http.max_manifest_size(), algorithm).await?;
+	let manifest = manifest.map_err(|error| FetchFailure::Fatal(ManifestError::NotManifest {
+		status: status.as_u16(),
+		content_type,
+		snippet: snippet(&head),
+		error
+	}))?;
+	if let Some((_, expected)) = expected {
+		// an explicit sha256: prefix isn't part of what's computed
+		if !found.eq_ignore_ascii_case(expected.strip_prefix("sha256:").unwrap_or(expected)) {
+			return Err(FetchFailure::Fatal(ManifestError::IntegrityMismatch {
+				expected: expected.to_owned(),
+				found
+			}));
+		}
+	}
+
+	manifest.check_limits(&http.manifest_limits()).map_err(FetchFailure::Fatal)?;
+	validated(manifest).map_err(FetchFailure::Fatal)
+}
+
+/// The start of a body for an error message, cut at [crate::MANIFEST_SNIPPET_LENGTH] characters with control characters escaped so it stays on one line.
+pub(crate) fn snippet(head: &[u8]) -> String {
+	let text = String::from_utf8_lossy(head);
+	let mut snippet = String::new();
+	for (index, character) in text.chars().enumerate() {
+		if index == crate::MANIFEST_SNIPPET_LENGTH {
+			snippet.push('…');
+			break;
+		}
+		match character.is_control() || character == '"' || character == '\\' {
+			true => snippet.extend(character.escape_default()),
+			false => snippet.push(character)
+		}
+	}
+	snippet
+}
+
+/// A parsed response body.
+struct Body<T> {
+	manifest: Result<T, Box<ParseError>>,
+	/// The digest of the bytes read, as [HashAlgorithm::digest] writes it.
+	digest: String,
+	/// Enough of the first bytes for a [snippet].
+	head: Vec<u8>
+}
+
+/// How many bytes of a body are kept for a [snippet], a character takes up to 4.
+pub(crate) const HEAD_LENGTH: usize = crate::MANIFEST_SNIPPET_LENGTH * 4 + 1;
+
+fn too_large(limit: u64, received: u64) -> FetchFailure {
+	FetchFailure::Fatal(ManifestError::TooLarge {
+		limit,
+		received
+	})
+}
+
+/// Parses a response body as it arrives and hashes the same bytes.
+#[cfg(not(target_arch = "wasm32"))]
+async fn parse_body<T: Manifest + Send + 'static>(mut response: reqwest::Response, limit: Option<u64>, algorithm: HashAlgorithm) -> Result<Body<T>, FetchFailure> {
+	let (sender, receiver) = tokio::sync::mpsc::channel(16);
+	let parse = tokio::task::spawn_blocking(move || {
+		let mut reader = StreamReader {
+			receiver,
+			chunk: bytes::Bytes::new(),
+			hasher: algorithm.hasher(),
+			locator: crate::manifest::Locator::new()
+		};
+		let manifest = serde_json::from_reader::<_, T>(std::io::BufReader::new(&mut reader));
+		let manifest = manifest.map_err(|error| reader.locator.locate(error));
+		(manifest, reader.hasher.finalize())
+	});
+
+	let mut received = 0;
+	let mut head = vec![];
+	while let Some(chunk) = response.chunk().await? {
+		received += chunk.len() as u64;
+		head.extend_from_slice(&chunk[..chunk.len().min(HEAD_LENGTH - head.len())]);
+		if let Some(limit) = limit.filter(|limit| received > *limit) {
+			return Err(too_large(limit, received));
+		}
+
+		// the parser only hangs up early when the manifest is already broken
+		if sender.send(chunk).await.is_err() {
+			break;
+		}
+	}
+	drop(sender);
+
+	let (manifest, digest) = parse.await.unwrap();
+	Ok(Body {
+		manifest,
+		digest,
+		head
+	})
+}
+
+/// The browser only hands over whole bodies and there are no threads to parse on, so the body is parsed once it's all there.
+#[cfg(target_arch = "wasm32")]
+async fn parse_body<T: Manifest>(response: reqwest::Response, limit: Option<u64>, algorithm: HashAlgorithm) -> Result<Body<T>, FetchFailure> {
+	if let Some(limit) = limit.filter(|limit| response.content_length().is_some_and(|length| length > *limit)) {
+		return Err(too_large(limit, 0));
+	}
+
+	let body = response.bytes().await?;
+	if let Some(limit) = limit.filter(|limit| body.len() as u64 > *limit) {
+		return Err(too_large(limit, body.len() as u64));
+	}
+	Ok(Body {
+		manifest: serde_json::from_slice(&body).map_err(|error| crate::manifest::located(error, &body)),
+		digest: algorithm.digest(&body),
+		head: body[..body.len().min(HEAD_LENGTH)].to_vec()
+	})
+}
@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use crate::{Directory, File, Side};
+#[cfg(feature = "net")]
+use crate::ValidationError;
+
+/// Drops the files below `directory` that `keep` rejects, children that are left empty are dropped too. Returns whether `directory` was left empty by it.
+pub(crate) fn retain<F: FnMut(&Path, &File) -> bool>(directory: &mut Directory, relative: &mut PathBuf, keep: &mut F) -> bool {
+	let was_empty = directory.files.is_empty() && directory.links.is_empty() && directory.children.is_empty();
+	directory.files.retain(|name, file| {
+		relative.push(name);
+		let kept = keep(relative, file);
+		relative.pop();
+		kept
+	});
+	directory.children.retain(|name, child| {
+		relative.push(name);
+		let emptied = retain(child, relative, keep);
+		relative.pop();
+		!emptied
+	});
+	!was_empty && directory.files.is_empty() && directory.links.is_empty() && directory.children.is_empty()
+}
+
+/// The part of `directory` that's installed for `side`, without copying it when that's all of it.
+pub(crate) fn for_side<'a>(directory: &'a Directory, side: &Side) -> Cow<'a, Directory> {
+	fn keep(directory: &mut Directory, side: &Side) {
+		directory.files.retain(|_, file| file.side.includes(side));
+		directory.children.retain(|_, child| child.side.includes(side));
+		for child in directory.children.values_mut() {
+			keep(child, side);
+		}
+	}
+
+	if side.is_both() {
+		return Cow::Borrowed(directory);
+	}
+	let mut sided = directory.clone();
+	keep(&mut sided, side);
+	Cow::Owned(sided)
+}
+
+/// Calls `edit` with every file below `directory`.
+pub(crate) fn map_files<F: FnMut(&Path, &mut File)>(directory: &mut Directory, relative: &mut PathBuf, edit: &mut F) {
+	for (name, file) in &mut directory.files {
+		relative.push(name);
+		edit(relative, file);
+		relative.pop();
+	}
+	for (name, child) in &mut directory.children {
+		relative.push(name);
+		map_files(child, relative, edit);
+		relative.pop();
+	}
+}
+
+/// The url `rewrite` returns for `url`, unless it's the same one.
+#[cfg(feature = "net")]
+fn rewritten<F: FnMut(&str) -> Option<String>>(url: &str, rewrite: &mut F) -> Option<String> {
+	rewrite(url).filter(|rewritten| rewritten != url)
+}
+
+#[cfg(feature = "net")]
+fn invalid_url(relative: &Path, url: &str) -> ValidationError {
+	ValidationError::InvalidUrl {
+		path: relative.to_owned(),
+		url: crate::http::without_userinfo(url)
+	}
+}
+
+/// Replaces the file urls and url templates below `directory` that `rewrite` returns another one for, returns how many it replaced.
+/// Stops at the first that isn't valid, leaving `directory` half rewritten.
+#[cfg(feature = "net")]
+pub(crate) fn rewrite_urls<F: FnMut(&str) -> Option<String>>(directory: &mut Directory, relative: &mut PathBuf, rewrite: &mut F) -> Result<usize, ValidationError> {
+	let is_url = |url: &str| reqwest::Url::parse(url).is_ok();
+	let mut count = 0;
+
+	if let Some(template) = &mut directory.url_template {
+		if let Some(url) = rewritten(template, rewrite) {
+			// a template only becomes a url once there's a sha in it
+			if !crate::template::expand(&url, &crate::hash::Sha256Hash([0; 32])).is_ok_and(|expanded| is_url(&expanded)) {
+				return Err(invalid_url(relative, &url));
+			}
+			*template = url;
+			count += 1;
+		}
+	}
+	for (name, file) in &mut directory.files {
+		let Some(url) = file.url.as_deref().and_then(|url| rewritten(url, rewrite)) else {
+			continue;
+		};
+		if !is_url(&url) {
+			return Err(invalid_url(&relative.join(name), &url));
+		}
+		file.url = Some(url);
+		count += 1;
+	}
+	for (name, child) in &mut directory.children {
+		relative.push(name);
+		count += rewrite_urls(child, relative, rewrite)?;
+		relative.pop();
+	}
+	Ok(count)
+}
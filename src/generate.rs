@@ -0,0 +1,124 @@
+use base64::Engine;
+use std::path::Path;
+use crate::executor::{Entry, Executor};
+use crate::hash::Sha256Hash;
+use crate::hash_cache::{self, HashCache};
+use crate::ignore::Rules;
+use crate::{Chunks, Directory, Excluded, File, FromPathOptions};
+
+fn name(name: std::ffi::OsString) -> std::io::Result<String> {
+	name.into_string().map_err(|name| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{name:?} isn't valid UTF-8")))
+}
+
+/// Walks a folder to build its manifest, collecting what it left out and the shas it found along the way.
+pub(crate) struct Generator<'a> {
+	pub(crate) options: &'a FromPathOptions,
+	pub(crate) executor: Executor,
+	pub(crate) rules: &'a Rules,
+	/// The shas of an earlier scan, trusted for files whose size and modification time didn't change.
+	pub(crate) previous: Option<&'a HashCache>,
+	pub(crate) excluded: Vec<Excluded>,
+	/// The shas of every file of this scan.
+	pub(crate) cache: HashCache,
+	/// How many files got their sha from [Generator::previous] instead of being hashed.
+	pub(crate) reused: usize
+}
+
+impl<'a> Generator<'a> {
+	/// Builds a manifest from the contents of `path`, `url_prefix` is the already encoded url of `path` itself and ends with a `/`.
+	/// `relative` is the `/` separated path of `path` in the folder, empty for the folder itself, and `previous` what an earlier manifest has there, for the chunks of files that didn't change.
+	#[async_recursion::async_recursion]
+	pub(crate) async fn directory(&mut self, path: &Path, url_prefix: &str, relative: &str, previous: Option<&'a Directory>) -> std::io::Result<Directory> {
+		let options = self.options;
+		let mut directory = Directory::default();
+		let entries = self.executor.read_dir(path).await?;
+
+		for Entry { name: entry_name, path: entry_path, file_type } in entries {
+			let name = name(entry_name)?;
+			let file_type = file_type?;
+
+			if relative.is_empty() && (name == hash_cache::STATE_DIR || name == crate::IGNORE_FILE) {
+				continue;
+			}
+
+			let entry_relative = match relative.is_empty() {
+				true => name.clone(),
+				false => format!("{relative}/{name}")
+			};
+			if let Some((pattern, source)) = self.rules.excluded(&entry_relative, file_type.is_dir()).filter(|_| file_type.is_dir() || file_type.is_file()) {
+				self.excluded.push(Excluded {
+					path: entry_relative.into(),
+					pattern: pattern.to_owned(),
+					source: source.clone()
+				});
+				continue;
+			}
+
+			if file_type.is_dir() {
+				let previous_child = previous.and_then(|previous| previous.children.get(&name));
+				let child = self.directory(&entry_path, &format!("{url_prefix}{}/", crate::util::encode_path_segment(&name)), &entry_relative, previous_child).await?;
+				directory.children.insert(name, child);
+			} else if file_type.is_file() {
+				let metadata = self.executor.symlink_metadata(&entry_path).await?;
+				let mtime = match options.record_mtimes {
+					true => crate::mtime::of(&metadata),
+					false => None
+				};
+				let inline = options.inline_below.is_some_and(|limit| metadata.len() < limit);
+				let chunk_size = options.chunks_above
+					.filter(|above| !inline && metadata.len() >= *above)
+					.map(|_| options.chunk_size.unwrap_or(crate::DEFAULT_CHUNK_SIZE));
+
+				let (sha, size, content_b64, chunks) = match self.unchanged(&entry_relative, &metadata, inline, chunk_size, previous.and_then(|previous| previous.files.get(&name))) {
+					Some((sha, chunks)) => {
+						self.reused += 1;
+						(sha, metadata.len(), None, chunks)
+					},
+					None => {
+						let (contents, sha, chunks) = self.executor.blocking(move || {
+							let contents = std::fs::read(entry_path)?;
+							let sha = Sha256Hash::digest(&contents);
+							let chunks = chunk_size.map(|size| Chunks::of(&contents, size));
+							Ok::<_, std::io::Error>((contents, sha, chunks))
+						}).await?;
+						let content_b64 = inline.then(|| base64::engine::general_purpose::STANDARD.encode(&contents));
+						(sha, contents.len() as u64, content_b64, chunks)
+					}
+				};
+				self.cache.insert(entry_relative, &metadata, sha);
+
+				let file = File {
+					sha,
+					size: Some(size),
+					url: (!inline && options.url_template.is_none()).then(|| format!("{url_prefix}{}", crate::util::encode_path_segment(&name))),
+					content_b64,
+					mtime,
+					side: Default::default(),
+					os: vec![],
+					arch: vec![],
+					headers: Default::default(),
+					query: vec![],
+					chunks,
+					extra: Default::default()
+				};
+				directory.files.insert(name, file);
+			}
+		}
+
+		Ok(directory)
+	}
+
+	/// The sha of a file the earlier scan already hashed, with the chunks it needs, if it didn't change and doesn't have to be read anyway.
+	/// Inline files are read for their contents, chunks are only taken from `previous` when it has the same sha and chunk size.
+	fn unchanged(&self, relative: &str, metadata: &std::fs::Metadata, inline: bool, chunk_size: Option<u64>, previous: Option<&File>) -> Option<(Sha256Hash, Option<Chunks>)> {
+		let sha = self.previous.filter(|_| !inline)?.get(relative, metadata)?;
+		match chunk_size {
+			None => Some((sha, None)),
+			Some(size) => previous
+				.filter(|previous| previous.sha == sha)
+				.and_then(|previous| previous.chunks.clone())
+				.filter(|chunks| chunks.size == size)
+				.map(|chunks| (sha, Some(chunks)))
+		}
+	}
+}
@@ -0,0 +1,235 @@
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use sha2::{Sha256, Sha512, Digest};
+
+/// A sha256 digest, (de)serialized as a hex string like the rest of the manifest format.
+/// Parsing accepts upper or lower case hex, displaying always produces lower case.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Sha256Hash(pub [u8; 32]);
+
+impl Sha256Hash {
+	/// # Description
+	/// Hashes some bytes.
+	pub fn digest<B: AsRef<[u8]>>(bytes: B) -> Self {
+		Self(Sha256::digest(bytes).into())
+	}
+}
+
+impl std::fmt::Display for Sha256Hash {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for byte in self.0 {
+			write!(f, "{byte:02x}")?;
+		}
+		Ok(())
+	}
+}
+
+impl std::fmt::Debug for Sha256Hash {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Sha256Hash({self})")
+	}
+}
+
+/// A string that isn't 64 hex characters.
+#[derive(Debug, Clone)]
+pub struct ParseSha256Error {
+	pub input: String
+}
+
+impl std::fmt::Display for ParseSha256Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "invalid sha256 {:?}, expected 64 hex characters", self.input)
+	}
+}
+
+impl std::error::Error for ParseSha256Error {}
+
+impl std::str::FromStr for Sha256Hash {
+	type Err = ParseSha256Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let error = || ParseSha256Error {
+			input: s.to_owned()
+		};
+
+		if s.len() != 64 {
+			return Err(error());
+		}
+
+		let nibble = |c: u8| (c as char).to_digit(16).map(|n| n as u8).ok_or_else(error);
+
+		let mut digest = [0; 32];
+		for (byte, pair) in digest.iter_mut().zip(s.as_bytes().chunks(2)) {
+			*byte = nibble(pair[0])? << 4 | nibble(pair[1])?;
+		}
+		Ok(Self(digest))
+	}
+}
+
+impl Serialize for Sha256Hash {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for Sha256Hash {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct Visitor;
+
+		impl serde::de::Visitor<'_> for Visitor {
+			type Value = Sha256Hash;
+
+			fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				write!(f, "a hex encoded sha256")
+			}
+
+			fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+				v.parse().map_err(E::custom)
+			}
+		}
+
+		deserializer.deserialize_str(Visitor)
+	}
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for Sha256Hash {
+	fn schema_name() -> String {
+		"Sha256Hash".to_owned()
+	}
+
+	fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+		schemars::schema::SchemaObject {
+			instance_type: Some(schemars::schema::InstanceType::String.into()),
+			string: Some(Box::new(schemars::schema::StringValidation {
+				pattern: Some("^[0-9a-fA-F]{64}$".to_owned()),
+				..Default::default()
+			})),
+			..Default::default()
+		}.into()
+	}
+}
+
+/// What the digest of a manifest in a packs list is computed with, written as a prefix like `sha512:` in front of the hex.
+/// Digests without a prefix are sha256, which is what older versions understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HashAlgorithm {
+	#[default]
+	Sha256,
+	Sha512,
+	/// Written as `blake3:`, only with the `blake3` feature, without it such digests fail with [UnsupportedHashAlgorithm].
+	#[cfg(feature = "blake3")]
+	Blake3
+}
+
+impl HashAlgorithm {
+	/// # Description
+	/// The prefix of the algorithm without the `:`.
+	pub fn name(self) -> &'static str {
+		match self {
+			HashAlgorithm::Sha256 => "sha256",
+			HashAlgorithm::Sha512 => "sha512",
+			#[cfg(feature = "blake3")]
+			HashAlgorithm::Blake3 => "blake3"
+		}
+	}
+
+	fn hex_length(self) -> usize {
+		match self {
+			HashAlgorithm::Sha256 => 64,
+			HashAlgorithm::Sha512 => 128,
+			#[cfg(feature = "blake3")]
+			HashAlgorithm::Blake3 => 64
+		}
+	}
+
+	/// # Description
+	/// Hashes some bytes into a digest as a packs list writes it, sha256 ones don't get a prefix so older versions can still check them.
+	pub fn digest<B: AsRef<[u8]>>(self, bytes: B) -> String {
+		let mut hasher = self.hasher();
+		hasher.update(bytes.as_ref());
+		hasher.finalize()
+	}
+
+	pub(crate) fn hasher(self) -> Hasher {
+		match self {
+			HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+			HashAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+			#[cfg(feature = "blake3")]
+			HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new()))
+		}
+	}
+
+	/// # Description
+	/// Splits a digest like `sha512:<hex>` into its algorithm and the hex, a digest without a prefix is sha256.
+	/// The hex isn't checked, only the algorithm.
+	pub fn split(digest: &str) -> Result<(HashAlgorithm, &str), UnsupportedHashAlgorithm> {
+		let Some((algorithm, hex)) = digest.split_once(':') else {
+			return Ok((HashAlgorithm::Sha256, digest));
+		};
+
+		match algorithm.to_ascii_lowercase().as_str() {
+			"sha256" => Ok((HashAlgorithm::Sha256, hex)),
+			"sha512" => Ok((HashAlgorithm::Sha512, hex)),
+			#[cfg(feature = "blake3")]
+			"blake3" => Ok((HashAlgorithm::Blake3, hex)),
+			_ => Err(UnsupportedHashAlgorithm {
+				algorithm: algorithm.to_owned()
+			})
+		}
+	}
+}
+
+/// Whether `digest` is a digest a packs list can have, one with an algorithm this version doesn't know only has to be hex so the list still loads.
+pub(crate) fn is_valid_digest(digest: &str) -> bool {
+	let is_hex = |hex: &str| !hex.is_empty() && hex.bytes().all(|byte| byte.is_ascii_hexdigit());
+	match HashAlgorithm::split(digest) {
+		Ok((algorithm, hex)) => hex.len() == algorithm.hex_length() && is_hex(hex),
+		Err(_) => is_hex(digest.split_once(':').unwrap().1)
+	}
+}
+
+/// A [HashAlgorithm] being computed.
+pub(crate) enum Hasher {
+	Sha256(Sha256),
+	Sha512(Sha512),
+	#[cfg(feature = "blake3")]
+	Blake3(Box<blake3::Hasher>)
+}
+
+impl Hasher {
+	pub(crate) fn update(&mut self, bytes: &[u8]) {
+		match self {
+			Hasher::Sha256(hasher) => hasher.update(bytes),
+			Hasher::Sha512(hasher) => hasher.update(bytes),
+			#[cfg(feature = "blake3")]
+			Hasher::Blake3(hasher) => {
+				hasher.update(bytes);
+			}
+		}
+	}
+
+	/// The digest as [HashAlgorithm::digest] writes it.
+	pub(crate) fn finalize(self) -> String {
+		let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+		match self {
+			Hasher::Sha256(hasher) => hex(&hasher.finalize()),
+			Hasher::Sha512(hasher) => format!("sha512:{}", hex(&hasher.finalize())),
+			#[cfg(feature = "blake3")]
+			Hasher::Blake3(hasher) => format!("blake3:{}", hasher.finalize().to_hex())
+		}
+	}
+}
+
+/// A digest uses an algorithm this version of green-lib doesn't have, like one added in a newer version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedHashAlgorithm {
+	pub algorithm: String
+}
+
+impl std::fmt::Display for UnsupportedHashAlgorithm {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "the manifest is hashed with {}, which needs a newer green-lib", self.algorithm)
+	}
+}
+
+impl std::error::Error for UnsupportedHashAlgorithm {}
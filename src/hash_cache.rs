@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::executor::Executor;
+use crate::hash::Sha256Hash;
+
+/// Folder inside the upgraded folder where green-lib keeps its own files, it's never treated as unknown.
+pub(crate) const STATE_DIR: &str = ".green-lib";
+const FILE_NAME: &str = "hashcache.bin";
+const MAGIC: &[u8; 8] = b"GLHASH01";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Entry {
+	size: u64,
+	mtime: (u64, u32),
+	sha: Sha256Hash
+}
+
+/// Remembers the sha of local files by relative path, trusted as long as the size and modification time still match.
+#[derive(Default)]
+pub(crate) struct HashCache {
+	entries: HashMap<String, Entry>
+}
+
+fn mtime(metadata: &std::fs::Metadata) -> Option<(u64, u32)> {
+	let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+	Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+/// Cache keys are relative paths with `/` separators so the cache doesn't depend on the platform, the same form `sha256sum` listings use.
+pub(crate) use crate::checksums::key;
+
+fn path(root: &Path) -> PathBuf {
+	root.join(STATE_DIR).join(FILE_NAME)
+}
+
+impl HashCache {
+	/// Loads the cache of a folder, a missing or corrupt cache just starts over empty.
+	pub(crate) async fn load(root: &Path, executor: &Executor) -> Self {
+		Self::load_file(&path(root), executor).await
+	}
+
+	pub(crate) async fn save(&self, root: &Path, executor: &Executor) -> std::io::Result<()> {
+		self.save_file(&path(root), executor).await
+	}
+
+	/// Same as [HashCache::load] for a cache kept somewhere else than in the folder it's about.
+	pub(crate) async fn load_file(path: &Path, executor: &Executor) -> Self {
+		match executor.read(path).await {
+			Ok(contents) => Self::decode(&contents).unwrap_or_default(),
+			Err(_) => Self::default()
+		}
+	}
+
+	/// Writes the cache to `path` through a temporary file, creating the folder it's in.
+	pub(crate) async fn save_file(&self, path: &Path, executor: &Executor) -> std::io::Result<()> {
+		executor.write_replacing(path, self.encode()).await
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Returns the cached sha if the file hasn't changed since it was hashed.
+	pub(crate) fn get(&self, key: &str, metadata: &std::fs::Metadata) -> Option<Sha256Hash> {
+		let entry = self.entries.get(key)?;
+		(entry.size == metadata.len() && Some(entry.mtime) == mtime(metadata)).then_some(entry.sha)
+	}
+
+	pub(crate) fn insert(&mut self, key: String, metadata: &std::fs::Metadata, sha: Sha256Hash) {
+		if let Some(mtime) = mtime(metadata) {
+			self.entries.insert(key, Entry {
+				size: metadata.len(),
+				mtime,
+				sha
+			});
+		}
+	}
+
+	/// Forgets a file, or everything under a directory.
+	pub(crate) fn remove(&mut self, key: &str) {
+		let prefix = format!("{key}/");
+		self.entries.retain(|path, _| path != key && !path.starts_with(&prefix));
+	}
+
+	fn encode(&self) -> Vec<u8> {
+		let mut out = MAGIC.to_vec();
+		out.extend((self.entries.len() as u64).to_le_bytes());
+
+		for (key, entry) in &self.entries {
+			out.extend((key.len() as u64).to_le_bytes());
+			out.extend(key.as_bytes());
+			out.extend(entry.size.to_le_bytes());
+			out.extend(entry.mtime.0.to_le_bytes());
+			out.extend(entry.mtime.1.to_le_bytes());
+			out.extend(entry.sha.0);
+		}
+
+		out
+	}
+
+	fn decode(mut contents: &[u8]) -> Option<Self> {
+		fn take<const N: usize>(contents: &mut &[u8]) -> Option<[u8; N]> {
+			let mut bytes = [0; N];
+			contents.read_exact(&mut bytes).ok()?;
+			Some(bytes)
+		}
+
+		if &take::<8>(&mut contents)? != MAGIC {
+			return None;
+		}
+
+		let count = u64::from_le_bytes(take(&mut contents)?);
+		let mut entries = HashMap::new();
+
+		for _ in 0..count {
+			let key_length = u64::from_le_bytes(take(&mut contents)?) as usize;
+			if key_length > contents.len() {
+				return None;
+			}
+			let (key, rest) = contents.split_at(key_length);
+			let key = String::from_utf8(key.to_vec()).ok()?;
+			contents = rest;
+
+			entries.insert(key, Entry {
+				size: u64::from_le_bytes(take(&mut contents)?),
+				mtime: (u64::from_le_bytes(take(&mut contents)?), u32::from_le_bytes(take(&mut contents)?)),
+				sha: Sha256Hash(take(&mut contents)?)
+			});
+		}
+
+		Some(Self {
+			entries
+		})
+	}
+}
+
@@ -0,0 +1,147 @@
+use std::path::Path;
+use crate::executor::Executor;
+use crate::{ExcludeSource, FromPathOptions, IGNORE_FILE};
+
+/// A single gitignore-style pattern.
+struct Rule {
+	pattern: String,
+	source: ExcludeSource,
+	negated: bool,
+	/// Only matches directories, the pattern ended with a `/`.
+	directory_only: bool,
+	/// The `/` separated components, a pattern without a `/` (other than a trailing one) matches at any depth like it started with `**/`.
+	components: Vec<String>
+}
+
+impl Rule {
+	/// Parses a line, [None] for blank lines and comments.
+	fn parse(line: &str, source: ExcludeSource) -> Option<Self> {
+		let pattern = trim_trailing_spaces(line.trim_start_matches('\u{feff}'));
+		if pattern.is_empty() || pattern.starts_with('#') {
+			return None;
+		}
+
+		let (negated, rest) = match pattern.strip_prefix('!') {
+			Some(rest) => (true, rest),
+			None => (false, pattern.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(pattern))
+		};
+		let (directory_only, rest) = match rest.strip_suffix('/') {
+			Some(rest) => (true, rest),
+			None => (false, rest)
+		};
+		let anchored = rest.contains('/');
+		let mut components: Vec<String> = rest.trim_start_matches('/').split('/').filter(|component| !component.is_empty()).map(str::to_owned).collect();
+		if components.is_empty() {
+			return None;
+		}
+		if !anchored {
+			components.insert(0, "**".to_owned());
+		}
+
+		Some(Self {
+			pattern: pattern.to_owned(),
+			source,
+			negated,
+			directory_only,
+			components
+		})
+	}
+
+	fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+		(is_dir || !self.directory_only) && components_match(&self.components, path)
+	}
+}
+
+/// Trailing spaces are dropped unless they're escaped with a backslash.
+fn trim_trailing_spaces(line: &str) -> &str {
+	let mut end = line.trim_end_matches(['\r', '\n']).len();
+	while line[..end].ends_with(' ') && !line[..end - 1].ends_with('\\') {
+		end -= 1;
+	}
+	&line[..end]
+}
+
+/// `**` matches any number of components, except at the end where it needs at least one, so `build/**` is what's inside `build`.
+fn components_match(pattern: &[String], path: &[&str]) -> bool {
+	match (pattern.split_first(), path.split_first()) {
+		(None, None) => true,
+		(None, Some(_)) => false,
+		(Some((first, [])), _) if first == "**" => !path.is_empty(),
+		(Some((first, rest)), _) if first == "**" => (0..=path.len()).any(|skipped| components_match(rest, &path[skipped..])),
+		(Some(_), None) => false,
+		(Some((first, rest)), Some((component, path))) => wildcard_match(first.as_bytes(), component.as_bytes()) && components_match(rest, path)
+	}
+}
+
+/// Matches one component against `*`, `?`, `[...]` classes (with `!` or `^` to negate and `a-z` ranges) and backslash escapes.
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+	match pattern.split_first() {
+		None => name.is_empty(),
+		Some((b'*', rest)) => (0..=name.len()).any(|skipped| wildcard_match(rest, &name[skipped..])),
+		Some((b'?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+		Some((b'[', rest)) => match (class(rest), name.split_first()) {
+			(Some((matches, rest)), Some((byte, name))) => matches(*byte) && wildcard_match(rest, name),
+			(Some(_), None) => false,
+			// an unclosed class is a literal bracket
+			(None, _) => name.first() == Some(&b'[') && wildcard_match(rest, &name[1..])
+		},
+		Some((b'\\', [escaped, rest @ ..])) => name.first() == Some(escaped) && wildcard_match(rest, &name[1..]),
+		Some((byte, rest)) => name.first() == Some(byte) && wildcard_match(rest, &name[1..])
+	}
+}
+
+/// Parses a class after its `[`, returning what it matches and the pattern after its `]`.
+fn class(pattern: &[u8]) -> Option<(impl Fn(u8) -> bool + '_, &[u8])> {
+	let (negated, body) = match pattern.first() {
+		Some(b'!' | b'^') => (true, &pattern[1..]),
+		_ => (false, pattern)
+	};
+	// a `]` right at the start is part of the class
+	let end = body.iter().skip(1).position(|byte| *byte == b']')? + 1;
+	let members = &body[..end];
+	let matches = move |byte: u8| {
+		let mut index = 0;
+		let mut found = false;
+		while index < members.len() {
+			match members.get(index + 1..index + 3) {
+				Some([b'-', last]) => {
+					found |= (members[index]..=*last).contains(&byte);
+					index += 3;
+				},
+				_ => {
+					found |= members[index] == byte;
+					index += 1;
+				}
+			}
+		}
+		found != negated
+	};
+	Some((matches, &body[end + 1..]))
+}
+
+/// The patterns [crate::Directory::from_path] leaves entries out with, the ignore file's first and then [FromPathOptions::exclude].
+/// Like in a gitignore the last pattern that matches decides, so the programmatic ones can re-include what the file leaves out and the other way around.
+pub(crate) struct Rules(Vec<Rule>);
+
+impl Rules {
+	/// Reads the [IGNORE_FILE] of `root` if it has one.
+	pub(crate) async fn load(root: &Path, options: &FromPathOptions, executor: &Executor) -> std::io::Result<Self> {
+		let mut rules = vec![];
+		match executor.read_to_string(&root.join(IGNORE_FILE)).await {
+			Ok(contents) => rules.extend(contents.lines().enumerate().filter_map(|(index, line)| Rule::parse(line, ExcludeSource::IgnoreFile {
+				line: index + 1
+			}))),
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+			Err(error) => return Err(error)
+		}
+		rules.extend(options.exclude.iter().filter_map(|pattern| Rule::parse(pattern, ExcludeSource::Options)));
+		Ok(Self(rules))
+	}
+
+	/// The pattern that leaves out the entry at `relative` (`/` separated), if one does.
+	pub(crate) fn excluded(&self, relative: &str, is_dir: bool) -> Option<(&str, &ExcludeSource)> {
+		let path: Vec<_> = relative.split('/').collect();
+		let rule = self.0.iter().rev().find(|rule| rule.matches(&path, is_dir))?;
+		(!rule.negated).then_some((rule.pattern.as_str(), &rule.source))
+	}
+}
@@ -0,0 +1,172 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::{FileOperation, LockError, LockRetries, LockRetry};
+
+/// Whether an error means another process has the file open in a way that stops us from changing it, which Windows reports instead of letting a running game lose its files.
+pub(crate) fn is_in_use(error: &std::io::Error) -> bool {
+	matches!(lock_error(error), Some(LockError::SharingViolation | LockError::LockViolation))
+}
+
+/// Whether an error is one [Locks::retry] would retry.
+pub(crate) fn is_locked(error: &std::io::Error) -> bool {
+	lock_error(error).is_some()
+}
+
+/// The kind of lock behind an error on Windows, where another program holding a file makes operations on it fail.
+fn lock_error(error: &std::io::Error) -> Option<LockError> {
+	if !cfg!(windows) {
+		return None;
+	}
+	match error.raw_os_error() {
+		Some(5) => Some(LockError::AccessDenied),
+		Some(32) => Some(LockError::SharingViolation),
+		Some(33) => Some(LockError::LockViolation),
+		_ => None
+	}
+}
+
+/// Retries file operations that fail because another program holds the file for a moment and remembers which ones were, see [crate::UpgradeOptions::lock_retries].
+pub(crate) struct Locks {
+	policy: LockRetries,
+	retried: Mutex<Vec<LockRetry>>
+}
+
+impl Locks {
+	pub(crate) fn new(policy: LockRetries) -> Self {
+		Self {
+			policy,
+			retried: Mutex::default()
+		}
+	}
+
+	/// Runs `attempt` until it doesn't fail with a [lock_error] or the retries run out, `relative` is the path of the file it's about.
+	pub(crate) async fn retry<T, F, Fut>(&self, relative: &Path, operation: FileOperation, mut attempt: F) -> std::io::Result<T>
+	where
+		F: FnMut() -> Fut,
+		Fut: Future<Output = std::io::Result<T>>
+	{
+		let mut result = attempt().await;
+		let (mut retries, mut delay, mut last) = (0, self.policy.delay, None);
+		while let Some(error) = result.as_ref().err().and_then(lock_error) {
+			last = Some(error);
+			if retries == self.policy.retries {
+				break;
+			}
+			tokio::time::sleep(delay).await;
+			delay *= 2;
+			retries += 1;
+			result = attempt().await;
+		}
+
+		if let (Some(error), true) = (last, retries > 0) {
+			self.retried.lock().unwrap().push(LockRetry {
+				path: relative.to_owned(),
+				operation,
+				error,
+				retries,
+				resolved: result.is_ok()
+			});
+		}
+		result
+	}
+
+	/// The last operation on `relative` if it was retried and still failed with a sharing or lock violation, which makes it [crate::UpgradeError::Locked] instead of [crate::UpgradeError::InUse].
+	pub(crate) fn exhausted(&self, relative: &Path) -> Option<LockRetry> {
+		self.retried.lock().unwrap().iter().rev()
+			.find(|retry| retry.path == relative)
+			.filter(|retry| !retry.resolved && retry.error != LockError::AccessDenied)
+			.cloned()
+	}
+
+	/// Every retried operation, sorted by path.
+	pub(crate) fn take(&self) -> Vec<LockRetry> {
+		let mut retried = std::mem::take(&mut *self.retried.lock().unwrap());
+		retried.sort_by(|a, b| a.path.cmp(&b.path));
+		retried
+	}
+}
+
+/// Looks for signs that a game is running from `path`, returns the file or folder that gave it away.
+pub(crate) async fn probe(path: &Path) -> Option<PathBuf> {
+	let path = tokio::fs::canonicalize(path).await.ok()?;
+	tokio::task::spawn_blocking(move || open_by_process(&path).or_else(|| locked_file(&path))).await.unwrap()
+}
+
+/// Another process holding a file in `path` open, from `/proc`.
+/// Only open files count, a shell or file manager whose working directory is in `path` doesn't stop an upgrade.
+#[cfg(target_os = "linux")]
+fn open_by_process(path: &Path) -> Option<PathBuf> {
+	let own = std::process::id().to_string();
+	for process in std::fs::read_dir("/proc").ok()?.flatten() {
+		let pid = process.file_name();
+		if pid == own.as_str() || !pid.to_string_lossy().bytes().all(|byte| byte.is_ascii_digit()) {
+			continue;
+		}
+
+		// other users' processes can't be inspected, which is fine since they can't be using our files either
+		let Ok(descriptors) = std::fs::read_dir(process.path().join("fd")) else {
+			continue;
+		};
+		for descriptor in descriptors.flatten() {
+			if let Ok(target) = std::fs::read_link(descriptor.path()) {
+				if target.starts_with(path) {
+					return Some(target);
+				}
+			}
+		}
+	}
+
+	None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_by_process(_path: &Path) -> Option<PathBuf> {
+	None
+}
+
+/// The files a running game keeps open: the lock of every world, the current log and the jars it loaded.
+fn candidates(path: &Path) -> Vec<PathBuf> {
+	let mut candidates = vec![path.join("logs").join("latest.log")];
+	let children = |folder: PathBuf| std::fs::read_dir(folder).into_iter().flatten().flatten().map(|entry| entry.path());
+
+	candidates.extend(children(path.join("saves")).map(|world| world.join("session.lock")));
+	candidates.extend(children(path.join("mods")).filter(|path| path.extension().is_some_and(|extension| extension == "jar")));
+	candidates.extend(children(path.join("versions")).flat_map(children).filter(|path| path.extension().is_some_and(|extension| extension == "jar")));
+	candidates
+}
+
+/// Opens the [candidates] without sharing them, which fails on Windows while the game has them open.
+#[cfg(windows)]
+fn locked_file(path: &Path) -> Option<PathBuf> {
+	use std::os::windows::fs::OpenOptionsExt;
+
+	candidates(path).into_iter().find(|candidate| {
+		std::fs::OpenOptions::new().read(true).share_mode(0).open(candidate).is_err_and(|error| is_in_use(&error))
+	})
+}
+
+/// Asks whether another process holds a lock on the `session.lock` of a world among the [candidates], which the game does while the world is open.
+/// Java locks them with `fcntl`, which `flock` doesn't see on Linux, so it's asked with `F_GETLK` without taking a lock.
+#[cfg(unix)]
+fn locked_file(path: &Path) -> Option<PathBuf> {
+	use std::os::fd::AsRawFd;
+
+	candidates(path).into_iter().filter(|candidate| candidate.ends_with("session.lock")).find(|candidate| {
+		let Ok(file) = std::fs::File::open(candidate) else {
+			return false;
+		};
+		// SAFETY: flock is plain data, all zeroes is a valid value for it
+		let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+		lock.l_type = libc::F_WRLCK as libc::c_short;
+		lock.l_whence = libc::SEEK_SET as libc::c_short;
+		// SAFETY: the descriptor stays open until the file is dropped and F_GETLK only writes to `lock`
+		let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) };
+		result == 0 && lock.l_type != libc::F_UNLCK as libc::c_short
+	})
+}
+
+#[cfg(not(any(unix, windows)))]
+fn locked_file(_path: &Path) -> Option<PathBuf> {
+	None
+}
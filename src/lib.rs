@@ -1,178 +1,3462 @@
+//! Installs minecraft mods from a manifest.
+//!
+//! What's available depends on the features:
+//! - without any, the manifest types ([Directory], [File], [packs::PacksListManifest]), their validation and serialization, and [hash], [template] and [util]
+//! - `net` adds [http] and fetching manifests, packs lists and what they point to
+//! - `fs` (on by default, implies `net`) adds upgrading, verifying and generating folders and reading and writing manifest files
+//! - `zip` adds exporting archives and installing [Bootstrap] archives
+//! - `publish` adds [publish], laying a folder out for a static web host
+//! - `test-util` adds [test_util], a local server and manifest builder for testing code built on this crate
+//! - `runtime-agnostic` adds [Spawner] and futures channels for progress, so scans and verifications can run without tokio
+//!
+//! `net` also needs a TLS backend on native targets, exactly one of `rustls-tls` (the default) or `native-tls`.
+//! To use `native-tls` turn off the default features and pick `fs` or `net` again.
+//!
+//! The async functions of `net` and `fs` run on tokio, they spawn tasks, sleep and use its blocking threads for the filesystem, so they have to be awaited inside a tokio runtime (with its time driver enabled).
+//! Awaited anywhere else they fail with [NoRuntime] instead of panicking, as [ManifestError::NoRuntime], [UpgradeError::NoRuntime] or an [std::io::Error] that wraps it.
+//! From another executor like smol or async-std keep a tokio runtime around and await them through it, `runtime.spawn(future)` gives a `JoinHandle` that can be awaited anywhere, upgrades can also be given one as [UpgradeOptions::runtime].
+//! Scanning with [Directory::from_path] and friends and [Directory::verify_folder] (whose report is what a repair or a `green-sync` plan goes by) don't need a runtime with the `runtime-agnostic` feature when they're given a [Spawner] in their options, they run their blocking work on it and progress can go to a futures channel ([Progress::Futures]).
+//! Everything that downloads still needs tokio then, reqwest runs on it, so `net` and `fs` still depend on it.
+//! The manifest types and everything else that's there without any features don't depend on a runtime.
+
+#[cfg(all(feature = "net", not(target_arch = "wasm32"), not(any(feature = "rustls-tls", feature = "native-tls"))))]
+compile_error!("green-lib needs a TLS backend with `net`, enable either the `rustls-tls` or the `native-tls` feature");
+
+#[cfg(all(feature = "rustls-tls", feature = "native-tls"))]
+compile_error!("the `rustls-tls` and `native-tls` features of green-lib can't be enabled together");
+
+use base64::Engine;
 use serde::{Serialize, Deserialize};
-use sha2::{Sha256, Digest};
-use tokio::task::JoinHandle;
+use hash::Sha256Hash;
+#[cfg(feature = "fs")]
 use tokio::sync::mpsc;
-use tokio::io::AsyncWriteExt;
-use once_cell::sync::Lazy;
+#[cfg(feature = "net")]
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "fs")]
+mod batch;
+#[cfg(feature = "fs")]
+mod cache;
+mod checksums;
+#[cfg(feature = "fs")]
+mod chunks;
+mod delta;
+#[cfg(feature = "fs")]
+mod download;
+#[cfg(feature = "notify")]
+mod drift;
+#[cfg(feature = "fs")]
+mod executor;
+#[cfg(feature = "net")]
+mod fetch;
+mod filter;
+#[cfg(feature = "fs")]
+mod generate;
+#[cfg(feature = "fs")]
+mod hash_cache;
+#[cfg(feature = "fs")]
+mod ignore;
+#[cfg(feature = "fs")]
+mod in_use;
+mod manifest;
+mod merge;
+#[cfg(feature = "fs")]
+mod metrics;
+#[cfg(feature = "fs")]
+mod mtime;
+mod normalize;
+#[cfg(feature = "fs")]
+mod owners;
+#[cfg(feature = "fs")]
+mod pause;
+#[cfg(feature = "fs")]
+mod prefetch;
+#[cfg(feature = "net")]
+mod refs;
+#[cfg(feature = "fs")]
+mod remove;
+#[cfg(feature = "fs")]
+mod rollback;
+mod stats;
+#[cfg(feature = "fs")]
+mod subtrees;
+#[cfg(feature = "fs")]
+mod upgrade;
+#[cfg(feature = "fs")]
+mod verify;
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+mod watch;
+pub mod hash;
+#[cfg(feature = "net")]
+pub mod http;
 pub mod packs;
+#[cfg(feature = "publish")]
+pub mod publish;
+pub mod template;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod util;
 
-pub(crate) static CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+/// Settings for [Directory::upgrade_game_folder_with_options].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default)]
+pub struct UpgradeOptions {
+	/// HTTP settings for this upgrade only, [None] uses the ones set with [http::configure].
+	/// To change a single setting start from [http::config].
+	pub http: Option<http::HttpConfig>,
+	/// Remember the sha of local files in `.green-lib/hashcache.bin` inside the upgraded folder, so files whose size and modification time haven't changed aren't hashed again.
+	/// The [Directory::subtree_hash] of children that were applied is remembered in `.green-lib/subtrees.json` as well, a child that still has it isn't scanned at all, so local changes inside it go unnoticed unless [UpgradeOptions::paranoid] is set.
+	/// Subtrees are only remembered after an upgrade without a [UpgradeOptions::filter] that went through without warnings or unknown entries being kept.
+	pub hash_cache: bool,
+	/// Hash every local file even if the hash cache says it's unchanged, and scan every child even if its [Directory::subtree_hash] says it was already applied, the cache is still refreshed.
+	pub paranoid: bool,
+	/// Give files that already match the manifest the [File::mtime] of the manifest when theirs is different, downloaded files always get it.
+	pub restore_mtimes: bool,
+	/// How many local files are hashed at once, defaults to the number of CPUs.
+	/// With the `rayon` feature, setting this hashes on a dedicated pool of this many threads instead of rayon's global pool.
+	pub hash_threads: Option<usize>,
+	/// How many bytes of downloaded files may be held in memory at once across all downloads, defaults to [DEFAULT_BUFFER_BUDGET].
+	/// Downloads are written to disk as they arrive, each one holds up to 512 KiB of the budget while it's receiving and files up to 16 KiB are kept in memory until they're written.
+	/// A download that needs more than the whole budget is admitted alone once nothing else holds any of it, so with a budget below 512 KiB a single download receives at a time.
+	pub buffer_budget: Option<u64>,
+	/// Fail the upgrade with [UpgradeError::Local] when a local entry can't be inspected, instead of only warning about it.
+	pub strict: bool,
+	/// What to do with symlinks in the upgraded folder.
+	pub symlinks: SymlinkPolicy,
+	/// Whether local entries that aren't in the manifest are deleted, see [Deletions::Disabled] for folders other tools write to as well.
+	pub deletions: Deletions,
+	/// Which unknown hidden entries are left in place when deletions are enabled, like caches a loader rebuilds on every launch.
+	pub hidden: HiddenPolicy,
+	/// Create the [Directory::links] of the manifest as symlinks on Windows, which needs developer mode or administrator rights.
+	/// Otherwise they're skipped with an [UpgradeStatus::Warning], other platforms always create them.
+	pub windows_symlinks: bool,
+	/// When most of the manifest's files are missing, like on a first install, download and extract this archive before the normal upgrade, which then fixes anything the archive got wrong.
+	/// A bootstrap that fails only produces an [UpgradeStatus::Warning], the normal upgrade still downloads everything.
+	#[cfg(feature = "zip")]
+	pub bootstrap: Option<Bootstrap>,
+	/// Called with the relative path and manifest entry of every file, ones it returns `false` for are left out of the upgrade.
+	/// They're neither hashed nor downloaded nor counted in [UpgradeStatus::Length], and a local copy is left alone instead of being deleted as unknown.
+	/// It's asked last, after everything else has decided the file is part of the upgrade.
+	pub filter: Option<DownloadFilter>,
+	/// Called with the relative path of every unknown entry right before it would be deleted, ones it returns `true` for are kept and reported with [UpgradeStatus::Kept] and in [UpgradeReport::kept].
+	/// A protected directory is kept as a whole, whatever is inside it. Unlike [UpgradeOptions::hidden] it decides by the whole path, so `mods/local.jar` can be kept while `config/local.jar` is deleted.
+	pub protect: Option<ProtectFilter>,
+	/// Changes the url of every download right before it's requested, like to pick a nearby mirror.
+	/// Errors still show the url from the manifest, and the downloaded file is still checked against the manifest's sha.
+	pub rewrite_url: Option<RewriteUrl>,
+	/// Asked for a new url when a download is refused with 401 or 403, like when a presigned url expired halfway through a slow upgrade.
+	/// It gets the url from the manifest and the download is retried at once with what it returns, at most [MAX_URL_REFRESHES] times per file.
+	/// Errors about refreshed urls leave out their query, where the credentials usually are, and the file is still checked against the manifest's sha.
+	pub refresh_url: Option<RefreshUrl>,
+	pub limits: SizeLimits,
+	/// Give up on an attempt and retry it with [RetryReason::Stalled] when the body of a download doesn't receive a single byte for this long, [None] waits as long as the connection stays open.
+	/// Unlike [http::HttpConfig::request_timeout] it doesn't cut off huge files that are still arriving.
+	pub stall_timeout: Option<std::time::Duration>,
+	/// Take files from this cache when it has them and add the ones that get downloaded, so files shared by several folders are only downloaded once.
+	pub cache: Option<ObjectCache>,
+	/// Whether files come from the [UpgradeOptions::cache], the network or both.
+	pub source: FileSource,
+	/// Only install files and directories for this side, the others aren't downloaded and a local copy is left alone, [Side::Both] installs everything.
+	pub side: Side,
+	/// What [Directory::upgrade_game_folder_multi] does when manifests have different entries at the same path.
+	pub precedence: MergePrecedence,
+	/// The notice of the pack being installed when it has to be accepted first, see [UpgradeOptions::require_notice].
+	pub notice: Option<packs::Notice>,
+	/// Upgrade even if a game seems to be running from the folder, for servers where whoever starts the upgrade knows better.
+	/// Otherwise the upgrade fails with [UpgradeError::InUse] before changing anything.
+	pub ignore_in_use: bool,
+	/// How deleting, renaming and creating files is retried on Windows when it fails with a sharing violation, lock violation or access denied, like while an antivirus scanner or the search indexer looks at a freshly written jar.
+	/// Retried operations are in [UpgradeReport::lock_retries], one that keeps failing is reported like it would have been without retrying, or as [UpgradeError::Locked] where that would have been [UpgradeError::InUse].
+	/// Other platforms never retry.
+	pub lock_retries: LockRetries,
+	/// Caps how many downloads run at once, [None] gives this upgrade its own cap of [DEFAULT_CONCURRENT_DOWNLOADS].
+	pub download_limiter: Option<DownloadLimiter>,
+	/// Lets the downloads of this upgrade be paused and resumed while it runs, see [PauseControl].
+	pub pause: Option<PauseControl>,
+	/// The longest the upgrade may take, resolving references and scanning included. Once it runs out nothing new is scanned or downloaded, downloads that are receiving stop, and the upgrade returns [UpgradeError::DeadlineExceeded] with what it got done.
+	/// Files are only ever replaced once they're completely downloaded and verified, so stopping leaves no half-written files behind.
+	pub deadline: Option<std::time::Duration>,
+	/// Stop the clock of [UpgradeOptions::deadline] while [UpgradeOptions::pause] is paused, by default paused time counts.
+	pub deadline_excludes_pauses: bool,
+	/// Accept downloads without checking their sha, and assume local files whose size matches the manifest's [File::size] are up to date without hashing them.
+	/// Only meant for content you just generated yourself, like a mirror on a LAN, a compromised or broken server can write anything. [UpgradeReport::verification_skipped] records that it was used.
+	pub dangerously_skip_hash_verification: bool,
+	/// Run the upgrade on this runtime, downloads and hashing included, like a small dedicated one so big upgrades don't slow down everything else.
+	/// [None] runs it on the runtime it's awaited on, with this set it can be awaited from anywhere.
+	pub runtime: Option<tokio::runtime::Handle>,
+	/// Read and hash the folder of [Directory::verify_folder] on this instead of tokio's blocking threads, so it can be awaited on any executor without a tokio runtime, [UpgradeOptions::runtime] isn't used then.
+	/// Upgrades and everything else that downloads still need tokio, see the crate docs.
+	#[cfg(feature = "runtime-agnostic")]
+	pub spawner: Option<Spawner>,
+	/// Record the files and links this upgrade manages under this name in `.green-lib/owners.json`, so several packs can be installed into one folder.
+	/// Unknown entries that another namespace owns (or that hold something it owns) are never deleted, whether this is set or not, they're reported with [UpgradeStatus::Kept] and in [UpgradeReport::kept].
+	/// Paths another namespace claims as well are reported in [UpgradeReport::ownership_conflicts].
+	pub namespace: Option<String>,
+	/// What to do with keys that contain a `/` or `\`, like `config\mod.json` from tools that flattened the tree.
+	/// They're split into nested children by default the way [Directory::normalize] does, for verifying and removing too, [SeparatorPolicy::Error] fails with [UpgradeError::Normalize] before changing anything.
+	pub separators: SeparatorPolicy
+}
+
+#[cfg(feature = "fs")]
+impl UpgradeOptions {
+	/// # Description
+	/// Makes upgrades with these options fail with [UpgradeError::NoticeNotAccepted] before changing anything, until [UpgradeOptions::accept_notice] is called, when `metadata` has [packs::ManifestMetadata::requires_acceptance].
+	/// A notice that was accepted before is replaced, so it has to be accepted again.
+	pub fn require_notice(&mut self, metadata: &packs::ManifestMetadata) {
+		self.notice = match metadata.requires_acceptance {
+			true => Some(metadata.notice().unwrap_or_default()),
+			false => None
+		};
+	}
+
+	/// # Description
+	/// Records that the user accepted the notice set with [UpgradeOptions::require_notice], or that a headless sync accepts it for them.
+	pub fn accept_notice(&mut self) {
+		if let Some(notice) = &mut self.notice {
+			notice.accepted = true;
+		}
+	}
 
-struct UpgradeState {
-	top_level: bool,
-	handles: Vec<JoinHandle<()>>,
-	tx: Option<mpsc::Sender<UpgradeStatus>>
+	#[allow(clippy::result_large_err)]
+	pub(crate) fn check_notice(&self) -> Result<(), UpgradeError> {
+		match &self.notice {
+			Some(notice) if !notice.accepted => Err(UpgradeError::NoticeNotAccepted {
+				text: notice.text.clone(),
+				url: notice.url.clone()
+			}),
+			_ => Ok(())
+		}
+	}
 }
 
-/// Contains information about a remote directory, created from a manifest that can be fetched with [Directory::from_url].
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-pub struct Directory {
-	pub files: HashMap<String, File>,
-	pub children: HashMap<String, Directory>
+/// A folder of files named by their sha, shared between upgrades (and folders) with [UpgradeOptions::cache].
+/// Files taken from the cache are checked against their sha every time, so a linked copy that was edited in place can't spread.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectCache {
+	pub path: PathBuf,
+	pub materialize: Materialize
 }
 
-/// A message that will give information about the status of an upgrade, note that you may recieve these events in any order (including [UpgradeStatus::Tick] before [UpgradeStatus::Length])
-#[derive(Debug)]
-pub enum UpgradeStatus {
-	Length(usize),
-	Tick
+#[cfg(feature = "fs")]
+impl ObjectCache {
+	pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+		Self {
+			path: path.into(),
+			materialize: Materialize::default()
+		}
+	}
+
+	/// # Description
+	/// Counts what's in the cache.
+	pub async fn stats(&self) -> std::io::Result<CacheStats> {
+		NoRuntime::check()?;
+		let cache = self.clone();
+		tokio::task::spawn_blocking(move || cache::stats(&cache)).await.unwrap()
+	}
+
+	/// # Description
+	/// Removes the blobs `policy` doesn't keep and reports which ones.
+	/// Waits for upgrades and prefetches using the cache to finish first, also ones in other processes, and they wait for it in turn.
+	pub async fn gc(&self, policy: &GcPolicy) -> std::io::Result<GcReport> {
+		NoRuntime::check()?;
+		let (cache, policy) = (self.clone(), policy.clone());
+		tokio::task::spawn_blocking(move || cache::gc(&cache, &policy)).await.unwrap()
+	}
+
+	/// # Description
+	/// Hashes every blob and removes the ones that don't match their sha anymore, like a hardlinked copy that was edited in place.
+	/// Upgrades check blobs before using them anyway, this finds the broken ones ahead of time.
+	pub async fn verify(&self) -> std::io::Result<CacheVerifyReport> {
+		NoRuntime::check()?;
+		let cache = self.clone();
+		tokio::task::spawn_blocking(move || cache::verify(&cache)).await.unwrap()
+	}
 }
 
-macro_rules! send {
-	($tx:expr, $val:expr) => {
-		if let Some(ref tx) = $tx {
-			tx.send($val).await.unwrap()
+/// What's in an [ObjectCache], see [ObjectCache::stats].
+/// Blobs count as accessed when an upgrade takes them from the cache, or whenever the filesystem records an access.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+	pub blobs: usize,
+	pub bytes: u64,
+	/// When the blob that went unused the longest was last accessed, [None] for an empty cache.
+	pub oldest_access: Option<std::time::SystemTime>,
+	/// When any blob was last accessed.
+	pub newest_access: Option<std::time::SystemTime>
+}
+
+/// Which blobs [ObjectCache::gc] removes, a blob is removed if any of the rules says so. The default removes nothing.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default)]
+pub struct GcPolicy {
+	/// Remove blobs that no file of these manifests has, like the current manifest of every installed pack.
+	pub referenced_by: Option<Vec<Directory>>,
+	/// Remove blobs that weren't accessed for this long.
+	pub max_age: Option<std::time::Duration>,
+	/// Remove the least recently accessed blobs until the ones left add up to at most this many bytes, after the other rules.
+	pub max_bytes: Option<u64>
+}
+
+/// What [ObjectCache::gc] did.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+	/// The shas of the blobs that were removed, sorted.
+	pub removed: Vec<Sha256Hash>,
+	pub removed_bytes: u64,
+	/// How many blobs are left.
+	pub kept: usize,
+	pub kept_bytes: u64
+}
+
+/// What [ObjectCache::verify] found.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheVerifyReport {
+	/// How many blobs were hashed.
+	pub checked: usize,
+	/// The shas of the blobs that didn't match and were removed, sorted.
+	pub evicted: Vec<Sha256Hash>
+}
+
+/// How files are put into a folder from an [ObjectCache].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Materialize {
+	/// Reflink where the filesystem supports it, otherwise hardlink, and copy when both fail (like across filesystems).
+	/// Upgrades always replace files instead of writing into them, so a linked file is never changed through the upgraded folder.
+	#[default]
+	Link,
+	/// Always make a plain copy, for folders that shouldn't share anything with the cache.
+	Copy
+}
+
+/// How many downloads an upgrade runs at once when it doesn't get a [DownloadLimiter].
+#[cfg(feature = "fs")]
+pub const DEFAULT_CONCURRENT_DOWNLOADS: usize = 16;
+
+/// Caps how many downloads run at once, clones share the cap so one limiter can be passed to several upgrades running at the same time.
+/// A download holds its slot while it's requested and received, not while it waits to retry.
+/// When a server answers with a `Retry-After` every download from that host waits it out (up to 30 seconds) before it's requested, not only the one that got it.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone)]
+pub struct DownloadLimiter {
+	slots: Arc<tokio::sync::Semaphore>,
+	hosts: Arc<download::Hosts>
+}
+
+#[cfg(feature = "fs")]
+impl DownloadLimiter {
+	pub fn new(max_downloads: usize) -> Self {
+		Self::with_host_limits(max_downloads, HostLimits::default())
+	}
+
+	/// # Description
+	/// Same as [DownloadLimiter::new], with downloads from a single host capped by `host_limits` on top of `max_downloads`, like for a mirror that rate-limits while your own CDN takes many streams.
+	pub fn with_host_limits(max_downloads: usize, host_limits: HostLimits) -> Self {
+		Self {
+			slots: Arc::new(tokio::sync::Semaphore::new(max_downloads.max(1))),
+			hosts: Arc::new(download::Hosts::new(host_limits))
 		}
 	}
+
+	/// Waits for a slot of the host of `url` and then one of the whole limiter.
+	pub(crate) async fn acquire(&self, url: &str) -> download::Slot {
+		let host = self.hosts.acquire(&download::host(url)).await;
+		download::Slot {
+			_host: host,
+			_slot: self.slots.clone().acquire_owned().await.unwrap()
+		}
+	}
+
+	/// Keeps downloads from the host of `url` from being requested for `duration`.
+	pub(crate) fn cool_down(&self, url: &str, duration: std::time::Duration) {
+		self.hosts.cool_down(&download::host(url), duration);
+	}
+}
+
+/// Caps on how many downloads come from a single host at once, see [DownloadLimiter::with_host_limits].
+/// Hosts are matched without their port and regardless of case, the host is the one of the url that's requested, after [UpgradeOptions::rewrite_url] but before redirects.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostLimits {
+	/// The cap for every host that isn't in [HostLimits::hosts], [None] leaves them to the cap of the whole limiter.
+	pub per_host: Option<usize>,
+	/// Caps for specific hosts by name, used instead of [HostLimits::per_host].
+	pub hosts: HashMap<String, usize>
 }
 
-const MAX_ATTEMPTS: u64 = 5;
+/// Pauses and resumes the downloads of upgrades that got it as [UpgradeOptions::pause], clones control the same upgrades.
+/// While paused no new download starts, and ones that are receiving stop between two chunks of their body and close their connection, so a long pause doesn't keep anything open.
+/// What they received is kept and they continue with a range request once resumed, from the start when the server ignores it. Scanning and hashing local files goes on.
+/// The upgrade reports [UpgradeStatus::Paused] and [UpgradeStatus::Resumed], and dropping it while paused still ends every paused download.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone)]
+pub struct PauseControl(Arc<tokio::sync::watch::Sender<bool>>);
 
-impl Directory {
+#[cfg(feature = "fs")]
+impl PauseControl {
+	pub fn new() -> Self {
+		Self(Arc::new(tokio::sync::watch::channel(false).0))
+	}
+
+	pub fn pause(&self) {
+		self.0.send_replace(true);
+	}
+
+	pub fn resume(&self) {
+		self.0.send_replace(false);
+	}
+
+	pub fn is_paused(&self) -> bool {
+		*self.0.borrow()
+	}
+}
+
+#[cfg(feature = "fs")]
+impl Default for PauseControl {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Caps on how much an upgrade downloads, so a broken or malicious server can't fill the disk or memory.
+/// [None] disables a limit.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeLimits {
+	/// The most bytes a single file may have, defaults to 16 GiB. A file with a manifest [File::size] is also stopped once it's bigger than that.
+	/// Going over fails that file with [DownloadError::TooLarge].
+	pub per_file: Option<u64>,
+	/// The most bytes an upgrade may receive over all attempts of all files, defaults to 256 GiB.
+	/// Going over fails every download that's still running with [DownloadError::TotalTooLarge].
+	pub total: Option<u64>
+}
+
+#[cfg(feature = "fs")]
+impl Default for SizeLimits {
+	fn default() -> Self {
+		Self {
+			per_file: Some(16 << 30),
+			total: Some(256 << 30)
+		}
+	}
+}
+
+/// A function from a url to the url that should actually be requested, see [UpgradeOptions::rewrite_url].
+#[cfg(feature = "net")]
+#[derive(Clone)]
+pub struct RewriteUrl(pub Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+#[cfg(feature = "net")]
+impl RewriteUrl {
+	pub fn new<F: Fn(&str) -> String + Send + Sync + 'static>(rewrite: F) -> Self {
+		Self(Arc::new(rewrite))
+	}
+
+	pub(crate) fn rewrite(&self, url: &str) -> String {
+		(self.0)(url)
+	}
+}
+
+#[cfg(feature = "net")]
+impl std::fmt::Debug for RewriteUrl {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "RewriteUrl(..)")
+	}
+}
+
+/// How many times the url of a single file is refreshed with [UpgradeOptions::refresh_url].
+#[cfg(feature = "fs")]
+pub const MAX_URL_REFRESHES: u64 = 2;
+
+/// What [RefreshUrl] resolves to, the new url or why there isn't one.
+#[cfg(feature = "fs")]
+pub type RefreshedUrl = std::pin::Pin<Box<dyn std::future::Future<Output = Result<String, Box<dyn std::error::Error + Send + Sync>>> + Send>>;
+
+/// A function from the url of a refused download to a fresh url for the same file, see [UpgradeOptions::refresh_url].
+#[cfg(feature = "fs")]
+#[derive(Clone)]
+pub struct RefreshUrl(pub Arc<dyn Fn(&str) -> RefreshedUrl + Send + Sync>);
+
+#[cfg(feature = "fs")]
+impl RefreshUrl {
 	/// # Description
-	/// Fetches a manifest from a URL.
-	/// # Warning
-	/// This function will trust any URL you stick into it, ideally make sure only trusted URLs are passed in, or at least ensure all URLs use encrypted protocols like HTTPS.
-	pub async fn from_url<U: reqwest::IntoUrl>(url: U) -> Option<Self> {
-		let resp = CLIENT.get(url).send().await.ok()?.text().await.ok()?;
-		serde_json::from_str(&resp).ok()
+	/// The future can't borrow the url, copy it when it's needed.
+	pub fn new<F, Fut>(refresh: F) -> Self
+	where
+		F: Fn(&str) -> Fut + Send + Sync + 'static,
+		Fut: std::future::Future<Output = Result<String, Box<dyn std::error::Error + Send + Sync>>> + Send + 'static
+	{
+		Self(Arc::new(move |url| Box::pin(refresh(url))))
+	}
+
+	pub(crate) async fn refresh(&self, url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+		(self.0)(url).await
+	}
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Debug for RefreshUrl {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "RefreshUrl(..)")
+	}
+}
+
+/// Decides which files of the manifest an upgrade installs, see [UpgradeOptions::filter].
+#[cfg(feature = "fs")]
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct DownloadFilter(pub Arc<dyn Fn(&Path, &File) -> bool + Send + Sync>);
+
+#[cfg(feature = "fs")]
+impl DownloadFilter {
+	pub fn new<F: Fn(&Path, &File) -> bool + Send + Sync + 'static>(filter: F) -> Self {
+		Self(Arc::new(filter))
+	}
+
+	pub(crate) fn includes(&self, relative: &Path, file: &File) -> bool {
+		(self.0)(relative, file)
+	}
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Debug for DownloadFilter {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "DownloadFilter(..)")
+	}
+}
+
+/// Decides which unknown entries an upgrade keeps instead of deleting, see [UpgradeOptions::protect].
+#[cfg(feature = "fs")]
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct ProtectFilter(pub Arc<dyn Fn(&Path) -> bool + Send + Sync>);
+
+#[cfg(feature = "fs")]
+impl ProtectFilter {
+	pub fn new<F: Fn(&Path) -> bool + Send + Sync + 'static>(protect: F) -> Self {
+		Self(Arc::new(protect))
+	}
+
+	pub(crate) fn protects(&self, relative: &Path) -> bool {
+		(self.0)(relative)
+	}
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Debug for ProtectFilter {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "ProtectFilter(..)")
+	}
+}
+
+/// Runs the blocking filesystem work of scans and verifications awaited outside of tokio, see [UpgradeOptions::spawner] and [FromPathOptions::spawner].
+/// It's handed every job as a boxed closure and has to run it somewhere blocking is fine, like `smol::unblock(job).detach()` or `async_std::task::spawn_blocking(job)`.
+#[cfg(feature = "runtime-agnostic")]
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct Spawner(pub Arc<dyn Fn(Box<dyn FnOnce() + Send>) + Send + Sync>);
+
+#[cfg(feature = "runtime-agnostic")]
+impl Spawner {
+	pub fn new<F: Fn(Box<dyn FnOnce() + Send>) + Send + Sync + 'static>(spawn: F) -> Self {
+		Self(Arc::new(spawn))
 	}
 
 	/// # Description
-	/// Updates a path to match the state of this instance.
-	/// The sender recieves a vague indication of status through the [UpgradeStatus] enum.
-	/// # Warning
-	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
-	pub async fn upgrade_game_folder(&self, path: &std::path::Path, tx: Option<mpsc::Sender<UpgradeStatus>>) {
-		let mut upgrade_state = UpgradeState {
-			top_level: true,
-			handles: vec![],
-			tx
-		};
+	/// Starts a thread for every job, for executors without a pool for blocking work.
+	pub fn threads() -> Self {
+		Self::new(|job| {
+			std::thread::spawn(job);
+		})
+	}
 
-		self.upgrade_folder_to(path, &mut upgrade_state).await;
-		send!(upgrade_state.tx, UpgradeStatus::Length(upgrade_state.handles.len()));
-
-		for handle in upgrade_state.handles.iter_mut() {
-			handle.await.unwrap();
-		}
-	}
-
-	#[async_recursion::async_recursion]
-	async fn upgrade_folder_to(&self, path: &std::path::Path, state: &mut UpgradeState) {
-		let mut fetch_set = self.files.clone();
-		let mut files = tokio::fs::read_dir(path).await.expect("cannot open path");
-
-		if state.top_level {
-			state.top_level = false;
-		} else {
-			while let Ok(Some(local_file)) = files.next_entry().await {
-				let local_file_type = local_file.file_type().await.unwrap();
-				let local_file_name = local_file.file_name();
-				let local_file_name = local_file_name.to_string_lossy();
-
-				if local_file_type.is_dir() {
-					if !self.children.contains_key(local_file_name.as_ref()) {
-						tokio::fs::remove_dir_all(local_file.path()).await.unwrap();
-					}
-				} else if local_file_type.is_file() {
-					let local_contents = tokio::fs::read(local_file.path()).await.unwrap();
-					let local_sha = tokio::task::spawn_blocking(move || {
-						format!("{:x}", Sha256::digest(local_contents))
-					}).await.unwrap();
-					match self.files.get(local_file_name.as_ref()).map(|remote_file| remote_file.sha == local_sha) {
-						Some(true) => {
-							fetch_set.remove(local_file_name.as_ref());
-						},
-						_ => {
-							tokio::fs::remove_file(local_file.path()).await.unwrap();
-						}
-					}
-				}
-			}
+	pub(crate) fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+		(self.0)(job)
+	}
+}
+
+#[cfg(feature = "runtime-agnostic")]
+impl std::fmt::Debug for Spawner {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Spawner(..)")
+	}
+}
+
+/// A zip archive of a whole pack whose paths mirror the manifest tree, for installing with one request instead of one per file.
+/// Download progress is reported for `.green-lib/bootstrap.zip`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Bootstrap {
+	pub url: String,
+	pub sha: Sha256Hash,
+	/// The [Directory::canonical_sha] of the manifest the archive was built from, informational since the upgrade fixes any drift.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub revision: Option<String>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// Whether an upgrade deletes what isn't in the manifest, see [UpgradeOptions::deletions].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Deletions {
+	#[default]
+	Enabled,
+	/// Only add missing files and replace ones that don't match, unknown entries are left in place and reported with [UpgradeStatus::Kept] and in [UpgradeReport::kept].
+	/// Unknown symlinks aren't deleted whatever the [SymlinkPolicy].
+	Disabled
+}
+
+/// What an upgrade does with unknown entries that are hidden, see [UpgradeOptions::hidden].
+/// An entry is hidden when its name starts with a `.`, or on Windows when it has the hidden attribute. green-lib's own `.green-lib` folder is never deleted whatever the policy.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HiddenPolicy {
+	/// Delete them like any other unknown entry.
+	#[default]
+	Delete,
+	/// Keep every hidden entry, reporting it with [UpgradeStatus::Kept] and in [UpgradeReport::kept].
+	Keep,
+	/// Keep the entries with one of these names, hidden or not, and delete the other ones. [HiddenPolicy::keep_listed] starts from [DEFAULT_KEPT_HIDDEN].
+	KeepListed(Vec<String>)
+}
+
+/// The names [HiddenPolicy::keep_listed] keeps without being asked to, bookkeeping that loaders and file managers put in the game folder.
+#[cfg(feature = "fs")]
+pub const DEFAULT_KEPT_HIDDEN: &[&str] = &[".fabric", ".mixin.out", ".DS_Store"];
+
+#[cfg(feature = "fs")]
+impl HiddenPolicy {
+	/// # Description
+	/// [HiddenPolicy::KeepListed] with [DEFAULT_KEPT_HIDDEN] and `names`.
+	pub fn keep_listed<I: IntoIterator<Item = S>, S: Into<String>>(names: I) -> Self {
+		HiddenPolicy::KeepListed(DEFAULT_KEPT_HIDDEN.iter().map(|name| name.to_string()).chain(names.into_iter().map(Into::into)).collect())
+	}
+
+	/// Whether an unknown entry called `name` is left in place.
+	/// `path` is where it is, for its attributes.
+	pub(crate) async fn keeps(&self, name: &str, path: &Path, executor: &executor::Executor) -> bool {
+		match self {
+			HiddenPolicy::Delete => false,
+			HiddenPolicy::Keep => name.starts_with('.') || hidden_attribute(path, executor).await,
+			HiddenPolicy::KeepListed(names) => names.iter().any(|listed| listed == name)
 		}
+	}
+}
 
-		for (name, to_fetch) in fetch_set.into_iter() {
-			let local_path = &path.join(&name);
-			let mut local_file = tokio::fs::File::create(local_path).await.unwrap();
-			let url = to_fetch.url;
-			let sha = to_fetch.sha;
-
-			let tx = state.tx.clone();
-
-			let fetch_handle = tokio::spawn(async move {
-				let mut attempts = 0;
-
-				loop {
-					let contents = match CLIENT.get(&url).send().await {
-						Ok(contents) => contents,
-						Err(reason) => {
-							if reason.is_request() {
-								attempts += 1;
-
-								if attempts > MAX_ATTEMPTS {
-									panic!("failed to download {url} after {MAX_ATTEMPTS} attempts");
-								}
-
-								tokio::time::sleep(tokio::time::Duration::from_millis(attempts * 250)).await;
-								continue;
-							} else {
-								panic!("{reason:?}");
-							}
-						}
-					}.bytes().await.unwrap();
-					let contents = Arc::new(contents);
-
-					let downloaded_sha = {
-						let contents = contents.clone();
-						tokio::task::spawn_blocking(move || {
-							format!("{:x}", Sha256::digest(contents.as_ref()))
-						}).await.unwrap()
-					};
-					if downloaded_sha != *sha {
-						panic!("sha256 for {url} didn't check out\nexpected {sha}\nfound {downloaded_sha}");
-					}
-
-					local_file.write_all(&contents).await.unwrap();
-					send!(tx, UpgradeStatus::Tick);
-					break;
-				}
-			});
+#[cfg(all(feature = "fs", windows))]
+async fn hidden_attribute(path: &Path, executor: &executor::Executor) -> bool {
+	use std::os::windows::fs::MetadataExt;
+	const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+	executor.symlink_metadata(path).await.is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(all(feature = "fs", not(windows)))]
+async fn hidden_attribute(_path: &Path, _executor: &executor::Executor) -> bool {
+	false
+}
+
+/// Where an upgrade gets the files it writes, see [UpgradeOptions::source].
+/// Files from the cache are checked against their sha before they're put in place whatever the source.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileSource {
+	/// Take files from the [UpgradeOptions::cache] when it has them and download the rest.
+	#[default]
+	CacheThenNetwork,
+	/// Download every file, the cache is only added to.
+	Network,
+	/// Never make a request, for a cache that was shipped on a drive or filled by [Directory::prefetch] while there was a network.
+	/// Fails with [UpgradeError::NotCached] before changing anything when the cache doesn't have a file that has to be written.
+	CacheOnly
+}
+
+/// How an upgrade treats symlinks it finds, they're never followed whatever the policy.
+/// A link with the name of a manifest entry is treated like an unknown one, since writing through it would change whatever it points to.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+	/// Leave links alone, including ones with the name of a manifest entry, which is then not installed.
+	#[default]
+	Ignore,
+	/// Remove the link itself, a manifest entry with its name is then installed in its place.
+	Delete,
+	/// Leave links alone and fail the upgrade with [UpgradeError::Symlink].
+	Error
+}
+
+/// Settings for [Directory::from_path].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default)]
+pub struct FromPathOptions {
+	/// The url the folder is served from, the `/` separated path of each file is appended to it to make its url like [util::join_url] does.
+	/// It's used as it is, so it can already be percent-encoded.
+	pub base_url: String,
+	/// Files smaller than this many bytes are inlined as [File::content_b64] instead of getting a url.
+	pub inline_below: Option<u64>,
+	/// Set this as the [Directory::url_template] of the manifest and leave out the url of every file.
+	pub url_template: Option<String>,
+	/// Record the modification time of every file as its [File::mtime].
+	pub record_mtimes: bool,
+	/// Files of at least this many bytes that aren't inlined get [File::chunks].
+	pub chunks_above: Option<u64>,
+	/// The [Chunks::size] of those files, defaults to [DEFAULT_CHUNK_SIZE].
+	pub chunk_size: Option<u64>,
+	/// Give every child a [Directory::subtree_hash].
+	pub subtree_hashes: bool,
+	/// Gitignore-style patterns of entries to leave out, like `*.bak` or `/build/`, on top of the [IGNORE_FILE] of the folder.
+	/// They come after the lines of the file, and the last pattern that matches an entry decides, so `!keep.bak` here brings back what the file leaves out.
+	pub exclude: Vec<String>,
+	/// Read and hash the folder on this instead of tokio's blocking threads, so [Directory::from_path] can be awaited on any executor without a tokio runtime.
+	#[cfg(feature = "runtime-agnostic")]
+	pub spawner: Option<Spawner>
+}
+
+/// The name of the file at the root of a folder with gitignore-style patterns of what [Directory::from_path] leaves out, it isn't in the manifest itself.
+/// Comments, `!` negations, trailing `/` for directories, leading or inner `/` to anchor a pattern to the root, `*`, `?`, `[a-z]` and `**` work like in a `.gitignore`.
+/// Only the one at the root is read, and what's inside a left out directory can't be brought back.
+#[cfg(feature = "fs")]
+pub const IGNORE_FILE: &str = ".greenignore";
+
+/// Where the pattern that left an entry out of [Directory::from_path_with_report] came from.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExcludeSource {
+	/// [FromPathOptions::exclude].
+	Options,
+	/// The [IGNORE_FILE], `line` counts from 1.
+	IgnoreFile {
+		line: usize
+	}
+}
+
+/// An entry [Directory::from_path_with_report] left out, a directory is reported without what's inside it.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Excluded {
+	/// Relative to the folder.
+	pub path: PathBuf,
+	/// The pattern as it was written.
+	pub pattern: String,
+	pub source: ExcludeSource
+}
+
+/// What [Directory::from_path_with_report] or [Directory::from_path_incremental] left out, sorted by path.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FromPathReport {
+	pub excluded: Vec<Excluded>,
+	/// How many files got their sha from the [ScanCache] instead of being hashed, always 0 for a full scan.
+	pub reused: usize
+}
+
+/// The size, modification time and sha of every file a scan of a folder found, so [Directory::from_path_incremental] only has to hash the files that changed since.
+/// Keep it next to the manifest rather than in the published folder, a missing or corrupt one just means everything is hashed again.
+#[cfg(feature = "fs")]
+#[derive(Default)]
+pub struct ScanCache(hash_cache::HashCache);
 
-			state.handles.push(fetch_handle);
+#[cfg(feature = "fs")]
+impl ScanCache {
+	/// # Description
+	/// Reads a cache written by [ScanCache::save], one that's missing or can't be read is empty, and so is any cache outside a tokio runtime.
+	pub async fn load(path: &Path) -> Self {
+		if NoRuntime::check().is_err() {
+			return Self::default();
 		}
+		Self(hash_cache::HashCache::load_file(path, &executor::Executor::Tokio).await)
+	}
 
-		for (name, child) in &self.children {
-			let local_path = &path.join(name);
+	/// # Description
+	/// Writes the cache to `path`, creating the folder it's in.
+	pub async fn save(&self, path: &Path) -> std::io::Result<()> {
+		NoRuntime::check()?;
+		self.0.save_file(path, &executor::Executor::Tokio).await
+	}
+}
 
-			if let Err(error) = tokio::fs::create_dir(local_path).await {
-				if error.kind() != std::io::ErrorKind::AlreadyExists {
-					panic!("cannot create {local_path:?}: {error}");
-				}
+#[cfg(feature = "fs")]
+impl std::fmt::Debug for ScanCache {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ScanCache")
+			.field("files", &self.0.len())
+			.finish()
+	}
+}
+
+/// The default [UpgradeOptions::buffer_budget], 512 MiB.
+#[cfg(feature = "fs")]
+pub const DEFAULT_BUFFER_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// How old a [VerificationReport] may be for [Directory::repair_game_folder] to go by it, 10 minutes.
+#[cfg(feature = "fs")]
+pub const MAX_VERIFICATION_AGE: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// What happened during a successful upgrade.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct UpgradeReport {
+	/// The most bytes of downloaded files that were held in memory at once.
+	pub peak_buffered_bytes: u64,
+	/// Local entries that couldn't be inspected and were left alone.
+	pub warnings: Vec<UpgradeWarning>,
+	/// Whether a [Bootstrap] archive was extracted.
+	pub bootstrapped: bool,
+	/// Whether [UpgradeOptions::dangerously_skip_hash_verification] was on, so nothing downloaded was checked.
+	pub verification_skipped: bool,
+	/// Local entries that aren't in the manifest but weren't deleted because of [Deletions::Disabled], [UpgradeOptions::hidden], because another [UpgradeOptions::namespace] owns them or because the network was down (see [UpgradeError::Offline]), sorted.
+	pub kept: Vec<PathBuf>,
+	/// Files and links of the manifest that another [UpgradeOptions::namespace] manages too, sorted, whichever upgrade ran last decides what they contain.
+	pub ownership_conflicts: Vec<OwnershipConflict>,
+	/// File operations that were retried because another program held the file, sorted by path, see [UpgradeOptions::lock_retries].
+	pub lock_retries: Vec<LockRetry>,
+	pub metrics: UpgradeMetrics,
+	/// Whether [Directory::repair_game_folder] went by its [VerificationReport] instead of scanning the folder.
+	pub repaired_from_report: bool
+}
+
+/// How file operations are retried while another program holds the file, see [UpgradeOptions::lock_retries].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockRetries {
+	/// How many times an operation is retried, 0 gives up at once.
+	pub retries: u32,
+	/// How long to wait before the first retry, doubled for every one after it.
+	pub delay: std::time::Duration
+}
+
+#[cfg(feature = "fs")]
+impl Default for LockRetries {
+	fn default() -> Self {
+		Self {
+			retries: 5,
+			delay: std::time::Duration::from_millis(100)
+		}
+	}
+}
+
+/// What a file operation of [LockRetry] was.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOperation {
+	Create,
+	Rename,
+	Delete
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for FileOperation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			FileOperation::Create => write!(f, "create"),
+			FileOperation::Rename => write!(f, "rename"),
+			FileOperation::Delete => write!(f, "delete")
+		}
+	}
+}
+
+/// The Windows error that made a file operation get retried.
+/// Sharing and lock violations that go away after a moment are the sign of an antivirus scanner or indexer, a game keeps its files open.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockError {
+	/// `ERROR_SHARING_VIOLATION`, another program has the file open without sharing it.
+	SharingViolation,
+	/// `ERROR_LOCK_VIOLATION`, another program locked part of the file.
+	LockViolation,
+	/// `ERROR_ACCESS_DENIED`, which is also what a file that's being scanned or still pending deletion gives, one that stays denied is usually a real permission problem.
+	AccessDenied
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for LockError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			LockError::SharingViolation => write!(f, "sharing violation"),
+			LockError::LockViolation => write!(f, "lock violation"),
+			LockError::AccessDenied => write!(f, "access denied")
+		}
+	}
+}
+
+/// A file operation that was retried because another program held the file, see [UpgradeReport::lock_retries].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockRetry {
+	pub path: PathBuf,
+	pub operation: FileOperation,
+	/// What the last failed try ran into.
+	pub error: LockError,
+	pub retries: u32,
+	/// Whether a retry went through, otherwise the operation failed.
+	pub resolved: bool
+}
+
+/// A path that two [UpgradeOptions::namespace]s both manage.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipConflict {
+	pub path: PathBuf,
+	/// The other namespace that claims it.
+	pub namespace: String
+}
+
+/// Settings for [Directory::remove_from_folder].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default)]
+pub struct RemoveOptions {
+	/// Only remove files that still match the manifest and links that still point at their target, ones that were changed are left and reported in [RemoveReport::modified].
+	pub only_matching: bool,
+	/// Report what would be removed, with the same events, without removing anything.
+	pub dry_run: bool,
+	/// Files it returns `false` for are left in place, like ones the user is meant to keep.
+	pub filter: Option<DownloadFilter>,
+	/// Remove files even if a game seems to be running from the folder, see [UpgradeOptions::ignore_in_use].
+	pub ignore_in_use: bool,
+	/// The [UpgradeOptions::namespace] the pack was installed with, which stops owning anything.
+	/// Whether this is set or not, entries another namespace owns too are left in place and reported in [RemoveReport::shared], and green-lib's own state is kept while other namespaces own something.
+	pub namespace: Option<String>,
+	/// See [UpgradeOptions::separators].
+	pub separators: SeparatorPolicy
+}
+
+/// What [Directory::remove_from_folder] did, or would do for a dry run. Paths are relative to the folder and sorted.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct RemoveReport {
+	/// Whether this was a dry run, so nothing was actually removed.
+	pub dry_run: bool,
+	/// Files and links of the manifest that were removed.
+	pub removed: Vec<PathBuf>,
+	/// Directories of the manifest that were removed because nothing else was left in them.
+	pub removed_directories: Vec<PathBuf>,
+	/// Entries that were left because they don't match the manifest anymore, with [RemoveOptions::only_matching] or when something else took their place.
+	pub modified: Vec<PathBuf>,
+	/// Files [RemoveOptions::filter] left in place.
+	pub filtered: Vec<PathBuf>,
+	/// Entries of the manifest that weren't there.
+	pub missing: Vec<PathBuf>,
+	/// Entries that were left because another [UpgradeOptions::namespace] owns them, see [RemoveOptions::namespace].
+	pub shared: Vec<PathBuf>,
+	/// Local entries that couldn't be inspected or removed.
+	pub warnings: Vec<UpgradeWarning>
+}
+
+/// Settings for [Directory::rollback_game_folder].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default)]
+pub struct RollbackOptions {
+	/// Used for the upgrade to the previous manifest, except that [UpgradeOptions::deletions] is always [Deletions::Disabled].
+	/// Its [UpgradeOptions::filter] also leaves files of the newer manifest in place, like ones the user is meant to keep.
+	pub upgrade: UpgradeOptions,
+	/// Report what would be reverted and removed, with the events of the removal, without changing anything.
+	pub dry_run: bool
+}
+
+/// What [Directory::rollback_game_folder] did, or would do for a dry run. Paths are relative to the folder and sorted.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct RollbackReport {
+	/// Whether this was a dry run, so nothing was changed.
+	pub dry_run: bool,
+	/// Files and links of the previous manifest that were missing or different and were put back.
+	pub reverted: Vec<PathBuf>,
+	/// What happened to the entries only the newer manifest has, ones that were changed since are left and listed in [RemoveReport::modified].
+	pub removal: RemoveReport,
+	/// The upgrade to the previous manifest, [None] for a dry run.
+	pub upgrade: Option<UpgradeReport>
+}
+
+/// What [Directory::prefetch] left in the cache. Files inlined in the manifest don't need the cache and aren't counted.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct PrefetchReport {
+	/// Blobs the cache has now, the ones it already had included.
+	pub cached: usize,
+	pub cached_bytes: u64,
+	/// The part of [PrefetchReport::cached] this prefetch downloaded.
+	pub downloaded: usize,
+	/// A file for every blob that couldn't be downloaded, sorted, [UpgradeStatus::Failed] says why.
+	pub missing: Vec<PathBuf>,
+	/// The size of the missing blobs, as far as the manifest has a [File::size] for them.
+	pub missing_bytes: u64,
+	pub metrics: UpgradeMetrics
+}
+
+#[cfg(feature = "fs")]
+impl PrefetchReport {
+	/// # Description
+	/// Whether an upgrade with the same cache can run without a network, apart from files that were added to the folder by hand.
+	pub fn is_complete(&self) -> bool {
+		self.missing.is_empty()
+	}
+}
+
+/// Where the time of an upgrade went, phases overlap since downloads start while the folder is still being scanned.
+#[cfg(feature = "fs")]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UpgradeMetrics {
+	/// The whole upgrade.
+	pub total: std::time::Duration,
+	/// Walking the folder and hashing local files.
+	pub scanning: std::time::Duration,
+	/// From the start of the first download to the end of the last one.
+	pub downloading: std::time::Duration,
+	/// Time spent writing downloaded files, added up over all files.
+	pub writing: std::time::Duration,
+	/// Bytes received over all attempts of all downloads.
+	pub received_bytes: u64,
+	/// Bytes of files taken from the [UpgradeOptions::cache] instead of being downloaded.
+	pub cached_bytes: u64,
+	/// The part of [UpgradeMetrics::cached_bytes] that was reflinked or hardlinked instead of copied, so it takes no extra space.
+	pub deduplicated_bytes: u64,
+	/// [UpgradeMetrics::received_bytes] over [UpgradeMetrics::downloading], in bytes per second.
+	pub average_throughput: u64,
+	/// The most bytes received within a single second of the upgrade.
+	pub peak_throughput: u64,
+	/// The [SLOWEST_DOWNLOADS] downloads that took the longest, slowest first.
+	pub slowest: Vec<DownloadTiming>,
+	pub retries: RetryCounts
+}
+
+/// How many downloads [UpgradeMetrics::slowest] keeps.
+#[cfg(feature = "fs")]
+pub const SLOWEST_DOWNLOADS: usize = 10;
+
+/// How long a single successful download took, including retries.
+#[cfg(feature = "fs")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DownloadTiming {
+	pub path: PathBuf,
+	pub duration: std::time::Duration,
+	pub bytes: u64
+}
+
+/// How many downloads were retried, by [RetryReason].
+#[cfg(feature = "fs")]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetryCounts {
+	pub connection: u64,
+	pub server_error: u64,
+	pub timeout: u64,
+	pub sha_mismatch: u64,
+	pub stalled: u64,
+	pub refreshed: u64,
+	#[serde(default)]
+	pub rate_limited: u64
+}
+
+/// How a local folder compares to a manifest, see [Directory::verify_folder]. Paths are relative to the folder and sorted.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+	/// Files that match the manifest and links that point at their target.
+	pub matched: Vec<PathBuf>,
+	pub mismatched: Vec<Mismatch>,
+	/// Files in the manifest that don't exist or aren't regular files.
+	pub missing: Vec<PathBuf>,
+	/// Links from [Directory::links] that don't exist or point somewhere else.
+	pub wrong_links: Vec<PathBuf>,
+	/// Local entries that aren't in the manifest and would be deleted by an upgrade.
+	pub unknown: Vec<PathBuf>,
+	/// Local entries that aren't in the manifest but that an upgrade keeps because of [UpgradeOptions::hidden].
+	pub kept_hidden: Vec<PathBuf>,
+	/// Local entries that aren't in the manifest but that an upgrade keeps because of [UpgradeOptions::protect].
+	pub protected: Vec<PathBuf>,
+	/// Local files that couldn't be hashed.
+	pub unreadable: Vec<UpgradeWarning>,
+	/// Files in the manifest that [UpgradeOptions::filter] left out, they aren't checked.
+	pub filtered: Vec<PathBuf>,
+	/// Files in the manifest that aren't applicable on this platform, see [File::applies_here], they aren't checked.
+	pub not_applicable: Vec<PathBuf>,
+	/// The folder doesn't exist (or isn't a folder), so the pack was never installed there and everything is [VerificationReport::missing].
+	pub not_installed: bool,
+	/// What the folder looked like, for [Directory::repair_game_folder].
+	snapshot: Option<verify::Snapshot>
+}
+
+/// How a single local file compares to its manifest entry, see [File::verify].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyOutcome {
+	/// It has the sha of the manifest.
+	Match,
+	/// It has different contents, `found_sha` is [None] when [File::size] already didn't match so it wasn't hashed.
+	Mismatch {
+		found_sha: Option<Sha256Hash>,
+		size: u64,
+		/// The modification time of the local file in unix seconds, like [File::mtime].
+		mtime: Option<i64>
+	},
+	/// Nothing is there, or something that isn't a file like a directory or a symlink.
+	Missing
+}
+
+/// A local file with a different sha than the manifest.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+	pub path: PathBuf,
+	pub expected: Sha256Hash,
+	pub found: Sha256Hash,
+	/// The size of the local file in bytes.
+	pub size: u64,
+	/// The modification time of the local file in unix seconds, like [File::mtime].
+	pub mtime: Option<i64>,
+	/// The indexes of the [File::chunks] that don't match, sorted, empty when the file has none or doesn't have the size they cover.
+	pub damaged_chunks: Vec<usize>
+}
+
+#[cfg(feature = "fs")]
+impl VerificationReport {
+	/// # Description
+	/// Whether the folder matches the manifest, unknown entries don't count.
+	pub fn is_ok(&self) -> bool {
+		self.mismatched.is_empty() && self.missing.is_empty() && self.wrong_links.is_empty() && self.unreadable.is_empty()
+	}
+
+	/// # Description
+	/// Lists the expected sha256 of every mismatched and missing file in `sha256sum` format, so `sha256sum -c` can confirm the damage.
+	pub fn to_sha256sums(&self, directory: &Directory) -> String {
+		let mut expected = vec![];
+		for mismatch in &self.mismatched {
+			expected.push((hash_cache::key(&mismatch.path), &mismatch.expected));
+		}
+		for path in &self.missing {
+			if let Some(file) = directory.file(path) {
+				expected.push((hash_cache::key(path), &file.sha));
 			}
+		}
+		checksums::format(expected)
+	}
+}
+
+/// A line of a `sha256sum` listing that couldn't be understood, `line` counts from 1.
+#[derive(Debug, Clone)]
+pub struct Sha256SumsError {
+	pub line: usize,
+	pub content: String
+}
+
+impl std::fmt::Display for Sha256SumsError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "invalid sha256sum line {}: {:?}", self.line, self.content)
+	}
+}
+
+impl std::error::Error for Sha256SumsError {}
+
+/// What [Directory::normalize_with_options], [UpgradeOptions::separators] and [Directory::validate_with] do with a key that contains a `/` or `\`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeparatorPolicy {
+	/// Split the key into nested children, `mods\a.jar` becomes the file `a.jar` in the child `mods`.
+	#[default]
+	Split,
+	/// Fail with [NormalizeError::Separator].
+	Error
+}
+
+/// Settings for [Directory::normalize_with_options].
+#[derive(Debug, Clone)]
+pub struct NormalizeOptions {
+	pub separators: SeparatorPolicy,
+	/// Remove children with nothing in them, on by default.
+	pub prune_empty: bool
+}
 
-			child.upgrade_folder_to(local_path, state).await;
+impl Default for NormalizeOptions {
+	fn default() -> Self {
+		Self {
+			separators: SeparatorPolicy::default(),
+			prune_empty: true
 		}
 	}
 }
 
-/// Contains information about a remote file, part of a [Directory].
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
-pub struct File {
-	pub sha: String,
-	pub url: String
+/// What [Directory::merge] does when manifests have different entries at the same path, like two different files or a file where another has a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergePrecedence {
+	/// The entry of the manifest that comes later wins, a directory is replaced as a whole.
+	#[default]
+	LaterWins,
+	/// Fail with [MergeError::Conflict].
+	Error
+}
+
+/// Why manifests couldn't be merged.
+#[derive(Debug, Clone)]
+pub enum MergeError {
+	/// `first` and `second` are the indices of the manifests with different entries at `path`, which is `/` separated.
+	Conflict {
+		path: String,
+		first: usize,
+		second: usize
+	},
+	/// Manifest `manifest` has a [Directory::reference] at `path` that wasn't resolved, see [Directory::resolve_refs].
+	UnresolvedRef {
+		path: String,
+		manifest: usize
+	}
+}
+
+impl std::fmt::Display for MergeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MergeError::Conflict { path, first, second } => write!(f, "manifests {first} and {second} have different entries at {path:?}"),
+			MergeError::UnresolvedRef { path, manifest } => write!(f, "manifest {manifest} has an unresolved $ref at {path:?}")
+		}
+	}
+}
+
+impl std::error::Error for MergeError {}
+
+/// What [Directory::upgrade_game_folder_multi] did, with the work split up by the manifest it was for.
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct MultiUpgradeReport {
+	pub upgrade: UpgradeReport,
+	/// One for every manifest, in the order they were given.
+	pub sources: Vec<SourceReport>
+}
+
+/// The part of a [MultiUpgradeReport] for one manifest. Paths are relative to the folder and sorted.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceReport {
+	/// Files that were written, whether they were downloaded or taken from the cache.
+	pub downloaded: Vec<PathBuf>,
+	/// Files and links that already matched.
+	pub skipped: Vec<PathBuf>,
+	pub linked: Vec<PathBuf>,
+	/// Files [UpgradeOptions::filter] left out.
+	pub filtered: Vec<PathBuf>,
+	/// Entries of this manifest that a later one replaced, see [MergePrecedence::LaterWins].
+	pub overridden: Vec<PathBuf>
+}
+
+/// A change to a watched folder that makes it drift from the manifest, see [Directory::watch_folder]. Paths are relative to the folder.
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriftEvent {
+	/// Something that isn't in the manifest appeared in one of its directories, an upgrade would delete it.
+	/// A new directory is reported once, not everything in it.
+	Appeared(PathBuf),
+	/// A file of the manifest no longer has its sha or a link no longer points at its target.
+	Modified(PathBuf),
+	/// A file or link of the manifest was deleted.
+	Deleted(PathBuf)
+}
+
+/// Settings for [Directory::watch_folder].
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone)]
+pub struct DriftOptions {
+	/// How long a path has to go without notifications before its change is reported, so a burst of them is reported once and a file that's still being written is only hashed once it's done.
+	pub debounce: std::time::Duration
+}
+
+#[cfg(feature = "notify")]
+impl Default for DriftOptions {
+	fn default() -> Self {
+		Self {
+			debounce: std::time::Duration::from_secs(1)
+		}
+	}
+}
+
+/// Watches a folder in the background and hands out how it drifts from a manifest, see [Directory::watch_folder].
+/// Watching stops when it's dropped, which also lets go of what the OS handed out for it.
+#[cfg(feature = "notify")]
+#[derive(Debug)]
+pub struct FolderWatcher {
+	receiver: mpsc::Receiver<DriftEvent>,
+	task: tokio::task::JoinHandle<()>,
+	_watcher: notify::RecommendedWatcher
+}
+
+#[cfg(feature = "notify")]
+impl FolderWatcher {
+	/// # Description
+	/// Waits for the next change, only returns [None] when the watching task panicked.
+	pub async fn next(&mut self) -> Option<DriftEvent> {
+		self.receiver.recv().await
+	}
+}
+
+#[cfg(feature = "notify")]
+impl Drop for FolderWatcher {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
+
+/// Settings for [Directory::watch_url] and [packs::PacksListManifest::watch_url].
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+	/// Every wait between polls is moved by up to this share of itself either way at random, so many watchers started together don't poll together.
+	pub jitter: f64,
+	/// After a failed poll the wait is doubled until a poll works again, up to this long.
+	pub max_backoff: std::time::Duration,
+	/// HTTP settings for this watcher only, [None] uses the ones set with [http::configure].
+	pub http: Option<http::HttpConfig>
+}
+
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+impl Default for WatchOptions {
+	fn default() -> Self {
+		Self {
+			jitter: 0.1,
+			max_backoff: std::time::Duration::from_secs(15 * 60),
+			http: None
+		}
+	}
+}
+
+/// Polls a manifest in the background and hands out what changed, see [Directory::watch_url].
+/// Polling stops when it's dropped.
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct Watcher<T> {
+	receiver: tokio::sync::mpsc::Receiver<Result<T, ManifestError>>,
+	task: Option<tokio::task::JoinHandle<()>>
+}
+
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+impl<T> Watcher<T> {
+	/// # Description
+	/// Waits for the next change, or for the next poll that failed, polling goes on after a failure either way.
+	/// Only returns [None] when the polling task panicked, or after the [ManifestError::Request] of a [WatchOptions::http] no client could be built from or the [ManifestError::NoRuntime] of a watcher started outside a tokio runtime.
+	pub async fn next(&mut self) -> Option<Result<T, ManifestError>> {
+		self.receiver.recv().await
+	}
+}
+
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+impl<T> Drop for Watcher<T> {
+	fn drop(&mut self) {
+		if let Some(task) = &self.task {
+			task.abort();
+		}
+	}
+}
+
+/// A manifest that changed since the last poll of a [Watcher].
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone)]
+pub struct ManifestChange {
+	pub directory: Directory,
+	/// Against the manifest of the previous change, or against an empty one for the first.
+	pub diff: DirectoryDiff
+}
+
+/// How two manifests differ on disk, see [Directory::diff]. Paths are `/` separated and sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryDiff {
+	/// Files and links only the newer manifest has.
+	pub added: Vec<String>,
+	/// Files and links only the older manifest has.
+	pub removed: Vec<String>,
+	/// Files with another sha and links with another target, or a file that became a link and the other way around.
+	pub changed: Vec<String>
+}
+
+impl DirectoryDiff {
+	/// # Description
+	/// Whether both manifests put the same files and links on disk.
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+/// How many of each kind of entry a tree has and how big its files are, see [DirectoryStats].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryStats {
+	pub files: u64,
+	pub links: u64,
+	/// Directories, not counting the root or children that are still a [Directory::reference].
+	pub directories: u64,
+	/// Children that are still a [Directory::reference], what's in them isn't counted.
+	pub references: u64,
+	/// The bytes of every file with a [File::size].
+	pub size: u64,
+	/// Files without a [File::size], which [EntryStats::size] leaves out.
+	pub unsized_files: u64
+}
+
+impl EntryStats {
+	fn add(&mut self, other: &EntryStats) {
+		self.files += other.files;
+		self.links += other.links;
+		self.directories += other.directories;
+		self.references += other.references;
+		self.size += other.size;
+		self.unsized_files += other.unsized_files;
+	}
+}
+
+/// A file of [DirectoryStats::largest], by `/` separated path.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LargestFile {
+	pub path: String,
+	pub size: u64
+}
+
+/// How many files [DirectoryStats::largest] keeps.
+pub const LARGEST_FILES: usize = 10;
+
+/// What a manifest holds, for showing a pack before it's installed, see [Directory::stats]. Paths are `/` separated.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryStats {
+	/// Everything in the manifest.
+	pub total: EntryStats,
+	/// Everything in each child of the root, like `mods` or `config`, by name. Files and links at the top level are only in [DirectoryStats::total].
+	pub children: BTreeMap<String, EntryStats>,
+	/// The [LARGEST_FILES] biggest files with a [File::size], biggest first and by path when they're the same size.
+	pub largest: Vec<LargestFile>,
+	/// The entry with the most components, the first in sort order when there are several, [None] for an empty manifest.
+	pub deepest: Option<String>,
+	/// How many components [DirectoryStats::deepest] has.
+	pub depth: usize
+}
+
+/// The changes that turn one manifest into another, see [Directory::delta_to] and [packs::ManifestMetadata::update_directory].
+/// Paths are `/` separated, entries that didn't change aren't in it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestDelta {
+	/// The [Directory::content_hash] of the manifest it applies to.
+	pub base: Sha256Hash,
+	/// The [Directory::content_hash] of the manifest it gives.
+	pub target: Sha256Hash,
+	/// Directories that were added or whose own fields changed, with no files, children or links, `""` is the root.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub directories: BTreeMap<String, Directory>,
+	/// Files that were added or changed in any way.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub files: BTreeMap<String, File>,
+	/// Links that were added or got another target.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub links: BTreeMap<String, String>,
+	/// Files, links and directories that were removed, or replaced by another kind of entry, sorted. A removed directory goes with everything in it.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub removed: Vec<String>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// Why [Directory::apply_delta] couldn't apply a [ManifestDelta].
+#[derive(Debug)]
+pub enum DeltaError {
+	/// The delta is for another manifest.
+	WrongBase {
+		expected: Sha256Hash,
+		found: Sha256Hash
+	},
+	/// A path of the delta is empty, has `.` or `..` in it, or removes something that isn't there.
+	InvalidPath(String),
+	/// The manifest it gave doesn't have the content hash the delta promised.
+	WrongTarget {
+		expected: Sha256Hash,
+		found: Sha256Hash
+	},
+	/// The manifest it gave isn't valid.
+	Invalid(ValidationError)
+}
+
+impl std::fmt::Display for DeltaError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DeltaError::WrongBase { expected, found } => write!(f, "delta applies to manifest {expected}, not {found}"),
+			DeltaError::InvalidPath(path) => write!(f, "delta has invalid path {path:?}"),
+			DeltaError::WrongTarget { expected, found } => write!(f, "applying the delta gave manifest {found} instead of {expected}"),
+			DeltaError::Invalid(error) => write!(f, "applying the delta gave an invalid manifest: {error}")
+		}
+	}
+}
+
+impl std::error::Error for DeltaError {}
+
+/// What [Directory::normalize] changed, paths are `/` separated and the old ones are under their already normalized parent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizeReport {
+	/// Keys that were trimmed or split, as old path and new path.
+	pub renamed: Vec<(String, String)>,
+	/// Empty children that were removed.
+	pub pruned: Vec<String>
+}
+
+impl NormalizeReport {
+	/// # Description
+	/// Whether the manifest was already normalized.
+	pub fn is_unchanged(&self) -> bool {
+		self.renamed.is_empty() && self.pruned.is_empty()
+	}
+}
+
+/// Why a manifest couldn't be normalized, it's left as it was.
+#[derive(Debug, Clone)]
+pub enum NormalizeError {
+	/// A key contains a separator and [SeparatorPolicy::Error] was chosen.
+	Separator {
+		path: String
+	},
+	/// A key is empty or `..` once trimmed and split.
+	InvalidKey {
+		path: String
+	},
+	/// Two entries end up with the same path, or a file or link is in the way of a child.
+	Collision {
+		path: String
+	}
+}
+
+impl std::fmt::Display for NormalizeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			NormalizeError::Separator { path } => write!(f, "key {path:?} contains a path separator"),
+			NormalizeError::InvalidKey { path } => write!(f, "key {path:?} isn't a valid name"),
+			NormalizeError::Collision { path } => write!(f, "more than one entry ends up at {path:?}")
+		}
+	}
+}
+
+impl std::error::Error for NormalizeError {}
+
+/// A local file or directory that couldn't be read, hashed or deleted, path is relative to the upgraded folder.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub struct UpgradeWarning {
+	pub path: PathBuf,
+	pub error: std::io::Error
+}
+
+/// Contains information about a remote directory, created from a manifest that can be fetched with [Directory::from_url].
+/// Two directories are equal when every field is, see [Directory::content_hash] to compare only what ends up on disk.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Directory {
+	#[serde(default)]
+	pub files: HashMap<String, File>,
+	#[serde(default)]
+	pub children: HashMap<String, Directory>,
+	/// Symlinks to create, by name, with a target relative to this directory that uses `/` separators and stays inside the manifest root.
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub links: HashMap<String, String>,
+	/// The url of files without a url or inline content, for this directory and its children unless they have their own.
+	/// `{sha}` expands to the file's sha and `{sha:0..2}` to part of it, like `https://cdn.example.com/objects/{sha:0..2}/{sha}`.
+	/// Urls are only expanded while upgrading, so big packs that use a template keep one copy of the common prefix in memory instead of one per file.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url_template: Option<String>,
+	/// The oldest green-lib version that understands this manifest, like `0.8.0`, only read from the root directory.
+	/// Older versions refuse to parse it with [ValidationError::ClientTooOld] instead of ignoring fields they don't know.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub min_green_lib: Option<String>,
+	/// Only installed on this side along with everything in it, see [UpgradeOptions::side].
+	#[serde(default, skip_serializing_if = "Side::is_both")]
+	pub side: Side,
+	/// The sub-manifest this child's contents are in, written as `$ref`, so huge packs can be split up and clients only fetch the subtrees that changed.
+	/// Until [Directory::resolve_refs] or an upgrade puts the sub-manifest in its place, the child has no files, children or links, and everything but upgrades, [Directory::prefetch] and [Directory::rollback_game_folder] sees it as empty.
+	/// Versions before references existed see an empty directory as well, so manifests that use them should set [Directory::min_green_lib].
+	#[serde(default, rename = "$ref", skip_serializing_if = "Option::is_none")]
+	pub reference: Option<DirectoryRef>,
+	/// The [Directory::content_hash] of this child, written by [Directory::set_subtree_hashes].
+	/// With [UpgradeOptions::hash_cache] the folder remembers the hashes of the children an upgrade applied, and later upgrades and verifications skip the ones that still have that hash without reading them.
+	/// A hash that doesn't match the contents of the child is ignored, so editing a manifest without updating them only costs the skip.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub subtree_hash: Option<Sha256Hash>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// Where the contents of a child are, see [Directory::reference].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DirectoryRef {
+	pub url: String,
+	/// The sha256 of the sub-manifest, which is only used if it matches, so references can't form a cycle.
+	#[serde(deserialize_with = "manifest::lowercase")]
+	pub sha: String,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// How deep references in sub-manifests may nest when an upgrade resolves them, see [Directory::reference].
+pub const DEFAULT_MAX_REF_DEPTH: usize = 8;
+
+/// The version of the serialized form of [UpgradeStatus], raised whenever an existing event changes shape. New events don't raise it, so skip types you don't know.
+#[cfg(feature = "fs")]
+pub const UPGRADE_STATUS_SCHEMA_VERSION: u32 = 1;
+
+/// A message that will give information about the status of an upgrade, note that you may recieve these events in any order (including [UpgradeStatus::Tick] before [UpgradeStatus::Length])
+/// Paths are relative to the folder being upgraded.
+///
+/// Events serialize as `{"type": ..., "data": ...}` for sending them to another process, see [UPGRADE_STATUS_SCHEMA_VERSION].
+/// The type is the snake case name of the variant, like `leaving_directory`. Fields become an object, a single value is the data itself and two of them an array, so `Progressed` is `{"type":"progressed","data":["mods/a.jar",1024]}`. [UpgradeStatus::Tick] and the other events without data have no `data`.
+/// Durations are `{"secs": ..., "nanos": ...}` and paths that aren't valid UTF-8 can't be serialized.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum UpgradeStatus {
+	Length(usize),
+	Tick,
+	/// A download of this file has started.
+	Started(PathBuf),
+	/// This many bytes of the file have been received.
+	Progressed(PathBuf, u64),
+	/// The file has been downloaded and written (always followed by a [UpgradeStatus::Tick]).
+	Finished(PathBuf),
+	/// The file could not be downloaded, with a description of why and how many attempts were made.
+	Failed {
+		path: PathBuf,
+		reason: String,
+		attempts: u64,
+		failure: Box<DownloadFailure>
+	},
+	/// A download failed and will be attempted again after `delay`, `attempt` is the number of the upcoming attempt.
+	Retrying {
+		path: PathBuf,
+		attempt: u64,
+		max_attempts: u64,
+		delay: std::time::Duration,
+		reason: RetryReason
+	},
+	/// A local file or directory that isn't part of the manifest was deleted.
+	Deleted(PathBuf),
+	/// A file of the manifest was left out by [UpgradeOptions::filter].
+	Filtered(PathBuf),
+	/// A file of the manifest is for another platform, see [File::applies_here]. It isn't downloaded and a local copy is left alone.
+	NotApplicable(PathBuf),
+	/// A local file or directory isn't part of the manifest but was left in place because of [Deletions::Disabled], [UpgradeOptions::hidden], because another [UpgradeOptions::namespace] owns it or because the network was down.
+	Kept(PathBuf),
+	/// A local file already matched the manifest and won't be downloaded.
+	Skipped(PathBuf),
+	/// A symlink from [Directory::links] was created or pointed at its new target.
+	Linked(PathBuf),
+	/// A file was extracted from a [Bootstrap] archive, it's still checked like any other local file afterwards.
+	Extracted(PathBuf),
+	/// A local entry couldn't be inspected so it was left alone, also listed in [UpgradeReport::warnings].
+	Warning {
+		path: PathBuf,
+		reason: String
+	},
+	/// The upgrade started going through a directory of the manifest, the top level has none.
+	/// Directories nest, every one of them is left with [UpgradeStatus::LeavingDirectory] after the ones inside it.
+	EnteringDirectory(PathBuf),
+	/// Every entry of the directory was scanned and queued, its downloads may still be running.
+	LeavingDirectory(PathBuf),
+	/// The [UpgradeOptions::pause] of the upgrade was paused, downloads stop until it's resumed.
+	Paused,
+	/// The [UpgradeOptions::pause] of the upgrade was resumed after a [UpgradeStatus::Paused].
+	Resumed
+}
+
+/// Why a download is being retried.
+/// Serializes as the snake case name of the variant, with the status as `{"server_error": 503}` for the ones that have one.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryReason {
+	/// The connection couldn't be established or broke while receiving the body.
+	Connection,
+	/// The server responded with a 5xx status code.
+	ServerError(u16),
+	/// The request timed out.
+	Timeout,
+	/// The downloaded bytes didn't match the sha256 in the manifest.
+	ShaMismatch,
+	/// No bytes arrived for [UpgradeOptions::stall_timeout].
+	Stalled,
+	/// The server refused the url with this status and [UpgradeOptions::refresh_url] gave a new one, it's tried without waiting.
+	Refreshed(u16),
+	/// The server answered 429 Too Many Requests, the retry waits for its `Retry-After` like every other download from the host.
+	RateLimited
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for RetryReason {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			RetryReason::Connection => write!(f, "connection error"),
+			RetryReason::ServerError(status) => write!(f, "server error {status}"),
+			RetryReason::Timeout => write!(f, "timed out"),
+			RetryReason::ShaMismatch => write!(f, "sha256 mismatch"),
+			RetryReason::Stalled => write!(f, "stalled"),
+			RetryReason::Refreshed(status) => write!(f, "url refreshed after status {status}"),
+			RetryReason::RateLimited => write!(f, "rate limited")
+		}
+	}
+}
+
+/// Why a single file couldn't be downloaded.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub enum DownloadError {
+	/// Every attempt failed, this is the reason the last one did.
+	Exhausted(RetryReason),
+	/// The server responded with a status that isn't worth retrying.
+	Status(u16),
+	/// The request couldn't be made at all.
+	Request(String),
+	/// A redirect wasn't allowed by [http::RedirectPolicy].
+	Redirect(http::RedirectError),
+	/// The downloaded file didn't match the manifest on the last attempt, `bytes` is how much was received.
+	ShaMismatch {
+		expected: Sha256Hash,
+		found: Sha256Hash,
+		bytes: u64,
+		diagnostics: Box<BodyDiagnostics>
+	},
+	/// Chunk `chunk` of the file didn't match its [File::chunks] on the last attempt, the diagnostics are about that chunk.
+	ChunkMismatch {
+		chunk: usize,
+		expected: Sha256Hash,
+		found: Sha256Hash,
+		diagnostics: Box<BodyDiagnostics>
+	},
+	/// The downloaded file couldn't be written.
+	Write(std::io::Error),
+	/// The inline content of the file isn't valid base64.
+	InvalidContent,
+	/// The file has neither a url nor inline content.
+	NoSource,
+	/// The file is bigger than [SizeLimits::per_file] or its manifest size, `received` is how much was read before giving up.
+	TooLarge {
+		limit: u64,
+		received: u64
+	},
+	/// The upgrade received more than [SizeLimits::total].
+	TotalTooLarge {
+		limit: u64
+	},
+	/// Given up on, without trying again, because the network appears to be unavailable.
+	Offline,
+	/// The download was stopped by [UpgradeOptions::deadline], or paused by [UpgradeOptions::pause] and the upgrade was dropped before it was resumed.
+	Cancelled,
+	/// The cache doesn't have an intact copy and [FileSource::CacheOnly] doesn't allow downloading it.
+	NotCached,
+	/// The server refused the url with `status` and [UpgradeOptions::refresh_url] couldn't give a new one.
+	RefreshFailed {
+		status: u16,
+		reason: String
+	},
+	/// The [http::TokenProvider] couldn't give a token, with its reason.
+	Token(String)
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for DownloadError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DownloadError::Exhausted(reason) => write!(f, "gave up retrying ({reason})"),
+			DownloadError::Status(status) => write!(f, "server responded with status {status}"),
+			DownloadError::Request(reason) => write!(f, "request failed: {reason}"),
+			DownloadError::Redirect(error) => write!(f, "{error}"),
+			DownloadError::ShaMismatch { expected, found, bytes, diagnostics } => {
+				write!(f, "sha256 didn't check out, expected {expected} found {found} ({bytes} bytes received")?;
+				if let Some(size) = diagnostics.expected_size {
+					write!(f, ", {size} expected")?;
+				}
+				write!(f, ", {diagnostics})")
+			},
+			DownloadError::ChunkMismatch { chunk, expected, found, diagnostics } => write!(f, "sha256 of chunk {chunk} didn't check out, expected {expected} found {found} ({diagnostics})"),
+			DownloadError::Write(error) => write!(f, "cannot write file: {error}"),
+			DownloadError::InvalidContent => write!(f, "inline content isn't valid base64"),
+			DownloadError::NoSource => write!(f, "file has neither a url nor inline content"),
+			DownloadError::TooLarge { limit, received } => write!(f, "file is bigger than the limit of {limit} bytes ({received} bytes received)"),
+			DownloadError::TotalTooLarge { limit } => write!(f, "upgrade downloaded more than the limit of {limit} bytes"),
+			DownloadError::Offline => write!(f, "not attempted, the network appears to be unavailable"),
+			DownloadError::Cancelled => write!(f, "cancelled"),
+			DownloadError::NotCached => write!(f, "not in the cache and downloading is disabled"),
+			DownloadError::RefreshFailed { status, reason } => write!(f, "server responded with status {status} and the url couldn't be refreshed: {reason}"),
+			DownloadError::Token(reason) => write!(f, "cannot get a token: {reason}")
+		}
+	}
+}
+
+#[cfg(feature = "fs")]
+impl std::error::Error for DownloadError {}
+
+/// What a download that didn't match its sha looked like, to tell a truncated file or an error page from a manifest that's wrong.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyDiagnostics {
+	/// The [File::size] of the manifest.
+	pub expected_size: Option<u64>,
+	/// The `Content-Type` the server sent.
+	pub content_type: Option<String>,
+	/// The `Content-Length` the server sent, of the last part for a download that was resumed.
+	pub content_length: Option<u64>,
+	pub kind: BodyKind,
+	/// Where the body came from after [UpgradeOptions::rewrite_url] and redirects, without its query when it may have credentials in it. [None] for inline content.
+	pub url: Option<String>,
+	/// How many redirects were followed to get there.
+	pub redirects: usize
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for BodyDiagnostics {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let Some(url) = &self.url else {
+			return write!(f, "{} inline content", self.kind);
+		};
+		write!(f, "{} body", self.kind)?;
+		match (&self.content_type, self.content_length) {
+			(Some(content_type), Some(length)) => write!(f, ", server sent {content_type} with content length {length}")?,
+			(Some(content_type), None) => write!(f, ", server sent {content_type} without a content length")?,
+			(None, Some(length)) => write!(f, ", server sent no content type with content length {length}")?,
+			(None, None) => write!(f, ", server sent neither content type nor content length")?
+		}
+		write!(f, ", from {url}")?;
+		match self.redirects {
+			0 => Ok(()),
+			1 => write!(f, " after 1 redirect"),
+			redirects => write!(f, " after {redirects} redirects")
+		}
+	}
+}
+
+/// Everything known about a download that failed for good, for showing it or filing a bug about it, part of [UpgradeError::Download] and [UpgradeStatus::Failed].
+/// Urls never have their query (where signed urls keep their signature) or userinfo, credential headers aren't kept at all.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadFailure {
+	/// Where the last attempt ended up after [UpgradeOptions::rewrite_url] and redirects, [None] for inline content.
+	pub final_url: Option<String>,
+	/// The url every attempt started at in order, with repeats left out, like the ones [UpgradeOptions::refresh_url] gave.
+	pub tried_urls: Vec<String>,
+	/// The status of the last response, [None] when the last attempt got no answer.
+	pub status: Option<u16>,
+	/// The `Content-Type` the server sent with the last response.
+	pub content_type: Option<String>,
+	/// The `Content-Length` the server sent with the last response.
+	pub content_length: Option<u64>,
+	/// The `Server` the server sent with the last response.
+	pub server: Option<String>,
+	/// The start of the body of an unsuccessful last response, cut at [MANIFEST_SNIPPET_LENGTH] characters with control characters escaped.
+	pub snippet: Option<String>,
+	pub attempts: u64,
+	/// From when the download started until it was given up on.
+	pub elapsed: std::time::Duration
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for DownloadFailure {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let mut details = vec![];
+		if let Some(url) = &self.final_url {
+			details.push(format!("from {url}"));
+		}
+		if let Some(status) = self.status {
+			details.push(format!("status {status}"));
+		}
+		if let Some(content_type) = &self.content_type {
+			details.push(format!("content type {content_type}"));
+		}
+		if let Some(length) = self.content_length {
+			details.push(format!("content length {length}"));
+		}
+		if let Some(server) = &self.server {
+			details.push(format!("server {server}"));
+		}
+		if let Some(snippet) = &self.snippet {
+			details.push(format!("body starts with \"{snippet}\""));
+		}
+		details.push(format!("{} attempts in {:.1?}", self.attempts, self.elapsed));
+		write!(f, "{}", details.join(", "))
+	}
+}
+
+/// What the start of a body looks like, see [BodyDiagnostics::kind].
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+	Empty,
+	/// Probably an error or login page instead of the file.
+	Html,
+	/// Valid UTF-8 without control characters, like an error message or a file that's text anyway.
+	Text,
+	Binary
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for BodyKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			BodyKind::Empty => write!(f, "empty"),
+			BodyKind::Html => write!(f, "HTML"),
+			BodyKind::Text => write!(f, "text"),
+			BodyKind::Binary => write!(f, "binary")
+		}
+	}
+}
+
+/// How many characters of a body [ManifestError::NotManifest] keeps.
+pub const MANIFEST_SNIPPET_LENGTH: usize = 200;
+
+/// A function that needs a tokio runtime was called outside of one, see the crate docs.
+#[cfg(feature = "net")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoRuntime;
+
+#[cfg(feature = "net")]
+impl NoRuntime {
+	/// Fails outside of a tokio runtime, requests in the browser don't need one.
+	pub(crate) fn check() -> Result<(), NoRuntime> {
+		#[cfg(not(target_arch = "wasm32"))]
+		if tokio::runtime::Handle::try_current().is_err() {
+			return Err(NoRuntime);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(feature = "net")]
+impl std::fmt::Display for NoRuntime {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "no tokio runtime, await green-lib inside one")
+	}
+}
+
+#[cfg(feature = "net")]
+impl std::error::Error for NoRuntime {}
+
+#[cfg(feature = "net")]
+impl From<NoRuntime> for std::io::Error {
+	fn from(error: NoRuntime) -> Self {
+		std::io::Error::other(error)
+	}
+}
+
+#[cfg(feature = "net")]
+impl From<NoRuntime> for ManifestError {
+	fn from(_: NoRuntime) -> Self {
+		ManifestError::NoRuntime
+	}
+}
+
+#[cfg(feature = "fs")]
+impl From<NoRuntime> for UpgradeError {
+	fn from(_: NoRuntime) -> Self {
+		UpgradeError::NoRuntime
+	}
+}
+
+/// Why a manifest couldn't be fetched.
+#[derive(Debug)]
+pub enum ManifestError {
+	/// The request failed or the body couldn't be read.
+	#[cfg(feature = "net")]
+	Request(reqwest::Error),
+	/// A redirect wasn't allowed by [http::RedirectPolicy].
+	#[cfg(feature = "net")]
+	Redirect(http::RedirectError),
+	/// The [http::TokenProvider] couldn't give a token, with its reason.
+	#[cfg(feature = "net")]
+	Token(String),
+	/// The server answered with an unsuccessful status.
+	Status(u16),
+	/// Every attempt failed, this is the error of the last one.
+	Exhausted {
+		attempts: u64,
+		error: Box<ManifestError>
+	},
+	/// The digest of the response doesn't match the expected one, see [hash::HashAlgorithm].
+	IntegrityMismatch {
+		expected: String,
+		found: String
+	},
+	/// The text isn't a valid manifest.
+	Parse(Box<ParseError>),
+	/// A response body didn't parse as a manifest, with what the server sent instead to tell a login page or a directory listing from a broken manifest.
+	NotManifest {
+		status: u16,
+		content_type: Option<String>,
+		/// The first [MANIFEST_SNIPPET_LENGTH] characters of the body, control characters escaped.
+		snippet: String,
+		error: Box<ParseError>
+	},
+	/// The response is bigger than [http::HttpConfig::max_manifest_size].
+	TooLarge {
+		limit: u64,
+		received: u64
+	},
+	/// The manifest nests directories deeper than [http::HttpConfig::max_manifest_depth], `path` is the first directory past the limit.
+	TooDeep {
+		limit: usize,
+		path: String
+	},
+	/// The manifest has more entries than [http::HttpConfig::max_manifest_entries].
+	TooManyEntries {
+		limit: u64
+	},
+	/// Strict parsing found a field the manifest types don't know about, `path` is the object it's in (keys joined with `/`, empty for the top level).
+	/// `line` and `column` are where its key ends like [serde_json::Error::line] and [serde_json::Error::column] count them and `excerpt` is the text around it, 0 and empty when it can't be found.
+	UnknownField {
+		path: String,
+		key: String,
+		line: usize,
+		column: usize,
+		excerpt: String
+	},
+	/// A manifest file couldn't be read.
+	Io {
+		path: PathBuf,
+		error: std::io::Error
+	},
+	/// The manifest parsed but contains invalid entries.
+	Invalid(ValidationError),
+	/// The sub-manifest of a [Directory::reference] couldn't be fetched, `path` is the child that references it.
+	Ref {
+		path: PathBuf,
+		error: Box<ManifestError>
+	},
+	/// References are still left after resolving this many levels of them.
+	RefDepth {
+		path: PathBuf,
+		max_depth: usize
+	},
+	/// The manifest url of pack `pack` is relative and its list has no [packs::PacksListManifest::base_url] to resolve it against, or it can't be resolved.
+	/// `pack` is the display name for a pack that was never in a list with a base url.
+	UnresolvedUrl {
+		pack: String,
+		url: String
+	},
+	/// The expected digest uses an algorithm this version can't check, the launcher needs a newer green-lib.
+	UnsupportedHash(hash::UnsupportedHashAlgorithm),
+	/// It wasn't awaited inside a tokio runtime, see [NoRuntime].
+	#[cfg(feature = "net")]
+	NoRuntime
+}
+
+impl std::fmt::Display for ManifestError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			#[cfg(feature = "net")]
+			ManifestError::Request(error) => write!(f, "cannot fetch manifest: {error}"),
+			#[cfg(feature = "net")]
+			ManifestError::Redirect(error) => write!(f, "cannot fetch manifest: {error}"),
+			#[cfg(feature = "net")]
+			ManifestError::Token(reason) => write!(f, "cannot fetch manifest: cannot get a token: {reason}"),
+			ManifestError::Status(status) => write!(f, "cannot fetch manifest: server responded with status {status}"),
+			ManifestError::Exhausted { attempts, error } => write!(f, "{error} (gave up after {attempts} attempts)"),
+			ManifestError::IntegrityMismatch { expected, found } => write!(f, "manifest digest didn't check out, expected {expected} found {found}"),
+			ManifestError::Parse(error) => write!(f, "cannot parse manifest: {error}"),
+			ManifestError::NotManifest { status, content_type, snippet, error } => {
+				write!(f, "cannot parse manifest: {error} (status {status}, content type {}, body starts with \"{snippet}\")", content_type.as_deref().unwrap_or("unknown"))?;
+				match snippet.trim_start().starts_with('<') {
+					true => write!(f, ", the server sent HTML so the url likely isn't serving a manifest"),
+					false => Ok(())
+				}
+			},
+			ManifestError::TooLarge { limit, received } => write!(f, "manifest is bigger than the limit of {limit} bytes ({received} bytes received)"),
+			ManifestError::TooDeep { limit, path } => write!(f, "manifest nests directories deeper than the limit of {limit} at {path:?}"),
+			ManifestError::TooManyEntries { limit } => write!(f, "manifest has more than the limit of {limit} entries"),
+			ManifestError::UnknownField { path, key, line, column, excerpt } => {
+				match path.is_empty() {
+					true => write!(f, "unknown manifest field \"{key}\" at the top level")?,
+					false => write!(f, "unknown manifest field \"{key}\" in {path}")?
+				}
+				if *line > 0 {
+					write!(f, " at line {line} column {column}, near \"{excerpt}\"")?;
+				}
+				Ok(())
+			},
+			ManifestError::Io { path, error } => write!(f, "cannot read manifest {}: {error}", path.display()),
+			ManifestError::Invalid(error) => write!(f, "invalid manifest: {error}"),
+			ManifestError::Ref { path, error } => write!(f, "cannot resolve the $ref of {}: {error}", path.display()),
+			ManifestError::RefDepth { path, max_depth } => write!(f, "the $ref of {} is nested more than {max_depth} levels deep", path.display()),
+			ManifestError::UnresolvedUrl { pack, url } => write!(f, "cannot resolve the manifest url {url:?} of pack {pack:?}"),
+			ManifestError::UnsupportedHash(error) => write!(f, "cannot check manifest: {error}"),
+			#[cfg(feature = "net")]
+			ManifestError::NoRuntime => write!(f, "cannot fetch manifest: {NoRuntime}")
+		}
+	}
+}
+
+impl std::error::Error for ManifestError {}
+
+/// Why a manifest didn't parse and where, see [ManifestError::Parse].
+#[derive(Debug)]
+pub struct ParseError {
+	/// What serde had to say, with the line and column it stopped at as [serde_json::Error::line] and [serde_json::Error::column].
+	pub error: serde_json::Error,
+	/// The keys and array indices leading to the value it stopped in joined with `/`, like `children/mods/files/foo.jar/sha`.
+	/// Empty at the top level and when the error has no position.
+	pub path: String,
+	/// The line around where it stopped, cut at [PARSE_EXCERPT_LENGTH] bytes on either side with control characters escaped. Empty when the error has no position.
+	pub excerpt: String
+}
+
+/// How many bytes of the text around a parse error [ParseError::excerpt] keeps on either side of it.
+pub const PARSE_EXCERPT_LENGTH: usize = 40;
+
+impl std::fmt::Display for ParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.error)?;
+		if !self.path.is_empty() {
+			write!(f, ", in {}", self.path)?;
+		}
+		if !self.excerpt.is_empty() {
+			write!(f, ", near \"{}\"", self.excerpt)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+/// A problem with the contents of a manifest, paths are relative to the manifest root.
+#[derive(Debug)]
+pub enum ValidationError {
+	/// A pack's manifest_sha isn't a digest, like 64 hex characters for sha256, see [hash::HashAlgorithm].
+	InvalidManifestSha {
+		pack: String,
+		sha: String
+	},
+	/// A link target is absolute or leads outside the manifest root.
+	InvalidLinkTarget {
+		path: PathBuf,
+		target: String
+	},
+	/// A file has both a url and inline content, or neither and no [Directory::url_template] applies.
+	InvalidSource {
+		path: PathBuf
+	},
+	/// A file's inline content isn't valid base64.
+	InvalidContent {
+		path: PathBuf
+	},
+	/// A [Directory::url_template] can't be expanded, `path` is the directory.
+	InvalidTemplate {
+		path: PathBuf,
+		error: template::TemplateError
+	},
+	/// The manifest needs a newer version of green-lib, the message is meant to be shown to users as is.
+	ClientTooOld {
+		required: String,
+		current: String
+	},
+	/// A `min_green_lib` isn't a semver version.
+	InvalidVersion {
+		version: String
+	},
+	/// The [packs::ManifestMetadata::min_launcher_version] of a pack isn't a semver version.
+	InvalidLauncherVersion {
+		pack: String,
+		version: String
+	},
+	/// The [packs::ManifestMetadata::successor] of a pack isn't in the packs list.
+	UnknownSuccessor {
+		pack: String,
+		successor: String
+	},
+	/// Following the [packs::ManifestMetadata::successor] of a pack never ends, because the chain loops.
+	SuccessorCycle {
+		pack: String
+	},
+	/// A packs list uses a newer schema than this version of green-lib understands.
+	SchemaTooNew {
+		version: u32,
+		supported: u32
+	},
+	/// The same name is used by more than one of a directory's files, children and links.
+	DuplicateName {
+		path: PathBuf
+	},
+	/// A [Directory::reference] has a sha that isn't 64 hex characters, is on the root, or is on a child that has entries of its own.
+	InvalidRef {
+		path: PathBuf
+	},
+	/// A file or directory has a `side` that isn't `client`, `server` or `both`.
+	InvalidSide {
+		path: PathBuf,
+		side: String
+	},
+	/// One of a file's [File::headers] isn't a valid header, or is one of [RESERVED_FILE_HEADERS]. Only the name is kept, the value may be a secret.
+	InvalidHeader {
+		path: PathBuf,
+		name: String
+	},
+	/// A file has [File::chunks] but no [File::size], or they don't cover its size.
+	InvalidChunks {
+		path: PathBuf
+	},
+	/// A key contains a `/` or `\` and [Directory::validate_with] was given [SeparatorPolicy::Error], `path` ends with the whole key.
+	SeparatorInKey {
+		path: PathBuf
+	},
+	/// [Directory::rewrite_urls] gave a file a url that isn't an absolute url, or a directory a [Directory::url_template] that doesn't expand to one.
+	/// Credentials in `url` are left out.
+	InvalidUrl {
+		path: PathBuf,
+		url: String
+	}
+}
+
+impl std::fmt::Display for ValidationError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ValidationError::InvalidManifestSha { pack, sha } => write!(f, "pack {pack} has an invalid manifest_sha {sha:?}"),
+			ValidationError::InvalidLinkTarget { path, target } => write!(f, "link {} has target {target:?} outside the manifest", path.display()),
+			ValidationError::InvalidSource { path } => write!(f, "file {} needs exactly one of url and content_b64, or a url_template", path.display()),
+			ValidationError::InvalidTemplate { path, error } => write!(f, "{error} in directory {path:?}"),
+			ValidationError::InvalidContent { path } => write!(f, "file {} has content_b64 that isn't valid base64", path.display()),
+			ValidationError::ClientTooOld { required, current } => write!(f, "this pack needs green-lib {required} or newer, but this launcher uses {current}, update the launcher"),
+			ValidationError::InvalidVersion { version } => write!(f, "min_green_lib {version:?} isn't a valid version"),
+			ValidationError::InvalidLauncherVersion { pack, version } => write!(f, "pack {pack} has a min_launcher_version {version:?} that isn't a valid version"),
+			ValidationError::UnknownSuccessor { pack, successor } => write!(f, "pack {pack} has a successor {successor:?} that isn't in the packs list"),
+			ValidationError::SuccessorCycle { pack } => write!(f, "the successors of pack {pack} go round in a loop"),
+			ValidationError::SchemaTooNew { version, supported } => write!(f, "this packs list uses schema version {version} but this launcher only understands up to {supported}, update the launcher"),
+			ValidationError::DuplicateName { path } => write!(f, "{} is declared more than once", path.display()),
+			ValidationError::InvalidRef { path } => write!(f, "directory {path:?} has an invalid $ref"),
+			ValidationError::InvalidSide { path, side } => write!(f, "{} has side {side:?}, expected client, server or both", path.display()),
+			ValidationError::InvalidHeader { path, name } => write!(f, "file {} has an invalid or reserved header {name:?}", path.display()),
+			ValidationError::InvalidChunks { path } => write!(f, "file {} has chunks that don't cover its size", path.display()),
+			ValidationError::SeparatorInKey { path } => write!(f, "key {} contains a path separator", path.display()),
+			ValidationError::InvalidUrl { path, url } => write!(f, "{} was given {url:?}, which isn't a valid absolute url", path.display())
+		}
+	}
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Why an upgrade failed.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub enum UpgradeError {
+	/// A file couldn't be downloaded, other downloads still ran to completion.
+	/// `url` is [None] for inline content.
+	Download {
+		path: PathBuf,
+		url: Option<String>,
+		attempts: u64,
+		/// Every URL the last attempt requested when it was redirected, starting with `url`, empty otherwise.
+		redirects: Vec<String>,
+		error: DownloadError,
+		failure: Box<DownloadFailure>
+	},
+	/// A symlink was found and the [SymlinkPolicy] is [SymlinkPolicy::Error].
+	Symlink {
+		path: PathBuf
+	},
+	/// A local entry couldn't be inspected and [UpgradeOptions::strict] is set.
+	Local {
+		path: PathBuf,
+		error: std::io::Error
+	},
+	/// The folder looks like a game is running from it, `path` is what gave it away.
+	/// Found before anything is changed unless [UpgradeOptions::ignore_in_use] is set, or later when a file turns out to be locked.
+	InUse {
+		path: PathBuf
+	},
+	/// A file was still locked after every retry of [UpgradeOptions::lock_retries].
+	/// Unlike [UpgradeError::InUse] the game usually isn't the cause, but an antivirus scanner or indexer that keeps grabbing the files green-lib writes.
+	Locked {
+		path: PathBuf,
+		operation: FileOperation,
+		error: LockError,
+		retries: u32
+	},
+	/// [UpgradeOptions::deadline] ran out and the folder is only partly upgraded, upgrading it again picks up where this left off.
+	/// `downloaded` and `unfinished` are the files that were downloaded and the ones whose downloads were stopped, sorted. Files the scan didn't get to when `scanned` is false aren't in either.
+	DeadlineExceeded {
+		deadline: std::time::Duration,
+		scanned: bool,
+		downloaded: Vec<PathBuf>,
+		unfinished: Vec<PathBuf>,
+		report: Box<UpgradeReport>
+	},
+	/// Nothing could connect, `url` is the request that made it clear.
+	/// A quick request is made once something would be deleted or downloaded, when it fails no unknown entries are deleted and the `not_attempted` downloads are skipped.
+	/// An upgrade of a folder that's already up to date doesn't need the network and won't fail with this.
+	Offline {
+		url: String,
+		not_attempted: usize
+	},
+	/// The manifests of [Directory::upgrade_game_folder_multi] conflict and [MergePrecedence::Error] was chosen, nothing was changed.
+	Merge(MergeError),
+	/// [FileSource::CacheOnly] was used and the cache is missing files that would have to be written, nothing was changed.
+	/// `missing` are their shas, sorted.
+	NotCached {
+		missing: Vec<Sha256Hash>
+	},
+	/// The pack has a notice that has to be accepted first and [UpgradeOptions::accept_notice] wasn't called, nothing was changed.
+	NoticeNotAccepted {
+		text: Option<String>,
+		url: Option<String>
+	},
+	/// A sub-manifest of a [Directory::reference] couldn't be fetched, nothing was changed.
+	Ref(ManifestError),
+	/// There's no [UpgradeOptions::runtime] and the upgrade isn't awaited inside a tokio runtime, or the runtime shut down before the upgrade finished.
+	NoRuntime,
+	/// [Directory::download_single] was given a path that isn't in the manifest.
+	NotInManifest(PathBuf),
+	/// [Directory::download_single] was given the path of a directory or link instead of a file.
+	NotAFile(PathBuf),
+	/// A key of the manifest contains a separator and [UpgradeOptions::separators] is [SeparatorPolicy::Error], or splitting it failed, nothing was changed.
+	Normalize(NormalizeError),
+	/// No HTTP client could be built from [UpgradeOptions::http], like for an invalid proxy url or TLS root certificate.
+	Client(reqwest::Error)
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for UpgradeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			UpgradeError::Download { path, url: Some(url), attempts, redirects, error, failure } if !redirects.is_empty() && !matches!(error, DownloadError::Redirect(_)) => {
+				let redirects: Vec<_> = redirects.iter().map(|url| http::redact(url)).collect();
+				write!(f, "failed to download {} from {} after {attempts} attempts: {error} (redirected through {})", path.display(), http::redact(url), redirects.join(" -> "))?;
+				failure.snippet.as_ref().map_or(Ok(()), |snippet| write!(f, ", body starts with \"{snippet}\""))
+			},
+			UpgradeError::Download { path, url: Some(url), attempts, error, failure, .. } => {
+				write!(f, "failed to download {} from {} after {attempts} attempts: {error}", path.display(), http::redact(url))?;
+				failure.snippet.as_ref().map_or(Ok(()), |snippet| write!(f, ", body starts with \"{snippet}\""))
+			},
+			UpgradeError::Download { path, url: None, error, .. } => write!(f, "failed to write inline {}: {error}", path.display()),
+			UpgradeError::Symlink { path } => write!(f, "found a symlink at {}", path.display()),
+			UpgradeError::Local { path, error } => write!(f, "cannot inspect {}: {error}", path.display()),
+			UpgradeError::InUse { path } => write!(f, "the folder appears to be in use ({} is open), close the game and try again", path.display()),
+			UpgradeError::DeadlineExceeded { deadline, scanned: true, downloaded, unfinished, .. } => write!(f, "the upgrade didn't finish within {deadline:?}, {} files were downloaded and {} were stopped", downloaded.len(), unfinished.len()),
+			UpgradeError::DeadlineExceeded { deadline, downloaded, .. } => write!(f, "the upgrade didn't finish scanning within {deadline:?}, {} files were downloaded", downloaded.len()),
+			UpgradeError::Locked { path, operation, error, retries } => write!(f, "cannot {operation} {}, it stayed locked ({error}) through {retries} retries, an antivirus scanner or search indexer may be holding it, excluding the folder from scanning usually helps", path.display()),
+			UpgradeError::Merge(error) => write!(f, "{error}"),
+			UpgradeError::Offline { url, not_attempted: 0 } => write!(f, "the network appears to be unavailable (cannot connect to {url})"),
+			UpgradeError::Offline { url, not_attempted } => write!(f, "the network appears to be unavailable (cannot connect to {url}), {not_attempted} downloads weren't attempted"),
+			UpgradeError::NotCached { missing } => write!(f, "the cache is missing {} files and downloading is disabled", missing.len()),
+			UpgradeError::NoticeNotAccepted { .. } => write!(f, "the notice of the pack has to be accepted before it's installed"),
+			UpgradeError::Ref(error) => write!(f, "{error}"),
+			UpgradeError::NoRuntime => write!(f, "no tokio runtime to upgrade on, set UpgradeOptions::runtime or await the upgrade inside one"),
+			UpgradeError::NotInManifest(path) => write!(f, "{} isn't in the manifest", path.display()),
+			UpgradeError::NotAFile(path) => write!(f, "{} isn't a file in the manifest", path.display()),
+			UpgradeError::Normalize(error) => write!(f, "{error}"),
+			UpgradeError::Client(error) => write!(f, "cannot build an HTTP client from the settings: {error}")
+		}
+	}
+}
+
+#[cfg(feature = "fs")]
+impl std::error::Error for UpgradeError {}
+
+/// Receives the same events as the [UpgradeStatus] channel, but through callbacks.
+/// Every method has an empty default implementation, so only the events you care about need implementing.
+/// Callbacks run on the tasks doing the upgrade, a panicking callback is caught and ignored.
+#[cfg(feature = "fs")]
+pub trait UpgradeObserver: Send + Sync {
+	/// Called for every event, dispatches to the more specific methods by default.
+	fn status(&self, status: &UpgradeStatus) {
+		match status {
+			UpgradeStatus::Length(length) => self.length(*length),
+			UpgradeStatus::Tick => {},
+			UpgradeStatus::Started(path) => self.started(path),
+			UpgradeStatus::Progressed(path, bytes) => self.progressed(path, *bytes),
+			UpgradeStatus::Finished(path) => self.finished(path),
+			UpgradeStatus::Failed { path, reason, failure, .. } => self.failed_with_details(path, reason, failure),
+			UpgradeStatus::Retrying { path, attempt, max_attempts, delay, reason } => self.retrying(path, *attempt, *max_attempts, *delay, reason),
+			UpgradeStatus::Deleted(path) => self.deleted(path),
+			UpgradeStatus::Kept(path) => self.kept(path),
+			UpgradeStatus::Filtered(path) => self.filtered(path),
+			UpgradeStatus::NotApplicable(path) => self.not_applicable(path),
+			UpgradeStatus::Skipped(path) => self.skipped(path),
+			UpgradeStatus::Linked(path) => self.linked(path),
+			UpgradeStatus::Extracted(path) => self.extracted(path),
+			UpgradeStatus::Warning { path, reason } => self.warning(path, reason),
+			UpgradeStatus::EnteringDirectory(path) => self.entering_directory(path),
+			UpgradeStatus::LeavingDirectory(path) => self.leaving_directory(path),
+			UpgradeStatus::Paused => self.paused(),
+			UpgradeStatus::Resumed => self.resumed()
+		}
+	}
+
+	fn length(&self, _length: usize) {}
+	fn started(&self, _path: &Path) {}
+	fn progressed(&self, _path: &Path, _bytes: u64) {}
+	fn finished(&self, _path: &Path) {}
+	fn failed(&self, _path: &Path, _reason: &str, _attempts: u64) {}
+	/// Same as [UpgradeObserver::failed] with everything known about the failure, calls that by default.
+	fn failed_with_details(&self, path: &Path, reason: &str, failure: &DownloadFailure) {
+		self.failed(path, reason, failure.attempts);
+	}
+	fn retrying(&self, _path: &Path, _attempt: u64, _max_attempts: u64, _delay: std::time::Duration, _reason: &RetryReason) {}
+	fn deleted(&self, _path: &Path) {}
+	fn kept(&self, _path: &Path) {}
+	fn filtered(&self, _path: &Path) {}
+	fn not_applicable(&self, _path: &Path) {}
+	fn skipped(&self, _path: &Path) {}
+	fn linked(&self, _path: &Path) {}
+	fn extracted(&self, _path: &Path) {}
+	fn warning(&self, _path: &Path, _reason: &str) {}
+	fn entering_directory(&self, _path: &Path) {}
+	fn leaving_directory(&self, _path: &Path) {}
+	fn paused(&self) {}
+	fn resumed(&self) {}
+}
+
+/// Where the [UpgradeStatus] events of an upgrade go.
+#[cfg(feature = "fs")]
+#[derive(Clone, Default)]
+pub enum Progress {
+	#[default]
+	None,
+	Channel(mpsc::Sender<UpgradeStatus>),
+	Observer(Arc<dyn UpgradeObserver>),
+	/// A channel that isn't tied to tokio, for listening from another executor.
+	#[cfg(feature = "runtime-agnostic")]
+	Futures(futures_channel::mpsc::Sender<UpgradeStatus>)
+}
+
+#[cfg(feature = "fs")]
+impl Progress {
+	pub(crate) async fn emit(&self, status: UpgradeStatus) {
+		match self {
+			Progress::None => {},
+			// nobody listening anymore isn't a reason to stop the upgrade
+			Progress::Channel(tx) => {
+				let _ = tx.send(status).await;
+			},
+			Progress::Observer(observer) => {
+				let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| observer.status(&status)));
+			},
+			#[cfg(feature = "runtime-agnostic")]
+			Progress::Futures(tx) => {
+				let mut tx = tx.clone();
+				if std::future::poll_fn(|cx| tx.poll_ready(cx)).await.is_ok() {
+					let _ = tx.start_send(status);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(feature = "fs")]
+impl From<Option<mpsc::Sender<UpgradeStatus>>> for Progress {
+	fn from(tx: Option<mpsc::Sender<UpgradeStatus>>) -> Self {
+		tx.map_or(Progress::None, Progress::Channel)
+	}
+}
+
+#[cfg(feature = "fs")]
+impl From<mpsc::Sender<UpgradeStatus>> for Progress {
+	fn from(tx: mpsc::Sender<UpgradeStatus>) -> Self {
+		Progress::Channel(tx)
+	}
+}
+
+#[cfg(feature = "fs")]
+impl From<Arc<dyn UpgradeObserver>> for Progress {
+	fn from(observer: Arc<dyn UpgradeObserver>) -> Self {
+		Progress::Observer(observer)
+	}
+}
+
+#[cfg(feature = "runtime-agnostic")]
+impl From<futures_channel::mpsc::Sender<UpgradeStatus>> for Progress {
+	fn from(tx: futures_channel::mpsc::Sender<UpgradeStatus>) -> Self {
+		Progress::Futures(tx)
+	}
+}
+
+impl Directory {
+	/// # Description
+	/// Fetches a manifest from a URL.
+	/// # Warning
+	/// This function will trust any URL you stick into it, ideally make sure only trusted URLs are passed in, or at least ensure all URLs use encrypted protocols like HTTPS.
+	#[cfg(feature = "net")]
+	pub async fn from_url<U: AsRef<str>>(url: U) -> Option<Self> {
+		Self::try_from_url(url).await.ok()
+	}
+
+	/// # Description
+	/// Same as [Directory::from_url], but returns why it failed instead of [None].
+	/// A body that isn't a manifest fails with [ManifestError::NotManifest], which tells what the server sent instead.
+	#[cfg(feature = "net")]
+	pub async fn try_from_url<U: AsRef<str>>(url: U) -> Result<Self, ManifestError> {
+		NoRuntime::check()?;
+		fetch::manifest(url, None).await
+	}
+
+	/// # Description
+	/// Parses a manifest from bytes.
+	pub fn from_slice(bytes: &[u8]) -> Result<Self, ManifestError> {
+		manifest::from_slice(bytes)
+	}
+
+	/// # Description
+	/// Parses a manifest from bytes like [Self::from_slice], but fails with [ManifestError::UnknownField] on any field this version doesn't know about.
+	/// Meant for checking manifests before publishing them, clients should stick to the permissive constructors so newer manifests keep working.
+	pub fn from_slice_strict(bytes: &[u8]) -> Result<Self, ManifestError> {
+		manifest::from_slice_strict(bytes)
+	}
+
+	/// # Description
+	/// Parses a manifest from a reader, wrap it in a [std::io::BufReader] if it isn't buffered already.
+	pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Self, ManifestError> {
+		manifest::from_reader(reader)
+	}
+
+	/// # Description
+	/// The JSON schema of manifests, derived from this type so it changes along with it, for checking manifests made by other tools before a client sees them.
+	/// Fields this version doesn't know are allowed like with [Directory::from_slice], and what [Directory::validate] checks on top, like a file having a url or inline content, isn't part of it.
+	#[cfg(feature = "schemars")]
+	pub fn json_schema() -> schemars::schema::RootSchema {
+		schemars::schema_for!(Directory)
+	}
+
+	/// # Description
+	/// Checks that every entry in this manifest makes sense, the constructors in this crate do this for you.
+	pub fn validate(&self) -> Result<(), ValidationError> {
+		self.validate_with(SeparatorPolicy::Split)
+	}
+
+	/// # Description
+	/// Same as [Directory::validate], but with [SeparatorPolicy::Error] a key that contains a `/` or `\` fails with [ValidationError::SeparatorInKey] instead of being left for [UpgradeOptions::separators] to split.
+	pub fn validate_with(&self, separators: SeparatorPolicy) -> Result<(), ValidationError> {
+		manifest::check_min_version(&self.min_green_lib)?;
+		self.validate_at(Path::new(""), None, separators)
+	}
+
+	fn validate_at(&self, relative: &Path, url_template: Option<&str>, separators: SeparatorPolicy) -> Result<(), ValidationError> {
+		if separators == SeparatorPolicy::Error {
+			let mut keys = self.files.keys().chain(self.children.keys()).chain(self.links.keys());
+			if let Some(key) = keys.find(|key| key.contains(['/', '\\'])) {
+				return Err(ValidationError::SeparatorInKey {
+					path: relative.join(key)
+				});
+			}
+		}
+		if let Side::Unknown(side) = &self.side {
+			return Err(ValidationError::InvalidSide {
+				path: relative.to_owned(),
+				side: side.clone()
+			});
+		}
+		if let Some(url_template) = &self.url_template {
+			if let Err(error) = template::expand(url_template, &Sha256Hash([0; 32])) {
+				return Err(ValidationError::InvalidTemplate {
+					path: relative.to_owned(),
+					error
+				});
+			}
+		}
+		let url_template = self.url_template.as_deref().or(url_template);
+
+		for (name, target) in &self.links {
+			let path = relative.join(name);
+			if self.files.contains_key(name) || self.children.contains_key(name) {
+				return Err(ValidationError::DuplicateName {
+					path
+				});
+			}
+
+			if !link_target_inside(relative.components().count(), target) {
+				return Err(ValidationError::InvalidLinkTarget {
+					path,
+					target: target.clone()
+				});
+			}
+		}
+
+		for (name, file) in &self.files {
+			let path = relative.join(name);
+			if self.children.contains_key(name) {
+				return Err(ValidationError::DuplicateName {
+					path
+				});
+			}
+
+			if let Side::Unknown(side) = &file.side {
+				return Err(ValidationError::InvalidSide {
+					path,
+					side: side.clone()
+				});
+			}
+
+			match &file.content_b64 {
+				Some(_) if file.url.is_some() => return Err(ValidationError::InvalidSource {
+					path
+				}),
+				Some(content) if base64::engine::general_purpose::STANDARD.decode(content).is_err() => return Err(ValidationError::InvalidContent {
+					path
+				}),
+				None if file.url.is_none() && url_template.is_none() => return Err(ValidationError::InvalidSource {
+					path
+				}),
+				_ => {}
+			}
+
+			if let Some(name) = file.headers.iter().find(|(name, value)| !valid_file_header(name, value)).map(|(name, _)| name) {
+				return Err(ValidationError::InvalidHeader {
+					path,
+					name: name.clone()
+				});
+			}
+
+			if file.chunks.as_ref().is_some_and(|chunks| !file.size.is_some_and(|size| chunks.fits(size))) {
+				return Err(ValidationError::InvalidChunks {
+					path
+				});
+			}
+		}
+
+		if let Some(reference) = &self.reference {
+			let has_entries = !(self.files.is_empty() && self.children.is_empty() && self.links.is_empty());
+			if relative.as_os_str().is_empty() || has_entries || !manifest::is_sha256(&reference.sha) {
+				return Err(ValidationError::InvalidRef {
+					path: relative.to_owned()
+				});
+			}
+		}
+
+		for (name, child) in &self.children {
+			child.validate_at(&relative.join(name), url_template, separators)?;
+		}
+
+		Ok(())
+	}
+
+	/// # Description
+	/// Reads a manifest from a file, like one bundled with a launcher.
+	#[cfg(feature = "fs")]
+	pub async fn from_file(path: &Path) -> Result<Self, ManifestError> {
+		NoRuntime::check()?;
+		manifest::read_file(path).await
+	}
+
+	/// # Description
+	/// Builds a manifest from a local folder, for publishing a pack.
+	/// Symlinks, the `.green-lib` folder, the [IGNORE_FILE] and what it or [FromPathOptions::exclude] match are left out, excluded entries aren't even read.
+	/// With [FromPathOptions::spawner] this and the other scans can be awaited on any executor.
+	#[cfg(feature = "fs")]
+	pub async fn from_path(path: &Path, options: &FromPathOptions) -> std::io::Result<Self> {
+		Self::from_path_with_report(path, options).await.map(|(directory, _)| directory)
+	}
+
+	/// # Description
+	/// Same as [Directory::from_path], also reporting which entries were left out by which pattern.
+	#[cfg(feature = "fs")]
+	pub async fn from_path_with_report(path: &Path, options: &FromPathOptions) -> std::io::Result<(Self, FromPathReport)> {
+		Self::scan(path, options, None, None).await.map(|(directory, report, _)| (directory, report))
+	}
+
+	/// # Description
+	/// Same as [Directory::from_path_with_report], but files whose size and modification time are still what `cache` recorded aren't read again, their sha comes from `cache`.
+	/// The [File::chunks] of those files are taken from `previous`, the manifest the scan of `cache` built, when it has them with the same sha and chunk size, otherwise the file is read after all.
+	/// Returns the cache to save for next time. Passing [ScanCache::default] hashes everything like [Directory::from_path] does, for when modification times can't be trusted.
+	/// A file that's changed without its size or modification time changing ends up in the manifest with its old sha, like with [UpgradeOptions::hash_cache].
+	#[cfg(feature = "fs")]
+	pub async fn from_path_incremental(path: &Path, options: &FromPathOptions, previous: &Directory, cache: &ScanCache) -> std::io::Result<(Self, FromPathReport, ScanCache)> {
+		Self::scan(path, options, Some(previous), Some(&cache.0)).await
+	}
+
+	#[cfg(feature = "fs")]
+	async fn scan(path: &Path, options: &FromPathOptions, previous: Option<&Directory>, cache: Option<&hash_cache::HashCache>) -> std::io::Result<(Self, FromPathReport, ScanCache)> {
+		let executor = executor::Executor::of_scan(options);
+		if executor.is_tokio() {
+			NoRuntime::check()?;
+		}
+		let url_prefix = match options.base_url.ends_with('/') {
+			true => options.base_url.clone(),
+			false => format!("{}/", options.base_url)
+		};
+		let rules = ignore::Rules::load(path, options, &executor).await?;
+		let mut generator = generate::Generator {
+			options,
+			executor,
+			rules: &rules,
+			previous: cache,
+			excluded: vec![],
+			cache: Default::default(),
+			reused: 0
+		};
+		let mut directory = generator.directory(path, &url_prefix, "", previous).await?;
+		directory.url_template = options.url_template.clone();
+		if options.subtree_hashes {
+			directory.set_subtree_hashes();
+		}
+
+		let mut excluded = generator.excluded;
+		excluded.sort_by(|a, b| a.path.cmp(&b.path));
+		let report = FromPathReport {
+			excluded,
+			reused: generator.reused
+		};
+		Ok((directory, report, ScanCache(generator.cache)))
+	}
+
+	/// # Description
+	/// Looks up a file by its path relative to the manifest root.
+	pub fn file(&self, path: &Path) -> Option<&File> {
+		let mut components = path.components().map(|component| component.as_os_str().to_str());
+		let name = components.next_back()??;
+
+		let mut directory = self;
+		for component in components {
+			directory = directory.children.get(component?)?;
+		}
+		directory.files.get(name)
+	}
+
+	/// # Description
+	/// Downloads the file at `relative` (`/` separated, like `mods/sodium.jar`) to the same path under `root`, without looking at anything else in the folder.
+	/// It's downloaded like in an upgrade with the same `options`, for repairing a file [Directory::verify_folder] found to be broken.
+	/// Fails with [UpgradeError::NotInManifest] or [UpgradeError::NotAFile] when there's no such file in the manifest.
+	#[cfg(feature = "fs")]
+	pub async fn download_single(&self, relative: &str, root: &Path, options: &UpgradeOptions, progress: impl Into<Progress>) -> Result<(), UpgradeError> {
+		let progress = progress.into();
+		match upgrade::runtime(options)? {
+			Some(runtime) => {
+				let (directory, relative, root, options) = (self.clone(), relative.to_owned(), root.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move { download::download_single(&directory, &relative, &root, &options, progress).await })).await
+			},
+			None => download::download_single(self, relative, root, options, progress).await
+		}
+	}
+
+	/// # Description
+	/// Lists every file in the `sha256sum` format, sorted by path with `/` separators.
+	/// Names with a backslash or line break are escaped the way GNU sha256sum does it.
+	pub fn to_sha256sums(&self) -> String {
+		let mut files = vec![];
+		checksums::files(self, Path::new(""), &mut files);
+		checksums::format(files)
+	}
+
+	/// # Description
+	/// Builds a manifest from a `sha256sum` listing, `url_for` gives the url of each relative path.
+	/// Text and binary mode (`*`) lines are both accepted, paths leaving the root are rejected.
+	pub fn from_sha256sums(text: &str, url_for: impl Fn(&Path) -> String) -> Result<Self, Sha256SumsError> {
+		checksums::parse(text, url_for)
+	}
+
+	/// # Description
+	/// Compares a folder to this manifest without changing anything.
+	/// Uses the hashing settings of `options`, files that match are reported as [UpgradeStatus::Skipped].
+	/// With [UpgradeOptions::hash_cache], children whose [Directory::subtree_hash] was already applied are reported as matching without being read.
+	/// Runs on [UpgradeOptions::runtime] like an upgrade, without one and outside a tokio runtime the only entry is a [VerificationReport::unreadable] for the folder itself (an empty path) with [NoRuntime].
+	/// With [UpgradeOptions::spawner] it runs where it's awaited instead, on any executor.
+	#[cfg(feature = "fs")]
+	pub async fn verify_folder(&self, path: &Path, options: &UpgradeOptions, progress: impl Into<Progress>) -> VerificationReport {
+		let progress = progress.into();
+		if !executor::Executor::of(options).is_tokio() {
+			return verify::verify(self, path, options, progress).await;
+		}
+		let report = match upgrade::runtime(options) {
+			Ok(Some(runtime)) => {
+				let (directory, path, options) = (self.clone(), path.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move { Ok(verify::verify(&directory, &path, &options, progress).await) })).await
+			},
+			Ok(None) => return verify::verify(self, path, options, progress).await,
+			Err(error) => Err(error)
+		};
+		report.unwrap_or_else(|_| VerificationReport {
+			unreadable: vec![UpgradeWarning {
+				path: PathBuf::new(),
+				error: NoRuntime.into()
+			}],
+			..Default::default()
+		})
+	}
+
+	/// # Description
+	/// Watches a folder this manifest was applied to and reports files of the manifest that are modified or deleted and unknown entries an upgrade would delete, so it can be upgraded again right away.
+	/// Changes from before the watcher started aren't reported, see [Directory::verify_folder] for those. Like the upgrade, unknown entries at the top level like saves don't count.
+	/// The OS notifies the watcher of changes (inotify, FSEvents, ReadDirectoryChangesW), the paths they're about are looked at without reading files once they settled for [DriftOptions::debounce]. A changed file of the manifest is only hashed then, and only reported if its sha is wrong.
+	/// Watching runs on the current tokio runtime until the [FolderWatcher] is dropped, outside of one this fails with [NoRuntime] as an [std::io::Error]. It also fails when the folder can't be watched, like when it doesn't exist or the OS is out of watches.
+	#[cfg(feature = "notify")]
+	pub fn watch_folder(&self, path: &Path, options: &DriftOptions) -> std::io::Result<FolderWatcher> {
+		NoRuntime::check()?;
+		drift::watch(self, path, options)
+	}
+
+	/// # Description
+	/// Uninstalls a pack by removing the files, links and directories this manifest describes from `path`, everything else like saves is left alone.
+	/// Directories are only removed once they're empty, the folder itself is never removed, and green-lib's own state in it goes when nothing of the pack is left.
+	/// Reports [UpgradeStatus::Deleted] for what's removed, [UpgradeStatus::Kept] for what was changed and left, and [UpgradeStatus::Filtered].
+	/// Fails with [UpgradeError::InUse] like an upgrade does, other problems end up in [RemoveReport::warnings].
+	#[cfg(feature = "fs")]
+	pub async fn remove_from_folder(&self, path: &Path, options: &RemoveOptions, progress: impl Into<Progress>) -> Result<RemoveReport, UpgradeError> {
+		NoRuntime::check()?;
+		remove::remove(self, path, options, progress.into()).await
+	}
+
+	/// # Description
+	/// Rolls a folder that `newer` was applied to back to this manifest, like after an update that broke something.
+	/// Files and links that only `newer` has are removed if they still match it, then the folder is upgraded to this manifest without deleting unknown entries, so saves and anything else the user added are never touched.
+	/// Reports like [Directory::remove_from_folder] followed by an upgrade, fails with [UpgradeError::InUse] before changing anything like both of them do.
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn rollback_game_folder(&self, newer: &Directory, path: &Path, options: &RollbackOptions, progress: impl Into<Progress>) -> Result<RollbackReport, UpgradeError> {
+		let progress = progress.into();
+		match upgrade::runtime(&options.upgrade)? {
+			Some(runtime) => {
+				let (previous, newer, path, options) = (self.clone(), newer.clone(), path.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move { rollback::rollback(&previous, &newer, &path, &options, progress).await })).await
+			},
+			None => rollback::rollback(self, newer, path, options, progress).await
+		}
+	}
+
+	/// # Description
+	/// Downloads every file of this manifest into `cache` without touching any folder, so a later upgrade with [UpgradeOptions::cache] doesn't need the network.
+	/// An upgrade whose cache has every file doesn't check for a connection first, so it also works offline.
+	/// Blobs the cache already has are checked and reported as [UpgradeStatus::Skipped], downloads report like they do in an upgrade and are always verified.
+	/// Uses the network settings and [UpgradeOptions::filter] of `options`, `options.cache` is ignored.
+	/// Only fails when the cache can't be written to, blobs that can't be downloaded end up in [PrefetchReport::missing].
+	#[cfg(feature = "fs")]
+	pub async fn prefetch(&self, cache: &ObjectCache, options: &UpgradeOptions, progress: impl Into<Progress>) -> Result<PrefetchReport, UpgradeError> {
+		let progress = progress.into();
+		match upgrade::runtime(options)? {
+			Some(runtime) => {
+				let (directory, cache, options) = (self.clone(), cache.clone(), options.clone());
+				upgrade::join(runtime.spawn(async move { prefetch::prefetch(&directory, &cache, &options, progress).await })).await
+			},
+			None => prefetch::prefetch(self, cache, options, progress).await
+		}
+	}
+
+	/// # Description
+	/// Serializes this manifest to compact JSON with sorted keys and no trailing newline.
+	/// This is the form [Directory::canonical_sha] hashes, serve exactly these bytes when pinning a sha.
+	pub fn to_string_canonical(&self) -> serde_json::Result<String> {
+		manifest::to_string_canonical(self)
+	}
+
+	/// # Description
+	/// Returns the sha256 of [Directory::to_string_canonical], suitable for a packs list `manifest_sha`.
+	pub fn canonical_sha(&self) -> serde_json::Result<String> {
+		Ok(manifest::sha256(self.to_string_canonical()?.as_bytes()))
+	}
+
+	/// # Description
+	/// Cleans up a manifest with [NormalizeOptions::default], see [Directory::normalize_with_options].
+	pub fn normalize(&mut self) -> Result<NormalizeReport, NormalizeError> {
+		self.normalize_with_options(&NormalizeOptions::default())
+	}
+
+	/// # Description
+	/// Trims whitespace around keys, deals with keys containing separators as `options` says and prunes empty children.
+	/// Call it before [Directory::canonical_sha] so the published sha is of the normalized manifest.
+	/// Nothing is changed when it fails.
+	pub fn normalize_with_options(&mut self, options: &NormalizeOptions) -> Result<NormalizeReport, NormalizeError> {
+		normalize::normalize(self, options)
+	}
+
+	/// # Description
+	/// Combines manifests into one that has the entries of all of them, for installing several packs into the same folder.
+	/// Conflicts are resolved as `precedence` says, files with the same sha at the same path don't conflict.
+	/// Url templates are expanded into the urls of the files, the newest [Directory::min_green_lib] is kept and unknown fields of later manifests win.
+	pub fn merge(directories: &[&Directory], precedence: MergePrecedence) -> Result<Directory, MergeError> {
+		merge::merge(directories, precedence).map(|merged| merged.directory)
+	}
+
+	/// # Description
+	/// The entries of this manifest whose path `other` doesn't have, like what an add-on pack puts on disk beyond its base pack.
+	/// Directories that are left empty are dropped, ones `other` doesn't have at all are kept as they are. Keys aren't split, references aren't resolved.
+	pub fn subtract(&self, other: &Directory) -> Directory {
+		merge::subtract(self, other, false)
+	}
+
+	/// # Description
+	/// Same as [Directory::subtract], but files and links that `other` has with another sha or target (or as another kind of entry) are kept too.
+	pub fn subtract_changed(&self, other: &Directory) -> Directory {
+		merge::subtract(self, other, true)
+	}
+
+	/// # Description
+	/// Keeps only the files `keep` returns true for, it gets their relative path from the root of this manifest.
+	/// Children left without anything in them are dropped, empty ones that were already empty, like references, are kept. Links aren't touched.
+	pub fn retain<F: FnMut(&Path, &File) -> bool>(&mut self, mut keep: F) {
+		filter::retain(self, &mut PathBuf::new(), &mut keep);
+	}
+
+	/// # Description
+	/// Same as [Directory::retain], but returns a filtered copy and leaves this manifest as it is.
+	pub fn filtered<F: FnMut(&Path, &File) -> bool>(&self, keep: F) -> Directory {
+		let mut filtered = self.clone();
+		filtered.retain(keep);
+		filtered
+	}
+
+	/// # Description
+	/// Calls `edit` with every file and its relative path from the root of this manifest, like for pointing urls at a mirror.
+	/// Files in children that are still references aren't there yet, see [Directory::resolve_refs].
+	pub fn map_files<F: FnMut(&Path, &mut File)>(&mut self, mut edit: F) {
+		filter::map_files(self, &mut PathBuf::new(), &mut edit);
+	}
+
+	/// # Description
+	/// Points every url that starts with `old_prefix` at `new_prefix` instead, like after moving the files of a pack to another host, and returns how many urls it changed.
+	/// See [Directory::rewrite_urls] for which urls that covers.
+	#[cfg(feature = "net")]
+	pub fn relocate_urls(&mut self, old_prefix: &str, new_prefix: &str) -> Result<usize, ValidationError> {
+		self.rewrite_urls(|url| url.strip_prefix(old_prefix).map(|rest| format!("{new_prefix}{rest}")))
+	}
+
+	/// # Description
+	/// Replaces the url of every file and every [Directory::url_template] that `rewrite` returns a new one for, and returns how many it changed. Shas and everything else are left as they are.
+	/// Fails with [ValidationError::InvalidUrl] without changing anything when a new url isn't an absolute url, or a new template doesn't expand to one.
+	/// Files in children that are still references aren't there yet, see [Directory::resolve_refs].
+	#[cfg(feature = "net")]
+	pub fn rewrite_urls<F: FnMut(&str) -> Option<String>>(&mut self, mut rewrite: F) -> Result<usize, ValidationError> {
+		let mut rewritten = self.clone();
+		let count = filter::rewrite_urls(&mut rewritten, &mut PathBuf::new(), &mut rewrite)?;
+		*self = rewritten;
+		Ok(count)
+	}
+
+	/// # Description
+	/// Fetches the manifest at `url` every `interval` and yields it whenever what it puts on disk changed, starting with the first one that could be fetched.
+	/// Polls are conditional requests, so a server that sends an `ETag` or `Last-Modified` answers unchanged manifests without a body.
+	/// Changes that don't show in a [DirectoryDiff], like new urls, aren't reported. Failed polls are, and the wait is doubled after each one up to [WatchOptions::max_backoff].
+	/// Polling runs on the current tokio runtime until the [Watcher] is dropped, outside of one the watcher only yields [ManifestError::NoRuntime].
+	#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+	pub fn watch_url<U: Into<String>>(url: U, interval: std::time::Duration, options: &WatchOptions) -> Watcher<ManifestChange> {
+		watch::watch(url.into(), interval, options, |previous: Option<&Directory>, directory: &Directory| {
+			let diff = previous.unwrap_or(&Directory::default()).diff(directory);
+			(previous.is_none() || !diff.is_empty()).then(|| ManifestChange {
+				directory: directory.clone(),
+				diff
+			})
+		})
+	}
+
+	/// # Description
+	/// The `/` separated paths of the children whose [Directory::reference] wasn't resolved yet, sorted.
+	pub fn unresolved_refs(&self) -> Vec<String> {
+		let mut unresolved = vec![];
+		let mut stack = vec![(self, String::new())];
+		while let Some((directory, path)) = stack.pop() {
+			for (name, child) in &directory.children {
+				let child_path = match path.is_empty() {
+					true => name.clone(),
+					false => format!("{path}/{name}")
+				};
+				match child.reference {
+					Some(_) => unresolved.push(child_path),
+					None => stack.push((child, child_path))
+				}
+			}
+		}
+		unresolved.sort();
+		unresolved
+	}
+
+	/// # Description
+	/// Compares what this manifest and `newer` put on disk, like [Directory::content_hash] urls, sizes and unknown fields don't count. A child that is still a `$ref` is compared by the sha it points to.
+	/// Keys that contain a separator are split first, like an upgrade does by default.
+	pub fn diff(&self, newer: &Directory) -> DirectoryDiff {
+		let separated = |directory| normalize::separated(directory, SeparatorPolicy::Split).unwrap_or(std::borrow::Cow::Borrowed(directory));
+		checksums::diff(&separated(self), &separated(newer))
+	}
+
+	/// # Description
+	/// Same as [Directory::diff], only with the files and children installed for `side`, so what's only for the other side is neither added, removed nor changed.
+	pub fn diff_for(&self, newer: &Directory, side: &Side) -> DirectoryDiff {
+		filter::for_side(self, side).diff(&filter::for_side(newer, side))
+	}
+
+	/// # Description
+	/// Counts what this manifest holds in a single pass, without copying it. Children that are still a `$ref` count as empty.
+	pub fn stats(&self) -> DirectoryStats {
+		stats::stats(self)
+	}
+
+	/// # Description
+	/// Same as [Directory::stats], only with the files and children installed for `side`, like the size of a server install.
+	/// Copies the manifest unless `side` is [Side::Both].
+	pub fn stats_for(&self, side: &Side) -> DirectoryStats {
+		stats::stats(&filter::for_side(self, side))
+	}
+
+	/// # Description
+	/// The [ManifestDelta] that turns this manifest into `newer`, for a server to publish next to it, see [packs::ManifestMetadata::delta_url_template].
+	/// Unlike [Directory::diff] every field counts, so a file that only moved to another url is in it too.
+	pub fn delta_to(&self, newer: &Directory) -> ManifestDelta {
+		delta::delta(self, newer)
+	}
+
+	/// # Description
+	/// Applies a [ManifestDelta] made from this manifest, checking that the result has the [Directory::content_hash] it promises and is valid.
+	pub fn apply_delta(&self, delta: &ManifestDelta) -> Result<Directory, DeltaError> {
+		delta::apply(self, delta)
+	}
+
+	/// # Description
+	/// Returns a sha256 of what this manifest puts on disk: the path of every file, directory and link, the sha of every file and the target of every link.
+	/// Urls, sizes, inline encoding and unknown fields don't change it, so a pack that moved to another host has the same hash.
+	/// It doesn't depend on [HashMap] order and stays the same across versions of green-lib unless the manifest format changes.
+	/// A child that is still a `$ref` counts by the sha of its sub-manifest, so resolving it changes the hash.
+	pub fn content_hash(&self) -> [u8; 32] {
+		checksums::content_hash(self)
+	}
+
+	/// # Description
+	/// Sets the [Directory::subtree_hash] of every child below this directory to its [Directory::content_hash].
+	/// Children that are still references don't get one, and neither do the directories above them, since resolving changes their contents.
+	pub fn set_subtree_hashes(&mut self) {
+		checksums::set_subtree_hashes(self);
+	}
+
+	/// # Description
+	/// Writes this manifest as JSON with sorted keys, `pretty` adds indentation.
+	pub fn to_writer<W: std::io::Write>(&self, writer: W, pretty: bool) -> serde_json::Result<()> {
+		manifest::to_writer(self, writer, pretty)
+	}
+
+	/// # Description
+	/// Writes this manifest to a file atomically (through a temporary file and a rename), `pretty` adds indentation.
+	#[cfg(feature = "fs")]
+	pub async fn to_file(&self, path: &Path, pretty: bool) -> std::io::Result<()> {
+		NoRuntime::check()?;
+		manifest::to_file(self, path, pretty).await
+	}
+
+	/// # Description
+	/// Packages every file of this manifest from `source` into a zip at `zip_path`, with paths that mirror the manifest tree.
+	/// Each file is checked against the manifest as it's added, the export fails if anything is missing or doesn't match.
+	/// The result can be used as a [Bootstrap] archive.
+	#[cfg(feature = "zip")]
+	pub async fn export_zip(&self, source: &Path, zip_path: &Path) -> Result<(), ExportError> {
+		self.export_zip_inner(source, zip_path, None).await
+	}
+
+	/// # Description
+	/// Same as [Directory::export_zip], but also writes this manifest as `manifest_name` at the root of the archive so it describes itself.
+	#[cfg(feature = "zip")]
+	pub async fn export_zip_with_manifest(&self, source: &Path, zip_path: &Path, manifest_name: &str) -> Result<(), ExportError> {
+		self.export_zip_inner(source, zip_path, Some(manifest_name.to_owned())).await
+	}
+
+	#[cfg(feature = "zip")]
+	async fn export_zip_inner(&self, source: &Path, zip_path: &Path, manifest_name: Option<String>) -> Result<(), ExportError> {
+		NoRuntime::check().map_err(|error| ExportError::Io(error.into()))?;
+		let (directory, source, zip_path) = (self.clone(), source.to_owned(), zip_path.to_owned());
+		tokio::task::spawn_blocking(move || archive::export(&directory, &source, &zip_path, manifest_name.as_deref())).await.unwrap()
+	}
+
+	/// # Description
+	/// Fetches a manifest from a URL and checks that the digest of the response matches `expected_sha` before parsing it, a sha256 or one prefixed with its [hash::HashAlgorithm] like `sha512:`.
+	/// This is the same check [packs::ManifestMetadata::to_directory] does.
+	#[cfg(feature = "net")]
+	pub async fn from_url_verified<U: AsRef<str>>(url: U, expected_sha: &str) -> Result<Self, ManifestError> {
+		NoRuntime::check()?;
+		fetch::manifest(url, Some(expected_sha)).await
+	}
+
+	/// # Description
+	/// Fetches the sub-manifest of every [Directory::reference] and puts it in place of the child, checking it against its sha.
+	/// References in sub-manifests are resolved as well, up to `max_depth` levels of them, deeper ones are left for a later call.
+	/// Upgrades resolve references themselves, this is for looking at a whole pack or for resolving only part of it.
+	#[cfg(feature = "net")]
+	pub async fn resolve_refs(&mut self, max_depth: usize) -> Result<(), ManifestError> {
+		self.resolve_refs_with(max_depth, &mut HashMap::new()).await
+	}
+
+	/// # Description
+	/// Same as [Directory::resolve_refs], but sub-manifests that are in `cache` by sha aren't fetched again and every fetched one is added to it.
+	/// Keeping the cache between updates of a pack means only the subtrees that changed are fetched and parsed.
+	#[cfg(feature = "net")]
+	pub async fn resolve_refs_with(&mut self, max_depth: usize, cache: &mut HashMap<String, Directory>) -> Result<(), ManifestError> {
+		NoRuntime::check()?;
+		refs::resolve(self, &http::shared(), max_depth, cache).await
+	}
+
+	/// # Description
+	/// Updates a path to match the state of this instance.
+	/// The sender recieves a vague indication of status through the [UpgradeStatus] enum.
+	/// Returns the first [UpgradeError] encountered, but only once every other download has finished, or an [UpgradeReport] if everything worked.
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn upgrade_game_folder(&self, path: &Path, tx: Option<mpsc::Sender<UpgradeStatus>>) -> Result<UpgradeReport, UpgradeError> {
+		self.upgrade_game_folder_with_options(path, &UpgradeOptions::default(), tx).await
+	}
+
+	/// # Description
+	/// Same as [Directory::upgrade_game_folder], but reports status through an [UpgradeObserver] instead of a channel.
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn upgrade_game_folder_with_observer(&self, path: &Path, observer: Arc<dyn UpgradeObserver>) -> Result<UpgradeReport, UpgradeError> {
+		self.upgrade_game_folder_with_options(path, &UpgradeOptions::default(), observer).await
+	}
+
+	/// # Description
+	/// Same as [Directory::upgrade_game_folder], but with [UpgradeOptions] and either a channel or an [UpgradeObserver] for status.
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn upgrade_game_folder_with_options(&self, path: &Path, options: &UpgradeOptions, progress: impl Into<Progress>) -> Result<UpgradeReport, UpgradeError> {
+		options.check_notice()?;
+		let progress = progress.into();
+		match upgrade::runtime(options)? {
+			Some(runtime) => {
+				let (directory, path, options) = (self.clone(), path.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move { upgrade::UpgradeState::new(&path, &options, progress).await?.run(&directory, &path).await })).await
+			},
+			None => upgrade::UpgradeState::new(path, options, progress).await?.run(self, path).await
+		}
+	}
+
+	/// # Description
+	/// Same as [Directory::upgrade_game_folder_with_options], but goes by a [VerificationReport] that [Directory::verify_folder] just made of `path` with the same options instead of scanning and hashing the folder again.
+	/// Only the files it found missing, mismatched or unreadable are downloaded and the unknown entries it found are deleted, the other files are reported as [UpgradeStatus::Skipped] without being looked at, so [UpgradeOptions::restore_mtimes] doesn't touch them.
+	/// The report is only trusted when it's for this manifest, folder, [UpgradeOptions::side] and filtering, at most [MAX_VERIFICATION_AGE] old, and the nested folders and a sample of the matching files still have the modification time and size they had.
+	/// Otherwise the folder is scanned like always, [UpgradeReport::repaired_from_report] says which one happened. A file changed in place without being in the sample goes unnoticed, like with [UpgradeOptions::hash_cache].
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn repair_game_folder(&self, path: &Path, report: &VerificationReport, options: &UpgradeOptions, progress: impl Into<Progress>) -> Result<UpgradeReport, UpgradeError> {
+		options.check_notice()?;
+		let progress = progress.into();
+		let repair = verify::Repair::new(report, self, path, options);
+		match upgrade::runtime(options)? {
+			Some(runtime) => {
+				let (directory, path, options) = (self.clone(), path.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move { upgrade::UpgradeState::new(&path, &options, progress).await?.repairing(repair).run(&directory, &path).await })).await
+			},
+			None => upgrade::UpgradeState::new(path, options, progress).await?.repairing(repair).run(self, path).await
+		}
+	}
+
+	/// # Description
+	/// Upgrades a path to match all of `directories` at once, like a base pack and add-ons, so nothing of one is deleted as unknown to another.
+	/// Does exactly what upgrading to the [Directory::merge] of them with [UpgradeOptions::precedence] does, the report also says which manifest every file was for.
+	/// Fails with [UpgradeError::Merge] before changing anything when they conflict and [MergePrecedence::Error] was chosen.
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn upgrade_game_folder_multi(directories: &[&Directory], path: &Path, options: &UpgradeOptions, progress: impl Into<Progress>) -> Result<MultiUpgradeReport, UpgradeError> {
+		let progress = progress.into();
+		match upgrade::runtime(options)? {
+			Some(runtime) => {
+				let (directories, path, options) = (directories.iter().map(|directory| (*directory).clone()).collect::<Vec<_>>(), path.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move {
+					let directories: Vec<_> = directories.iter().collect();
+					upgrade::upgrade_multi(&directories, &path, &options, progress).await
+				})).await
+			},
+			None => upgrade::upgrade_multi(directories, path, options, progress).await
+		}
+	}
+}
+
+/// Whether a link target relative to a directory `depth` levels below the root stays inside the root.
+fn link_target_inside(mut depth: usize, target: &str) -> bool {
+	if target.is_empty() || target.starts_with('/') || target.contains('\\') || target.contains(':') {
+		return false;
+	}
+
+	for component in target.split('/') {
+		match component {
+			"" | "." => {},
+			".." => match depth.checked_sub(1) {
+				Some(parent) => depth = parent,
+				None => return false
+			},
+			_ => depth += 1
+		}
+	}
+	true
+}
+
+impl manifest::Manifest for ManifestDelta {
+	fn validate(&self) -> Result<(), ValidationError> {
+		Ok(())
+	}
+
+	fn unknown_field(&self) -> Option<(String, String)> {
+		manifest::first_extra("", &self.extra)
+			.or_else(|| self.files.iter().find_map(|(path, file)| manifest::first_extra(&manifest::join_path("", &["files", path]), &file.extra)))
+			.or_else(|| self.directories.iter().find_map(|(path, directory)| manifest::first_extra(&manifest::join_path("", &["directories", path]), &directory.extra)))
+	}
+
+	#[cfg(feature = "net")]
+	fn check_limits(&self, limits: &manifest::Limits) -> Result<(), ManifestError> {
+		let mut entries = 0;
+		limits.count(&mut entries, self.files.len() + self.links.len() + self.removed.len())?;
+		for (path, directory) in &self.directories {
+			limits.count(&mut entries, 1)?;
+			limits.check_directory(directory, path, path.split('/').count(), &mut entries)?;
+		}
+		Ok(())
+	}
+}
+
+impl manifest::Manifest for Directory {
+	fn validate(&self) -> Result<(), ValidationError> {
+		Directory::validate(self)
+	}
+
+	fn unknown_field(&self) -> Option<(String, String)> {
+		fn at(directory: &Directory, path: &str) -> Option<(String, String)> {
+			manifest::first_extra(path, &directory.extra)
+				.or_else(|| directory.files.iter().find_map(|(name, file)| manifest::first_extra(&manifest::join_path(path, &["files", name]), &file.extra)))
+				.or_else(|| directory.reference.as_ref().and_then(|reference| manifest::first_extra(&manifest::join_path(path, &["$ref"]), &reference.extra)))
+				.or_else(|| directory.children.iter().find_map(|(name, child)| at(child, &manifest::join_path(path, &["children", name]))))
+		}
+
+		at(self, "")
+	}
+
+	#[cfg(feature = "net")]
+	fn check_limits(&self, limits: &manifest::Limits) -> Result<(), ManifestError> {
+		limits.check_directory(self, "", 0, &mut 0)
+	}
+}
+
+/// Why [Directory::export_zip] failed, paths are relative to the manifest root.
+#[cfg(feature = "zip")]
+#[derive(Debug)]
+pub enum ExportError {
+	/// A file in the manifest isn't in the source folder.
+	Missing {
+		path: PathBuf
+	},
+	/// A file in the source folder doesn't match the manifest.
+	ShaMismatch {
+		path: PathBuf,
+		expected: Sha256Hash,
+		found: Sha256Hash
+	},
+	/// A file's inline content isn't valid base64.
+	InvalidContent {
+		path: PathBuf
+	},
+	Io(std::io::Error),
+	Zip(zip::result::ZipError)
+}
+
+#[cfg(feature = "zip")]
+impl std::fmt::Display for ExportError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ExportError::Missing { path } => write!(f, "{} is missing", path.display()),
+			ExportError::ShaMismatch { path, expected, found } => write!(f, "{} doesn't match the manifest, expected {expected} found {found}", path.display()),
+			ExportError::InvalidContent { path } => write!(f, "file {} has content_b64 that isn't valid base64", path.display()),
+			ExportError::Io(error) => write!(f, "cannot write archive: {error}"),
+			ExportError::Zip(error) => write!(f, "cannot write archive: {error}")
+		}
+	}
+}
+
+#[cfg(feature = "zip")]
+impl std::error::Error for ExportError {}
+
+/// Which installs a file or directory is for, like mods that only work on clients or plugins only a dedicated server needs.
+/// Manifests use `"client"`, `"server"` and `"both"`, which is the default and isn't written out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Side {
+	Client,
+	Server,
+	#[default]
+	Both,
+	/// A value this version doesn't know, kept so parsing doesn't fail without saying where, [Directory::validate] rejects it with [ValidationError::InvalidSide].
+	Unknown(String)
+}
+
+impl Side {
+	pub fn is_both(&self) -> bool {
+		*self == Side::Both
+	}
+
+	/// # Description
+	/// Whether something for this side is part of an install of `selected`, everything is when `selected` is [Side::Both].
+	pub fn includes(&self, selected: &Side) -> bool {
+		selected.is_both() || self.is_both() || self == selected
+	}
+}
+
+impl std::fmt::Display for Side {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Side::Client => write!(f, "client"),
+			Side::Server => write!(f, "server"),
+			Side::Both => write!(f, "both"),
+			Side::Unknown(side) => write!(f, "{side}")
+		}
+	}
+}
+
+impl Serialize for Side {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for Side {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Ok(match String::deserialize(deserializer)?.as_str() {
+			"client" => Side::Client,
+			"server" => Side::Server,
+			"both" => Side::Both,
+			side => Side::Unknown(side.to_owned())
+		})
+	}
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for Side {
+	fn schema_name() -> String {
+		"Side".to_owned()
+	}
+
+	fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+		open_enum_schema(&["client", "server", "both"])
+	}
+}
+
+/// The schema of a string that's parsed into an enum which keeps the values it doesn't know, so any string is valid and the known ones are examples.
+#[cfg(feature = "schemars")]
+pub(crate) fn open_enum_schema(known: &[&str]) -> schemars::schema::Schema {
+	schemars::schema::SchemaObject {
+		instance_type: Some(schemars::schema::InstanceType::String.into()),
+		metadata: Some(Box::new(schemars::schema::Metadata {
+			examples: known.iter().map(|value| serde_json::Value::from(*value)).collect(),
+			..Default::default()
+		})),
+		..Default::default()
+	}.into()
+}
+
+/// Contains information about a remote file, part of a [Directory].
+/// A file has either a `url` or inline `content_b64`, never both.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct File {
+	/// The sha256 of the file as it's stored, never of an encoded body: green-lib asks servers for `Accept-Encoding: identity` and doesn't decode a `Content-Encoding`.
+	pub sha: Sha256Hash,
+	/// The size of the file in bytes, a download that gets bigger than this is stopped.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub size: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	/// The whole file as standard base64, for tiny files that aren't worth a request.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_b64: Option<String>,
+	/// The modification time in unix seconds that the file gets when it's written, see [UpgradeOptions::restore_mtimes] for files that are already there.
+	/// Files hardlinked from an [ObjectCache] share it with the cached copy.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub mtime: Option<i64>,
+	/// Only installed on this side, see [UpgradeOptions::side].
+	#[serde(default, skip_serializing_if = "Side::is_both")]
+	pub side: Side,
+	/// Only installed on these operating systems, named like [std::env::consts::OS] (`windows`, `linux`, `macos`), empty for every one.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub os: Vec<String>,
+	/// Only installed on these CPU architectures, named like [std::env::consts::ARCH] (`x86_64`, `aarch64`), empty for every one.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub arch: Vec<String>,
+	/// Headers sent with the download of this file only, like a token for a single object.
+	/// Like the credentials of [http::HttpConfig::headers] they don't follow a redirect to another host unless [http::RedirectPolicy::forward_auth] is on.
+	/// They may be secrets, so they never show up in errors.
+	#[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+	pub headers: std::collections::BTreeMap<String, String>,
+	/// Query parameters added to the url of this file's download, after [UpgradeOptions::rewrite_url].
+	/// Errors show the url without them and leave the query out of redirects, since they may be secrets too.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub query: Vec<(String, String)>,
+	/// The shas of the file's chunks, for big files, which needs [File::size].
+	/// Downloads are checked chunk by chunk so a corrupted one is retried right away, a retry continues after the last good chunk,
+	/// and a local copy of the right size that doesn't match only has its damaged chunks downloaded again when the server answers range requests.
+	/// [File::sha] is still checked for the whole file.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub chunks: Option<Chunks>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// The default chunk size of [FromPathOptions::chunks_above], 8 MiB.
+pub const DEFAULT_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// The shas of consecutive pieces of a file, see [File::chunks].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Chunks {
+	/// The length of every chunk but the last, which has what's left.
+	pub size: u64,
+	pub shas: Vec<Sha256Hash>
+}
+
+impl Chunks {
+	/// # Description
+	/// Splits `contents` into chunks of `size` bytes, at least one, and hashes each of them.
+	pub fn of(contents: &[u8], size: u64) -> Self {
+		let size = size.max(1);
+		Self {
+			size,
+			shas: contents.chunks(size.min(usize::MAX as u64) as usize).map(Sha256Hash::digest).collect()
+		}
+	}
+
+	/// # Description
+	/// Whether there's exactly one sha for every chunk of a file of `length` bytes.
+	pub fn fits(&self, length: u64) -> bool {
+		self.size > 0 && self.shas.len() as u64 == length.div_ceil(self.size)
+	}
+
+	/// # Description
+	/// The bytes chunk `index` covers in a file of `length` bytes.
+	pub fn range(&self, index: usize, length: u64) -> std::ops::Range<u64> {
+		let start = (index as u64).saturating_mul(self.size).min(length);
+		start..start.saturating_add(self.size).min(length)
+	}
+}
+
+/// Headers a [File::headers] can't set, they describe the connection or the body instead of the file.
+pub const RESERVED_FILE_HEADERS: [&str; 9] = ["host", "content-length", "transfer-encoding", "connection", "keep-alive", "upgrade", "te", "trailer", "expect"];
+
+/// A header name is a token as HTTP defines it, and the value can't break out of its line.
+fn valid_file_header(name: &str, value: &str) -> bool {
+	let token = !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte));
+	let reserved = RESERVED_FILE_HEADERS.iter().any(|reserved| reserved.eq_ignore_ascii_case(name));
+	token && !reserved && !value.bytes().any(|byte| byte == b'\r' || byte == b'\n' || byte == 0)
+}
+
+impl File {
+	/// # Description
+	/// Downloads just this file to `dest` like an upgrade would: retried, checked against [File::sha] and written to a temporary file that's renamed over `dest`, with its parent folders created.
+	/// Meant for fixing a single file, like one [Directory::verify_folder] found to be corrupted. Paths in `progress` and errors are `dest`.
+	/// The upgrade settings of `options` apply, and [UpgradeOptions::cache] is used the same way too.
+	#[cfg(feature = "fs")]
+	pub async fn download_to(&self, dest: &Path, options: &UpgradeOptions, progress: impl Into<Progress>) -> Result<(), UpgradeError> {
+		let progress = progress.into();
+		match upgrade::runtime(options)? {
+			Some(runtime) => {
+				let (file, dest, options) = (self.clone(), dest.to_owned(), options.clone());
+				upgrade::join(runtime.spawn(async move { download::download_to(&file, &dest, &dest, &options, progress).await })).await
+			},
+			None => download::download_to(self, dest, dest, options, progress).await
+		}
+	}
+
+	/// # Description
+	/// Checks whether the file at `path` is this one, hashing it the way an upgrade does unless its size already differs from [File::size].
+	/// Fails only when the file is there but can't be read.
+	#[cfg(feature = "fs")]
+	pub async fn verify(&self, path: &Path) -> std::io::Result<VerifyOutcome> {
+		NoRuntime::check()?;
+		verify::verify_file(self, path).await
+	}
+
+	/// # Description
+	/// Whether [File::os] and [File::arch] allow the platform this was compiled for.
+	pub fn applies_here(&self) -> bool {
+		(self.os.is_empty() || self.os.iter().any(|os| os == std::env::consts::OS))
+			&& (self.arch.is_empty() || self.arch.iter().any(|arch| arch == std::env::consts::ARCH))
+	}
 }
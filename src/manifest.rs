@@ -0,0 +1,439 @@
+use serde::{Serialize, Deserialize, Deserializer, de::DeserializeOwned};
+use serde_json::Value;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use crate::{ManifestError, ParseError, ValidationError};
+
+/// Implemented by the manifest formats so the shared helpers can validate whatever they parse.
+pub(crate) trait Manifest: DeserializeOwned {
+	fn validate(&self) -> Result<(), ValidationError>;
+
+	/// The first field that ended up in an `extra` map, as the path of the object it's in and its key.
+	fn unknown_field(&self) -> Option<(String, String)>;
+
+	/// Checks the nesting and the number of entries against what a fetched manifest may have.
+	#[cfg(feature = "net")]
+	fn check_limits(&self, limits: &Limits) -> Result<(), ManifestError>;
+}
+
+/// How deep and big a fetched manifest may be, see [crate::http::HttpConfig::max_manifest_depth] and [crate::http::HttpConfig::max_manifest_entries].
+#[cfg(feature = "net")]
+pub(crate) struct Limits {
+	pub(crate) depth: Option<usize>,
+	pub(crate) entries: Option<u64>
+}
+
+#[cfg(feature = "net")]
+impl Limits {
+	/// Adds `count` entries to `entries`, failing once they're over the limit.
+	pub(crate) fn count(&self, entries: &mut u64, count: usize) -> Result<(), ManifestError> {
+		*entries += count as u64;
+		match self.entries {
+			Some(limit) if *entries > limit => Err(ManifestError::TooManyEntries {
+				limit
+			}),
+			_ => Ok(())
+		}
+	}
+
+	/// Checks `directory` at `/` separated `path`, which is `depth` directories deep, and everything in it.
+	pub(crate) fn check_directory(&self, directory: &crate::Directory, path: &str, depth: usize, entries: &mut u64) -> Result<(), ManifestError> {
+		if self.depth.is_some_and(|limit| depth > limit) {
+			return Err(ManifestError::TooDeep {
+				limit: self.depth.unwrap(),
+				path: path.to_owned()
+			});
+		}
+		self.count(entries, directory.files.len() + directory.links.len() + directory.children.len())?;
+		for (name, child) in &directory.children {
+			self.check_directory(child, &join_path(path, &[name]), depth + 1, entries)?;
+		}
+		Ok(())
+	}
+}
+
+/// A path of keys joined with `/`, like [ManifestError::UnknownField] reports them.
+pub(crate) fn join_path(path: &str, keys: &[&str]) -> String {
+	std::iter::once(path).filter(|path| !path.is_empty()).chain(keys.iter().copied()).collect::<Vec<_>>().join("/")
+}
+
+/// The path and key of the first entry of an `extra` map.
+pub(crate) fn first_extra(path: &str, extra: &serde_json::Map<String, Value>) -> Option<(String, String)> {
+	extra.keys().next().map(|key| (path.to_owned(), key.clone()))
+}
+
+pub(crate) fn validated<T: Manifest>(manifest: T) -> Result<T, ManifestError> {
+	manifest.validate().map_err(ManifestError::Invalid)?;
+	Ok(manifest)
+}
+
+/// One of the objects and arrays a [Tracker] is in.
+enum Frame {
+	/// The key of the member being read, [None] until the key of the next one is.
+	Object(Option<String>),
+	/// The index of the element being read.
+	Array(usize)
+}
+
+/// Follows the keys and array indices of JSON text as it's fed byte by byte, to tell what a place in a manifest belongs to without parsing it again.
+/// Only the brackets, strings and separators matter, the text doesn't have to be valid.
+struct Tracker {
+	stack: Vec<Frame>,
+	in_string: bool,
+	escaped: bool,
+	/// The bytes of the key being read, [None] outside of keys.
+	key: Option<Vec<u8>>,
+	/// Whether the last byte ended a key.
+	key_ended: bool,
+	/// The position after the last byte like [serde_json::Error::line] and [serde_json::Error::column] count them, lines from 1 and columns in bytes from 0.
+	line: usize,
+	column: usize
+}
+
+impl Tracker {
+	fn new() -> Self {
+		Self {
+			stack: vec![],
+			in_string: false,
+			escaped: false,
+			key: None,
+			key_ended: false,
+			line: 1,
+			column: 0
+		}
+	}
+
+	fn feed(&mut self, byte: u8) {
+		self.key_ended = false;
+		if self.in_string {
+			match (self.escaped, byte) {
+				(true, _) => self.escaped = false,
+				(false, b'\\') => self.escaped = true,
+				(false, b'"') => {
+					self.in_string = false;
+					if let (Some(key), Some(Frame::Object(current))) = (self.key.take(), self.stack.last_mut()) {
+						let quoted = [&b"\""[..], &key, b"\""].concat();
+						*current = Some(serde_json::from_slice(&quoted).unwrap_or_else(|_| String::from_utf8_lossy(&key).into_owned()));
+						self.key_ended = true;
+					}
+				},
+				(false, _) => {}
+			}
+			if let Some(key) = self.key.as_mut().filter(|_| self.in_string) {
+				key.push(byte);
+			}
+		} else {
+			match byte {
+				b'{' => self.stack.push(Frame::Object(None)),
+				b'[' => self.stack.push(Frame::Array(0)),
+				b'}' | b']' => {
+					self.stack.pop();
+				},
+				b'"' => {
+					self.in_string = true;
+					self.key = matches!(self.stack.last(), Some(Frame::Object(None))).then(Vec::new);
+				},
+				b',' => match self.stack.last_mut() {
+					Some(Frame::Object(key)) => *key = None,
+					Some(Frame::Array(index)) => *index += 1,
+					None => {}
+				},
+				_ => {}
+			}
+		}
+
+		match byte {
+			b'\n' => {
+				self.line += 1;
+				self.column = 0;
+			},
+			_ => self.column += 1
+		}
+	}
+
+	/// Feeds `bytes` until the position of `error`, returning how many it took.
+	fn reach(&mut self, bytes: &[u8], error: &serde_json::Error) -> usize {
+		let mut fed = 0;
+		while fed < bytes.len() && (self.line, self.column) < (error.line(), error.column()) {
+			self.feed(bytes[fed]);
+			fed += 1;
+		}
+		fed
+	}
+
+	/// The keys and indices leading to the last byte, joined with `/`.
+	fn path(&self) -> String {
+		let keys: Vec<_> = self.stack.iter().filter_map(|frame| match frame {
+			Frame::Object(key) => key.clone(),
+			Frame::Array(index) => Some(index.to_string())
+		}).collect();
+		keys.join("/")
+	}
+}
+
+/// The line of `text` around `offset`, cut at [crate::PARSE_EXCERPT_LENGTH] bytes on either side and kept on one line.
+fn excerpt(text: &[u8], offset: usize) -> String {
+	let offset = offset.min(text.len());
+	let line_start = text[..offset].iter().rposition(|byte| *byte == b'\n').map_or(0, |newline| newline + 1);
+	let line_end = text[offset..].iter().position(|byte| *byte == b'\n').map_or(text.len(), |newline| offset + newline);
+	// cut between characters, not in the middle of one
+	let boundary = |index: usize| text.get(index).is_none_or(|byte| byte & 0b1100_0000 != 0b1000_0000);
+	let mut start = offset.saturating_sub(crate::PARSE_EXCERPT_LENGTH).max(line_start);
+	while !boundary(start) {
+		start += 1;
+	}
+	let mut end = (offset + crate::PARSE_EXCERPT_LENGTH).min(line_end);
+	while !boundary(end) {
+		end -= 1;
+	}
+
+	let mut excerpt = String::new();
+	if start > line_start {
+		excerpt.push('…');
+	}
+	for character in String::from_utf8_lossy(&text[start..end]).trim().chars() {
+		match character.is_control() || character == '"' || character == '\\' {
+			true => excerpt.extend(character.escape_default()),
+			false => excerpt.push(character)
+		}
+	}
+	if end < line_end {
+		excerpt.push('…');
+	}
+	excerpt
+}
+
+/// A [ParseError] for an error without the text it happened in, like one of serializing.
+pub(crate) fn unlocated(error: serde_json::Error) -> Box<ParseError> {
+	Box::new(ParseError {
+		error,
+		path: String::new(),
+		excerpt: String::new()
+	})
+}
+
+/// A [ParseError] for an error of parsing `text`.
+pub(crate) fn located(error: serde_json::Error, text: &[u8]) -> Box<ParseError> {
+	if error.line() == 0 {
+		return unlocated(error);
+	}
+	let mut tracker = Tracker::new();
+	let offset = tracker.reach(text, &error);
+	Box::new(ParseError {
+		path: tracker.path(),
+		excerpt: excerpt(text, offset),
+		error
+	})
+}
+
+/// How much of a manifest that's parsed as it arrives a [Locator] keeps, the parser never reads that far ahead of where it fails.
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+const LOCATOR_WINDOW: usize = 64 << 10;
+
+/// Locates a parse error in text that isn't kept around, by following the text that's dropped with a [Tracker].
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+pub(crate) struct Locator {
+	tracker: Tracker,
+	/// The text after what [Locator::tracker] was fed.
+	window: Vec<u8>
+}
+
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+impl Locator {
+	pub(crate) fn new() -> Self {
+		Self {
+			tracker: Tracker::new(),
+			window: vec![]
+		}
+	}
+
+	pub(crate) fn push(&mut self, bytes: &[u8]) {
+		self.window.extend_from_slice(bytes);
+		if self.window.len() > LOCATOR_WINDOW * 2 {
+			let dropped = self.window.len() - LOCATOR_WINDOW;
+			for byte in self.window.drain(..dropped) {
+				self.tracker.feed(byte);
+			}
+		}
+	}
+
+	pub(crate) fn locate(mut self, error: serde_json::Error) -> Box<ParseError> {
+		if error.line() == 0 || (self.tracker.line, self.tracker.column) > (error.line(), error.column()) {
+			return unlocated(error);
+		}
+		let offset = self.tracker.reach(&self.window, &error);
+		Box::new(ParseError {
+			path: self.tracker.path(),
+			excerpt: excerpt(&self.window, offset),
+			error
+		})
+	}
+}
+
+/// Where the key at `path` and `key` ends in `text` and the text around it, for an [ManifestError::UnknownField].
+fn find_key(text: &[u8], path: &str, key: &str) -> Option<(usize, usize, String)> {
+	let wanted = join_path(path, &[key]);
+	let mut tracker = Tracker::new();
+	for (index, byte) in text.iter().enumerate() {
+		tracker.feed(*byte);
+		if tracker.key_ended && tracker.path() == wanted {
+			return Some((tracker.line, tracker.column, excerpt(text, index + 1)));
+		}
+	}
+	None
+}
+
+pub(crate) fn from_slice<T: Manifest>(bytes: &[u8]) -> Result<T, ManifestError> {
+	validated(serde_json::from_slice(bytes).map_err(|error| ManifestError::Parse(located(error, bytes)))?)
+}
+
+/// Like [from_slice], but any field the manifest types don't know about is an error instead of being ignored or kept in an `extra` map.
+pub(crate) fn from_slice_strict<T: Manifest>(bytes: &[u8]) -> Result<T, ManifestError> {
+	let mut unknown = None;
+	let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+	let manifest: T = serde_ignored::deserialize(&mut deserializer, |path| {
+		if unknown.is_none() {
+			unknown = Some(unknown_field(&path));
+		}
+	}).map_err(|error| ManifestError::Parse(located(error, bytes)))?;
+	deserializer.end().map_err(|error| ManifestError::Parse(located(error, bytes)))?;
+
+	if let Some((path, key)) = unknown.or_else(|| manifest.unknown_field()) {
+		let (line, column, excerpt) = find_key(bytes, &path, &key).unwrap_or_default();
+		return Err(ManifestError::UnknownField {
+			path,
+			key,
+			line,
+			column,
+			excerpt
+		});
+	}
+	validated(manifest)
+}
+
+/// Splits the path of an ignored field into the path of the object it's in (keys joined with `/`) and its key.
+fn unknown_field(path: &serde_ignored::Path) -> (String, String) {
+	fn segments(path: &serde_ignored::Path, out: &mut Vec<String>) {
+		match path {
+			serde_ignored::Path::Root => {},
+			serde_ignored::Path::Seq { parent, index } => {
+				segments(parent, out);
+				out.push(index.to_string());
+			},
+			serde_ignored::Path::Map { parent, key } => {
+				segments(parent, out);
+				out.push(key.clone());
+			},
+			serde_ignored::Path::Some { parent } | serde_ignored::Path::NewtypeStruct { parent } | serde_ignored::Path::NewtypeVariant { parent } => segments(parent, out)
+		}
+	}
+
+	let mut out = vec![];
+	segments(path, &mut out);
+	let key = out.pop().unwrap_or_default();
+	(out.join("/"), key)
+}
+
+/// Reads all of `reader` first, so a parse error can be located in the text.
+pub(crate) fn from_reader<T: Manifest, R: std::io::Read>(mut reader: R) -> Result<T, ManifestError> {
+	let mut bytes = vec![];
+	reader.read_to_end(&mut bytes).map_err(|error| ManifestError::Parse(unlocated(serde_json::Error::io(error))))?;
+	from_slice(&bytes)
+}
+
+/// Checks a `min_green_lib` field against the version of this crate.
+pub(crate) fn check_min_version(min_green_lib: &Option<String>) -> Result<(), ValidationError> {
+	let Some(required) = min_green_lib else {
+		return Ok(());
+	};
+
+	let current = env!("CARGO_PKG_VERSION");
+	let invalid = || ValidationError::InvalidVersion {
+		version: required.clone()
+	};
+	match semver::Version::parse(required.trim_start_matches('v')) {
+		Ok(version) if version > semver::Version::parse(current).map_err(|_| invalid())? => Err(ValidationError::ClientTooOld {
+			required: required.clone(),
+			current: current.to_owned()
+		}),
+		Ok(_) => Ok(()),
+		Err(_) => Err(invalid())
+	}
+}
+
+/// Reads and parses a manifest file, errors include the path.
+#[cfg(feature = "fs")]
+pub(crate) async fn read_file<T: Manifest>(path: &Path) -> Result<T, ManifestError> {
+	let contents = tokio::fs::read(path).await.map_err(|error| ManifestError::Io {
+		path: path.to_owned(),
+		error
+	})?;
+	from_slice(&contents)
+}
+
+/// The hash manifests are verified with.
+pub(crate) fn sha256(bytes: &[u8]) -> String {
+	crate::hash::Sha256Hash::digest(bytes).to_string()
+}
+
+/// Whether a string is a hex encoded sha256 (in either case).
+pub(crate) fn is_sha256(sha: &str) -> bool {
+	sha.len() == 64 && sha.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Lowercases hex digests as they're parsed, since other tools sometimes emit uppercase hex.
+pub(crate) fn lowercase<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+	Ok(String::deserialize(deserializer)?.to_ascii_lowercase())
+}
+
+/// Rebuilds objects with their keys inserted in sorted order, so the output doesn't depend on [std::collections::HashMap] iteration order.
+fn sorted(value: Value) -> Value {
+	match value {
+		Value::Object(map) => {
+			let mut entries: Vec<_> = map.into_iter().collect();
+			entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+			Value::Object(entries.into_iter().map(|(key, value)| (key, sorted(value))).collect())
+		},
+		Value::Array(values) => Value::Array(values.into_iter().map(sorted).collect()),
+		other => other
+	}
+}
+
+/// Compact JSON with sorted keys and no trailing newline.
+pub(crate) fn to_string_canonical<T: Serialize>(manifest: &T) -> serde_json::Result<String> {
+	serde_json::to_string(&sorted(serde_json::to_value(manifest)?))
+}
+
+pub(crate) fn to_writer<T: Serialize, W: std::io::Write>(manifest: &T, writer: W, pretty: bool) -> serde_json::Result<()> {
+	let value = sorted(serde_json::to_value(manifest)?);
+	if pretty {
+		serde_json::to_writer_pretty(writer, &value)
+	} else {
+		serde_json::to_writer(writer, &value)
+	}
+}
+
+/// A hidden file next to `path` to write to before renaming it into place.
+#[cfg(feature = "fs")]
+pub(crate) fn temp_path(path: &Path) -> std::io::Result<std::path::PathBuf> {
+	let file_name = path.file_name().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name"))?;
+	let mut temp_name = std::ffi::OsString::from(".");
+	temp_name.push(file_name);
+	temp_name.push(format!(".{}.tmp", std::process::id()));
+	Ok(path.with_file_name(temp_name))
+}
+
+/// Writes to a temporary file next to `path` and renames it into place, so nobody ever reads a half written manifest.
+#[cfg(feature = "fs")]
+pub(crate) async fn to_file<T: Serialize>(manifest: &T, path: &Path, pretty: bool) -> std::io::Result<()> {
+	let mut contents = vec![];
+	to_writer(manifest, &mut contents, pretty)?;
+
+	let temp_path = temp_path(path)?;
+	tokio::fs::write(&temp_path, contents).await?;
+	if let Err(error) = tokio::fs::rename(&temp_path, path).await {
+		let _ = tokio::fs::remove_file(&temp_path).await;
+		return Err(error);
+	}
+	Ok(())
+}
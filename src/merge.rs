@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use crate::{template, Directory, File, MergeError, MergePrecedence, Side};
+
+/// A merged manifest along with which of the merged ones every file and link came from.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) struct Merged {
+	pub(crate) directory: Directory,
+	/// The index of the manifest every file and link of the result came from, by `/` separated path.
+	pub(crate) sources: HashMap<String, usize>,
+	/// Entries a later manifest replaced, with the manifest they came from.
+	pub(crate) overridden: Vec<(String, usize)>
+}
+
+struct State {
+	precedence: MergePrecedence,
+	sources: HashMap<String, usize>,
+	overridden: Vec<(String, usize)>
+}
+
+fn join(path: &str, name: &str) -> String {
+	match path.is_empty() {
+		true => name.to_owned(),
+		false => format!("{path}/{name}")
+	}
+}
+
+impl State {
+	/// Something of `source` replaces what's at `path` and everything below it.
+	fn replace(&mut self, path: &str, source: usize) -> Result<(), MergeError> {
+		let prefix = format!("{path}/");
+		let replaced: Vec<_> = self.sources.iter().filter(|(key, _)| *key == path || key.starts_with(&prefix)).map(|(key, first)| (key.clone(), *first)).collect();
+		if let (MergePrecedence::Error, Some((_, first))) = (self.precedence, replaced.first()) {
+			return Err(MergeError::Conflict {
+				path: path.to_owned(),
+				first: *first,
+				second: source
+			});
+		}
+
+		for (key, first) in replaced {
+			self.sources.remove(&key);
+			self.overridden.push((key, first));
+		}
+		Ok(())
+	}
+}
+
+pub(crate) fn merge(directories: &[&Directory], precedence: MergePrecedence) -> Result<Merged, MergeError> {
+	let mut state = State {
+		precedence,
+		sources: HashMap::new(),
+		overridden: vec![]
+	};
+	for (manifest, directory) in directories.iter().enumerate() {
+		if let Some(path) = directory.unresolved_refs().into_iter().next() {
+			return Err(MergeError::UnresolvedRef {
+				path,
+				manifest
+			});
+		}
+	}
+
+	let mut merged = Directory::default();
+	for (source, directory) in directories.iter().enumerate() {
+		merge_into(&mut merged, directory, "", None, &Side::Both, source, &mut state)?;
+
+		// the newest requirement of any of them
+		let newer = match (&merged.min_green_lib, &directory.min_green_lib) {
+			(Some(current), Some(version)) => match (semver::Version::parse(current), semver::Version::parse(version)) {
+				(Ok(current), Ok(version)) => version > current,
+				_ => true
+			},
+			(None, Some(_)) => true,
+			_ => false
+		};
+		if newer {
+			merged.min_green_lib = directory.min_green_lib.clone();
+		}
+	}
+
+	state.overridden.sort();
+	Ok(Merged {
+		directory: merged,
+		sources: state.sources,
+		overridden: state.overridden
+	})
+}
+
+/// The same file with its url expanded from the template and the side of its directory, so it doesn't depend on the directory it ends up in.
+fn resolve(file: &File, url_template: Option<&str>, side: &Side) -> File {
+	let mut file = file.clone();
+	if file.side.is_both() {
+		file.side = side.clone();
+	}
+	if let (None, None, Some(url_template)) = (&file.url, &file.content_b64, url_template) {
+		file.url = template::expand(url_template, &file.sha).ok();
+	}
+	file
+}
+
+fn merge_into(into: &mut Directory, from: &Directory, path: &str, url_template: Option<&str>, side: &Side, source: usize, state: &mut State) -> Result<(), MergeError> {
+	let url_template = from.url_template.as_deref().or(url_template);
+	let side = match from.side.is_both() {
+		true => side,
+		false => &from.side
+	};
+	for (key, value) in &from.extra {
+		into.extra.insert(key.clone(), value.clone());
+	}
+
+	// sorted so the same manifests always conflict at the same path
+	let mut files: Vec<_> = from.files.iter().collect();
+	files.sort_by_key(|(name, _)| *name);
+	for (name, file) in files {
+		let file_path = join(path, name);
+		// the same file from two manifests isn't a conflict, only the url might differ
+		let same = into.files.get(name).is_some_and(|existing| existing.sha == file.sha);
+		if !same {
+			state.replace(&file_path, source)?;
+		}
+		into.children.remove(name);
+		into.links.remove(name);
+		into.files.insert(name.clone(), resolve(file, url_template, side));
+		state.sources.insert(file_path, source);
+	}
+
+	let mut links: Vec<_> = from.links.iter().collect();
+	links.sort_by_key(|(name, _)| *name);
+	for (name, target) in links {
+		let link_path = join(path, name);
+		if into.links.get(name) != Some(target) {
+			state.replace(&link_path, source)?;
+		}
+		into.children.remove(name);
+		into.files.remove(name);
+		into.links.insert(name.clone(), target.clone());
+		state.sources.insert(link_path, source);
+	}
+
+	let mut children: Vec<_> = from.children.iter().collect();
+	children.sort_by_key(|(name, _)| *name);
+	for (name, child) in children {
+		let child_path = join(path, name);
+		if into.files.contains_key(name) || into.links.contains_key(name) {
+			state.replace(&child_path, source)?;
+			into.files.remove(name);
+			into.links.remove(name);
+		}
+		// a directory two manifests disagree about is for both, its files still have their own side
+		let merged = match into.children.get_mut(name) {
+			Some(merged) => {
+				if merged.side != child.side {
+					merged.side = Side::Both;
+				}
+				merged
+			},
+			None => into.children.entry(name.clone()).or_insert_with(|| Directory {
+				side: child.side.clone(),
+				..Default::default()
+			})
+		};
+		merge_into(merged, child, &child_path, url_template, side, source, state)?;
+	}
+	Ok(())
+}
+
+/// `directory` without the entries `other` has at the same path, see [Directory::subtract]. With `keep_changed` files and links that differ from what `other` has there are kept.
+pub(crate) fn subtract(directory: &Directory, other: &Directory, keep_changed: bool) -> Directory {
+	let present = |name: &String| other.files.contains_key(name) || other.links.contains_key(name) || other.children.contains_key(name);
+	let files = directory.files.iter()
+		.filter(|(name, file)| match other.files.get(*name) {
+			Some(theirs) => keep_changed && theirs.sha != file.sha,
+			None => keep_changed || !present(name)
+		})
+		.map(|(name, file)| (name.clone(), file.clone()))
+		.collect();
+	let links = directory.links.iter()
+		.filter(|(name, target)| match other.links.get(*name) {
+			Some(theirs) => keep_changed && theirs != *target,
+			None => keep_changed || !present(name)
+		})
+		.map(|(name, target)| (name.clone(), target.clone()))
+		.collect();
+	let children = directory.children.iter().filter_map(|(name, child)| match other.children.get(name) {
+		Some(theirs) => {
+			// a directory both have is only kept for what's left in it
+			let subtracted = subtract(child, theirs, keep_changed);
+			let empty = subtracted.files.is_empty() && subtracted.links.is_empty() && subtracted.children.is_empty();
+			(!empty).then(|| (name.clone(), subtracted))
+		},
+		None => (keep_changed || !present(name)).then(|| (name.clone(), child.clone()))
+	}).collect();
+
+	Directory {
+		files,
+		children,
+		links,
+		url_template: directory.url_template.clone(),
+		min_green_lib: directory.min_green_lib.clone(),
+		side: directory.side.clone(),
+		reference: directory.reference.clone(),
+		// what's left is another subtree
+		subtree_hash: None,
+		extra: directory.extra.clone()
+	}
+}
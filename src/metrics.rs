@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::{DownloadTiming, RetryCounts, RetryReason, UpgradeMetrics, SLOWEST_DOWNLOADS};
+
+/// Collects the [UpgradeMetrics] of an upgrade from every download.
+pub(crate) struct Recorder {
+	start: Instant,
+	recorded: Mutex<Recorded>
+}
+
+#[derive(Default)]
+struct Recorded {
+	/// Bytes received during each second since the start.
+	seconds: Vec<u64>,
+	received_bytes: u64,
+	cached_bytes: u64,
+	deduplicated_bytes: u64,
+	writing: Duration,
+	downloads: Vec<DownloadTiming>,
+	first_download: Option<Instant>,
+	last_download: Option<Instant>,
+	retries: RetryCounts
+}
+
+impl Recorder {
+	pub(crate) fn new() -> Self {
+		Self {
+			start: Instant::now(),
+			recorded: Mutex::new(Recorded::default())
+		}
+	}
+
+	pub(crate) fn started(&self) -> Instant {
+		let now = Instant::now();
+		self.recorded.lock().unwrap().first_download.get_or_insert(now);
+		now
+	}
+
+	pub(crate) fn received(&self, bytes: u64) {
+		let second = self.start.elapsed().as_secs() as usize;
+		let mut recorded = self.recorded.lock().unwrap();
+		if recorded.seconds.len() <= second {
+			recorded.seconds.resize(second + 1, 0);
+		}
+		recorded.seconds[second] += bytes;
+		recorded.received_bytes += bytes;
+	}
+
+	pub(crate) fn retried(&self, reason: &RetryReason) {
+		let retries = &mut self.recorded.lock().unwrap().retries;
+		match reason {
+			RetryReason::Connection => retries.connection += 1,
+			RetryReason::ServerError(_) => retries.server_error += 1,
+			RetryReason::Timeout => retries.timeout += 1,
+			RetryReason::ShaMismatch => retries.sha_mismatch += 1,
+			RetryReason::Stalled => retries.stalled += 1,
+			RetryReason::Refreshed(_) => retries.refreshed += 1,
+			RetryReason::RateLimited => retries.rate_limited += 1
+		}
+	}
+
+	/// A file was taken from the cache, `linked` when it shares its data with the cached copy.
+	pub(crate) fn cached(&self, bytes: u64, linked: bool) {
+		let mut recorded = self.recorded.lock().unwrap();
+		recorded.cached_bytes += bytes;
+		if linked {
+			recorded.deduplicated_bytes += bytes;
+		}
+	}
+
+	pub(crate) fn wrote(&self, duration: Duration) {
+		self.recorded.lock().unwrap().writing += duration;
+	}
+
+	/// A download that was [Recorder::started] at `started` has succeeded or failed, only successes count towards the slowest downloads.
+	pub(crate) fn finished(&self, started: Instant, path: Option<PathBuf>, bytes: u64) {
+		let now = Instant::now();
+		let mut recorded = self.recorded.lock().unwrap();
+		recorded.last_download = Some(now);
+		if let Some(path) = path {
+			recorded.downloads.push(DownloadTiming {
+				path,
+				duration: now - started,
+				bytes
+			});
+		}
+	}
+
+	pub(crate) fn finish(&self, scanning: Duration) -> UpgradeMetrics {
+		let mut recorded = std::mem::take(&mut *self.recorded.lock().unwrap());
+		let downloading = match (recorded.first_download, recorded.last_download) {
+			(Some(first), Some(last)) => last.saturating_duration_since(first),
+			_ => Duration::ZERO
+		};
+
+		recorded.downloads.sort_by_key(|download| std::cmp::Reverse(download.duration));
+		recorded.downloads.truncate(SLOWEST_DOWNLOADS);
+
+		UpgradeMetrics {
+			total: self.start.elapsed(),
+			scanning,
+			downloading,
+			writing: recorded.writing,
+			received_bytes: recorded.received_bytes,
+			cached_bytes: recorded.cached_bytes,
+			deduplicated_bytes: recorded.deduplicated_bytes,
+			average_throughput: match downloading.as_secs_f64() {
+				0.0 => 0,
+				seconds => (recorded.received_bytes as f64 / seconds) as u64
+			},
+			peak_throughput: recorded.seconds.iter().copied().max().unwrap_or(0),
+			slowest: recorded.downloads,
+			retries: recorded.retries
+		}
+	}
+
+	pub(crate) fn elapsed(&self) -> Duration {
+		self.start.elapsed()
+	}
+}
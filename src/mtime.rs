@@ -0,0 +1,36 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The modification time of a file in whole unix seconds, like [crate::File::mtime].
+pub(crate) fn of(metadata: &std::fs::Metadata) -> Option<i64> {
+	let modified = metadata.modified().ok()?;
+	match modified.duration_since(UNIX_EPOCH) {
+		Ok(since_epoch) => i64::try_from(since_epoch.as_secs()).ok(),
+		// before 1970, rounded down like the positive side
+		Err(error) => {
+			let before = error.duration();
+			let seconds = i64::try_from(before.as_secs()).ok()?;
+			Some(-seconds - (before.subsec_nanos() > 0) as i64)
+		}
+	}
+}
+
+fn to_system_time(mtime: i64) -> SystemTime {
+	match u64::try_from(mtime) {
+		Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+		Err(_) => UNIX_EPOCH - Duration::from_secs(mtime.unsigned_abs())
+	}
+}
+
+/// Sets the modification time of the file at `path`.
+pub(crate) async fn set(path: &Path, mtime: i64) -> std::io::Result<()> {
+	let path = path.to_owned();
+	tokio::task::spawn_blocking(move || {
+		set_file(&std::fs::OpenOptions::new().write(true).open(path)?, mtime)
+	}).await.unwrap()
+}
+
+/// Sets the modification time of a file that's already open for writing.
+pub(crate) fn set_file(file: &std::fs::File, mtime: i64) -> std::io::Result<()> {
+	file.set_modified(to_system_time(mtime))
+}
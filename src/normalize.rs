@@ -0,0 +1,172 @@
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use crate::{Directory, File, NormalizeError, NormalizeOptions, NormalizeReport, SeparatorPolicy};
+
+enum Node {
+	File(File),
+	Link(String),
+	Directory(Directory)
+}
+
+fn join(path: &str, name: &str) -> String {
+	match path.is_empty() {
+		true => name.to_owned(),
+		false => format!("{path}/{name}")
+	}
+}
+
+/// Whether a key below `directory` contains a separator.
+fn has_separators(directory: &Directory) -> bool {
+	let mut keys = directory.files.keys().chain(directory.children.keys()).chain(directory.links.keys());
+	keys.any(|key| key.contains(['/', '\\'])) || directory.children.values().any(has_separators)
+}
+
+/// `directory` with keys that contain a separator dealt with as `separators` says, without pruning, it's only copied when there are such keys.
+pub(crate) fn separated(directory: &Directory, separators: SeparatorPolicy) -> Result<Cow<'_, Directory>, NormalizeError> {
+	if !has_separators(directory) {
+		return Ok(Cow::Borrowed(directory));
+	}
+
+	let mut separated = directory.clone();
+	normalize(&mut separated, &NormalizeOptions {
+		separators,
+		prune_empty: false
+	})?;
+	Ok(Cow::Owned(separated))
+}
+
+pub(crate) fn normalize(directory: &mut Directory, options: &NormalizeOptions) -> Result<NormalizeReport, NormalizeError> {
+	let mut report = NormalizeReport::default();
+	let mut normalized = directory.clone();
+	rebuild(&mut normalized, "", options, &mut report)?;
+	if options.prune_empty {
+		prune(&mut normalized, "", &mut report);
+	}
+
+	report.renamed.sort();
+	report.pruned.sort();
+	*directory = normalized;
+	Ok(report)
+}
+
+/// The trimmed components a key stands for.
+fn components(key: &str, path: &str, options: &NormalizeOptions) -> Result<Vec<String>, NormalizeError> {
+	let key_path = || join(path, key);
+	if options.separators == SeparatorPolicy::Error && key.contains(['/', '\\']) {
+		return Err(NormalizeError::Separator {
+			path: key_path()
+		});
+	}
+
+	let components: Vec<_> = key.split(['/', '\\']).map(str::trim).filter(|component| !component.is_empty() && *component != ".").map(str::to_owned).collect();
+	if components.is_empty() || components.iter().any(|component| component == "..") {
+		return Err(NormalizeError::InvalidKey {
+			path: key_path()
+		});
+	}
+	Ok(components)
+}
+
+/// Puts every entry of `directory` back under its normalized key, children are normalized before they're inserted.
+fn rebuild(directory: &mut Directory, path: &str, options: &NormalizeOptions, report: &mut NormalizeReport) -> Result<(), NormalizeError> {
+	// sorted so the same manifest always fails on the same entry
+	let mut entries: Vec<_> = std::mem::take(&mut directory.children).into_iter().map(|(key, child)| (key, Node::Directory(child)))
+		.chain(std::mem::take(&mut directory.files).into_iter().map(|(key, file)| (key, Node::File(file))))
+		.chain(std::mem::take(&mut directory.links).into_iter().map(|(key, target)| (key, Node::Link(target))))
+		.collect();
+	entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+	for (key, mut node) in entries {
+		let components = components(&key, path, options)?;
+		let new_path = join(path, &components.join("/"));
+		if components.len() != 1 || components[0] != key {
+			report.renamed.push((join(path, &key), new_path.clone()));
+		}
+
+		if let Node::Directory(child) = &mut node {
+			rebuild(child, &new_path, options, report)?;
+		}
+		insert(directory, path, &components, node)?;
+	}
+	Ok(())
+}
+
+/// Inserts `node` at `components` below `directory`, creating the children in between and merging into a child that's already there.
+fn insert(directory: &mut Directory, path: &str, components: &[String], node: Node) -> Result<(), NormalizeError> {
+	let (name, parents) = components.split_last().unwrap();
+	let mut parent = directory;
+	let mut parent_path = path.to_owned();
+	for component in parents {
+		parent_path = join(&parent_path, component);
+		if parent.files.contains_key(component) || parent.links.contains_key(component) {
+			return Err(NormalizeError::Collision {
+				path: parent_path
+			});
+		}
+		parent = parent.children.entry(component.clone()).or_default();
+	}
+
+	let path = join(&parent_path, name);
+	let collision = || NormalizeError::Collision {
+		path: path.clone()
+	};
+	if parent.files.contains_key(name) || parent.links.contains_key(name) {
+		return Err(collision());
+	}
+
+	match node {
+		Node::File(_) | Node::Link(_) if parent.children.contains_key(name) => Err(collision()),
+		Node::File(file) => {
+			parent.files.insert(name.clone(), file);
+			Ok(())
+		},
+		Node::Link(target) => {
+			parent.links.insert(name.clone(), target);
+			Ok(())
+		},
+		Node::Directory(child) => match parent.children.entry(name.clone()) {
+			Entry::Vacant(entry) => {
+				entry.insert(child);
+				Ok(())
+			},
+			Entry::Occupied(mut entry) => merge(entry.get_mut(), &path, child)
+		}
+	}
+}
+
+/// Moves the entries of `from` into `into`, both already normalized.
+fn merge(into: &mut Directory, path: &str, from: Directory) -> Result<(), NormalizeError> {
+	let collision = || NormalizeError::Collision {
+		path: path.to_owned()
+	};
+	match (&into.url_template, from.url_template) {
+		(Some(a), Some(b)) if *a != b => return Err(collision()),
+		(None, Some(template)) => into.url_template = Some(template),
+		_ => {}
+	}
+	for (key, value) in from.extra {
+		into.extra.entry(key).or_insert(value);
+	}
+
+	let entries = from.children.into_iter().map(|(key, child)| (key, Node::Directory(child)))
+		.chain(from.files.into_iter().map(|(key, file)| (key, Node::File(file))))
+		.chain(from.links.into_iter().map(|(key, target)| (key, Node::Link(target))));
+	for (key, node) in entries {
+		insert(into, path, &[key], node)?;
+	}
+	Ok(())
+}
+
+/// Removes children without files or links anywhere below them.
+fn prune(directory: &mut Directory, path: &str, report: &mut NormalizeReport) {
+	directory.children.retain(|name, child| {
+		let child_path = join(path, name);
+		prune(child, &child_path, report);
+
+		let empty = child.files.is_empty() && child.links.is_empty() && child.children.is_empty();
+		if empty {
+			report.pruned.push(child_path);
+		}
+		!empty
+	});
+}
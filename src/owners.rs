@@ -0,0 +1,94 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::hash_cache::{self, key};
+use crate::{Directory, DownloadFilter, OwnershipConflict, Side};
+
+const FILE_NAME: &str = "owners.json";
+
+/// The files and links each [crate::UpgradeOptions::namespace] manages in a folder, by `/` separated relative path.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Owners {
+	namespaces: BTreeMap<String, BTreeSet<String>>
+}
+
+fn path(root: &Path) -> PathBuf {
+	root.join(hash_cache::STATE_DIR).join(FILE_NAME)
+}
+
+impl Owners {
+	/// Loads the owners of a folder, a missing or corrupt file means nothing is owned.
+	pub(crate) async fn load(root: &Path) -> Self {
+		match tokio::fs::read(path(root)).await {
+			Ok(contents) => serde_json::from_slice(&contents).unwrap_or_default(),
+			Err(_) => Self::default()
+		}
+	}
+
+	pub(crate) async fn save(&self, root: &Path) -> std::io::Result<()> {
+		let owners_path = path(root);
+		if self.namespaces.is_empty() {
+			return match tokio::fs::remove_file(owners_path).await {
+				Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+				_ => Ok(())
+			};
+		}
+		tokio::fs::create_dir_all(root.join(hash_cache::STATE_DIR)).await?;
+
+		let temp_path = owners_path.with_extension("tmp");
+		tokio::fs::write(&temp_path, serde_json::to_vec(self).unwrap()).await?;
+		tokio::fs::rename(temp_path, owners_path).await
+	}
+
+	pub(crate) fn is_empty(&self) -> bool {
+		self.namespaces.is_empty()
+	}
+
+	/// A namespace other than `namespace` that owns `relative` or anything below it.
+	pub(crate) fn other_owner(&self, relative: &Path, namespace: Option<&str>) -> Option<&str> {
+		let key = key(relative);
+		let prefix = format!("{key}/");
+		self.namespaces.iter()
+			.filter(|(owner, _)| Some(owner.as_str()) != namespace)
+			.find(|(_, owned)| owned.contains(&key) || owned.range(prefix.clone()..).next().is_some_and(|owned| owned.starts_with(&prefix)))
+			.map(|(owner, _)| owner.as_str())
+	}
+
+	/// Makes `owned` everything `namespace` owns, returning the paths other namespaces own as well.
+	pub(crate) fn claim(&mut self, namespace: &str, owned: BTreeSet<String>) -> Vec<OwnershipConflict> {
+		let mut conflicts: Vec<_> = self.namespaces.iter()
+			.filter(|(owner, _)| *owner != namespace)
+			.flat_map(|(owner, theirs)| owned.intersection(theirs).map(|key| OwnershipConflict {
+				path: key.split('/').collect(),
+				namespace: owner.clone()
+			}))
+			.collect();
+		conflicts.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.namespace.cmp(&b.namespace)));
+
+		self.namespaces.insert(namespace.to_owned(), owned);
+		conflicts
+	}
+
+	pub(crate) fn release(&mut self, namespace: &str) {
+		self.namespaces.remove(namespace);
+	}
+}
+
+/// The files and links of `directory` an upgrade for `side` with `filter` writes, by `/` separated relative path.
+pub(crate) fn managed(directory: &Directory, side: &Side, filter: Option<&DownloadFilter>) -> BTreeSet<String> {
+	let mut managed = BTreeSet::new();
+	let mut stack = vec![(directory, PathBuf::new())];
+	while let Some((directory, relative)) = stack.pop() {
+		for (name, file) in &directory.files {
+			let relative = relative.join(name);
+			if file.side.includes(side) && file.applies_here() && filter.is_none_or(|filter| filter.includes(&relative, file)) {
+				managed.insert(key(&relative));
+			}
+		}
+		managed.extend(directory.links.keys().map(|name| key(&relative.join(name))));
+		for (name, child) in directory.children.iter().filter(|(_, child)| child.side.includes(side)) {
+			stack.push((child, relative.join(name)));
+		}
+	}
+	managed
+}
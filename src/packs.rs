@@ -1,19 +1,312 @@
-use serde::Deserialize;
-use sha2::{Sha256, Digest};
+use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
-use crate::{CLIENT, Directory};
+use crate::{manifest, ManifestError, ValidationError};
+#[cfg(feature = "net")]
+use crate::{fetch, http, Directory};
+use crate::hash::Sha256Hash;
 
-/// Contains information about a set of [Directories](Directory).
-#[derive(Deserialize, Debug, Clone)]
+/// Contains information about a set of [Directories](crate::Directory).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct PacksListManifest {
+	/// The schema version the list was written with, [None] for lists from before schema versions (which are version 1).
+	/// Serializing always writes [SCHEMA_VERSION], see [PacksListManifest::schema_version].
+	#[serde(default, serialize_with = "current_schema_version")]
+	pub schema_version: Option<u32>,
 	pub packs: HashMap<String, ManifestMetadata>,
-	pub featured_pack: Option<String>
+	/// The legacy single featured pack, see [PacksListManifest::featured_packs] for how it combines with `featured`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub featured_pack: Option<String>,
+	/// Packs to feature, in the order they should be shown.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub featured: Vec<String>,
+	/// The oldest green-lib version that understands this packs list, older versions refuse to parse it with [ValidationError::ClientTooOld].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub min_green_lib: Option<String>,
+	#[serde(skip)]
+	base_url: Option<String>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
 }
 
 #[derive(Debug)]
 pub enum FeaturedPackError {
 	Unspecified,
-	Invalid
+	Invalid,
+	/// The featured pack is [ManifestMetadata::hidden].
+	Hidden,
+	/// The featured pack is [ManifestMetadata::deprecated] in favour of this one, see [PacksListManifest::resolve_successor].
+	Deprecated {
+		successor: String
+	},
+	/// There are no packs to choose from.
+	Empty
+}
+
+/// What [PacksListManifest::merge] does with a key that's in both lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeStrategy {
+	/// Fail with [MergeError::Collision].
+	Error,
+	/// Keep the pack that was there first.
+	FirstWins,
+	/// Prefix every key of the other list, colliding or not, so keys don't depend on what else was merged, a key that still collides is an error.
+	Prefix(String)
+}
+
+/// Why a packs list couldn't be merged into another.
+#[derive(Debug)]
+pub enum MergeError {
+	/// The list couldn't be fetched.
+	Fetch(ManifestError),
+	/// Both lists have a pack with this key.
+	Collision {
+		key: String
+	}
+}
+
+impl std::fmt::Display for MergeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MergeError::Fetch(error) => write!(f, "{error}"),
+			MergeError::Collision { key } => write!(f, "more than one packs list has a pack called {key:?}")
+		}
+	}
+}
+
+impl std::error::Error for MergeError {}
+
+/// The newest packs list schema this version of green-lib understands.
+pub const SCHEMA_VERSION: u32 = 1;
+
+fn current_schema_version<S: serde::Serializer>(_: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_u32(SCHEMA_VERSION)
+}
+
+/// How many percent [ManifestMetadata::size_bytes] and [ManifestMetadata::file_count] may be off from the manifest before [ManifestMetadata::size_mismatch] reports it.
+pub const SIZE_TOLERANCE_PERCENT: u64 = 1;
+
+/// The most bytes [ManifestMetadata::fetch_icon] accepts.
+#[cfg(feature = "net")]
+pub const MAX_ICON_SIZE: u64 = 4 << 20;
+
+/// The most bytes [ManifestMetadata::fetch_changelog] accepts.
+#[cfg(feature = "net")]
+pub const MAX_CHANGELOG_SIZE: u64 = 4 << 20;
+
+/// Fetches the packs list at `url`, which relative manifest urls are resolved against.
+#[cfg(feature = "net")]
+async fn fetch_list(url: &str) -> Result<PacksListManifest, ManifestError> {
+	crate::NoRuntime::check()?;
+	let mut list: PacksListManifest = fetch::manifest(url, None).await?;
+	list.set_base_url(url);
+	Ok(list)
+}
+
+/// Fetches packs lists at most `concurrency` at a time, in the order of `urls`.
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+async fn fetch_lists(urls: Vec<String>, concurrency: usize) -> Vec<Result<PacksListManifest, ManifestError>> {
+	if crate::NoRuntime::check().is_err() {
+		return urls.iter().map(|_| Err(ManifestError::NoRuntime)).collect();
+	}
+	let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+	let mut fetches = tokio::task::JoinSet::new();
+	let count = urls.len();
+	for (index, url) in urls.into_iter().enumerate() {
+		let semaphore = semaphore.clone();
+		fetches.spawn(async move {
+			let _permit = semaphore.acquire_owned().await.unwrap();
+			(index, fetch_list(&url).await)
+		});
+	}
+
+	let mut lists: Vec<_> = (0..count).map(|_| None).collect();
+	while let Some(fetched) = fetches.join_next().await {
+		let (index, list) = fetched.unwrap();
+		lists[index] = Some(list);
+	}
+	lists.into_iter().map(Option::unwrap).collect()
+}
+
+/// There's nothing to spawn on in the browser, so the lists are fetched one after another.
+#[cfg(all(feature = "net", target_arch = "wasm32"))]
+async fn fetch_lists(urls: Vec<String>, _concurrency: usize) -> Vec<Result<PacksListManifest, ManifestError>> {
+	let mut lists = vec![];
+	for url in urls {
+		lists.push(fetch_list(&url).await);
+	}
+	lists
+}
+
+/// Why something a [ManifestMetadata] points to couldn't be fetched.
+#[cfg(feature = "net")]
+#[derive(Debug)]
+pub enum PackFetchError {
+	/// The pack doesn't have one.
+	Unspecified,
+	/// The request failed or the body couldn't be read.
+	Request(reqwest::Error),
+	/// A redirect wasn't allowed by [http::RedirectPolicy].
+	Redirect(http::RedirectError),
+	/// The [http::TokenProvider] couldn't give a token, with its reason.
+	Token(String),
+	/// The server answered with an unsuccessful status.
+	Status(u16),
+	/// The sha256 of the response doesn't match the one in the packs list.
+	IntegrityMismatch {
+		expected: Sha256Hash,
+		found: Sha256Hash
+	},
+	/// The response is bigger than the limit.
+	TooLarge {
+		limit: u64,
+		received: u64
+	},
+	/// Text that isn't UTF-8.
+	InvalidText,
+	/// It wasn't awaited inside a tokio runtime, see [crate::NoRuntime].
+	NoRuntime
+}
+
+#[cfg(feature = "net")]
+impl std::fmt::Display for PackFetchError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PackFetchError::Unspecified => write!(f, "the pack doesn't have one"),
+			PackFetchError::Request(error) => write!(f, "request failed: {error}"),
+			PackFetchError::Redirect(error) => write!(f, "{error}"),
+			PackFetchError::Token(reason) => write!(f, "cannot get a token: {reason}"),
+			PackFetchError::Status(status) => write!(f, "server responded with status {status}"),
+			PackFetchError::IntegrityMismatch { expected, found } => write!(f, "sha256 didn't check out, expected {expected} found {found}"),
+			PackFetchError::TooLarge { limit, received } => write!(f, "response is bigger than the limit of {limit} bytes ({received} bytes received)"),
+			PackFetchError::InvalidText => write!(f, "response isn't valid UTF-8"),
+			PackFetchError::NoRuntime => write!(f, "{}", crate::NoRuntime)
+		}
+	}
+}
+
+#[cfg(feature = "net")]
+impl std::error::Error for PackFetchError {}
+
+#[cfg(feature = "net")]
+impl From<crate::NoRuntime> for PackFetchError {
+	fn from(_: crate::NoRuntime) -> Self {
+		PackFetchError::NoRuntime
+	}
+}
+
+/// Why [ManifestMetadata::verify_local] or [ManifestMetadata::upgrade_local] failed.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub enum PackError {
+	/// The manifest couldn't be fetched, or it doesn't match the packs list.
+	Manifest(ManifestError),
+	Upgrade(crate::UpgradeError)
+}
+
+#[cfg(feature = "fs")]
+impl std::fmt::Display for PackError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PackError::Manifest(error) => write!(f, "{error}"),
+			PackError::Upgrade(error) => write!(f, "{error}")
+		}
+	}
+}
+
+#[cfg(feature = "fs")]
+impl std::error::Error for PackError {}
+
+#[cfg(feature = "net")]
+impl From<http::GetError> for PackFetchError {
+	fn from(error: http::GetError) -> Self {
+		match error {
+			http::GetError::Request(error, _) => PackFetchError::Request(error),
+			http::GetError::Redirect(error) => PackFetchError::Redirect(error),
+			http::GetError::Token(reason) => PackFetchError::Token(reason)
+		}
+	}
+}
+
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+async fn read_body(mut response: reqwest::Response, limit: u64) -> Result<bytes::Bytes, PackFetchError> {
+	let mut contents = bytes::BytesMut::new();
+	while let Some(chunk) = response.chunk().await.map_err(PackFetchError::Request)? {
+		contents.extend_from_slice(&chunk);
+		if contents.len() as u64 > limit {
+			return Err(PackFetchError::TooLarge {
+				limit,
+				received: contents.len() as u64
+			});
+		}
+	}
+	Ok(contents.freeze())
+}
+
+/// The browser only hands over whole bodies, so the limit is checked against the Content-Length first.
+#[cfg(all(feature = "net", target_arch = "wasm32"))]
+async fn read_body(response: reqwest::Response, limit: u64) -> Result<bytes::Bytes, PackFetchError> {
+	let too_large = |received| PackFetchError::TooLarge {
+		limit,
+		received
+	};
+	if response.content_length().is_some_and(|length| length > limit) {
+		return Err(too_large(0));
+	}
+
+	let contents = response.bytes().await.map_err(PackFetchError::Request)?;
+	match contents.len() as u64 > limit {
+		true => Err(too_large(contents.len() as u64)),
+		false => Ok(contents)
+	}
+}
+
+/// A pack icon as it was served, decoding it is up to the caller.
+#[cfg(feature = "net")]
+#[derive(Debug, Clone)]
+pub struct Icon {
+	pub bytes: bytes::Bytes,
+	/// The Content-Type the server sent, or one guessed from the first bytes for common image formats when it sent none or `application/octet-stream`.
+	pub content_type: Option<String>
+}
+
+/// Fetches something a packs list points to, checking its size as it arrives and its sha256 when there is one.
+#[cfg(feature = "net")]
+async fn fetch_asset(url: &str, expected_sha: Option<Sha256Hash>, limit: u64) -> Result<(bytes::Bytes, Option<String>), PackFetchError> {
+	crate::NoRuntime::check()?;
+	let response = http::shared().get(url).await?;
+	if !response.status().is_success() {
+		return Err(PackFetchError::Status(response.status().as_u16()));
+	}
+
+	let content_type = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(str::to_owned);
+	let contents = read_body(response, limit).await?;
+
+	if let Some(expected) = expected_sha {
+		let found = Sha256Hash::digest(&contents);
+		if found != expected {
+			return Err(PackFetchError::IntegrityMismatch {
+				expected,
+				found
+			});
+		}
+	}
+
+	Ok((contents, content_type))
+}
+
+/// Guesses the type of an image from its magic bytes.
+#[cfg(feature = "net")]
+fn sniff_image(bytes: &[u8]) -> Option<&'static str> {
+	match bytes {
+		[0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+		[0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+		[b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+		[b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+		[0, 0, 1, 0, ..] => Some("image/x-icon"),
+		_ => None
+	}
 }
 
 impl PacksListManifest {
@@ -21,39 +314,1050 @@ impl PacksListManifest {
 	/// Fetches a packs list manifest from a URL.
 	/// # Warning
 	/// This function will trust any URL you stick into it, ideally make sure only trusted URLs are passed in, or at least ensure all URLs use encrypted protocols like HTTPS.
-	pub async fn from_url<U: reqwest::IntoUrl>(url: U) -> Option<Self> {
-		let resp = CLIENT.get(url).send().await.ok()?.text().await.ok()?;
-		serde_json::from_str(&resp).ok()
+	#[cfg(feature = "net")]
+	pub async fn from_url<U: AsRef<str>>(url: U) -> Option<Self> {
+		Self::try_from_url(url).await.ok()
+	}
+
+	/// # Description
+	/// Same as [PacksListManifest::from_url], but returns why it failed instead of [None], see [crate::Directory::try_from_url].
+	#[cfg(feature = "net")]
+	pub async fn try_from_url<U: AsRef<str>>(url: U) -> Result<Self, ManifestError> {
+		fetch_list(url.as_ref()).await
+	}
+
+	/// # Description
+	/// The url relative manifest urls in this list are resolved against, the one it was fetched from unless [PacksListManifest::set_base_url] changed it.
+	pub fn base_url(&self) -> Option<&str> {
+		self.base_url.as_deref()
+	}
+
+	/// # Description
+	/// Resolves the relative manifest urls of the packs in this list against `base_url` from now on, like for a list that was parsed from a file or built by hand.
+	/// Packs that are added later don't get it, call this again after adding them.
+	pub fn set_base_url<U: Into<String>>(&mut self, base_url: U) {
+		let base_url = base_url.into();
+		for (key, metadata) in &mut self.packs {
+			metadata.origin = Some(Origin {
+				key: key.clone(),
+				base_url: base_url.clone()
+			});
+		}
+		self.base_url = Some(base_url);
+	}
+
+	/// # Description
+	/// Fetches the packs list at `url` every `interval` and yields it whenever it changed, like [Directory::watch_url] does for manifests.
+	/// Any change counts, not only ones to the packs.
+	#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+	pub fn watch_url<U: Into<String>>(url: U, interval: std::time::Duration, options: &crate::WatchOptions) -> crate::Watcher<PacksListChange> {
+		let url = url.into();
+		let base_url = url.clone();
+		crate::watch::watch(url, interval, options, move |previous: Option<&PacksListManifest>, list: &PacksListManifest| {
+			let canonical = |list: &PacksListManifest| list.to_string_canonical().ok();
+			if previous.is_some_and(|previous| canonical(previous) == canonical(list)) {
+				return None;
+			}
+
+			let json = |metadata: &ManifestMetadata| serde_json::to_value(metadata).ok();
+			let empty = PacksListManifest::default();
+			let previous = previous.unwrap_or(&empty);
+			let mut change = PacksListChange {
+				list: list.clone(),
+				added: list.packs.keys().filter(|key| !previous.packs.contains_key(*key)).cloned().collect(),
+				removed: previous.packs.keys().filter(|key| !list.packs.contains_key(*key)).cloned().collect(),
+				changed: list.packs.iter().filter(|(key, metadata)| previous.packs.get(*key).is_some_and(|old| json(old) != json(metadata))).map(|(key, _)| key.clone()).collect()
+			};
+			change.list.set_base_url(&base_url);
+			change.added.sort();
+			change.removed.sort();
+			change.changed.sort();
+			Some(change)
+		})
+	}
+
+	/// # Description
+	/// Fetches several packs lists at most `concurrency` at a time and merges them in the order they're given, each with its own [MergeStrategy] (the first one's is unused).
+	/// A list that can't be fetched or merged is left out and reported with its url.
+	/// # Warning
+	/// This function will trust any URL you stick into it, ideally make sure only trusted URLs are passed in, or at least ensure all URLs use encrypted protocols like HTTPS.
+	#[cfg(feature = "net")]
+	pub async fn from_urls<U: AsRef<str>>(sources: &[(U, MergeStrategy)], concurrency: usize) -> (Self, Vec<(String, MergeError)>) {
+		let lists = fetch_lists(sources.iter().map(|(url, _)| url.as_ref().to_owned()).collect(), concurrency).await;
+
+		let mut merged: Option<Self> = None;
+		let mut errors = vec![];
+		for ((url, strategy), list) in sources.iter().zip(lists) {
+			let result = match (list, &mut merged) {
+				(Ok(list), Some(merged)) => merged.merge(list, strategy),
+				(Ok(list), None) => {
+					merged = Some(list);
+					Ok(())
+				},
+				(Err(error), _) => Err(MergeError::Fetch(error))
+			};
+
+			if let Err(error) = result {
+				errors.push((url.as_ref().to_owned(), error));
+			}
+		}
+
+		(merged.unwrap_or_default(), errors)
+	}
+
+	/// # Description
+	/// Adds the packs of `other` to this list, `strategy` decides what happens to keys that are in both.
+	/// This list's featured pack wins, `featured` lists are concatenated and the stricter `min_green_lib` is kept.
+	/// Nothing is changed when it fails.
+	pub fn merge(&mut self, other: Self, strategy: &MergeStrategy) -> Result<(), MergeError> {
+		let rename = |key: &str| match strategy {
+			MergeStrategy::Prefix(prefix) => format!("{prefix}{key}"),
+			_ => key.to_owned()
+		};
+
+		if *strategy != MergeStrategy::FirstWins {
+			if let Some(key) = other.packs.keys().map(|key| rename(key)).find(|key| self.packs.contains_key(key)) {
+				return Err(MergeError::Collision {
+					key
+				});
+			}
+		}
+
+		for (key, mut metadata) in other.packs {
+			let key = rename(&key);
+			if let Some(origin) = &mut metadata.origin {
+				origin.key = key.clone();
+			}
+			self.packs.entry(key).or_insert(metadata);
+		}
+
+		if self.featured_pack.is_none() {
+			self.featured_pack = other.featured_pack.map(|name| rename(&name));
+		}
+		for name in other.featured {
+			let name = rename(&name);
+			if !self.featured.contains(&name) {
+				self.featured.push(name);
+			}
+		}
+
+		let version = |version: &Option<String>| version.as_deref().and_then(|version| semver::Version::parse(version.trim_start_matches('v')).ok());
+		if version(&other.min_green_lib) > version(&self.min_green_lib) {
+			self.min_green_lib = other.min_green_lib;
+		}
+		self.schema_version = self.schema_version.max(other.schema_version);
+		for (key, value) in other.extra {
+			self.extra.entry(key).or_insert(value);
+		}
+
+		Ok(())
+	}
+
+	/// # Description
+	/// Parses a packs list manifest from bytes.
+	pub fn from_slice(bytes: &[u8]) -> Result<Self, ManifestError> {
+		manifest::from_slice(bytes)
+	}
+
+	/// # Description
+	/// Parses a packs list manifest from bytes like [Self::from_slice], but fails with [ManifestError::UnknownField] on any field this version doesn't know about.
+	/// Meant for checking manifests before publishing them, clients should stick to the permissive constructors so newer manifests keep working.
+	pub fn from_slice_strict(bytes: &[u8]) -> Result<Self, ManifestError> {
+		manifest::from_slice_strict(bytes)
+	}
+
+	/// # Description
+	/// Parses a packs list manifest from a reader, wrap it in a [std::io::BufReader] if it isn't buffered already.
+	pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Self, ManifestError> {
+		manifest::from_reader(reader)
+	}
+
+	/// # Description
+	/// The JSON schema of packs lists, see [crate::Directory::json_schema] for what it leaves to [PacksListManifest::validate].
+	#[cfg(feature = "schemars")]
+	pub fn json_schema() -> schemars::schema::RootSchema {
+		schemars::schema_for!(PacksListManifest)
+	}
+
+	/// # Description
+	/// Checks that every pack in this list makes sense, the constructors in this crate do this for you.
+	pub fn validate(&self) -> Result<(), ValidationError> {
+		manifest::check_min_version(&self.min_green_lib)?;
+		if self.schema_version() > SCHEMA_VERSION {
+			return Err(ValidationError::SchemaTooNew {
+				version: self.schema_version(),
+				supported: SCHEMA_VERSION
+			});
+		}
+
+		for (key, metadata) in &self.packs {
+			if !crate::hash::is_valid_digest(&metadata.manifest_sha) {
+				return Err(ValidationError::InvalidManifestSha {
+					pack: key.clone(),
+					sha: metadata.manifest_sha.clone()
+				});
+			}
+
+			if let Some(version) = metadata.min_launcher_version.as_ref().filter(|version| semver::Version::parse(version.trim_start_matches('v')).is_err()) {
+				return Err(ValidationError::InvalidLauncherVersion {
+					pack: key.clone(),
+					version: version.clone()
+				});
+			}
+
+			if let Some(entry) = metadata.versions.iter().find(|entry| !crate::hash::is_valid_digest(&entry.manifest_sha)) {
+				return Err(ValidationError::InvalidManifestSha {
+					pack: format!("{key} version {}", entry.version),
+					sha: entry.manifest_sha.clone()
+				});
+			}
+
+			if let Some(successor) = metadata.successor.as_ref().filter(|successor| !self.packs.contains_key(*successor)) {
+				return Err(ValidationError::UnknownSuccessor {
+					pack: key.clone(),
+					successor: successor.clone()
+				});
+			}
+		}
+
+		// every successor exists, so a chain that doesn't end within as many hops as there are packs loops
+		let mut keys: Vec<_> = self.packs.keys().collect();
+		keys.sort();
+		if let Some(key) = keys.into_iter().find(|key| self.resolve_successor(key).is_none()) {
+			return Err(ValidationError::SuccessorCycle {
+				pack: key.clone()
+			});
+		}
+
+		Ok(())
+	}
+
+	/// # Description
+	/// The schema version the list was written with, lists without one are version 1.
+	pub fn schema_version(&self) -> u32 {
+		self.schema_version.unwrap_or(1)
+	}
+
+	/// # Description
+	/// Reads a packs list manifest from a file.
+	#[cfg(feature = "fs")]
+	pub async fn from_file(path: &std::path::Path) -> Result<Self, ManifestError> {
+		crate::NoRuntime::check()?;
+		manifest::read_file(path).await
+	}
+
+	/// # Description
+	/// Serializes this packs list to compact JSON with sorted keys and no trailing newline.
+	pub fn to_string_canonical(&self) -> serde_json::Result<String> {
+		manifest::to_string_canonical(self)
+	}
+
+	/// # Description
+	/// Writes this packs list as JSON with sorted keys, `pretty` adds indentation.
+	pub fn to_writer<W: std::io::Write>(&self, writer: W, pretty: bool) -> serde_json::Result<()> {
+		manifest::to_writer(self, writer, pretty)
 	}
 
 	/// # Description
-	/// Returns the metadata of the featured pack.
+	/// Writes this packs list to a file atomically (through a temporary file and a rename), `pretty` adds indentation.
+	#[cfg(feature = "fs")]
+	pub async fn to_file(&self, path: &std::path::Path, pretty: bool) -> std::io::Result<()> {
+		crate::NoRuntime::check()?;
+		manifest::to_file(self, path, pretty).await
+	}
+
+	/// # Description
+	/// Returns the metadata of the featured pack, `featured_pack` if it's set and otherwise the first pack in `featured`.
+	/// A hidden pack is never featured, see [ManifestMetadata::hidden], and neither is a deprecated one that has a [ManifestMetadata::successor].
 	pub fn get_featured_pack_metadata(&self) -> Result<&ManifestMetadata, FeaturedPackError> {
-		let featured_pack = self.featured_pack.as_ref().ok_or(FeaturedPackError::Unspecified)?;
-		self.packs.get(featured_pack).ok_or(FeaturedPackError::Invalid)
+		let featured_pack = self.featured_pack.as_ref().or(self.featured.first()).ok_or(FeaturedPackError::Unspecified)?;
+		let metadata = self.packs.get(featured_pack).ok_or(FeaturedPackError::Invalid)?;
+		match (metadata.hidden, metadata.deprecated, &metadata.successor) {
+			(true, _, _) => Err(FeaturedPackError::Hidden),
+			(false, true, Some(successor)) => Err(FeaturedPackError::Deprecated {
+				successor: successor.clone()
+			}),
+			_ => Ok(metadata)
+		}
+	}
+
+	/// # Description
+	/// Follows the [ManifestMetadata::successor] of `key` to the pack that replaced it, returning its key, its metadata and how many packs were skipped on the way, `0` when `key` has no successor.
+	/// [None] when `key` isn't in the list, or when a list that wasn't validated has a successor that isn't in it or a chain that loops.
+	pub fn resolve_successor(&self, key: &str) -> Option<(&str, &ManifestMetadata, usize)> {
+		let (mut key, mut metadata) = self.packs.get_key_value(key)?;
+		let mut hops = 0;
+		while let Some(successor) = &metadata.successor {
+			if hops == self.packs.len() {
+				return None;
+			}
+			(key, metadata) = self.packs.get_key_value(successor)?;
+			hops += 1;
+		}
+		Some((key, metadata, hops))
+	}
+
+	/// # Description
+	/// Returns the key and metadata of the declared featured pack like [PacksListManifest::get_featured_pack_metadata], falling back to any other pack in `featured` and then to the visible pack with the smallest key.
+	/// Hidden packs are skipped, so it only fails when there are no visible packs at all.
+	pub fn featured_or_default(&self) -> Result<(&str, &ManifestMetadata), FeaturedPackError> {
+		self.featured_pack.iter().chain(&self.featured)
+			.find_map(|name| self.packs.get_key_value(name).filter(|(_, metadata)| !metadata.hidden))
+			.or_else(|| self.packs.iter().filter(|(_, metadata)| !metadata.hidden).min_by_key(|(name, _)| name.as_str()))
+			.map(|(name, metadata)| (name.as_str(), metadata))
+			.ok_or(FeaturedPackError::Empty)
+	}
+
+	/// # Description
+	/// Returns the featured packs by key in the order of `featured`, or just `featured_pack` for lists that don't have `featured`.
+	/// Names that aren't in the list and hidden packs are skipped.
+	pub fn featured_packs(&self) -> Vec<(&str, &ManifestMetadata)> {
+		let names = match self.featured.is_empty() {
+			true => self.featured_pack.as_slice(),
+			false => self.featured.as_slice()
+		};
+		names.iter().filter_map(|name| self.packs.get_key_value(name)).filter(|(_, metadata)| !metadata.hidden).map(|(name, metadata)| (name.as_str(), metadata)).collect()
+	}
+
+	/// # Description
+	/// Returns the packs everyone should see by key, sorted by key: the ones that aren't [ManifestMetadata::hidden] and aren't in a [ManifestMetadata::channel].
+	pub fn visible_packs(&self) -> Vec<(&str, &ManifestMetadata)> {
+		let mut packs: Vec<_> = self.packs.iter().filter(|(_, metadata)| !metadata.hidden && metadata.channel.is_none()).map(|(name, metadata)| (name.as_str(), metadata)).collect();
+		packs.sort_by_key(|(name, _)| *name);
+		packs
+	}
+
+	/// # Description
+	/// Returns the packs of a channel like `beta` by key, sorted by key, hidden ones aren't included.
+	pub fn packs_in_channel(&self, channel: &str) -> Vec<(&str, &ManifestMetadata)> {
+		let mut packs: Vec<_> = self.packs.iter().filter(|(_, metadata)| !metadata.hidden && metadata.channel.as_deref() == Some(channel)).map(|(name, metadata)| (name.as_str(), metadata)).collect();
+		packs.sort_by_key(|(name, _)| *name);
+		packs
+	}
+
+	/// # Description
+	/// Returns the packs a launcher of `launcher_version` can install by key, sorted by key, see [ManifestMetadata::compatible_with].
+	pub fn packs_compatible_with(&self, launcher_version: &semver::Version) -> Vec<(&str, &ManifestMetadata)> {
+		let mut packs: Vec<_> = self.packs.iter().filter(|(_, metadata)| metadata.compatible_with(launcher_version)).map(|(name, metadata)| (name.as_str(), metadata)).collect();
+		packs.sort_by_key(|(name, _)| *name);
+		packs
+	}
+
+	/// # Description
+	/// Returns the packs that have a tag by key, sorted by key.
+	pub fn packs_with_tag(&self, tag: &str) -> Vec<(&str, &ManifestMetadata)> {
+		let mut packs: Vec<_> = self.packs.iter().filter(|(_, metadata)| metadata.tags.iter().any(|pack_tag| pack_tag == tag)).map(|(name, metadata)| (name.as_str(), metadata)).collect();
+		packs.sort_by_key(|(name, _)| *name);
+		packs
+	}
+
+	/// # Description
+	/// Returns every pack by key in the order a pack browser should show them: by [ManifestMetadata::sort_weight], then by display name, then by key.
+	/// Packs without a weight come after the ones with one.
+	pub fn packs_sorted(&self) -> Vec<(&str, &ManifestMetadata)> {
+		let mut packs: Vec<_> = self.packs.iter().map(|(name, metadata)| (name.as_str(), metadata)).collect();
+		packs.sort_by(|(a_name, a), (b_name, b)| {
+			(a.sort_weight.is_none(), a.sort_weight, &a.display_name, a_name).cmp(&(b.sort_weight.is_none(), b.sort_weight, &b.display_name, b_name))
+		});
+		packs
 	}
 }
 
-/// Contains metadata about a certain [Directory] in a [PacksListManifest].
-#[derive(Deserialize, Debug, Clone)]
+impl manifest::Manifest for PacksListManifest {
+	fn validate(&self) -> Result<(), ValidationError> {
+		PacksListManifest::validate(self)
+	}
+
+	fn unknown_field(&self) -> Option<(String, String)> {
+		manifest::first_extra("", &self.extra).or_else(|| self.packs.iter().find_map(|(key, metadata)| {
+			let path = manifest::join_path("", &["packs", key]);
+			manifest::first_extra(&path, &metadata.extra)
+				.or_else(|| metadata.bootstrap.as_ref().and_then(|bootstrap| manifest::first_extra(&manifest::join_path(&path, &["bootstrap"]), &bootstrap.extra)))
+				.or_else(|| metadata.versions.iter().enumerate().find_map(|(index, entry)| manifest::first_extra(&manifest::join_path(&path, &["versions", &index.to_string()]), &entry.extra)))
+		}))
+	}
+
+	#[cfg(feature = "net")]
+	fn check_limits(&self, limits: &manifest::Limits) -> Result<(), ManifestError> {
+		let mut entries = 0;
+		for metadata in self.packs.values() {
+			limits.count(&mut entries, 1 + metadata.versions.len())?;
+		}
+		Ok(())
+	}
+}
+
+/// Builds a [PacksListManifest] whose manifest shas are computed from the manifests themselves, so they match what the server sends.
+/// Errors are kept until [PacksListBuilder::build].
+#[derive(Debug, Default)]
+pub struct PacksListBuilder {
+	list: PacksListManifest,
+	base_url: Option<String>,
+	algorithm: crate::hash::HashAlgorithm,
+	error: Option<PacksListBuildError>
+}
+
+/// Why [PacksListBuilder::build] couldn't build a packs list.
+#[derive(Debug)]
+pub enum PacksListBuildError {
+	/// A pack with this key was already added.
+	Duplicate {
+		key: String
+	},
+	/// The manifest of this pack couldn't be serialized, or its bytes aren't a manifest.
+	InvalidManifest {
+		key: String,
+		error: ManifestError
+	},
+	/// A featured pack that wasn't added.
+	UnknownFeatured {
+		key: String
+	},
+	/// The list didn't pass [PacksListManifest::validate].
+	Invalid(ValidationError)
+}
+
+impl std::fmt::Display for PacksListBuildError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PacksListBuildError::Duplicate { key } => write!(f, "more than one pack is called {key:?}"),
+			PacksListBuildError::InvalidManifest { key, error } => write!(f, "manifest of pack {key:?} is invalid: {error}"),
+			PacksListBuildError::UnknownFeatured { key } => write!(f, "featured pack {key:?} isn't in the list"),
+			PacksListBuildError::Invalid(error) => write!(f, "{error}")
+		}
+	}
+}
+
+impl std::error::Error for PacksListBuildError {}
+
+impl PacksListBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// # Description
+	/// What the manifest shas of the packs added after this are computed with, sha256 by default.
+	/// Launchers with a green-lib that doesn't have the algorithm can't install those packs.
+	pub fn hash_algorithm(mut self, algorithm: crate::hash::HashAlgorithm) -> Self {
+		self.algorithm = algorithm;
+		self
+	}
+
+	/// # Description
+	/// Adds a pack whose manifest is `directory`, served at `manifest_url` as [crate::Directory::to_string_canonical].
+	pub fn add_pack(self, key: &str, display_name: &str, manifest_url: &str, directory: &crate::Directory) -> Self {
+		match directory.to_string_canonical() {
+			Ok(manifest) => self.add(key, display_name, manifest_url, manifest.as_bytes(), directory),
+			Err(error) => self.fail(PacksListBuildError::InvalidManifest {
+				key: key.to_owned(),
+				error: ManifestError::Parse(crate::manifest::unlocated(error))
+			})
+		}
+	}
+
+	/// # Description
+	/// Adds a pack whose manifest is served at `manifest_url` as exactly `manifest`, for manifests that were written some other way.
+	pub fn add_pack_bytes(self, key: &str, display_name: &str, manifest_url: &str, manifest: &[u8]) -> Self {
+		match crate::Directory::from_slice(manifest) {
+			Ok(directory) => self.add(key, display_name, manifest_url, manifest, &directory),
+			Err(error) => self.fail(PacksListBuildError::InvalidManifest {
+				key: key.to_owned(),
+				error
+			})
+		}
+	}
+
+	fn add(mut self, key: &str, display_name: &str, manifest_url: &str, manifest: &[u8], directory: &crate::Directory) -> Self {
+		if self.list.packs.contains_key(key) {
+			return self.fail(PacksListBuildError::Duplicate {
+				key: key.to_owned()
+			});
+		}
+
+		let stats = directory.stats().total;
+		self.list.packs.insert(key.to_owned(), ManifestMetadata {
+			display_name: display_name.to_owned(),
+			display_name_localized: HashMap::new(),
+			description: None,
+			description_localized: HashMap::new(),
+			manifest_url: manifest_url.to_owned(),
+			manifest_sha: self.algorithm.digest(manifest),
+			origin: None,
+			content_hash: Some(Sha256Hash(directory.content_hash())),
+			size_bytes: Some(stats.size),
+			file_count: Some(stats.files),
+			delta_url_template: None,
+			bootstrap: None,
+			icon_url: None,
+			icon_sha: None,
+			changelog_url: None,
+			changelog_sha: None,
+			tags: vec![],
+			sort_weight: None,
+			hidden: false,
+			channel: None,
+			notice: None,
+			notice_url: None,
+			requires_acceptance: false,
+			versions: vec![],
+			min_launcher_version: None,
+			install_hint: None,
+			deprecated: false,
+			successor: None,
+			extra: Default::default()
+		});
+		self
+	}
+
+	/// # Description
+	/// Changes the rest of the metadata of a pack that was added, like its description or tags.
+	pub fn edit_pack<F: FnOnce(&mut ManifestMetadata)>(mut self, key: &str, edit: F) -> Self {
+		if let Some(metadata) = self.list.packs.get_mut(key) {
+			edit(metadata);
+		}
+		self
+	}
+
+	/// # Description
+	/// Features a pack, packs are featured in the order this is called in, see [PacksListManifest::featured].
+	/// The first one is the `featured_pack` too, for clients that only know that.
+	pub fn featured(mut self, key: &str) -> Self {
+		self.list.featured_pack.get_or_insert_with(|| key.to_owned());
+		self.list.featured.push(key.to_owned());
+		self
+	}
+
+	/// # Description
+	/// Resolves relative manifest urls against `base_url`, see [PacksListManifest::set_base_url].
+	pub fn base_url(mut self, base_url: &str) -> Self {
+		self.base_url = Some(base_url.to_owned());
+		self
+	}
+
+	fn fail(mut self, error: PacksListBuildError) -> Self {
+		self.error.get_or_insert(error);
+		self
+	}
+
+	/// # Description
+	/// The packs list, failing with the first problem of any pack, or when a featured pack wasn't added.
+	pub fn build(self) -> Result<PacksListManifest, PacksListBuildError> {
+		if let Some(error) = self.error {
+			return Err(error);
+		}
+		if let Some(key) = self.list.featured.iter().find(|key| !self.list.packs.contains_key(*key)) {
+			return Err(PacksListBuildError::UnknownFeatured {
+				key: key.clone()
+			});
+		}
+
+		self.list.validate().map_err(PacksListBuildError::Invalid)?;
+		let mut list = self.list;
+		if let Some(base_url) = self.base_url {
+			list.set_base_url(base_url);
+		}
+		Ok(list)
+	}
+}
+
+/// A packs list that changed since the last poll of a [crate::Watcher], see [PacksListManifest::watch_url].
+/// Keys are sorted and compared against the list of the previous change, or an empty one for the first.
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone)]
+pub struct PacksListChange {
+	pub list: PacksListManifest,
+	pub added: Vec<String>,
+	pub removed: Vec<String>,
+	/// Packs whose metadata changed, like a new manifest sha.
+	pub changed: Vec<String>
+}
+
+/// The notice of a pack and whether the user accepted it, see [ManifestMetadata::requires_acceptance].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Notice {
+	pub text: Option<String>,
+	pub url: Option<String>,
+	pub accepted: bool
+}
+
+/// Contains metadata about a certain [Directory](crate::Directory) in a [PacksListManifest].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ManifestMetadata {
 	pub display_name: String,
-	manifest_url: String,
-	manifest_sha: String
+	/// Translations of `display_name` by locale, like `de` or `pt-BR`, see [ManifestMetadata::display_name_for].
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub display_name_localized: HashMap<String, String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// Translations of `description` by locale.
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub description_localized: HashMap<String, String>,
+	/// Relative ones are resolved against the [PacksListManifest::base_url] of the list.
+	pub(crate) manifest_url: String,
+	/// The digest of the manifest, sha256 hex or prefixed with another [crate::hash::HashAlgorithm] like `sha512:`, or `blake3:` with the `blake3` feature.
+	#[serde(deserialize_with = "manifest::lowercase")]
+	pub(crate) manifest_sha: String,
+	/// The key of the pack and the url of its list, set by [PacksListManifest::set_base_url].
+	#[serde(skip)]
+	pub(crate) origin: Option<Origin>,
+	/// The [crate::Directory::content_hash] of the manifest, which [ManifestMetadata::update_directory] checks a delta against.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_hash: Option<Sha256Hash>,
+	/// The bytes of every file of the manifest with a size like [crate::EntryStats::size], for showing how big a pack is without fetching its manifest.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub size_bytes: Option<u64>,
+	/// How many files the manifest has like [crate::EntryStats::files].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub file_count: Option<u64>,
+	/// Where a [crate::ManifestDelta] from an older manifest to the current one is, `{sha}` is the [crate::Directory::content_hash] of the older one, see [crate::template].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub delta_url_template: Option<String>,
+	/// An archive of the whole pack for first-time installs, pass it to [crate::UpgradeOptions] to use it.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub bootstrap: Option<crate::Bootstrap>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub icon_url: Option<String>,
+	/// Checked by [ManifestMetadata::fetch_icon] when present.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub icon_sha: Option<Sha256Hash>,
+	/// Markdown describing what changed in the current version of the pack.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub changelog_url: Option<String>,
+	/// Checked by [ManifestMetadata::fetch_changelog] when present.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub changelog_sha: Option<Sha256Hash>,
+	/// Sections this pack belongs in, like `tech` or `archived`.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+	/// Where the pack goes in [PacksListManifest::packs_sorted], lower weights come first.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub sort_weight: Option<i32>,
+	/// Left out of [PacksListManifest::visible_packs] and never featured, but still in the list and installable, like for a deprecated pack.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub hidden: bool,
+	/// A release channel like `beta` the pack is only shown in, see [PacksListManifest::packs_in_channel]. [None] is the stable channel everyone sees.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub channel: Option<String>,
+	/// Text like an attribution or a license the launcher shows before the pack is installed.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub notice: Option<String>,
+	/// Where the full text of the [ManifestMetadata::notice] is, for ones too long to put in the list.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub notice_url: Option<String>,
+	/// The user has to accept the notice before the pack is installed, see [crate::UpgradeOptions::require_notice].
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub requires_acceptance: bool,
+	/// Earlier (or upcoming) revisions of the pack that can be installed instead of the current one, see [ManifestMetadata::versions].
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub versions: Vec<VersionEntry>,
+	/// The oldest launcher (not green-lib) version that can run the pack, as semver, see [ManifestMetadata::check_launcher].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub min_launcher_version: Option<String>,
+	/// Where launchers should install the pack by default, see [ManifestMetadata::install_location].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub install_hint: Option<InstallHint>,
+	/// The pack is retired, launchers should tell its users and offer to move them to the [ManifestMetadata::successor].
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub deprecated: bool,
+	/// The key of the pack in the same list that replaces this one, see [PacksListManifest::resolve_successor].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub successor: Option<String>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// How the manifest of a pack differs from the [ManifestMetadata::size_bytes] and [ManifestMetadata::file_count] its list advertises, see [ManifestMetadata::size_mismatch].
+/// The advertised values are [None] when the list doesn't have them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeMismatch {
+	pub advertised_bytes: Option<u64>,
+	pub actual_bytes: u64,
+	pub advertised_files: Option<u64>,
+	pub actual_files: u64
+}
+
+/// What [ManifestMetadata::update_directory] gave.
+#[cfg(feature = "net")]
+#[derive(Debug, Clone)]
+pub struct DirectoryUpdate {
+	pub directory: Directory,
+	/// The manifest came from a delta (or was already up to date), not a full fetch.
+	pub from_delta: bool
+}
+
+/// A pack needs a newer launcher, see [ManifestMetadata::check_launcher].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LauncherTooOld {
+	/// [None] when [ManifestMetadata::min_launcher_version] isn't a valid version.
+	pub required: Option<semver::Version>,
+	pub actual: semver::Version
+}
+
+impl std::fmt::Display for LauncherTooOld {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.required {
+			Some(required) => write!(f, "this pack needs launcher {required} or newer, but this is {}, update the launcher", self.actual),
+			None => write!(f, "this pack needs a launcher version that can't be understood")
+		}
+	}
+}
+
+impl std::error::Error for LauncherTooOld {}
+
+/// Where a pack is meant to be installed, part of [ManifestMetadata::install_hint].
+/// Lists use `"instance"`, which is the default, and `"minecraft_folder"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum InstallLocation {
+	/// A folder of its own, see [crate::util::instance_path].
+	#[default]
+	Instance,
+	/// Over the game folder itself, see [crate::util::minecraft_path], like for a pack that only adds a few things to vanilla.
+	MinecraftFolder,
+	/// A value this version doesn't know, launchers should treat it like [InstallLocation::Instance].
+	Unknown(String)
+}
+
+impl std::fmt::Display for InstallLocation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			InstallLocation::Instance => write!(f, "instance"),
+			InstallLocation::MinecraftFolder => write!(f, "minecraft_folder"),
+			InstallLocation::Unknown(location) => write!(f, "{location}")
+		}
+	}
+}
+
+impl Serialize for InstallLocation {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for InstallLocation {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Ok(match String::deserialize(deserializer)?.as_str() {
+			"instance" => InstallLocation::Instance,
+			"minecraft_folder" => InstallLocation::MinecraftFolder,
+			location => InstallLocation::Unknown(location.to_owned())
+		})
+	}
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for InstallLocation {
+	fn schema_name() -> String {
+		"InstallLocation".to_owned()
+	}
+
+	fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+		crate::open_enum_schema(&["instance", "minecraft_folder"])
+	}
+}
+
+/// Where launchers should install a pack by default, only a suggestion, the launcher picks the final path.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct InstallHint {
+	#[serde(default)]
+	pub location: InstallLocation,
+	/// The name of the instance folder, instead of one made from the key of the pack.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub folder_name: Option<String>,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// Where the packs list a [ManifestMetadata] came from, for resolving relative urls.
+#[derive(Debug, Clone)]
+pub(crate) struct Origin {
+	key: String,
+	#[cfg_attr(not(feature = "net"), allow(dead_code))]
+	base_url: String
+}
+
+/// A revision of a pack that users can pin or roll back to, part of [ManifestMetadata::versions].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct VersionEntry {
+	/// What the revision is called, like `1.4.2`, shown as is.
+	pub version: String,
+	pub(crate) manifest_url: String,
+	#[serde(deserialize_with = "manifest::lowercase")]
+	pub(crate) manifest_sha: String,
+	/// When the revision was released in unix seconds.
+	pub released_at: i64,
+	/// Fields this version doesn't know about, kept so a manifest survives being parsed and written back out.
+	#[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+	pub extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// Looks a locale up by dropping subtags from the end until something matches (`de-AT` tries `de-AT` then `de`), ignoring case and treating `_` like `-`.
+fn localized<'a>(translations: &'a HashMap<String, String>, locale: &str) -> Option<&'a str> {
+	let normalize = |locale: &str| locale.replace('_', "-").to_ascii_lowercase();
+	let mut locale = normalize(locale);
+
+	loop {
+		if let Some(translation) = translations.iter().find(|(key, _)| normalize(key) == locale).map(|(_, translation)| translation.as_str()) {
+			return Some(translation);
+		}
+
+		locale.truncate(locale.rfind('-')?);
+	}
 }
 
 impl ManifestMetadata {
+	/// # Description
+	/// Checks [ManifestMetadata::min_launcher_version] against the version of the launcher, which green-lib doesn't know itself.
+	/// Versions that can't be parsed never pass, [PacksListManifest::validate] already rejects them.
+	pub fn check_launcher(&self, launcher_version: &semver::Version) -> Result<(), LauncherTooOld> {
+		let Some(required) = &self.min_launcher_version else {
+			return Ok(());
+		};
+
+		let too_old = |required| Err(LauncherTooOld {
+			required,
+			actual: launcher_version.clone()
+		});
+		match semver::Version::parse(required.trim_start_matches('v')) {
+			Ok(required) if required > *launcher_version => too_old(Some(required)),
+			Ok(_) => Ok(()),
+			Err(_) => too_old(None)
+		}
+	}
+
+	/// # Description
+	/// Whether a launcher of `launcher_version` can install this pack, see [ManifestMetadata::check_launcher].
+	pub fn compatible_with(&self, launcher_version: &semver::Version) -> bool {
+		self.check_launcher(launcher_version).is_ok()
+	}
+
+	/// # Description
+	/// Where the pack should be installed by default, [InstallLocation::Instance] without an [ManifestMetadata::install_hint].
+	pub fn install_location(&self) -> &InstallLocation {
+		static DEFAULT: InstallLocation = InstallLocation::Instance;
+		self.install_hint.as_ref().map_or(&DEFAULT, |hint| &hint.location)
+	}
+
+	/// # Description
+	/// The name the pack suggests for its instance folder, pass it to [crate::util::instance_path].
+	pub fn suggested_folder_name(&self) -> Option<&str> {
+		self.install_hint.as_ref().and_then(|hint| hint.folder_name.as_deref())
+	}
+
+	/// # Description
+	/// The notice of this pack, [None] when it has neither a [ManifestMetadata::notice] nor a [ManifestMetadata::notice_url].
+	pub fn notice(&self) -> Option<Notice> {
+		(self.notice.is_some() || self.notice_url.is_some()).then(|| Notice {
+			text: self.notice.clone(),
+			url: self.notice_url.clone(),
+			accepted: false
+		})
+	}
+
+	/// # Description
+	/// Returns the display name for a locale like `de-AT`, falling back to the language and then to `display_name`.
+	pub fn display_name_for(&self, locale: &str) -> &str {
+		localized(&self.display_name_localized, locale).unwrap_or(&self.display_name)
+	}
+
+	/// # Description
+	/// Returns the description for a locale, with the same fallbacks as [ManifestMetadata::display_name_for].
+	pub fn description_for(&self, locale: &str) -> Option<&str> {
+		localized(&self.description_localized, locale).or(self.description.as_deref())
+	}
+
+	/// # Description
+	/// The size the list advertises for the pack's manifest, see [ManifestMetadata::size_bytes]. [None] for lists with no size.
+	pub fn size_bytes(&self) -> Option<u64> {
+		self.size_bytes
+	}
+
+	/// # Description
+	/// The file count the list advertises for the pack's manifest, see [ManifestMetadata::file_count]. [None] for lists with no count.
+	pub fn file_count(&self) -> Option<u64> {
+		self.file_count
+	}
+
+	/// # Description
+	/// Compares the [ManifestMetadata::size_bytes] and [ManifestMetadata::file_count] the list advertises to `directory`, the pack's manifest.
+	/// Returns [None] when they're within [SIZE_TOLERANCE_PERCENT] of it or the list doesn't have them.
+	/// The manifest is what [ManifestMetadata::manifest_sha] vouches for either way, a mismatch means the list is out of date and the size shown for the pack was wrong.
+	pub fn size_mismatch(&self, directory: &crate::Directory) -> Option<SizeMismatch> {
+		let stats = directory.stats().total;
+		let off = |advertised: Option<u64>, actual: u64| advertised.is_some_and(|advertised| advertised.abs_diff(actual).saturating_mul(100) > actual.saturating_mul(SIZE_TOLERANCE_PERCENT));
+		(off(self.size_bytes, stats.size) || off(self.file_count, stats.files)).then_some(SizeMismatch {
+			advertised_bytes: self.size_bytes,
+			actual_bytes: stats.size,
+			advertised_files: self.file_count,
+			actual_files: stats.files
+		})
+	}
+
 	/// # Description
 	/// Fetches a manifest from its packs list metadata.
 	/// The integrity of the returned [Directory] will be checked.
 	/// If the integrity check fails this function will return [None].
+	/// A manifest that doesn't match the advertised size is still returned, check it with [ManifestMetadata::size_mismatch].
+	#[cfg(feature = "net")]
 	pub async fn to_directory(&self) -> Option<Directory> {
-		let resp = CLIENT.get(&self.manifest_url).send().await.ok()?.text().await.ok()?;
+		self.try_to_directory().await.ok()
+	}
 
-		if self.manifest_sha != format!("{:x}", Sha256::digest(&resp)) {
+	/// # Description
+	/// Same as [ManifestMetadata::to_directory], but returns why it failed instead of [None], see [crate::Directory::try_from_url].
+	#[cfg(feature = "net")]
+	pub async fn try_to_directory(&self) -> Result<Directory, ManifestError> {
+		crate::NoRuntime::check()?;
+		fetch::manifest(self.resolve(&self.manifest_url)?, Some(&self.manifest_sha)).await
+	}
+
+	/// `url` made absolute against the url of the list this pack is in, absolute ones are left as they are.
+	#[cfg(feature = "net")]
+	fn resolve(&self, url: &str) -> Result<String, ManifestError> {
+		if reqwest::Url::parse(url).is_ok() {
+			return Ok(url.to_owned());
+		}
+
+		let error = || ManifestError::UnresolvedUrl {
+			pack: self.origin.as_ref().map_or(&self.display_name, |origin| &origin.key).clone(),
+			url: http::without_userinfo(url)
+		};
+		let origin = self.origin.as_ref().ok_or_else(error)?;
+		reqwest::Url::parse(&origin.base_url).and_then(|base| base.join(url)).map(String::from).map_err(|_| error())
+	}
+
+	/// # Description
+	/// Brings `current`, an older manifest of this pack, up to date with a [crate::ManifestDelta] from `delta_url_template` instead of fetching the whole manifest.
+	/// Falls back to [ManifestMetadata::try_to_directory] when there's no template or `content_hash`, or the delta can't be fetched or doesn't give a manifest with `content_hash`.
+	#[cfg(feature = "net")]
+	pub async fn update_directory(&self, current: &Directory) -> Result<DirectoryUpdate, ManifestError> {
+		crate::NoRuntime::check()?;
+		let current_hash = Sha256Hash(current.content_hash());
+		if self.content_hash == Some(current_hash) {
+			return Ok(DirectoryUpdate {
+				directory: current.clone(),
+				from_delta: true
+			});
+		}
+
+		if let Some(directory) = self.fetch_delta(current, current_hash).await {
+			return Ok(DirectoryUpdate {
+				directory,
+				from_delta: true
+			});
+		}
+		Ok(DirectoryUpdate {
+			directory: self.try_to_directory().await?,
+			from_delta: false
+		})
+	}
+
+	/// The manifest a delta from `current` gives, [None] when anything about it doesn't work out.
+	#[cfg(feature = "net")]
+	async fn fetch_delta(&self, current: &Directory, current_hash: Sha256Hash) -> Option<Directory> {
+		let (template, content_hash) = (self.delta_url_template.as_ref()?, self.content_hash?);
+		let url = crate::template::expand(template, &current_hash).ok()?;
+		let delta: crate::ManifestDelta = fetch::manifest(url, None).await.ok()?;
+		if delta.target != content_hash {
 			return None;
 		}
+		current.apply_delta(&delta).ok()
+	}
+
+	/// # Description
+	/// The revisions of [ManifestMetadata::versions], newest release first.
+	pub fn versions(&self) -> Vec<&VersionEntry> {
+		let mut versions: Vec<_> = self.versions.iter().collect();
+		versions.sort_by(|a, b| b.released_at.cmp(&a.released_at).then_with(|| a.version.cmp(&b.version)));
+		versions
+	}
 
-		serde_json::from_str(&resp).ok()
+	/// # Description
+	/// Looks up a revision of [ManifestMetadata::versions] by its name.
+	pub fn version(&self, version: &str) -> Option<&VersionEntry> {
+		self.versions.iter().find(|entry| entry.version == version)
+	}
+
+	/// # Description
+	/// Same as [ManifestMetadata::to_directory], but fetches the revision called `version` from [ManifestMetadata::versions].
+	/// Returns [None] when there's no such revision too.
+	#[cfg(feature = "net")]
+	pub async fn to_directory_version(&self, version: &str) -> Option<Directory> {
+		let entry = self.version(version)?;
+		crate::NoRuntime::check().ok()?;
+		fetch::manifest(self.resolve(&entry.manifest_url).ok()?, Some(&entry.manifest_sha)).await.ok()
+	}
+
+	/// # Description
+	/// Fetches the pack's icon with the shared client, it's checked against `icon_sha` when there is one and can't be bigger than [MAX_ICON_SIZE].
+	#[cfg(feature = "net")]
+	pub async fn fetch_icon(&self) -> Result<Icon, PackFetchError> {
+		let icon_url = self.icon_url.as_ref().ok_or(PackFetchError::Unspecified)?;
+		let (bytes, content_type) = fetch_asset(icon_url, self.icon_sha, MAX_ICON_SIZE).await?;
+		// octet-stream is what servers send when they don't know either
+		let content_type = content_type.filter(|content_type| !content_type.starts_with("application/octet-stream")).or_else(|| sniff_image(&bytes).map(str::to_owned));
+
+		Ok(Icon {
+			bytes,
+			content_type
+		})
+	}
+
+	/// # Description
+	/// Fetches the pack's changelog with the shared client, it's checked against `changelog_sha` when there is one and can't be bigger than [MAX_CHANGELOG_SIZE].
+	#[cfg(feature = "net")]
+	pub async fn fetch_changelog(&self) -> Result<String, PackFetchError> {
+		let changelog_url = self.changelog_url.as_ref().ok_or(PackFetchError::Unspecified)?;
+		let (bytes, _) = fetch_asset(changelog_url, self.changelog_sha, MAX_CHANGELOG_SIZE).await?;
+		String::from_utf8(bytes.into()).map_err(|_| PackFetchError::InvalidText)
+	}
+
+	/// # Description
+	/// Same as [ManifestMetadata::to_directory], but with its own [http::HttpConfig] instead of the one set with [http::configure], like a token for a private pack or a shorter timeout.
+	/// Returns why it failed instead of [None].
+	#[cfg(feature = "net")]
+	pub async fn to_directory_with(&self, config: &http::HttpConfig) -> Result<Directory, ManifestError> {
+		crate::NoRuntime::check()?;
+		let http = http::Http::new(config.clone()).map_err(ManifestError::Request)?;
+		fetch::manifest_with(&http, self.resolve(&self.manifest_url)?, Some(&self.manifest_sha)).await
+	}
+
+	/// The manifest with the client of an upgrade with `options`, the shared one unless they have their own [crate::UpgradeOptions::http].
+	#[cfg(feature = "fs")]
+	async fn directory_for(&self, options: &crate::UpgradeOptions) -> Result<Directory, PackError> {
+		crate::NoRuntime::check().map_err(|error| PackError::Manifest(error.into()))?;
+		let http = crate::upgrade::http_for(options).map_err(|error| PackError::Upgrade(crate::UpgradeError::Client(error)))?;
+		fetch::manifest_with(&http, self.resolve(&self.manifest_url).map_err(PackError::Manifest)?, Some(&self.manifest_sha)).await.map_err(PackError::Manifest)
+	}
+
+	/// # Description
+	/// Fetches and checks the manifest, then compares `path` to it like [Directory::verify_folder], for telling whether an installed pack is up to date.
+	/// A folder that doesn't exist yet isn't an error, the report has [crate::VerificationReport::not_installed] set.
+	#[cfg(feature = "fs")]
+	pub async fn verify_local(&self, path: &std::path::Path, options: &crate::UpgradeOptions, progress: impl Into<crate::Progress>) -> Result<crate::VerificationReport, PackError> {
+		let directory = self.directory_for(options).await?;
+		Ok(directory.verify_folder(path, options, progress).await)
+	}
+
+	/// # Description
+	/// Fetches and checks the manifest, then upgrades `path` to it like [Directory::upgrade_game_folder_with_options].
+	/// Call [crate::UpgradeOptions::require_notice] with this pack first for packs that have a notice to accept.
+	/// # Warning
+	/// It's up to you to get the minecraft folder right, this function deletes stuff so make sure to add some checks so users can't footgun themselves.
+	#[cfg(feature = "fs")]
+	pub async fn upgrade_local(&self, path: &std::path::Path, options: &crate::UpgradeOptions, progress: impl Into<crate::Progress>) -> Result<crate::UpgradeReport, PackError> {
+		let directory = self.directory_for(options).await?;
+		directory.upgrade_game_folder_with_options(path, options, progress).await.map_err(PackError::Upgrade)
+	}
+
+	/// # Description
+	/// Same as [ManifestMetadata::to_directory], but the manifest url goes through `rewrite_url` first, like the downloads of an upgrade with [crate::UpgradeOptions::rewrite_url].
+	#[cfg(feature = "net")]
+	pub async fn to_directory_rewritten(&self, rewrite_url: &crate::RewriteUrl) -> Option<Directory> {
+		crate::NoRuntime::check().ok()?;
+		fetch::manifest(rewrite_url.rewrite(&self.resolve(&self.manifest_url).ok()?), Some(&self.manifest_sha)).await.ok()
 	}
 }
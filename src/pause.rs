@@ -0,0 +1,139 @@
+use std::future::Future;
+use std::pin::pin;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::watch;
+use crate::{PauseControl, Progress, UpgradeStatus};
+
+/// How the downloads of an upgrade see its [PauseControl] and whether it stopped, every download has its own clone.
+#[derive(Clone)]
+pub(crate) struct Pause {
+	paused: Option<watch::Receiver<bool>>,
+	/// Turns true when the upgrade stops waiting for its downloads, like when its [crate::UpgradeOptions::deadline] runs out, and closes once it's dropped.
+	/// Either way paused downloads give up instead of waiting forever, and receiving ones stop.
+	stopped: watch::Receiver<bool>
+}
+
+/// Held by whatever awaits the downloads, stopping or dropping it lets their [Pause] know nothing is waiting for them anymore.
+pub(crate) struct Running(watch::Sender<bool>);
+
+impl Running {
+	pub(crate) fn stop(&self) {
+		self.0.send_replace(true);
+	}
+}
+
+/// Also reports [UpgradeStatus::Paused] and [UpgradeStatus::Resumed] to `progress` until the [Running] is dropped.
+pub(crate) fn new(control: Option<&PauseControl>, progress: &Progress) -> (Pause, Running) {
+	let (sender, stopped) = watch::channel(false);
+	let pause = Pause {
+		paused: control.map(|control| control.0.subscribe()),
+		stopped
+	};
+	if pause.paused.is_some() {
+		tokio::spawn(pause.clone().report(progress.clone()));
+	}
+	(pause, Running(sender))
+}
+
+/// Polls `future` until it's done, [None] when `first` finished before it.
+pub(crate) async fn unless<F: Future>(future: F, first: impl Future) -> Option<F::Output> {
+	let (mut future, mut first) = (pin!(future), pin!(first));
+	std::future::poll_fn(|cx| {
+		if first.as_mut().poll(cx).is_ready() {
+			return Poll::Ready(None);
+		}
+		future.as_mut().poll(cx).map(Some)
+	}).await
+}
+
+/// Resolves once `paused` is `to`, never when its [PauseControl] is dropped first.
+async fn turns(paused: &mut watch::Receiver<bool>, to: bool) {
+	if paused.wait_for(|paused| *paused == to).await.is_err() {
+		std::future::pending::<()>().await;
+	}
+}
+
+/// Resolves once the upgrade stopped or was dropped.
+async fn stops(stopped: &mut watch::Receiver<bool>) {
+	let _ = stopped.wait_for(|stopped| *stopped).await;
+}
+
+impl Pause {
+	/// Waits while the upgrade is paused, false when it stopped before it was resumed.
+	/// A [PauseControl] that's dropped while paused counts as resumed, nothing could resume it anymore.
+	pub(crate) async fn resumed(&mut self) -> bool {
+		let (paused, stopped) = (&mut self.paused, &mut self.stopped);
+		let resumed = async {
+			if let Some(paused) = paused {
+				let _ = paused.wait_for(|paused| !paused).await;
+			}
+		};
+		unless(resumed, stops(stopped)).await.is_some() && !*self.stopped.borrow()
+	}
+
+	/// Polls `future` unless the upgrade is paused or stops first, for receiving a body that has to stop when it is.
+	pub(crate) async fn unless_paused<F: Future>(&mut self, future: F) -> Option<F::Output> {
+		let (paused, stopped) = (&mut self.paused, &mut self.stopped);
+		let paused = async {
+			match paused {
+				Some(paused) => turns(paused, true).await,
+				None => std::future::pending().await
+			}
+		};
+		unless(future, unless(paused, stops(stopped))).await
+	}
+
+	/// Resolves once the upgrade ran for `limit`, never without one. With `excluding_pauses` the time it's paused doesn't count.
+	pub(crate) fn deadline(&self, limit: Option<Duration>, excluding_pauses: bool) -> impl Future<Output = ()> {
+		let mut paused = self.paused.clone().filter(|_| excluding_pauses);
+		async move {
+			let Some(mut remaining) = limit else {
+				return std::future::pending().await;
+			};
+			let Some(paused) = &mut paused else {
+				return tokio::time::sleep(remaining).await;
+			};
+			loop {
+				let _ = paused.wait_for(|paused| !paused).await;
+				let started = tokio::time::Instant::now();
+				if unless(tokio::time::sleep(remaining), turns(paused, true)).await.is_some() {
+					return;
+				}
+				remaining = remaining.saturating_sub(started.elapsed());
+			}
+		}
+	}
+
+	async fn report(mut self, progress: Progress) {
+		let Some(mut paused) = self.paused.take() else {
+			return;
+		};
+		let mut reported = false;
+		loop {
+			let now = *paused.borrow_and_update();
+			if now != reported {
+				reported = now;
+				progress.emit(match now {
+					true => UpgradeStatus::Paused,
+					false => UpgradeStatus::Resumed
+				}).await;
+			}
+
+			// stopping doesn't end the events yet, the downloads may still be finishing
+			let dropped = async {
+				while self.stopped.changed().await.is_ok() {}
+			};
+			match unless(paused.changed(), dropped).await {
+				Some(Ok(())) => {},
+				Some(Err(_)) => {
+					if reported {
+						progress.emit(UpgradeStatus::Resumed).await;
+					}
+					return;
+				},
+				None => return
+			}
+		}
+	}
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::hash::Sha256Hash;
+use crate::{download, upgrade, Directory, File, ObjectCache, PrefetchReport, Progress, UpgradeError, UpgradeOptions, UpgradeStatus};
+
+/// Downloads every blob of `directory` that `cache` doesn't have yet, each blob once no matter how many files share it.
+pub(crate) async fn prefetch(directory: &Directory, cache: &ObjectCache, options: &UpgradeOptions, progress: Progress) -> Result<PrefetchReport, UpgradeError> {
+	let http = upgrade::http_for(options).map_err(UpgradeError::Client)?;
+	let directory = &*upgrade::prepared(directory, &http, options.separators).await?;
+	let mut blobs = HashMap::new();
+	collect(directory, None, Path::new(""), options, &mut blobs);
+
+	// like in an upgrade, keeps a gc of the cache from running at the same time
+	let _lock = {
+		let cache = cache.clone();
+		tokio::task::spawn_blocking(move || crate::cache::lock(&cache, false)).await.unwrap().ok()
+	};
+
+	let objects = cache.path.join("objects");
+	tokio::fs::create_dir_all(&objects).await.map_err(|error| UpgradeError::Local {
+		path: objects,
+		error
+	})?;
+
+	let budget = Arc::new(download::Budget::new(options.buffer_budget.unwrap_or(crate::DEFAULT_BUFFER_BUDGET)));
+	let limits = Arc::new(download::Limits::new(&options.limits));
+	let metrics = Arc::new(crate::metrics::Recorder::new());
+	let limiter = options.download_limiter.clone().unwrap_or_else(|| crate::DownloadLimiter::new(crate::DEFAULT_CONCURRENT_DOWNLOADS));
+	let connectivity = Arc::new(download::Connectivity::default());
+	let locks = Arc::new(crate::in_use::Locks::new(options.lock_retries));
+	let (pause, _running) = crate::pause::new(options.pause.as_ref(), &progress);
+
+	let mut report = PrefetchReport::default();
+	let mut handles = vec![];
+	for (sha, (relative, file)) in blobs {
+		if let Some(object) = crate::cache::hit(cache, sha, file.size).await {
+			report.cached += 1;
+			report.cached_bytes += tokio::fs::metadata(object).await.map_or(0, |metadata| metadata.len());
+			progress.emit(UpgradeStatus::Skipped(relative)).await;
+			continue;
+		}
+
+		let local_path = crate::cache::object_path(cache, &sha);
+		if let Err(error) = tokio::fs::create_dir_all(local_path.parent().unwrap()).await {
+			return Err(UpgradeError::Local {
+				path: local_path,
+				error
+			});
+		}
+
+		// written straight to where the cache keeps it, nothing else can see it before it's verified and renamed into place
+		let headers = download::file_headers(&file);
+		let download = download::Download {
+			http: http.clone(),
+			progress: progress.clone(),
+			relative: relative.clone(),
+			url: file.url,
+			content_b64: None,
+			sha,
+			size: file.size,
+			mtime: None,
+			local_path,
+			hash_cache: None,
+			budget: budget.clone(),
+			rewrite_url: options.rewrite_url.clone(),
+			refresh_url: options.refresh_url.clone(),
+			headers,
+			query: file.query,
+			chunks: file.chunks,
+			limits: limits.clone(),
+			stall_timeout: options.stall_timeout,
+			// unverified content would be handed to every folder that uses the cache
+			skip_verification: false,
+			metrics: metrics.clone(),
+			limiter: limiter.clone(),
+			cache: None,
+			source: crate::FileSource::Network,
+			redirects: Default::default(),
+			failure: Default::default(),
+			connectivity: connectivity.clone(),
+			locks: locks.clone(),
+			pause: pause.clone()
+		};
+		handles.push((relative, file.size, tokio::spawn(download.run())));
+	}
+	progress.emit(UpgradeStatus::Length(handles.len())).await;
+
+	for (relative, size, handle) in handles {
+		match handle.await.unwrap() {
+			Ok(()) => {
+				report.cached += 1;
+				report.downloaded += 1;
+				report.cached_bytes += size.unwrap_or_default();
+			},
+			Err(_) => {
+				report.missing_bytes += size.unwrap_or_default();
+				report.missing.push(relative);
+			}
+		}
+	}
+
+	report.missing.sort();
+	report.metrics = metrics.finish(std::time::Duration::ZERO);
+	Ok(report)
+}
+
+/// Adds the files of `directory` that are downloaded from a url, keyed by sha so shared blobs are only fetched once, sorted paths make the one that's reported the same every time.
+fn collect(directory: &Directory, url_template: Option<&str>, relative: &Path, options: &UpgradeOptions, blobs: &mut HashMap<Sha256Hash, (PathBuf, File)>) {
+	let url_template = directory.url_template.as_deref().or(url_template);
+	for (name, file) in &directory.files {
+		let relative = relative.join(name);
+		// inline files are in the manifest already
+		if file.content_b64.is_some() || !file.side.includes(&options.side) || !file.applies_here() || options.filter.as_ref().is_some_and(|filter| !filter.includes(&relative, file)) {
+			continue;
+		}
+
+		let file = upgrade::to_fetch(file, url_template);
+		match blobs.get(&file.sha) {
+			Some((existing, _)) if *existing <= relative => {},
+			_ => {
+				blobs.insert(file.sha, (relative, file));
+			}
+		}
+	}
+
+	for (name, child) in directory.children.iter().filter(|(_, child)| child.side.includes(&options.side)) {
+		collect(child, url_template, &relative.join(name), options, blobs);
+	}
+}
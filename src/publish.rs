@@ -0,0 +1,262 @@
+//! Lays a local folder out for a static web host, with its manifest and optionally a packs list next to it.
+//!
+//! [publish_to_dir] writes this under the output folder, which `base_url` is the url of:
+//! - `manifest.json`, the [Directory::to_string_canonical] of the manifest
+//! - `files/<path>` for [Layout::Mirrored] or `blobs/<first two characters of the sha>/<sha>` for [Layout::ContentAddressed]
+//! - `packs.json` with [PublishOptions::pack]
+//!
+//! Publishing again only writes the files that changed, [Layout::Mirrored] remembers what it wrote in `.green-lib` like an upgraded folder does.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::executor::Executor;
+use crate::hash::Sha256Hash;
+use crate::hash_cache::{self, HashCache};
+use crate::packs::{ManifestMetadata, PacksListBuilder, PacksListManifest};
+use crate::{manifest, util, Directory, Excluded, File, FromPathOptions};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const PACKS_LIST_NAME: &str = "packs.json";
+const FILES_DIR: &str = "files";
+const BLOBS_DIR: &str = "blobs";
+
+/// Where the files of a published pack go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+	/// At their own path under `files`, every file has a url. Files that aren't in the pack anymore are deleted.
+	#[default]
+	Mirrored,
+	/// Under `blobs` by sha, the manifest gets a [Directory::url_template] instead of urls. Files with the same contents are stored once and old ones are kept, so clients still upgrading from an older manifest don't break.
+	ContentAddressed
+}
+
+/// A pack to add to (or update in) the `packs.json` of the output folder.
+#[derive(Debug, Clone)]
+pub struct PublishedPack {
+	pub key: String,
+	pub display_name: String
+}
+
+/// How [publish_to_dir] builds the manifest, see [FromPathOptions] for the fields it shares.
+#[derive(Debug, Clone, Default)]
+pub struct PublishOptions {
+	pub inline_below: Option<u64>,
+	pub record_mtimes: bool,
+	pub chunks_above: Option<u64>,
+	pub chunk_size: Option<u64>,
+	pub subtree_hashes: bool,
+	pub exclude: Vec<String>,
+	/// Also write a `packs.json` with this pack, the other packs and fields of one that's already there are kept.
+	pub pack: Option<PublishedPack>
+}
+
+/// What [publish_to_dir] did, paths are relative to the output folder and sorted.
+#[derive(Debug, Clone)]
+pub struct PublishReport {
+	pub manifest: Directory,
+	/// The sha of `manifest.json`, what a [ManifestMetadata] pointing to it needs.
+	pub manifest_sha: Sha256Hash,
+	pub manifest_url: String,
+	/// Files that were written because they were new or changed.
+	pub written: Vec<PathBuf>,
+	/// How many of `written` were reflinked or hardlinked instead of copied.
+	pub linked: usize,
+	/// Files that were already published and left alone.
+	pub skipped: usize,
+	/// Files of an earlier [Layout::Mirrored] publish that aren't in the pack anymore.
+	pub removed: Vec<PathBuf>,
+	/// What the [crate::IGNORE_FILE] of the source and [PublishOptions::exclude] left out of the pack, relative to the source.
+	pub excluded: Vec<Excluded>
+}
+
+/// Braces in the base url are literal in a [Directory::url_template].
+fn escape_template(url: &str) -> String {
+	url.replace('{', "{{").replace('}', "}}")
+}
+
+/// The files of `directory` that are downloaded rather than inlined, by relative path.
+fn downloaded(directory: &Directory) -> Vec<(PathBuf, &File)> {
+	let mut files = vec![];
+	let mut stack = vec![(directory, PathBuf::new())];
+	while let Some((directory, relative)) = stack.pop() {
+		files.extend(directory.files.iter().filter(|(_, file)| file.content_b64.is_none()).map(|(name, file)| (relative.join(name), file)));
+		stack.extend(directory.children.iter().map(|(name, child)| (child, relative.join(name))));
+	}
+	files.sort_by(|(a, _), (b, _)| a.cmp(b));
+	files
+}
+
+/// Puts `source` at `destination` through a temporary file, linking it when the filesystem can, returns whether it was linked.
+/// A hardlinked file changes along with the source when that's written to in place, the next publish writes it again.
+fn place(source: &Path, destination: &Path) -> io::Result<bool> {
+	std::fs::create_dir_all(destination.parent().unwrap())?;
+	let temp_path = manifest::temp_path(destination)?;
+	let _ = std::fs::remove_file(&temp_path);
+
+	let linked = reflink_copy::reflink(source, &temp_path).is_ok() || std::fs::hard_link(source, &temp_path).is_ok();
+	if !linked {
+		std::fs::copy(source, &temp_path)?;
+	}
+	let renamed = std::fs::rename(&temp_path, destination);
+	// renaming a hardlink onto the same file does nothing, which leaves the temporary file behind
+	let _ = std::fs::remove_file(&temp_path);
+	renamed.map(|_| linked)
+}
+
+/// Deletes the files under `root` that aren't in `keep`, and the folders that are empty after that, returning what was deleted.
+fn remove_stale(root: &Path, relative: &Path, keep: &BTreeSet<PathBuf>, removed: &mut Vec<PathBuf>) -> io::Result<()> {
+	let entries = match std::fs::read_dir(root.join(relative)) {
+		Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+		entries => entries?
+	};
+
+	for entry in entries {
+		let entry = entry?;
+		let relative = relative.join(entry.file_name());
+		match entry.file_type()?.is_dir() {
+			true => {
+				remove_stale(root, &relative, keep, removed)?;
+				if std::fs::read_dir(entry.path())?.next().is_none() {
+					std::fs::remove_dir(entry.path())?;
+				}
+			},
+			false if !keep.contains(&relative) => {
+				std::fs::remove_file(entry.path())?;
+				removed.push(relative);
+			},
+			false => ()
+		}
+	}
+	Ok(())
+}
+
+/// # Description
+/// Builds a manifest of `source` with [Directory::from_path] and writes everything a static web host needs to serve it from `base_url` to `out`, see the [module docs](self).
+/// `out` can't be inside `source`.
+pub async fn publish_to_dir(source: &Path, out: &Path, base_url: &str, layout: Layout, options: &PublishOptions) -> io::Result<PublishReport> {
+	crate::NoRuntime::check()?;
+	tokio::fs::create_dir_all(out).await?;
+	if tokio::fs::canonicalize(out).await?.starts_with(tokio::fs::canonicalize(source).await?) {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "the output folder can't be inside the published folder"));
+	}
+
+	let base_url = base_url.trim_end_matches('/');
+	let from_path_options = FromPathOptions {
+		base_url: util::join_url(base_url, FILES_DIR),
+		inline_below: options.inline_below,
+		url_template: (layout == Layout::ContentAddressed).then(|| format!("{}/{BLOBS_DIR}/{{sha:..2}}/{{sha}}", escape_template(base_url))),
+		record_mtimes: options.record_mtimes,
+		chunks_above: options.chunks_above,
+		chunk_size: options.chunk_size,
+		subtree_hashes: options.subtree_hashes,
+		exclude: options.exclude.clone(),
+		..Default::default()
+	};
+	let (directory, from_path_report) = Directory::from_path_with_report(source, &from_path_options).await?;
+
+	let mut cache = HashCache::load(out, &Executor::Tokio).await;
+	let (mut written, mut linked, mut skipped, mut removed) = (vec![], 0, 0, vec![]);
+	let mut published = BTreeSet::new();
+	for (relative, file) in downloaded(&directory) {
+		let destination = match layout {
+			Layout::Mirrored => Path::new(FILES_DIR).join(&relative),
+			Layout::ContentAddressed => {
+				let sha = file.sha.to_string();
+				Path::new(BLOBS_DIR).join(&sha[..2]).join(sha)
+			}
+		};
+		if !published.insert(destination.clone()) {
+			continue;
+		}
+
+		let key = hash_cache::key(&destination);
+		let up_to_date = match tokio::fs::metadata(out.join(&destination)).await {
+			Ok(metadata) => match layout {
+				Layout::Mirrored => cache.get(&key, &metadata) == Some(file.sha),
+				// the name is the sha
+				Layout::ContentAddressed => file.size.is_none_or(|size| size == metadata.len())
+			},
+			Err(_) => false
+		};
+		if up_to_date {
+			skipped += 1;
+			continue;
+		}
+
+		let (source_path, destination_path) = (source.join(&relative), out.join(&destination));
+		if tokio::task::spawn_blocking(move || place(&source_path, &destination_path)).await.unwrap()? {
+			linked += 1;
+		}
+		if layout == Layout::Mirrored {
+			cache.insert(key, &tokio::fs::metadata(out.join(&destination)).await?, file.sha);
+		}
+		written.push(destination);
+	}
+
+	if layout == Layout::Mirrored {
+		let (out_path, keep) = (out.to_owned(), published.iter().filter_map(|path| path.strip_prefix(FILES_DIR).ok().map(Path::to_owned)).collect());
+		removed = tokio::task::spawn_blocking(move || {
+			let mut removed = vec![];
+			remove_stale(&out_path.join(FILES_DIR), Path::new(""), &keep, &mut removed).map(|_| removed)
+		}).await.unwrap()?.into_iter().map(|path| Path::new(FILES_DIR).join(path)).collect();
+		removed.sort();
+
+		for path in &removed {
+			cache.remove(&hash_cache::key(path));
+		}
+		cache.save(out, &Executor::Tokio).await?;
+	}
+
+	// written the way to_string_canonical serializes, so the sha matches what's served
+	let manifest_url = util::join_url(base_url, MANIFEST_NAME);
+	let manifest_sha = Sha256Hash::digest(directory.to_string_canonical()?);
+	directory.to_file(&out.join(MANIFEST_NAME), false).await?;
+	if let Some(pack) = &options.pack {
+		write_packs_list(out, pack, &manifest_url, &directory).await?;
+	}
+
+	written.sort();
+	Ok(PublishReport {
+		manifest: directory,
+		manifest_sha,
+		manifest_url,
+		written,
+		linked,
+		skipped,
+		removed,
+		excluded: from_path_report.excluded
+	})
+}
+
+/// Adds `pack` to the `packs.json` of `out`, keeping what's already in the list.
+async fn write_packs_list(out: &Path, pack: &PublishedPack, manifest_url: &str, directory: &Directory) -> io::Result<()> {
+	let path = out.join(PACKS_LIST_NAME);
+	let mut list = match tokio::fs::try_exists(&path).await? {
+		true => PacksListManifest::from_file(&path).await.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?,
+		false => PacksListManifest::default()
+	};
+
+	let built = PacksListBuilder::new()
+		.add_pack(&pack.key, &pack.display_name, manifest_url, directory)
+		.build()
+		.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+	let new = built.packs.into_values().next().unwrap();
+	let metadata = match list.packs.remove(&pack.key) {
+		Some(existing) => ManifestMetadata {
+			display_name: new.display_name,
+			manifest_url: new.manifest_url,
+			manifest_sha: new.manifest_sha,
+			content_hash: new.content_hash,
+			size_bytes: new.size_bytes,
+			file_count: new.file_count,
+			..existing
+		},
+		None => new
+	};
+	list.packs.insert(pack.key.clone(), metadata);
+	if list.featured_pack.is_none() && list.featured.is_empty() {
+		list.featured_pack = Some(pack.key.clone());
+	}
+	list.to_file(&path, true).await
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use crate::{fetch, http, Directory, DirectoryRef, ManifestError};
+
+/// A reference that's waiting to be resolved, by the keys of the children leading to it.
+struct Pending {
+	keys: Vec<String>,
+	reference: DirectoryRef
+}
+
+/// The unresolved references below `directory`, whose keys start with `keys`.
+fn collect(directory: &Directory, keys: &[String], out: &mut Vec<Pending>) {
+	for (name, child) in &directory.children {
+		let mut child_keys = keys.to_vec();
+		child_keys.push(name.clone());
+		match &child.reference {
+			Some(reference) => out.push(Pending {
+				keys: child_keys,
+				reference: reference.clone()
+			}),
+			None => collect(child, &child_keys, out)
+		}
+	}
+}
+
+fn child_mut<'a>(directory: &'a mut Directory, keys: &[String]) -> &'a mut Directory {
+	keys.iter().fold(directory, |directory, key| directory.children.get_mut(key).unwrap())
+}
+
+/// Puts the sub-manifest of every reference in its place, one level of references at a time up to `max_depth` levels, deeper ones are left.
+/// Sub-manifests are fetched once per sha, the ones in `cache` aren't fetched at all.
+pub(crate) async fn resolve(directory: &mut Directory, http: &http::Http, max_depth: usize, cache: &mut HashMap<String, Directory>) -> Result<(), ManifestError> {
+	let mut pending = vec![];
+	collect(directory, &[], &mut pending);
+
+	for _ in 0..max_depth {
+		if pending.is_empty() {
+			break;
+		}
+
+		let mut next = vec![];
+		for Pending { keys, reference } in pending {
+			let sub_manifest = match cache.get(&reference.sha) {
+				Some(sub_manifest) => sub_manifest.clone(),
+				None => {
+					let sub_manifest: Directory = fetch::manifest_with(http, &reference.url, Some(&reference.sha)).await.map_err(|error| ManifestError::Ref {
+						path: keys.iter().collect(),
+						error: Box::new(error)
+					})?;
+					cache.insert(reference.sha.clone(), sub_manifest.clone());
+					sub_manifest
+				}
+			};
+
+			let child = child_mut(directory, &keys);
+			// the side of the referencing child still applies to everything in it
+			let side = std::mem::take(&mut child.side);
+			*child = Directory {
+				side,
+				// only read from the root
+				min_green_lib: None,
+				..sub_manifest
+			};
+			collect(child, &keys, &mut next);
+		}
+		pending = next;
+	}
+	Ok(())
+}
+
+/// `directory` with every reference resolved, failing when they nest deeper than [crate::DEFAULT_MAX_REF_DEPTH], it's only copied when it has references.
+#[cfg(feature = "fs")]
+pub(crate) async fn resolved<'a>(directory: &'a Directory, http: &http::Http) -> Result<std::borrow::Cow<'a, Directory>, crate::UpgradeError> {
+	if directory.unresolved_refs().is_empty() {
+		return Ok(std::borrow::Cow::Borrowed(directory));
+	}
+
+	let mut resolved = directory.clone();
+	resolve(&mut resolved, http, crate::DEFAULT_MAX_REF_DEPTH, &mut HashMap::new()).await.map_err(crate::UpgradeError::Ref)?;
+	match resolved.unresolved_refs().first() {
+		Some(path) => Err(crate::UpgradeError::Ref(ManifestError::RefDepth {
+			path: std::path::PathBuf::from(path),
+			max_depth: crate::DEFAULT_MAX_REF_DEPTH
+		})),
+		None => Ok(std::borrow::Cow::Owned(resolved))
+	}
+}
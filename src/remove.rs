@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+use crate::hash_cache;
+use crate::owners::Owners;
+use crate::upgrade::LocalHasher;
+use crate::{Directory, Progress, RemoveOptions, RemoveReport, UpgradeError, UpgradeOptions, UpgradeStatus, UpgradeWarning};
+
+struct Remover<'a> {
+	options: &'a RemoveOptions,
+	hasher: LocalHasher,
+	owners: Owners,
+	progress: Progress,
+	report: RemoveReport,
+	/// A file that couldn't be removed because something has it open.
+	in_use: Option<PathBuf>
+}
+
+/// Removes what `directory` describes from `path`, children first so directories can be removed once they're empty.
+pub(crate) async fn remove(directory: &Directory, path: &Path, options: &RemoveOptions, progress: Progress) -> Result<RemoveReport, UpgradeError> {
+	let mut report = remove_entries(directory, path, options, progress).await?;
+	let mut owners = Owners::load(path).await;
+	if let (Some(namespace), false) = (&options.namespace, report.dry_run) {
+		owners.release(namespace);
+		if let Err(error) = owners.save(path).await {
+			report.warnings.push(UpgradeWarning {
+				path: PathBuf::from(hash_cache::STATE_DIR),
+				error
+			});
+		}
+	}
+
+	// the hash cache and whatever else green-lib keeps only matter while files of the pack, or of other namespaces, are left
+	if !report.dry_run && owners.is_empty() && report.modified.is_empty() && report.filtered.is_empty() && report.warnings.is_empty() {
+		match tokio::fs::remove_dir_all(path.join(hash_cache::STATE_DIR)).await {
+			Err(error) if error.kind() != std::io::ErrorKind::NotFound => report.warnings.push(UpgradeWarning {
+				path: PathBuf::from(hash_cache::STATE_DIR),
+				error
+			}),
+			_ => {}
+		}
+	}
+	Ok(report)
+}
+
+/// Like [remove] but leaves green-lib's own state alone, for removing only part of what's installed.
+pub(crate) async fn remove_entries(directory: &Directory, path: &Path, options: &RemoveOptions, progress: Progress) -> Result<RemoveReport, UpgradeError> {
+	if !options.ignore_in_use {
+		if let Some(path) = crate::in_use::probe(path).await {
+			return Err(UpgradeError::InUse { path });
+		}
+	}
+	let directory = &*crate::normalize::separated(directory, options.separators).map_err(UpgradeError::Normalize)?;
+
+	let hasher_options = UpgradeOptions {
+		hash_cache: true,
+		..Default::default()
+	};
+	let mut remover = Remover {
+		options,
+		hasher: LocalHasher::new(path, &hasher_options).await.0,
+		owners: Owners::load(path).await,
+		progress,
+		report: RemoveReport {
+			dry_run: options.dry_run,
+			..Default::default()
+		},
+		in_use: None
+	};
+	remover.folder(directory, path, Path::new("")).await;
+
+	if let Some(path) = remover.in_use {
+		return Err(UpgradeError::InUse { path });
+	}
+
+	let mut report = remover.report;
+	report.removed.sort();
+	report.removed_directories.sort();
+	report.modified.sort();
+	report.filtered.sort();
+	report.missing.sort();
+	report.shared.sort();
+	Ok(report)
+}
+
+impl Remover<'_> {
+	async fn warn(&mut self, path: PathBuf, error: std::io::Error) {
+		if crate::in_use::is_in_use(&error) && !self.options.ignore_in_use {
+			self.in_use.get_or_insert(path.clone());
+		}
+
+		self.progress.emit(UpgradeStatus::Warning {
+			path: path.clone(),
+			reason: error.to_string()
+		}).await;
+		self.report.warnings.push(UpgradeWarning {
+			path,
+			error
+		});
+	}
+
+	/// Removes a file or link unless this is a dry run or another namespace owns it, returns whether it's gone (or would be).
+	async fn remove_entry(&mut self, local_path: &Path, relative: PathBuf) -> bool {
+		if self.owners.other_owner(&relative, self.options.namespace.as_deref()).is_some() {
+			self.progress.emit(UpgradeStatus::Kept(relative.clone())).await;
+			self.report.shared.push(relative);
+			return false;
+		}
+
+		let removed = match self.options.dry_run {
+			true => Ok(()),
+			false => match tokio::fs::remove_file(local_path).await {
+				Ok(()) => Ok(()),
+				// directory symlinks on windows
+				Err(error) => tokio::fs::remove_dir(local_path).await.map_err(|_| error)
+			}
+		};
+
+		match removed {
+			Ok(()) => {
+				self.progress.emit(UpgradeStatus::Deleted(relative.clone())).await;
+				self.report.removed.push(relative);
+				true
+			},
+			Err(error) => {
+				self.warn(relative, error).await;
+				false
+			}
+		}
+	}
+
+	/// Returns whether `path` is left empty (or would be) and was removed.
+	#[async_recursion::async_recursion]
+	async fn folder(&mut self, directory: &Directory, path: &Path, relative: &Path) -> bool {
+		let mut removed = 0;
+
+		for (name, file) in &directory.files {
+			let (local_path, relative) = (path.join(name), relative.join(name));
+			if self.options.filter.as_ref().is_some_and(|filter| !filter.includes(&relative, file)) {
+				self.progress.emit(UpgradeStatus::Filtered(relative.clone())).await;
+				self.report.filtered.push(relative);
+				continue;
+			}
+
+			match tokio::fs::symlink_metadata(&local_path).await {
+				Ok(metadata) if metadata.is_file() => {},
+				// something else took the file's place, it isn't ours to remove
+				Ok(_) => {
+					self.progress.emit(UpgradeStatus::Kept(relative.clone())).await;
+					self.report.modified.push(relative);
+					continue;
+				},
+				Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+					self.report.missing.push(relative);
+					continue;
+				},
+				Err(error) => {
+					self.warn(relative, error).await;
+					continue;
+				}
+			}
+
+			if self.options.only_matching {
+				match self.hasher.local_sha(&local_path, &relative).await {
+					Ok(sha) if sha == file.sha => {},
+					Ok(_) => {
+						self.progress.emit(UpgradeStatus::Kept(relative.clone())).await;
+						self.report.modified.push(relative);
+						continue;
+					},
+					Err(error) => {
+						self.warn(relative, error).await;
+						continue;
+					}
+				}
+			}
+
+			removed += self.remove_entry(&local_path, relative).await as usize;
+		}
+
+		for (name, target) in &directory.links {
+			let (local_path, relative) = (path.join(name), relative.join(name));
+			let target: PathBuf = target.split('/').filter(|component| !component.is_empty() && *component != ".").collect();
+			match tokio::fs::read_link(&local_path).await {
+				Ok(existing) if !self.options.only_matching || existing.components().eq(target.components()) => {
+					removed += self.remove_entry(&local_path, relative).await as usize;
+				},
+				Ok(_) => {
+					self.progress.emit(UpgradeStatus::Kept(relative.clone())).await;
+					self.report.modified.push(relative);
+				},
+				Err(_) if tokio::fs::symlink_metadata(&local_path).await.is_err() => self.report.missing.push(relative),
+				// not a link anymore
+				Err(_) => {
+					self.progress.emit(UpgradeStatus::Kept(relative.clone())).await;
+					self.report.modified.push(relative);
+				}
+			}
+		}
+
+		for (name, child) in &directory.children {
+			let local_path = path.join(name);
+			// a link in place of a directory is left alone, whatever it points to isn't part of the pack
+			if tokio::fs::symlink_metadata(&local_path).await.is_ok_and(|metadata| metadata.is_dir()) {
+				removed += self.folder(child, &local_path, &relative.join(name)).await as usize;
+			}
+		}
+
+		// the root is the folder that was given, it's never removed
+		if relative.as_os_str().is_empty() {
+			return false;
+		}
+
+		let left = match tokio::fs::read_dir(path).await {
+			Ok(mut entries) => {
+				let mut left = 0;
+				while let Ok(Some(_)) = entries.next_entry().await {
+					left += 1;
+				}
+				left
+			},
+			Err(error) => {
+				self.warn(relative.to_owned(), error).await;
+				return false;
+			}
+		};
+		let empty = match self.options.dry_run {
+			true => left == removed,
+			false => left == 0
+		};
+		if !empty {
+			return false;
+		}
+
+		if !self.options.dry_run {
+			if let Err(error) = tokio::fs::remove_dir(path).await {
+				self.warn(relative.to_owned(), error).await;
+				return false;
+			}
+		}
+		self.progress.emit(UpgradeStatus::Deleted(relative.to_owned())).await;
+		self.report.removed_directories.push(relative.to_owned());
+		true
+	}
+}
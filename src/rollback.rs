@@ -0,0 +1,68 @@
+use std::path::Path;
+use crate::{Deletions, Directory, Progress, RemoveOptions, RollbackOptions, RollbackReport, UpgradeError, UpgradeOptions};
+
+/// Puts `path` back to `previous` after `newer` was applied to it, removing what only `newer` has first so nothing else is deleted.
+pub(crate) async fn rollback(previous: &Directory, newer: &Directory, path: &Path, options: &RollbackOptions, progress: Progress) -> Result<RollbackReport, UpgradeError> {
+	options.upgrade.check_notice()?;
+	let http = crate::upgrade::http_for(&options.upgrade).map_err(UpgradeError::Client)?;
+	let separators = options.upgrade.separators;
+	let (previous, newer) = (&*crate::upgrade::prepared(previous, &http, separators).await?, &*crate::upgrade::prepared(newer, &http, separators).await?);
+	let upgrade_options = UpgradeOptions {
+		deletions: Deletions::Disabled,
+		..options.upgrade.clone()
+	};
+
+	let verification = crate::verify::verify(previous, path, &upgrade_options, Progress::None).await;
+	let mut reverted: Vec<_> = verification.missing.into_iter()
+		.chain(verification.mismatched.into_iter().map(|mismatch| mismatch.path))
+		.chain(verification.wrong_links)
+		.collect();
+	reverted.sort();
+
+	let remove_options = RemoveOptions {
+		only_matching: true,
+		dry_run: options.dry_run,
+		filter: options.upgrade.filter.clone(),
+		ignore_in_use: options.upgrade.ignore_in_use,
+		namespace: options.upgrade.namespace.clone(),
+		separators
+	};
+	let removal = crate::remove::remove_entries(&introduced(newer, Some(previous)), path, &remove_options, progress.clone()).await?;
+
+	let upgrade = match options.dry_run {
+		true => None,
+		false => Some(crate::upgrade::UpgradeState::new(path, &upgrade_options, progress).await?.run(previous, path).await?)
+	};
+
+	Ok(RollbackReport {
+		dry_run: options.dry_run,
+		reverted,
+		removal,
+		upgrade
+	})
+}
+
+/// The entries of `newer` that `previous` doesn't have at the same path with the same kind, children `previous` also has are left out once nothing of theirs is.
+fn introduced(newer: &Directory, previous: Option<&Directory>) -> Directory {
+	let files = newer.files.iter()
+		.filter(|(name, _)| previous.is_none_or(|previous| !previous.files.contains_key(*name)))
+		.map(|(name, file)| (name.clone(), file.clone()))
+		.collect();
+	let links = newer.links.iter()
+		.filter(|(name, _)| previous.is_none_or(|previous| !previous.links.contains_key(*name)))
+		.map(|(name, target)| (name.clone(), target.clone()))
+		.collect();
+	let children = newer.children.iter().filter_map(|(name, child)| {
+		let previous_child = previous.and_then(|previous| previous.children.get(name));
+		let introduced = introduced(child, previous_child);
+		let empty = introduced.files.is_empty() && introduced.links.is_empty() && introduced.children.is_empty();
+		(previous_child.is_none() || !empty).then(|| (name.clone(), introduced))
+	}).collect();
+
+	Directory {
+		files,
+		children,
+		links,
+		..Default::default()
+	}
+}
@@ -0,0 +1,104 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::{Directory, DirectoryStats, EntryStats, LargestFile, LARGEST_FILES};
+
+/// Walks a tree once, paths are only built for files that make it into the largest ones and for the deepest entry.
+struct Walk {
+	largest: BinaryHeap<Reverse<(u64, Reverse<String>)>>,
+	deepest: Option<(usize, String)>
+}
+
+fn join(prefix: &str, name: &str) -> String {
+	match prefix.is_empty() {
+		true => name.to_owned(),
+		false => format!("{prefix}/{name}")
+	}
+}
+
+impl Walk {
+	fn file(&mut self, prefix: &str, name: &str, size: u64) {
+		let smallest = self.largest.peek().map(|Reverse((size, _))| *size);
+		if self.largest.len() == LARGEST_FILES && smallest.is_some_and(|smallest| size < smallest) {
+			return;
+		}
+		self.largest.push(Reverse((size, Reverse(join(prefix, name)))));
+		if self.largest.len() > LARGEST_FILES {
+			self.largest.pop();
+		}
+	}
+
+	/// An entry `depth` components deep, the first path in sort order wins a tie.
+	fn entry(&mut self, prefix: &str, name: &str, depth: usize) {
+		let is_deeper = match &self.deepest {
+			None => true,
+			Some((deepest, _)) if depth != *deepest => depth > *deepest,
+			Some((_, path)) => join(prefix, name) < *path
+		};
+		if is_deeper {
+			self.deepest = Some((depth, join(prefix, name)));
+		}
+	}
+
+	/// The files and links of `directory`, without what's in its children.
+	fn leaves(&mut self, directory: &Directory, prefix: &str, depth: usize, stats: &mut EntryStats) {
+		for (name, file) in &directory.files {
+			stats.files += 1;
+			match file.size {
+				Some(size) => {
+					stats.size += size;
+					self.file(prefix, name, size);
+				},
+				None => stats.unsized_files += 1
+			}
+			self.entry(prefix, name, depth);
+		}
+		for name in directory.links.keys() {
+			stats.links += 1;
+			self.entry(prefix, name, depth);
+		}
+	}
+
+	fn directory(&mut self, directory: &Directory, prefix: &str, depth: usize, stats: &mut EntryStats) {
+		self.leaves(directory, prefix, depth, stats);
+		for (name, child) in &directory.children {
+			match child.reference {
+				Some(_) => stats.references += 1,
+				None => stats.directories += 1
+			}
+			self.entry(prefix, name, depth);
+			self.directory(child, &join(prefix, name), depth + 1, stats);
+		}
+	}
+}
+
+/// See [Directory::stats].
+pub(crate) fn stats(directory: &Directory) -> DirectoryStats {
+	let mut walk = Walk {
+		largest: BinaryHeap::new(),
+		deepest: None
+	};
+	let mut stats = DirectoryStats::default();
+
+	walk.leaves(directory, "", 1, &mut stats.total);
+	for (name, child) in &directory.children {
+		let mut child_stats = EntryStats::default();
+		walk.entry("", name, 1);
+		walk.directory(child, name, 2, &mut child_stats);
+
+		match child.reference {
+			Some(_) => stats.total.references += 1,
+			None => stats.total.directories += 1
+		}
+		stats.total.add(&child_stats);
+		stats.children.insert(name.clone(), child_stats);
+	}
+
+	stats.largest = walk.largest.into_sorted_vec().into_iter()
+		.map(|Reverse((size, Reverse(path)))| LargestFile {
+			path,
+			size
+		})
+		.collect();
+	(stats.depth, stats.deepest) = walk.deepest.map_or((0, None), |(depth, path)| (depth, Some(path)));
+	stats
+}
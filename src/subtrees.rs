@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::hash::Sha256Hash;
+use crate::hash_cache::{self, key};
+use crate::{Directory, Side, UpgradeOptions};
+
+const FILE_NAME: &str = "subtrees.json";
+
+/// The [Directory::subtree_hash] of every child the last upgrade applied in full, by `/` separated relative path.
+#[derive(Default, Serialize, Deserialize)]
+struct Applied {
+	side: Side,
+	subtrees: BTreeMap<String, Sha256Hash>
+}
+
+fn path(root: &Path) -> PathBuf {
+	root.join(hash_cache::STATE_DIR).join(FILE_NAME)
+}
+
+/// What an upgrade or verification knows about the subtrees of a folder, so children that didn't change since they were applied aren't scanned.
+/// Only used with [UpgradeOptions::hash_cache], [UpgradeOptions::paranoid] scans everything but still records what was applied.
+pub(crate) struct Subtrees {
+	enabled: bool,
+	trusted: BTreeMap<String, Sha256Hash>,
+	/// What's saved, the subtrees that were skipped with whatever was recorded below them.
+	kept: Mutex<BTreeMap<String, Sha256Hash>>,
+	/// The subtrees that were scanned, saved only when the whole upgrade went through.
+	scanned: Mutex<BTreeMap<String, Sha256Hash>>,
+	side: Side
+}
+
+impl Subtrees {
+	/// Loads what was applied to `root`, a missing or corrupt file or one for another side means nothing is trusted.
+	pub(crate) async fn load(root: &Path, options: &UpgradeOptions) -> Self {
+		let applied = match (options.hash_cache, crate::executor::Executor::of(options).read(&path(root)).await) {
+			(true, Ok(contents)) => serde_json::from_slice(&contents).unwrap_or_default(),
+			_ => Applied::default()
+		};
+		let trusted = match !options.paranoid && applied.side == options.side {
+			true => applied.subtrees,
+			false => BTreeMap::new()
+		};
+
+		Self {
+			enabled: options.hash_cache,
+			trusted,
+			kept: Mutex::default(),
+			scanned: Mutex::default(),
+			side: options.side.clone()
+		}
+	}
+
+	/// Whether the child at `relative` is what was applied there last time, so it doesn't have to be scanned.
+	/// A [Directory::subtree_hash] is only trusted when it matches the contents of the child.
+	pub(crate) fn unchanged(&self, directory: &Directory, relative: &Path) -> bool {
+		let key = key(relative);
+		let unchanged = directory.subtree_hash.is_some_and(|hash| self.trusted.get(&key) == Some(&hash) && directory.content_hash() == hash.0);
+		if unchanged {
+			let prefix = format!("{key}/");
+			let below = self.trusted.range(key.clone()..).take_while(|(path, _)| **path == key || path.starts_with(&prefix));
+			self.kept.lock().unwrap().extend(below.map(|(path, hash)| (path.clone(), *hash)));
+		}
+		unchanged
+	}
+
+	/// The child at `relative` was scanned.
+	pub(crate) fn scanned(&self, directory: &Directory, relative: &Path) {
+		if let Some(hash) = directory.subtree_hash {
+			self.scanned.lock().unwrap().insert(key(relative), hash);
+		}
+	}
+
+	/// Writes what's known to be applied, the scanned subtrees only count when the upgrade was `complete`.
+	/// Like the hash cache, failing to write it is ignored.
+	pub(crate) async fn save(&self, root: &Path, complete: bool) {
+		if !self.enabled {
+			return;
+		}
+
+		let mut subtrees = std::mem::take(&mut *self.kept.lock().unwrap());
+		if complete {
+			subtrees.extend(std::mem::take(&mut *self.scanned.lock().unwrap()));
+		}
+		let applied = Applied {
+			side: self.side.clone(),
+			subtrees
+		};
+
+		let applied_path = path(root);
+		if tokio::fs::create_dir_all(root.join(hash_cache::STATE_DIR)).await.is_err() {
+			return;
+		}
+		let temp_path = applied_path.with_extension("tmp");
+		if tokio::fs::write(&temp_path, serde_json::to_vec(&applied).unwrap()).await.is_ok() {
+			let _ = tokio::fs::rename(temp_path, applied_path).await;
+		}
+	}
+}
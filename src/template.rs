@@ -0,0 +1,63 @@
+use crate::hash::Sha256Hash;
+
+/// Why a url template can't be expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+	pub template: String,
+	pub reason: String
+}
+
+impl std::fmt::Display for TemplateError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "invalid url template {:?}: {}", self.template, self.reason)
+	}
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Expands `{sha}` and `{sha:start..end}` (either bound can be left out) in a url template, `{{` and `}}` are literal braces.
+pub(crate) fn expand(template: &str, sha: &Sha256Hash) -> Result<String, TemplateError> {
+	let error = |reason: &str| TemplateError {
+		template: template.to_owned(),
+		reason: reason.to_owned()
+	};
+
+	let sha = sha.to_string();
+	let mut out = String::with_capacity(template.len() + sha.len());
+	let mut rest = template;
+
+	while let Some(index) = rest.find(['{', '}']) {
+		out.push_str(&rest[..index]);
+		let brace = &rest[index..];
+
+		if let Some(after) = brace.strip_prefix("{{").or_else(|| brace.strip_prefix("}}")) {
+			out.push_str(&brace[..1]);
+			rest = after;
+			continue;
+		}
+
+		let Some(end) = brace.find('}').filter(|_| brace.starts_with('{')) else {
+			return Err(error("unmatched brace"));
+		};
+		let placeholder = &brace[1..end];
+		rest = &brace[end + 1..];
+
+		let range = match placeholder.strip_prefix("sha") {
+			Some("") => 0..sha.len(),
+			Some(range) => {
+				let (start, end) = range.strip_prefix(':').and_then(|range| range.split_once("..")).ok_or_else(|| error("expected {sha:start..end}"))?;
+				let bound = |bound: &str, default| match bound {
+					"" => Ok(default),
+					bound => bound.parse().map_err(|_| error("slice bounds must be numbers"))
+				};
+				bound(start, 0)?..bound(end, sha.len())?
+			},
+			None => return Err(error(&format!("unknown placeholder {{{placeholder}}}")))
+		};
+
+		out.push_str(sha.get(range).ok_or_else(|| error("slice is out of range"))?);
+	}
+
+	out.push_str(rest);
+	Ok(out)
+}
@@ -0,0 +1,467 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::{Directory, File};
+use crate::hash::Sha256Hash;
+use crate::packs::{PacksListBuilder, PacksListManifest};
+
+/// The most bytes of request line and headers the server reads before giving up on a request.
+const MAX_REQUEST_HEAD: usize = 64 << 10;
+
+/// Something [FixtureServer] does instead of just serving a path, see [FixtureServer::fail].
+#[derive(Debug, Clone)]
+pub enum Failure {
+	/// Answer the next `count` requests with `status`, then serve the path normally.
+	Status {
+		status: u16,
+		count: usize
+	},
+	/// Wait this long before answering every request.
+	Slow(Duration),
+	/// Serve these bytes instead of the ones that were inserted.
+	WrongBytes(Bytes),
+	/// Answer the next `count` requests with the headers and the first `after` bytes of the body, then keep the connection open without sending anything else.
+	Stall {
+		after: usize,
+		count: usize
+	},
+	/// Answer the next `count` requests with a `302 Found` to `location`, then serve the path normally.
+	Redirect {
+		location: String,
+		count: usize
+	},
+	/// Send every body with this `Content-Encoding`, whatever the request accepts, like a server that stores files compressed.
+	/// The bytes aren't encoded by the server, insert ones that already are.
+	ContentEncoding(String)
+}
+
+#[derive(Default)]
+struct Fixtures {
+	files: HashMap<String, Bytes>,
+	failures: HashMap<String, Vec<Failure>>,
+	requests: HashMap<String, usize>,
+	/// The headers of the last request for every path, names in lower case.
+	headers: HashMap<String, Vec<(String, String)>>,
+	/// How many `GET` requests are being answered, and the most there were at once, by the host they were sent to and altogether.
+	in_flight: HashMap<String, usize>,
+	most_in_flight: HashMap<String, usize>,
+	total_in_flight: usize,
+	most_total_in_flight: usize,
+	/// How many connections were accepted.
+	connections: usize
+}
+
+/// What a request gets once its failures were applied.
+enum Answer {
+	Status(u16),
+	Body(Bytes),
+	/// The whole body is announced but only the first bytes are sent.
+	Stall(Bytes, usize),
+	Redirect(String),
+	NotFound
+}
+
+impl Fixtures {
+	fn answer(&mut self, path: &str, headers: Vec<(String, String)>) -> (Answer, Duration, Option<String>) {
+		*self.requests.entry(path.to_owned()).or_default() += 1;
+		self.headers.insert(path.to_owned(), headers);
+
+		let mut delay = Duration::ZERO;
+		let mut body = self.files.get(path).cloned();
+		let mut stall = None;
+		let mut encoding = None;
+		for failure in self.failures.get_mut(path).into_iter().flatten() {
+			match failure {
+				Failure::Status { status, count } if *count > 0 => {
+					*count -= 1;
+					return (Answer::Status(*status), delay, None);
+				},
+				Failure::Redirect { location, count } if *count > 0 => {
+					*count -= 1;
+					return (Answer::Redirect(location.clone()), delay, None);
+				},
+				Failure::Stall { after, count } if *count > 0 && stall.is_none() => {
+					*count -= 1;
+					stall = Some(*after);
+				},
+				Failure::Status { .. } | Failure::Stall { .. } | Failure::Redirect { .. } => {},
+				Failure::Slow(duration) => delay += *duration,
+				Failure::WrongBytes(bytes) => body = Some(bytes.clone()),
+				Failure::ContentEncoding(value) => encoding = Some(value.clone())
+			}
+		}
+		let answer = match (body, stall) {
+			(Some(body), Some(after)) => Answer::Stall(body, after),
+			(Some(body), None) => Answer::Body(body),
+			(None, _) => Answer::NotFound
+		};
+		(answer, delay, encoding)
+	}
+}
+
+/// A local HTTP server serving an in-memory map of paths to bytes, for testing code that fetches manifests or upgrades folders.
+/// Paths don't start with `/`, so the url of `mods/a.jar` is `http://127.0.0.1:<port>/mods/a.jar`.
+/// Every body comes with its sha as `ETag`, a request with that `If-None-Match` gets a 304, and a request with a single `Range` gets that part of it with a 206.
+/// Connections are kept alive for more requests like a real server's, until the client closes them or asks for `Connection: close`.
+/// The server stops when it's dropped, connections that are still open included.
+pub struct FixtureServer {
+	address: SocketAddr,
+	fixtures: Arc<Mutex<Fixtures>>,
+	task: tokio::task::JoinHandle<()>
+}
+
+impl FixtureServer {
+	/// # Description
+	/// Starts a server on a free port of 127.0.0.1, it runs on the current tokio runtime.
+	pub async fn start() -> std::io::Result<Self> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let address = listener.local_addr()?;
+		let fixtures = Arc::new(Mutex::new(Fixtures::default()));
+
+		let task = tokio::spawn({
+			let fixtures = fixtures.clone();
+			async move {
+				// aborted along with the accept loop when the server is dropped
+				let mut connections = tokio::task::JoinSet::new();
+				while let Ok((stream, _)) = listener.accept().await {
+					while connections.try_join_next().is_some() {}
+					fixtures.lock().unwrap().connections += 1;
+					let _ = stream.set_nodelay(true);
+					connections.spawn(serve(stream, fixtures.clone()));
+				}
+			}
+		});
+
+		Ok(Self {
+			address,
+			fixtures,
+			task
+		})
+	}
+
+	/// # Description
+	/// The url a path is served at.
+	pub fn url(&self, path: &str) -> String {
+		format!("http://{}/{}", self.address, path.trim_start_matches('/'))
+	}
+
+	/// # Description
+	/// Serves `contents` at `path`, replacing whatever was there.
+	pub fn insert(&self, path: &str, contents: impl Into<Bytes>) {
+		self.fixtures.lock().unwrap().files.insert(path.trim_start_matches('/').to_owned(), contents.into());
+	}
+
+	/// # Description
+	/// Adds a failure to the requests for `path`, failures are applied in the order they were added.
+	pub fn fail(&self, path: &str, failure: Failure) {
+		self.fixtures.lock().unwrap().failures.entry(path.trim_start_matches('/').to_owned()).or_default().push(failure);
+	}
+
+	/// # Description
+	/// Removes every failure of `path`.
+	pub fn clear_failures(&self, path: &str) {
+		self.fixtures.lock().unwrap().failures.remove(path.trim_start_matches('/'));
+	}
+
+	/// # Description
+	/// How many requests for `path` were answered so far, failed ones included.
+	pub fn requests(&self, path: &str) -> usize {
+		self.fixtures.lock().unwrap().requests.get(path.trim_start_matches('/')).copied().unwrap_or_default()
+	}
+
+	/// # Description
+	/// The headers of the last request for `path` with their names in lower case, [None] before it was requested.
+	pub fn request_headers(&self, path: &str) -> Option<Vec<(String, String)>> {
+		self.fixtures.lock().unwrap().headers.get(path.trim_start_matches('/')).cloned()
+	}
+
+	/// # Description
+	/// The most `GET` requests that were being answered at the same time so far, from receiving them until their body was sent.
+	/// `HEAD` requests aren't counted, an upgrade checks whether the network is up with one before its downloads.
+	pub fn most_concurrent_requests(&self) -> usize {
+		self.fixtures.lock().unwrap().most_total_in_flight
+	}
+
+	/// # Description
+	/// Same as [FixtureServer::most_concurrent_requests], for the requests whose `Host` is `host` (without a port), like `localhost` when the server is reached with it instead of `127.0.0.1`.
+	pub fn most_concurrent_requests_to(&self, host: &str) -> usize {
+		self.fixtures.lock().unwrap().most_in_flight.get(&host.to_ascii_lowercase()).copied().unwrap_or_default()
+	}
+
+	/// # Description
+	/// How many connections were accepted so far, a client that keeps them alive opens far fewer than it sends requests.
+	pub fn connections(&self) -> usize {
+		self.fixtures.lock().unwrap().connections
+	}
+
+	/// # Description
+	/// Serves the files of `builder` under `prefix` and its manifest at `<prefix>/manifest.json`, returning the [Directory] that was served.
+	pub fn serve(&self, prefix: &str, builder: &DirectoryBuilder) -> Directory {
+		let prefix = prefix.trim_matches('/');
+		let join = |path: &str| match prefix.is_empty() {
+			true => path.to_owned(),
+			false => format!("{prefix}/{path}")
+		};
+
+		for (path, contents) in builder.blobs() {
+			self.insert(&join(&path), contents);
+		}
+
+		let directory = builder.build(&self.url(prefix));
+		self.insert(&join("manifest.json"), directory.to_string_canonical().unwrap());
+		directory
+	}
+}
+
+impl Drop for FixtureServer {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
+
+/// Counts a `GET` request to a host as being answered until it's dropped.
+struct InFlight(Arc<Mutex<Fixtures>>, String);
+
+impl InFlight {
+	fn new(fixtures: Arc<Mutex<Fixtures>>, host: String) -> Self {
+		let mut locked = fixtures.lock().unwrap();
+		let in_flight = *locked.in_flight.entry(host.clone()).and_modify(|count| *count += 1).or_insert(1);
+		let most = locked.most_in_flight.entry(host.clone()).or_default();
+		*most = (*most).max(in_flight);
+		locked.total_in_flight += 1;
+		locked.most_total_in_flight = locked.most_total_in_flight.max(locked.total_in_flight);
+		drop(locked);
+		Self(fixtures, host)
+	}
+}
+
+impl Drop for InFlight {
+	fn drop(&mut self) {
+		let mut fixtures = self.0.lock().unwrap();
+		fixtures.total_in_flight -= 1;
+		*fixtures.in_flight.get_mut(&self.1).unwrap() -= 1;
+	}
+}
+
+/// Answers the requests of a connection one after the other.
+async fn serve(mut stream: TcpStream, fixtures: Arc<Mutex<Fixtures>>) {
+	// what was read past the end of a request, the start of the next one
+	let mut received = vec![];
+	while answer(&mut stream, &mut received, &fixtures).await {}
+}
+
+/// Answers the next request of a connection, returns whether it can be used for another one.
+async fn answer(stream: &mut TcpStream, received: &mut Vec<u8>, fixtures: &Arc<Mutex<Fixtures>>) -> bool {
+	let mut buf = [0; 4096];
+	let end = loop {
+		if let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") {
+			break end + 4;
+		}
+		if received.len() > MAX_REQUEST_HEAD {
+			return false;
+		}
+		match stream.read(&mut buf).await {
+			Ok(0) | Err(_) => return false,
+			Ok(read) => received.extend_from_slice(&buf[..read])
+		}
+	};
+	let head: Vec<u8> = received.drain(..end).collect();
+
+	let head = String::from_utf8_lossy(&head);
+	let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+	let method = request_line.next().unwrap_or_default().to_owned();
+	let target = request_line.next().unwrap_or_default();
+	let path = percent_decode(target.split(['?', '#']).next().unwrap_or_default().trim_start_matches('/'));
+	let header = |wanted: &str| head.lines().find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case(wanted)).map(|(_, value)| value.trim().to_owned()));
+	let (if_none_match, range) = (header("if-none-match"), header("range"));
+	let close = header("connection").is_some_and(|connection| connection.eq_ignore_ascii_case("close"));
+	let host = header("host").unwrap_or_default();
+	let host = host.rsplit_once(':').filter(|(_, port)| port.bytes().all(|byte| byte.is_ascii_digit())).map_or(host.as_str(), |(host, _)| host).to_ascii_lowercase();
+	let _in_flight = (method == "GET").then(|| InFlight::new(fixtures.clone(), host));
+
+	let headers = head.lines().skip(1).filter_map(|line| line.split_once(':')).map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_owned())).collect();
+	let (answer, delay, encoding) = fixtures.lock().unwrap().answer(&path, headers);
+	if !delay.is_zero() {
+		tokio::time::sleep(delay).await;
+	}
+
+	let etag = match &answer {
+		Answer::Body(body) | Answer::Stall(body, _) => Some(format!("\"{}\"", Sha256Hash::digest(body))),
+		Answer::Status(_) | Answer::Redirect(_) | Answer::NotFound => None
+	};
+	let location = match &answer {
+		Answer::Redirect(location) => format!("Location: {location}\r\n"),
+		_ => String::new()
+	};
+	let (mut status, mut body, stall) = match answer {
+		_ if etag.is_some() && etag == if_none_match => (304, Bytes::new(), None),
+		Answer::Body(body) => (200, body, None),
+		Answer::Stall(body, after) => (200, body, Some(after)),
+		Answer::Status(status) => (status, Bytes::new(), None),
+		Answer::Redirect(_) => (302, Bytes::new(), None),
+		Answer::NotFound => (404, Bytes::new(), None)
+	};
+	let mut content_range = String::new();
+	if let Some((start, end)) = range.as_deref().filter(|_| status == 200).and_then(|range| byte_range(range, body.len())) {
+		content_range = format!("Content-Range: bytes {start}-{end}/{}\r\n", body.len());
+		(status, body) = (206, body.slice(start..end + 1));
+	}
+	let etag = etag.map(|etag| format!("ETag: {etag}\r\n")).unwrap_or_default();
+	let encoding = encoding.filter(|_| matches!(status, 200 | 206)).map(|encoding| format!("Content-Encoding: {encoding}\r\n")).unwrap_or_default();
+	let connection = match close || stall.is_some() {
+		true => "close",
+		false => "keep-alive"
+	};
+	let response = format!("HTTP/1.1 {status} {}\r\n{etag}{location}{content_range}{encoding}Content-Length: {}\r\nConnection: {connection}\r\n\r\n", reason(status), body.len());
+	if stream.write_all(response.as_bytes()).await.is_err() {
+		return false;
+	}
+	if method == "HEAD" {
+		return !close;
+	}
+
+	if let Some(after) = stall {
+		let _ = stream.write_all(&body[..after.min(body.len())]).await;
+		let _ = stream.flush().await;
+		// the connection stays open until the client gives up
+		while let Ok(1..) = stream.read(&mut buf).await {}
+		return false;
+	}
+	if stream.write_all(&body).await.is_err() || close {
+		let _ = stream.shutdown().await;
+		return false;
+	}
+	true
+}
+
+/// The first and last byte a `bytes=start-end` or `bytes=start-` range asks for, [None] for anything else or a range outside the body.
+fn byte_range(range: &str, length: usize) -> Option<(usize, usize)> {
+	let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+	let start: usize = start.trim().parse().ok()?;
+	let end = match end.trim() {
+		"" => length.checked_sub(1)?,
+		end => end.parse::<usize>().ok()?.min(length.checked_sub(1)?)
+	};
+	(start <= end).then_some((start, end))
+}
+
+fn reason(status: u16) -> &'static str {
+	match status {
+		200 => "OK",
+		206 => "Partial Content",
+		302 => "Found",
+		304 => "Not Modified",
+		404 => "Not Found",
+		429 => "Too Many Requests",
+		500 => "Internal Server Error",
+		502 => "Bad Gateway",
+		503 => "Service Unavailable",
+		_ => "Unknown"
+	}
+}
+
+/// Undoes the `%XX` escapes a client puts in request paths.
+fn percent_decode(path: &str) -> String {
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut index = 0;
+	while index < bytes.len() {
+		let escaped = bytes.get(index + 1..index + 3).filter(|_| bytes[index] == b'%').and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok());
+		match escaped {
+			Some(byte) => {
+				decoded.push(byte);
+				index += 3;
+			},
+			None => {
+				decoded.push(bytes[index]);
+				index += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Builds a [Directory] out of byte blobs, hashing them so the manifest matches what a [FixtureServer] serves.
+/// Paths use `/` separators and become nested children.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryBuilder {
+	files: Vec<(String, Bytes, bool)>,
+	chunk_size: Option<u64>
+}
+
+impl DirectoryBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// # Description
+	/// Adds a file that's downloaded from the base url of [DirectoryBuilder::build].
+	pub fn file(mut self, path: &str, contents: impl Into<Bytes>) -> Self {
+		self.files.push((path.trim_matches('/').to_owned(), contents.into(), false));
+		self
+	}
+
+	/// # Description
+	/// Adds a file whose contents are inlined in the manifest as `content_b64`.
+	pub fn inline_file(mut self, path: &str, contents: impl Into<Bytes>) -> Self {
+		self.files.push((path.trim_matches('/').to_owned(), contents.into(), true));
+		self
+	}
+
+	/// # Description
+	/// Gives files that don't fit in one chunk of `size` bytes [File::chunks], inline ones never get them.
+	pub fn chunk_size(mut self, size: u64) -> Self {
+		self.chunk_size = Some(size);
+		self
+	}
+
+	/// # Description
+	/// The files that have to be served, by path, inline ones aren't included.
+	pub fn blobs(&self) -> Vec<(String, Bytes)> {
+		self.files.iter().filter(|(_, _, inline)| !inline).map(|(path, contents, _)| (path.clone(), contents.clone())).collect()
+	}
+
+	/// # Description
+	/// Builds the manifest, the url of a file is its path appended to `base_url` with [crate::util::join_url].
+	pub fn build(&self, base_url: &str) -> Directory {
+		use base64::Engine;
+
+		let mut root = Directory::default();
+		for (path, contents, inline) in &self.files {
+			let mut keys: Vec<_> = path.split('/').collect();
+			let name = keys.pop().unwrap();
+			let directory = keys.into_iter().fold(&mut root, |directory, key| directory.children.entry(key.to_owned()).or_default());
+
+			directory.files.insert(name.to_owned(), File {
+				sha: Sha256Hash::digest(contents),
+				size: Some(contents.len() as u64),
+				url: (!inline).then(|| crate::util::join_url(base_url, path)),
+				content_b64: inline.then(|| base64::engine::general_purpose::STANDARD.encode(contents)),
+				mtime: None,
+				side: Default::default(),
+				os: vec![],
+				arch: vec![],
+				headers: Default::default(),
+				query: vec![],
+				chunks: self.chunk_size.filter(|size| !inline && contents.len() as u64 > *size).map(|size| crate::Chunks::of(contents, size)),
+				extra: Default::default()
+			});
+		}
+		root
+	}
+
+	/// # Description
+	/// A packs list with a single pack called `name`, whose manifest is [DirectoryBuilder::build] with `base_url` served at `manifest_url` as [Directory::to_string_canonical].
+	/// [FixtureServer::serve] serves it at `<prefix>/manifest.json` with `base_url` being the url of the prefix.
+	pub fn packs_list(&self, name: &str, base_url: &str, manifest_url: &str) -> PacksListManifest {
+		PacksListBuilder::new()
+			.add_pack(name, name, manifest_url, &self.build(base_url))
+			.featured(name)
+			.build()
+			.unwrap()
+	}
+}
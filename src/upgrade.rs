@@ -0,0 +1,1107 @@
+use tokio::sync::Semaphore;
+use tokio::task::{JoinHandle, JoinSet};
+use futures_util::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use crate::executor::Executor;
+use crate::hash::Sha256Hash;
+use crate::hash_cache::{self, HashCache};
+use crate::{download, template, http, Deletions, Directory, File, FileOperation, Progress, SymlinkPolicy, UpgradeError, UpgradeOptions, UpgradeReport, UpgradeStatus, UpgradeWarning};
+
+/// A local file that is in the manifest and is being hashed to see if it needs downloading again.
+struct Hashed {
+	local_path: PathBuf,
+	relative: PathBuf,
+	remote_file: File,
+	local_sha: std::io::Result<Sha256Hash>
+}
+
+/// The scan walks the folder and deletes unknown entries (unless deletions are disabled), local files that are in the manifest are hashed by a bounded set of tasks,
+/// and downloads are spawned as soon as a file is known to be missing or outdated, so the three stages overlap.
+/// Sibling directories are scanned at the same time, so everything the scan adds to is behind a lock.
+pub(crate) struct UpgradeState {
+	/// The spawned downloads with the file each one is for.
+	handles: Mutex<Vec<(PathBuf, DownloadHandle)>>,
+	hashing: Mutex<JoinSet<Hashed>>,
+	/// A permit for every local file that may be hashed at once.
+	hash_slots: Arc<Semaphore>,
+	/// A permit for every directory that may be read at once.
+	scan_slots: Semaphore,
+	hasher: LocalHasher,
+	progress: Progress,
+	http: Arc<http::Http>,
+	budget: Arc<download::Budget>,
+	warnings: Mutex<Vec<UpgradeWarning>>,
+	strict: bool,
+	symlinks: SymlinkPolicy,
+	deletions: Deletions,
+	hidden: crate::HiddenPolicy,
+	protect: Option<crate::ProtectFilter>,
+	/// Unknown entries left in place because of [Deletions::Disabled], [crate::HiddenPolicy], [crate::ProtectFilter] or another namespace.
+	kept: Mutex<Vec<PathBuf>>,
+	owners: crate::owners::Owners,
+	subtrees: crate::subtrees::Subtrees,
+	namespace: Option<String>,
+	filter: Option<crate::DownloadFilter>,
+	side: crate::Side,
+	#[cfg_attr(not(windows), allow(dead_code))]
+	windows_symlinks: bool,
+	/// The first error the scan ran into, returned once the downloads are done.
+	scan_error: Mutex<Option<UpgradeError>>,
+	#[cfg(feature = "zip")]
+	bootstrap: Option<crate::Bootstrap>,
+	bootstrapped: bool,
+	rewrite_url: Option<crate::RewriteUrl>,
+	refresh_url: Option<crate::RefreshUrl>,
+	limits: Arc<download::Limits>,
+	stall_timeout: Option<std::time::Duration>,
+	skip_verification: bool,
+	restore_mtimes: bool,
+	metrics: Arc<crate::metrics::Recorder>,
+	limiter: crate::DownloadLimiter,
+	cache: Option<crate::ObjectCache>,
+	source: crate::FileSource,
+	ignore_in_use: bool,
+	separators: crate::SeparatorPolicy,
+	connectivity: Arc<download::Connectivity>,
+	locks: Arc<crate::in_use::Locks>,
+	pause: crate::pause::Pause,
+	running: crate::pause::Running,
+	deadline: Option<std::time::Duration>,
+	deadline_excludes_pauses: bool,
+	/// Set by [crate::Directory::repair_game_folder] while its report holds, nested folders aren't scanned then.
+	repair: Option<crate::verify::Repair>,
+	/// A url of the manifest that's checked before the first deletion or download, [None] when everything is in the cache, see [UpgradeState::offline].
+	probe_url: Option<String>,
+	probed: tokio::sync::OnceCell<bool>,
+	/// Downloads that weren't started because the check of [UpgradeState::probe_url] failed.
+	not_attempted: std::sync::atomic::AtomicUsize
+}
+
+type DownloadHandle = JoinHandle<Result<(), UpgradeError>>;
+
+/// Everything the hashing tasks need to hash local files.
+#[derive(Clone)]
+pub(crate) struct LocalHasher {
+	hash_cache: Option<Arc<Mutex<HashCache>>>,
+	paranoid: bool,
+	executor: Executor,
+	#[cfg(feature = "rayon")]
+	pool: Option<Arc<rayon::ThreadPool>>
+}
+
+impl LocalHasher {
+	/// Also returns how many files should be hashed at once.
+	pub(crate) async fn new(path: &Path, options: &UpgradeOptions) -> (Self, usize) {
+		let executor = Executor::of(options);
+		let hash_cache = match options.hash_cache {
+			true => Some(Arc::new(Mutex::new(HashCache::load(path, &executor).await))),
+			false => None
+		};
+
+		let max_hashing = options.hash_threads
+			.unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |threads| threads.get()))
+			.max(1);
+
+		let hasher = Self {
+			hash_cache,
+			paranoid: options.paranoid,
+			executor,
+			#[cfg(feature = "rayon")]
+			pool: options.hash_threads.map(|_| {
+				Arc::new(rayon::ThreadPoolBuilder::new().num_threads(max_hashing).build().expect("cannot build hashing pool"))
+			})
+		};
+		(hasher, max_hashing)
+	}
+
+	/// Writes the hash cache back, the cache only saves time so failing to write it is ignored.
+	pub(crate) async fn save(&self, path: &Path) {
+		if let Some(cache) = &self.hash_cache {
+			let cache = std::mem::take(&mut *cache.lock().unwrap());
+			let _ = cache.save(path, &self.executor).await;
+		}
+	}
+
+	/// Hashes a local file, or trusts the hash cache if the file hasn't changed since it was last hashed.
+	pub(crate) async fn local_sha(&self, path: &Path, relative: &Path) -> std::io::Result<Sha256Hash> {
+		let (hasher, path, relative) = (self.clone(), path.to_owned(), relative.to_owned());
+		self.run(move || hasher.local_sha_now(&path, &relative)).await
+	}
+
+	/// Same as [LocalHasher::local_sha] on the current thread, for work that's already where blocking is fine.
+	pub(crate) fn local_sha_now(&self, path: &Path, relative: &Path) -> std::io::Result<Sha256Hash> {
+		let metadata = std::fs::metadata(path)?;
+		let key = hash_cache::key(relative);
+
+		if let (Some(cache), false) = (&self.hash_cache, self.paranoid) {
+			if let Some(sha) = cache.lock().unwrap().get(&key, &metadata) {
+				return Ok(sha);
+			}
+		}
+
+		let local_sha = std::fs::read(path).map(Sha256Hash::digest)?;
+
+		if let Some(cache) = &self.hash_cache {
+			cache.lock().unwrap().insert(key, &metadata, local_sha);
+		}
+		Ok(local_sha)
+	}
+
+	/// Runs blocking hashing work on the blocking threads of the executor.
+	#[cfg(not(feature = "rayon"))]
+	pub(crate) async fn run<T: Send + 'static>(&self, job: impl FnOnce() -> T + Send + 'static) -> T {
+		self.executor.blocking(job).await
+	}
+
+	/// Runs blocking hashing work on the rayon pool, which has less overhead per file than the blocking pool when there are lots of small files.
+	#[cfg(feature = "rayon")]
+	pub(crate) async fn run<T: Send + 'static>(&self, job: impl FnOnce() -> T + Send + 'static) -> T {
+		let (tx, rx) = tokio::sync::oneshot::channel();
+		let job = move || {
+			let _ = tx.send(job());
+		};
+
+		match &self.pool {
+			Some(pool) => pool.spawn(job),
+			None => rayon::spawn(job)
+		}
+		rx.await.unwrap()
+	}
+}
+
+/// The client of an upgrade with `options`, which fails when reqwest rejects their [UpgradeOptions::http], see [UpgradeError::Client].
+pub(crate) fn http_for(options: &UpgradeOptions) -> reqwest::Result<Arc<http::Http>> {
+	match &options.http {
+		Some(config) => Ok(Arc::new(http::Http::new(config.clone())?)),
+		None => Ok(http::shared())
+	}
+}
+
+/// `directory` with every reference resolved and the keys that contain a separator dealt with as `separators` says, it's only copied when either changes something.
+pub(crate) async fn prepared<'a>(directory: &'a Directory, http: &http::Http, separators: crate::SeparatorPolicy) -> Result<std::borrow::Cow<'a, Directory>, UpgradeError> {
+	let resolved = crate::refs::resolved(directory, http).await?;
+	let separated = match crate::normalize::separated(&resolved, separators).map_err(UpgradeError::Normalize)? {
+		std::borrow::Cow::Owned(separated) => Some(separated),
+		std::borrow::Cow::Borrowed(_) => None
+	};
+	Ok(separated.map_or(resolved, std::borrow::Cow::Owned))
+}
+
+/// Where to spawn an upgrade: [UpgradeOptions::runtime] when it's set, [None] to run it where it's awaited.
+#[allow(clippy::result_large_err)]
+pub(crate) fn runtime(options: &UpgradeOptions) -> Result<Option<tokio::runtime::Handle>, UpgradeError> {
+	match &options.runtime {
+		Some(runtime) => Ok(Some(runtime.clone())),
+		None if tokio::runtime::Handle::try_current().is_ok() => Ok(None),
+		None => Err(UpgradeError::NoRuntime)
+	}
+}
+
+/// Waits for an upgrade spawned on [UpgradeOptions::runtime], passing on its panic.
+pub(crate) async fn join<T>(handle: JoinHandle<Result<T, UpgradeError>>) -> Result<T, UpgradeError> {
+	match handle.await {
+		Ok(result) => result,
+		Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+		Err(_) => Err(UpgradeError::NoRuntime)
+	}
+}
+
+impl UpgradeState {
+	pub(crate) async fn new(path: &Path, options: &UpgradeOptions, progress: Progress) -> Result<Self, UpgradeError> {
+		let http = http_for(options).map_err(UpgradeError::Client)?;
+		let (hasher, max_hashing) = LocalHasher::new(path, options).await;
+		let (pause, running) = crate::pause::new(options.pause.as_ref(), &progress);
+
+		Ok(Self {
+			handles: Mutex::default(),
+			hashing: Mutex::default(),
+			hash_slots: Arc::new(Semaphore::new(max_hashing)),
+			scan_slots: Semaphore::new(max_hashing),
+			hasher,
+			progress,
+			http,
+			budget: Arc::new(download::Budget::new(options.buffer_budget.unwrap_or(crate::DEFAULT_BUFFER_BUDGET))),
+			warnings: Mutex::default(),
+			strict: options.strict,
+			symlinks: options.symlinks,
+			deletions: options.deletions,
+			hidden: options.hidden.clone(),
+			protect: options.protect.clone(),
+			kept: Mutex::default(),
+			owners: crate::owners::Owners::load(path).await,
+			subtrees: crate::subtrees::Subtrees::load(path, options).await,
+			namespace: options.namespace.clone(),
+			filter: options.filter.clone(),
+			side: options.side.clone(),
+			windows_symlinks: options.windows_symlinks,
+			scan_error: Mutex::default(),
+			#[cfg(feature = "zip")]
+			bootstrap: options.bootstrap.clone(),
+			bootstrapped: false,
+			rewrite_url: options.rewrite_url.clone(),
+			refresh_url: options.refresh_url.clone(),
+			limits: Arc::new(download::Limits::new(&options.limits)),
+			stall_timeout: options.stall_timeout,
+			skip_verification: options.dangerously_skip_hash_verification,
+			restore_mtimes: options.restore_mtimes,
+			metrics: Arc::new(crate::metrics::Recorder::new()),
+			limiter: options.download_limiter.clone().unwrap_or_else(|| crate::DownloadLimiter::new(crate::DEFAULT_CONCURRENT_DOWNLOADS)),
+			cache: options.cache.clone(),
+			source: options.source,
+			ignore_in_use: options.ignore_in_use,
+			separators: options.separators,
+			connectivity: Arc::default(),
+			locks: Arc::new(crate::in_use::Locks::new(options.lock_retries)),
+			pause,
+			running,
+			deadline: options.deadline,
+			deadline_excludes_pauses: options.deadline_excludes_pauses,
+			repair: None,
+			probe_url: None,
+			probed: tokio::sync::OnceCell::new(),
+			not_attempted: Default::default()
+		})
+	}
+
+	/// Goes by `repair` instead of scanning, if it still holds once the upgrade starts.
+	pub(crate) fn repairing(mut self, repair: Option<crate::verify::Repair>) -> Self {
+		self.repair = repair;
+		self
+	}
+
+	/// Upgrades `path` to match `directory`, waiting for every download to finish.
+	/// Once the [crate::UpgradeOptions::deadline] runs out nothing new is scanned or downloaded and running downloads are stopped, what got done is in [UpgradeError::DeadlineExceeded].
+	pub(crate) async fn run(mut self, directory: &Directory, path: &Path) -> Result<UpgradeReport, UpgradeError> {
+		let mut deadline = std::pin::pin!(self.pause.deadline(self.deadline, self.deadline_excludes_pauses));
+		let http = self.http.clone();
+		let prepared = crate::pause::unless(prepared(directory, &http, self.separators), deadline.as_mut()).await;
+		let directory = &*match prepared {
+			Some(prepared) => prepared?,
+			None => return Err(self.deadline_exceeded(false, vec![], vec![], Default::default()))
+		};
+		if !self.ignore_in_use {
+			if let Some(path) = crate::in_use::probe(path).await {
+				return Err(UpgradeError::InUse { path });
+			}
+		}
+
+		// keeps a gc of the cache from removing objects this upgrade is about to link, an upgrade still works with a cache that can't be locked
+		let _lock = match self.cache.clone() {
+			Some(cache) => tokio::task::spawn_blocking(move || crate::cache::lock(&cache, false)).await.unwrap().ok(),
+			None => None
+		};
+
+		let scanned = match crate::pause::unless(self.schedule(directory, path), deadline.as_mut()).await {
+			Some(scheduled) => {
+				scheduled?;
+				true
+			},
+			None => false
+		};
+		let mut expired = !scanned;
+		if expired {
+			self.running.stop();
+		}
+		let scanning = self.metrics.elapsed();
+		let handles = std::mem::take(self.handles.get_mut().unwrap());
+		self.progress.emit(UpgradeStatus::Length(handles.len())).await;
+
+		let mut result = self.scan_error.get_mut().unwrap().take().map_or(Ok(()), Err);
+		let mut not_attempted = 0;
+		let (mut downloaded, mut unfinished) = (vec![], vec![]);
+		for (relative, mut handle) in handles {
+			let finished = match expired {
+				false => crate::pause::unless(&mut handle, deadline.as_mut()).await,
+				true => None
+			};
+			let finished = match finished {
+				Some(finished) => finished,
+				None => {
+					// the downloads that are still running stop at once
+					expired = true;
+					self.running.stop();
+					handle.await
+				}
+			};
+			match finished.unwrap() {
+				Err(UpgradeError::Download { error: crate::DownloadError::Offline, attempts, .. }) => not_attempted += (attempts == 0) as usize,
+				Err(UpgradeError::Download { error: crate::DownloadError::Cancelled, .. }) if expired => unfinished.push(relative),
+				Err(error) => result = result.and(Err(error)),
+				Ok(()) => downloaded.push(relative)
+			}
+		}
+
+		let not_checked = *self.not_attempted.get_mut();
+		let probed = self.probe_url.as_deref().filter(|_| not_checked > 0).map(http::without_userinfo);
+		if let Some(url) = self.connectivity.offline().or(probed) {
+			result = Err(UpgradeError::Offline {
+				url,
+				not_attempted: not_attempted + not_checked
+			});
+		}
+
+		self.hasher.save(path).await;
+
+		// also after a failed upgrade, since what it did install shouldn't be deleted by the upgrades of other namespaces
+		let mut ownership_conflicts = vec![];
+		if let Some(namespace) = self.namespace.take() {
+			let managed = crate::owners::managed(directory, &self.side, self.filter.as_ref());
+			ownership_conflicts = self.owners.claim(&namespace, managed);
+			if let Err(error) = self.owners.save(path).await {
+				self.warn(PathBuf::from(hash_cache::STATE_DIR), error).await;
+			}
+		}
+
+		// sorted since sibling directories are scanned at the same time
+		let mut warnings = std::mem::take(self.warnings.get_mut().unwrap());
+		warnings.sort_by(|a, b| a.path.cmp(&b.path));
+		if self.strict && !warnings.is_empty() && !expired {
+			let warning = warnings.remove(0);
+			result = result.and(Err(UpgradeError::Local {
+				path: warning.path,
+				error: warning.error
+			}));
+		}
+
+		let mut kept = std::mem::take(self.kept.get_mut().unwrap());
+		kept.sort();
+		// a subtree that was only partly applied has to be scanned again next time
+		let complete = result.is_ok() && !expired && warnings.is_empty() && kept.is_empty() && self.filter.is_none();
+		self.subtrees.save(path, complete).await;
+		let report = UpgradeReport {
+			peak_buffered_bytes: self.budget.peak(),
+			warnings,
+			bootstrapped: self.bootstrapped,
+			verification_skipped: self.skip_verification,
+			kept,
+			ownership_conflicts,
+			lock_retries: self.locks.take(),
+			metrics: self.metrics.finish(scanning),
+			repaired_from_report: self.repair.is_some()
+		};
+		match expired {
+			true => Err(self.deadline_exceeded(scanned, downloaded, unfinished, report)),
+			false => result.map(|()| report)
+		}
+	}
+
+	/// Checks whether the upgrade can go ahead, then scans the folder and spawns the downloads it needs.
+	async fn schedule(&mut self, directory: &Directory, path: &Path) -> Result<(), UpgradeError> {
+		if let Some(repair) = self.repair.take() {
+			self.repair = repair.holds(path).await.then_some(repair);
+		}
+
+		if self.source == crate::FileSource::CacheOnly {
+			let missing = self.uncached(directory, path).await;
+			if !missing.is_empty() {
+				return Err(UpgradeError::NotCached { missing });
+			}
+		}
+
+		// checked before anything is deleted, so an installed pack isn't broken by upgrading without a network, unless a prefetch already put everything in the cache
+		let cached = match (&self.cache, self.source) {
+			(_, crate::FileSource::CacheOnly) => true,
+			(Some(cache), crate::FileSource::CacheThenNetwork) => crate::cache::covers(cache, directory, &self.side).await,
+			_ => false
+		};
+		self.probe_url = first_url(directory).filter(|_| !cached).map(|url| match &self.rewrite_url {
+			Some(rewrite_url) => rewrite_url.rewrite(url),
+			None => url.to_owned()
+		});
+
+		#[cfg(feature = "zip")]
+		if let Some(bootstrap) = self.bootstrap.take().filter(|_| self.source != crate::FileSource::CacheOnly) {
+			if crate::archive::mostly_missing(directory, path).await {
+				self.bootstrap(bootstrap, path).await;
+				// what it extracted has to be scanned
+				self.repair = None;
+			}
+		}
+
+		self.upgrade_folder_to(directory, path, Path::new(""), None).await;
+		let mut hashing = std::mem::take(self.hashing.get_mut().unwrap());
+		while let Some(hashed) = hashing.join_next().await {
+			self.hashed(hashed.unwrap()).await;
+		}
+		Ok(())
+	}
+
+	fn deadline_exceeded(&self, scanned: bool, mut downloaded: Vec<PathBuf>, mut unfinished: Vec<PathBuf>, report: UpgradeReport) -> UpgradeError {
+		downloaded.sort();
+		unfinished.sort();
+		UpgradeError::DeadlineExceeded {
+			deadline: self.deadline.unwrap_or_default(),
+			scanned,
+			downloaded,
+			unfinished,
+			report: Box::new(report)
+		}
+	}
+
+	/// The shas of files that would have to be written but aren't in the cache, sorted, local files that already match don't need it.
+	/// The top level is always written, so its local files don't count.
+	async fn uncached(&self, directory: &Directory, path: &Path) -> Vec<Sha256Hash> {
+		let mut missing = vec![];
+		let mut stack = vec![(directory, path.to_owned(), PathBuf::new())];
+		while let Some((directory, path, relative)) = stack.pop() {
+			for (name, file) in &directory.files {
+				let (local_path, relative) = (path.join(name), relative.join(name));
+				if file.content_b64.is_some() || !file.side.includes(&self.side) || !file.applies_here() || self.filter.as_ref().is_some_and(|filter| !filter.includes(&relative, file)) {
+					continue;
+				}
+				if let Some(cache) = &self.cache {
+					if tokio::fs::try_exists(crate::cache::object_path(cache, &file.sha)).await.unwrap_or(false) {
+						continue;
+					}
+				}
+
+				let nested = relative.components().count() > 1;
+				let is_file = tokio::fs::symlink_metadata(&local_path).await.is_ok_and(|metadata| metadata.is_file());
+				if !(nested && is_file && self.hasher.local_sha(&local_path, &relative).await.is_ok_and(|sha| sha == file.sha)) {
+					missing.push(file.sha);
+				}
+			}
+			for (name, child) in directory.children.iter().filter(|(_, child)| child.side.includes(&self.side)) {
+				stack.push((child, path.join(name), relative.join(name)));
+			}
+		}
+
+		missing.sort_by_key(|sha| sha.0);
+		missing.dedup();
+		missing
+	}
+
+	#[cfg(feature = "zip")]
+	async fn bootstrap(&mut self, bootstrap: crate::Bootstrap, path: &Path) {
+		let relative = Path::new(hash_cache::STATE_DIR).join("bootstrap.zip");
+		let download = download::Download {
+			http: self.http.clone(),
+			progress: self.progress.clone(),
+			relative: relative.clone(),
+			url: Some(bootstrap.url),
+			content_b64: None,
+			mtime: None,
+			sha: bootstrap.sha,
+			size: None,
+			local_path: path.join(&relative),
+			hash_cache: None,
+			budget: self.budget.clone(),
+			rewrite_url: self.rewrite_url.clone(),
+			refresh_url: self.refresh_url.clone(),
+			headers: Default::default(),
+			query: vec![],
+			chunks: None,
+			limits: self.limits.clone(),
+			stall_timeout: self.stall_timeout,
+			skip_verification: self.skip_verification,
+			metrics: self.metrics.clone(),
+			limiter: self.limiter.clone(),
+			cache: self.cache.clone(),
+			source: self.source,
+			redirects: Default::default(),
+			failure: Default::default(),
+			connectivity: self.connectivity.clone(),
+			locks: self.locks.clone(),
+			pause: self.pause.clone()
+		};
+
+		// the archive is received into a temp file next to where it's named after
+		let _ = tokio::fs::create_dir_all(path.join(hash_cache::STATE_DIR)).await;
+		let extracted = match download.contents().await {
+			Ok((body, _, _permit)) => {
+				let root = path.to_owned();
+				let progress = self.progress.clone();
+				tokio::task::spawn_blocking(move || body.extract(&root, &progress)).await.unwrap().map_err(|error| error.to_string())
+			},
+			Err((error, _)) => Err(error.to_string())
+		};
+
+		match extracted {
+			Ok(()) => self.bootstrapped = true,
+			Err(reason) => self.progress.emit(UpgradeStatus::Warning {
+				path: relative,
+				reason: format!("cannot bootstrap: {reason}")
+			}).await
+		}
+	}
+
+	/// Something local couldn't be inspected, it's left alone rather than failing the whole upgrade.
+	async fn warn(&self, path: PathBuf, error: std::io::Error) {
+		// a locked file means the game is still running, so the folder would be left half upgraded
+		if crate::in_use::is_in_use(&error) && !self.ignore_in_use {
+			let error = match self.locks.exhausted(&path) {
+				Some(retry) => UpgradeError::Locked {
+					path: path.clone(),
+					operation: retry.operation,
+					error: retry.error,
+					retries: retry.retries
+				},
+				None => UpgradeError::InUse { path: path.clone() }
+			};
+			self.scan_error.lock().unwrap().get_or_insert(error);
+		}
+
+		self.progress.emit(UpgradeStatus::Warning {
+			path: path.clone(),
+			reason: error.to_string()
+		}).await;
+		self.warnings.lock().unwrap().push(UpgradeWarning {
+			path,
+			error
+		});
+	}
+
+	/// Whether an unknown entry is, or holds, something another namespace owns.
+	fn owned_elsewhere(&self, relative: &Path) -> bool {
+		self.owners.other_owner(relative, self.namespace.as_deref()).is_some()
+	}
+
+	/// Whether an unknown entry is protected by [crate::UpgradeOptions::protect].
+	fn protected(&self, relative: &Path) -> bool {
+		self.protect.as_ref().is_some_and(|protect| protect.protects(relative))
+	}
+
+	/// Whether an unknown file or directory stays, because deletions are disabled, it's protected, it's a hidden entry that's kept or another namespace owns it.
+	async fn kept_unknown(&self, name: &str, path: &Path, relative: &Path) -> bool {
+		self.deletions == Deletions::Disabled || self.protected(relative) || self.owned_elsewhere(relative) || self.hidden.keeps(name, path, &Executor::Tokio).await
+	}
+
+	/// Whether [UpgradeState::probe_url] can't be connected to, it's only checked once something is about to be deleted or downloaded.
+	/// An upgrade of a folder that's already up to date works without a network that way, unknown entries are kept while it's down so the installed pack stays as it was.
+	async fn offline(&self) -> bool {
+		let Some(url) = &self.probe_url else {
+			return false;
+		};
+		*self.probed.get_or_init(|| async { self.http.reachable(url).await.is_err_and(|error| error.is_connect()) }).await
+	}
+
+	/// Whether a file can be written, false when it has to be downloaded while [UpgradeState::offline], which counts it as not attempted.
+	async fn reachable_for(&self, remote_file: &File) -> bool {
+		if remote_file.content_b64.is_some() || !self.offline().await {
+			return true;
+		}
+		if let (Some(cache), crate::FileSource::CacheThenNetwork) = (&self.cache, self.source) {
+			if tokio::fs::try_exists(crate::cache::object_path(cache, &remote_file.sha)).await.unwrap_or(false) {
+				return true;
+			}
+		}
+		self.not_attempted.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+		false
+	}
+
+	/// An unknown entry that isn't deleted because of [Deletions::Disabled], [crate::HiddenPolicy], [crate::ProtectFilter], another namespace or because the network is down.
+	async fn keep(&self, relative: PathBuf) {
+		self.progress.emit(UpgradeStatus::Kept(relative.clone())).await;
+		self.kept.lock().unwrap().push(relative);
+	}
+
+	fn forget(&self, relative: &Path) {
+		if let Some(cache) = &self.hasher.hash_cache {
+			cache.lock().unwrap().remove(&hash_cache::key(relative));
+		}
+	}
+
+	fn spawn_download(&self, local_path: &Path, relative: PathBuf, remote_file: File) {
+		let headers = download::file_headers(&remote_file);
+		let download = download::Download {
+			http: self.http.clone(),
+			progress: self.progress.clone(),
+			relative,
+			url: remote_file.url,
+			content_b64: remote_file.content_b64,
+			sha: remote_file.sha,
+			size: remote_file.size,
+			mtime: remote_file.mtime,
+			local_path: local_path.to_owned(),
+			hash_cache: self.hasher.hash_cache.clone(),
+			budget: self.budget.clone(),
+			rewrite_url: self.rewrite_url.clone(),
+			refresh_url: self.refresh_url.clone(),
+			headers,
+			query: remote_file.query,
+			chunks: remote_file.chunks,
+			limits: self.limits.clone(),
+			stall_timeout: self.stall_timeout,
+			skip_verification: self.skip_verification,
+			metrics: self.metrics.clone(),
+			limiter: self.limiter.clone(),
+			cache: self.cache.clone(),
+			source: self.source,
+			redirects: Default::default(),
+			failure: Default::default(),
+			connectivity: self.connectivity.clone(),
+			locks: self.locks.clone(),
+			pause: self.pause.clone()
+		};
+
+		self.handles.lock().unwrap().push((download.relative.clone(), tokio::spawn(download.run())));
+	}
+
+	/// Starts hashing a local file, waiting for an earlier one to finish if too many are in flight.
+	async fn queue_hash(&self, local_path: PathBuf, relative: PathBuf, remote_file: File) {
+		self.drain_hashed().await;
+		let permit = self.hash_slots.clone().acquire_owned().await.unwrap();
+
+		let hasher = self.hasher.clone();
+		let trusted_size = remote_file.size.filter(|_| self.skip_verification);
+		self.hashing.lock().unwrap().spawn(async move {
+			let _permit = permit;
+			let size_matches = match trusted_size {
+				Some(size) => tokio::fs::metadata(&local_path).await.is_ok_and(|metadata| metadata.len() == size),
+				None => false
+			};
+			let local_sha = match size_matches {
+				true => Ok(remote_file.sha),
+				false => hasher.local_sha(&local_path, &relative).await
+			};
+			Hashed {
+				local_path,
+				relative,
+				remote_file,
+				local_sha
+			}
+		});
+	}
+
+	/// Deals with the local files that finished hashing so far, without waiting for the others.
+	async fn drain_hashed(&self) {
+		let finished: Vec<_> = {
+			let mut hashing = self.hashing.lock().unwrap();
+			std::iter::from_fn(|| hashing.try_join_next()).collect()
+		};
+		for hashed in finished {
+			self.hashed(hashed.unwrap()).await;
+		}
+	}
+
+	async fn hashed(&self, hashed: Hashed) {
+		match hashed.local_sha {
+			Ok(local_sha) if local_sha == hashed.remote_file.sha => {
+				if let (true, Some(mtime)) = (self.restore_mtimes, hashed.remote_file.mtime) {
+					self.restore_mtime(&hashed.local_path, &hashed.relative, mtime, local_sha).await;
+				}
+				self.progress.emit(UpgradeStatus::Skipped(hashed.relative)).await
+			},
+			Ok(_) => {
+				self.forget(&hashed.relative);
+				if self.reachable_for(&hashed.remote_file).await {
+					self.spawn_download(&hashed.local_path, hashed.relative, hashed.remote_file);
+				}
+			},
+			Err(error) => self.warn(hashed.relative, error).await
+		}
+	}
+
+	/// Gives a file that matches the manifest the manifest's modification time, keeping the hash cache entry valid.
+	async fn restore_mtime(&self, local_path: &Path, relative: &Path, mtime: i64, sha: Sha256Hash) {
+		let Ok(metadata) = tokio::fs::metadata(local_path).await else {
+			return;
+		};
+		if crate::mtime::of(&metadata) == Some(mtime) {
+			return;
+		}
+
+		if let Err(error) = crate::mtime::set(local_path, mtime).await {
+			return self.warn(relative.to_owned(), error).await;
+		}
+		if let (Some(cache), Ok(metadata)) = (&self.hasher.hash_cache, tokio::fs::metadata(local_path).await) {
+			cache.lock().unwrap().insert(hash_cache::key(relative), &metadata, sha);
+		}
+	}
+
+	/// Applies the [SymlinkPolicy] to a symlink, returns whether it was deleted.
+	/// Links are removed themselves and never followed.
+	async fn symlink(&self, local_path: &Path, relative: PathBuf) -> bool {
+		match self.symlinks {
+			SymlinkPolicy::Ignore => false,
+			SymlinkPolicy::Delete => {
+				let removed = self.remove_link(local_path, &relative).await;
+
+				match removed {
+					Ok(()) => {
+						self.forget(&relative);
+						self.progress.emit(UpgradeStatus::Deleted(relative)).await;
+						true
+					},
+					Err(error) => {
+						self.warn(relative, error).await;
+						false
+					}
+				}
+			},
+			SymlinkPolicy::Error => {
+				self.scan_error.lock().unwrap().get_or_insert(UpgradeError::Symlink {
+					path: relative
+				});
+				false
+			}
+		}
+	}
+
+	/// Removes the symlink at `local_path` without following it.
+	async fn remove_link(&self, local_path: &Path, relative: &Path) -> std::io::Result<()> {
+		self.locks.retry(relative, FileOperation::Delete, || async {
+			// directory symlinks on windows have to be removed like directories, neither call follows the link
+			match tokio::fs::remove_file(local_path).await {
+				Ok(()) => Ok(()),
+				Err(error) => tokio::fs::remove_dir(local_path).await.map_err(|_| error)
+			}
+		}).await
+	}
+
+	/// Whether files can be written at `local_path`, which is the case unless it's a symlink the [SymlinkPolicy] left in place.
+	async fn writable(&self, local_path: &Path, relative: PathBuf) -> bool {
+		match tokio::fs::symlink_metadata(local_path).await {
+			Ok(metadata) if metadata.file_type().is_symlink() => self.symlink(local_path, relative).await,
+			_ => true
+		}
+	}
+
+	/// Makes `local_path` a symlink to `target`, unless it already is one.
+	async fn link(&self, local_path: &Path, relative: PathBuf, target: &str) {
+		let target: PathBuf = target.split('/').filter(|component| !component.is_empty() && *component != ".").collect();
+
+		if let Ok(existing) = tokio::fs::read_link(local_path).await {
+			if existing.components().eq(target.components()) {
+				return self.progress.emit(UpgradeStatus::Skipped(relative)).await;
+			}
+
+			if let Err(error) = self.remove_link(local_path, &relative).await {
+				return self.warn(relative, error).await;
+			}
+		}
+
+		match self.create_link(local_path, &target).await {
+			Ok(()) => self.progress.emit(UpgradeStatus::Linked(relative)).await,
+			Err(error) => self.warn(relative, error).await
+		}
+	}
+
+	#[cfg(unix)]
+	async fn create_link(&self, local_path: &Path, target: &Path) -> std::io::Result<()> {
+		tokio::fs::symlink(target, local_path).await
+	}
+
+	/// Windows has separate symlinks for files and directories, the target may not have been downloaded yet so anything that isn't a directory gets a file link.
+	#[cfg(windows)]
+	async fn create_link(&self, local_path: &Path, target: &Path) -> std::io::Result<()> {
+		if !self.windows_symlinks {
+			return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "symlinks are disabled on windows"));
+		}
+
+		let resolved = local_path.parent().unwrap_or(local_path).join(target);
+		if tokio::fs::metadata(resolved).await.is_ok_and(|metadata| metadata.is_dir()) {
+			tokio::fs::symlink_dir(target, local_path).await
+		} else {
+			tokio::fs::symlink_file(target, local_path).await
+		}
+	}
+
+	#[cfg(not(any(unix, windows)))]
+	async fn create_link(&self, _local_path: &Path, _target: &Path) -> std::io::Result<()> {
+		Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "symlinks aren't supported on this platform"))
+	}
+
+	/// Deletes local entries that aren't in `directory` and queues the ones that are for hashing, taking them out of `fetch_set`.
+	/// Entries that can't be inspected are warned about and never deleted, with `only` the others are left alone too.
+	async fn scan(&self, directory: &Directory, fetch_set: &mut HashMap<&str, &File>, url_template: Option<&str>, path: &Path, relative: &Path, only: Option<&HashSet<PathBuf>>) {
+		let mut files = match tokio::fs::read_dir(path).await {
+			Ok(files) => files,
+			Err(error) => return self.warn(relative.to_owned(), error).await
+		};
+
+		loop {
+			let local_file = match files.next_entry().await {
+				Ok(Some(local_file)) => local_file,
+				Ok(None) => break,
+				Err(error) => return self.warn(relative.to_owned(), error).await
+			};
+			let local_file_name = local_file.file_name();
+			let local_file_name = local_file_name.to_string_lossy();
+			let local_relative = relative.join(local_file_name.as_ref());
+
+			if relative.as_os_str().is_empty() && local_file_name == hash_cache::STATE_DIR {
+				continue;
+			}
+			if only.is_some_and(|only| !only.contains(&local_relative)) {
+				continue;
+			}
+
+			let local_file_type = match local_file.file_type().await {
+				Ok(local_file_type) => local_file_type,
+				Err(error) => {
+					self.warn(local_relative, error).await;
+					continue;
+				}
+			};
+
+			let deleted = if local_file_type.is_symlink() {
+				// links with a name from the manifest are dealt with when that entry is written
+				let known = directory.files.contains_key(local_file_name.as_ref())
+					|| directory.children.contains_key(local_file_name.as_ref())
+					|| directory.links.contains_key(local_file_name.as_ref());
+				match (known, self.symlinks, self.deletions) {
+					(true, _, _) => {},
+					(false, SymlinkPolicy::Delete, _) if self.offline().await => self.keep(local_relative).await,
+					(false, SymlinkPolicy::Delete, Deletions::Disabled) => self.keep(local_relative).await,
+					(false, SymlinkPolicy::Delete | SymlinkPolicy::Error, _) if self.owned_elsewhere(&local_relative) || self.protected(&local_relative) => self.keep(local_relative).await,
+					(false, SymlinkPolicy::Delete, _) if self.hidden.keeps(&local_file_name, &local_file.path(), &Executor::Tokio).await => self.keep(local_relative).await,
+					(false, _, _) => {
+						self.symlink(&local_file.path(), local_relative).await;
+					}
+				}
+				continue;
+			} else if local_file_type.is_dir() {
+				if directory.children.contains_key(local_file_name.as_ref()) {
+					continue;
+				}
+				if self.kept_unknown(&local_file_name, &local_file.path(), &local_relative).await || self.offline().await {
+					self.keep(local_relative).await;
+					continue;
+				}
+				// remove_dir_all removes symlinks inside the directory without following them
+				self.locks.retry(&local_relative, FileOperation::Delete, || tokio::fs::remove_dir_all(local_file.path())).await
+			} else if local_file_type.is_file() {
+				match fetch_set.remove(local_file_name.as_ref()) {
+					Some(remote_file) => {
+						self.queue_hash(local_file.path(), local_relative, to_fetch(remote_file, url_template)).await;
+						continue;
+					},
+					// left out by the filter
+					None if directory.files.contains_key(local_file_name.as_ref()) => continue,
+					None if self.kept_unknown(&local_file_name, &local_file.path(), &local_relative).await || self.offline().await => {
+						self.keep(local_relative).await;
+						continue;
+					},
+					None => self.locks.retry(&local_relative, FileOperation::Delete, || tokio::fs::remove_file(local_file.path())).await
+				}
+			} else {
+				continue;
+			};
+
+			match deleted {
+				Ok(()) => {
+					self.forget(&local_relative);
+					self.progress.emit(UpgradeStatus::Deleted(local_relative)).await;
+				},
+				Err(error) => self.warn(local_relative, error).await
+			}
+		}
+	}
+
+	/// Instead of a scan, takes the files `repair` found in place out of `fetch_set` and deletes the unknown entries it found.
+	async fn repaired(&self, repair: &crate::verify::Repair, directory: &Directory, fetch_set: &mut HashMap<&str, &File>, url_template: Option<&str>, path: &Path, relative: &Path) {
+		let mut in_place: Vec<_> = fetch_set.keys().filter(|name| !repair.fix.contains(&relative.join(name))).copied().collect();
+		in_place.sort();
+		for name in in_place {
+			fetch_set.remove(name);
+			self.progress.emit(UpgradeStatus::Skipped(relative.join(name))).await;
+		}
+
+		if repair.listed.contains(relative) {
+			self.scan(directory, fetch_set, url_template, path, relative, Some(&repair.unknown)).await;
+		}
+	}
+
+	#[async_recursion::async_recursion]
+	async fn upgrade_folder_to(&self, directory: &Directory, path: &Path, relative: &Path, url_template: Option<&str>) {
+		let url_template = directory.url_template.as_deref().or(url_template);
+		// borrowed so only files that are being hashed or downloaded get copied, files for the other side are known but left alone like filtered ones
+		let mut fetch_set: HashMap<&str, &File> = directory.files.iter().filter(|(_, file)| file.side.includes(&self.side)).map(|(name, file)| (name.as_str(), file)).collect();
+		let nested = !relative.as_os_str().is_empty();
+		if nested {
+			self.progress.emit(UpgradeStatus::EnteringDirectory(relative.to_owned())).await;
+		}
+
+		let mut not_applicable: Vec<_> = fetch_set.iter().filter(|(_, file)| !file.applies_here()).map(|(name, _)| *name).collect();
+		not_applicable.sort();
+		for name in not_applicable {
+			fetch_set.remove(name);
+			self.progress.emit(UpgradeStatus::NotApplicable(relative.join(name))).await;
+		}
+
+		if let Some(filter) = self.filter.clone() {
+			let mut filtered: Vec<_> = fetch_set.iter().filter(|(name, file)| !filter.includes(&relative.join(name), file)).map(|(name, _)| *name).collect();
+			filtered.sort();
+			for name in filtered {
+				fetch_set.remove(name);
+				self.progress.emit(UpgradeStatus::Filtered(relative.join(name))).await;
+			}
+		}
+
+		if nested {
+			let _permit = self.scan_slots.acquire().await.unwrap();
+			match &self.repair {
+				Some(repair) => self.repaired(repair, directory, &mut fetch_set, url_template, path, relative).await,
+				None => self.scan(directory, &mut fetch_set, url_template, path, relative, None).await
+			}
+		}
+
+		for (name, remote_file) in fetch_set.into_iter() {
+			let local_path = path.join(name);
+			if self.reachable_for(remote_file).await && self.writable(&local_path, relative.join(name)).await {
+				self.spawn_download(&local_path, relative.join(name), to_fetch(remote_file, url_template));
+			}
+		}
+
+		for (name, target) in &directory.links {
+			self.link(&path.join(name), relative.join(name), target).await;
+		}
+
+		self.drain_hashed().await;
+
+		let children = directory.children.iter().filter(|(_, child)| child.side.includes(&self.side)).map(|(name, child)| async move {
+			let local_path = &path.join(name);
+			if !self.writable(local_path, relative.join(name)).await {
+				return;
+			}
+
+			let existed = match tokio::fs::create_dir(local_path).await {
+				Ok(()) => false,
+				Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => true,
+				Err(error) => return self.warn(local_path.clone(), error).await
+			};
+
+			let child_relative = relative.join(name);
+			if existed && self.subtrees.unchanged(child, &child_relative) {
+				return self.unchanged(child, child_relative).await;
+			}
+			self.upgrade_folder_to(child, local_path, &child_relative, url_template).await;
+			// only a scan shows that all of it is in place
+			if self.repair.is_none() {
+				self.subtrees.scanned(child, &child_relative);
+			}
+		});
+		join_all(children).await;
+
+		if nested {
+			self.progress.emit(UpgradeStatus::LeavingDirectory(relative.to_owned())).await;
+		}
+	}
+
+	/// Reports a child that was already applied like a scan that found everything in place, without looking at the folder.
+	#[async_recursion::async_recursion]
+	async fn unchanged(&self, directory: &Directory, relative: PathBuf) {
+		self.progress.emit(UpgradeStatus::EnteringDirectory(relative.clone())).await;
+
+		let mut files: Vec<_> = directory.files.iter().filter(|(_, file)| file.side.includes(&self.side)).collect();
+		files.sort_by_key(|(name, _)| *name);
+		for (name, file) in files {
+			let file_relative = relative.join(name);
+			let status = if !file.applies_here() {
+				UpgradeStatus::NotApplicable(file_relative)
+			} else if self.filter.as_ref().is_some_and(|filter| !filter.includes(&file_relative, file)) {
+				UpgradeStatus::Filtered(file_relative)
+			} else {
+				UpgradeStatus::Skipped(file_relative)
+			};
+			self.progress.emit(status).await;
+		}
+		for name in directory.links.keys() {
+			self.progress.emit(UpgradeStatus::Skipped(relative.join(name))).await;
+		}
+		for (name, child) in directory.children.iter().filter(|(_, child)| child.side.includes(&self.side)) {
+			self.unchanged(child, relative.join(name)).await;
+		}
+
+		self.progress.emit(UpgradeStatus::LeavingDirectory(relative)).await;
+	}
+}
+
+/// Runs futures that borrow the upgrade at the same time on the current task, since they can't be spawned.
+/// Only the ones that were woken are polled again, so a wide directory doesn't poll every sibling whenever one of them makes progress.
+async fn join_all<F: Future<Output = ()>>(futures: impl IntoIterator<Item = F>) {
+	let mut futures: futures_util::stream::FuturesUnordered<F> = futures.into_iter().collect();
+	while futures.next().await.is_some() {}
+}
+
+/// Copies a manifest entry for hashing or downloading, giving it the url from the template if it has no source of its own.
+pub(crate) fn to_fetch(file: &File, url_template: Option<&str>) -> File {
+	let mut file = file.clone();
+	if let Some(url_template) = url_template {
+		// validation makes sure templates expand, a file that's still left without a url fails with DownloadError::NoSource
+		if file.url.is_none() && file.content_b64.is_none() {
+			file.url = template::expand(url_template, &file.sha).ok();
+		}
+	}
+	file
+}
+
+/// Any URL of the manifest, the first one found.
+fn first_url(directory: &Directory) -> Option<&str> {
+	directory.files.values().find_map(|file| file.url.as_deref()).or_else(|| directory.children.values().find_map(first_url))
+}
+
+/// What an event of a multi upgrade says was done for a path, see [crate::SourceReport].
+enum Work {
+	Downloaded,
+	Skipped,
+	Linked,
+	Filtered
+}
+
+/// Upgrades to the merge of `directories`, events go through a channel of our own so they can be attributed to their manifest on the way to `progress`.
+pub(crate) async fn upgrade_multi(directories: &[&Directory], path: &Path, options: &UpgradeOptions, progress: Progress) -> Result<crate::MultiUpgradeReport, UpgradeError> {
+	options.check_notice()?;
+	let http = http_for(options).map_err(UpgradeError::Client)?;
+	let mut resolved = vec![];
+	for directory in directories {
+		resolved.push(prepared(directory, &http, options.separators).await?);
+	}
+	let resolved: Vec<&Directory> = resolved.iter().map(|directory| &**directory).collect();
+	let merged = crate::merge::merge(&resolved, options.precedence).map_err(UpgradeError::Merge)?;
+
+	let mut sources = vec![crate::SourceReport::default(); directories.len()];
+	for (path, source) in &merged.overridden {
+		sources[*source].overridden.push(PathBuf::from(path));
+	}
+
+	let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+	let forward = tokio::spawn(async move {
+		let mut events = vec![];
+		while let Some(status) = rx.recv().await {
+			let attributed = match &status {
+				UpgradeStatus::Finished(path) => Some((path.clone(), Work::Downloaded)),
+				UpgradeStatus::Skipped(path) => Some((path.clone(), Work::Skipped)),
+				UpgradeStatus::Linked(path) => Some((path.clone(), Work::Linked)),
+				UpgradeStatus::Filtered(path) => Some((path.clone(), Work::Filtered)),
+				_ => None
+			};
+			events.extend(attributed);
+			progress.emit(status).await;
+		}
+		events
+	});
+
+	let result = UpgradeState::new(path, options, Progress::Channel(tx)).await?.run(&merged.directory, path).await;
+	let events = forward.await.unwrap();
+	let upgrade = result?;
+
+	for (path, work) in events {
+		let key = path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+		let Some(report) = merged.sources.get(&key).map(|source| &mut sources[*source]) else {
+			continue;
+		};
+		match work {
+			Work::Downloaded => report.downloaded.push(path),
+			Work::Skipped => report.skipped.push(path),
+			Work::Linked => report.linked.push(path),
+			Work::Filtered => report.filtered.push(path)
+		}
+	}
+
+	for report in &mut sources {
+		report.downloaded.sort();
+		report.skipped.sort();
+		report.linked.sort();
+		report.filtered.sort();
+		report.overridden.sort();
+	}
+	Ok(crate::MultiUpgradeReport {
+		upgrade,
+		sources
+	})
+}
@@ -1,20 +1,324 @@
+use std::path::{Path, PathBuf};
+
+/// The environment variable that replaces the default minecraft path, see [minecraft_path].
+pub const MINECRAFT_PATH_VAR: &str = "GREEN_LIB_MINECRAFT_PATH";
+
 /// Gets the default minecraft path.
+/// If [MINECRAFT_PATH_VAR] is set to something other than an empty string it's returned as it is.
+/// Otherwise it's the first of [minecraft_path_candidates] that exists, or the platform default when none do.
 /// # Warning
 /// MacOS implementation is currently not tested.
-pub fn minecraft_path() -> std::path::PathBuf {
+pub fn minecraft_path() -> PathBuf {
+	if let Some(path) = override_var() {
+		return path;
+	}
+
+	minecraft_path_candidates().into_iter().find(|candidate| candidate.exists).map_or_else(platform_default, |candidate| candidate.path)
+}
+
+/// Which kind of installation a [MinecraftPathCandidate] belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinecraftInstall {
+	/// Set with [MINECRAFT_PATH_VAR].
+	Override,
+	/// Where the launcher keeps the game when it isn't sandboxed.
+	Default,
+	/// The Flatpak launcher (`com.mojang.Minecraft`) on Linux.
+	Flatpak,
+	/// The Snap launcher (`mc-installer`) on Linux.
+	Snap,
+	/// The Microsoft Store launcher on Windows, whose files are redirected into its package folder.
+	MicrosoftStore
+}
+
+/// A place the game may be installed, see [minecraft_path_candidates].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinecraftPathCandidate {
+	pub path: PathBuf,
+	pub install: MinecraftInstall,
+	/// Whether it's a directory right now.
+	pub exists: bool
+}
+
+/// # Description
+/// Lists every place the game is likely installed on this platform, in the order [minecraft_path] prefers them.
+/// [MINECRAFT_PATH_VAR] comes first when it's set, then the default location, then the sandboxed launchers.
+pub fn minecraft_path_candidates() -> Vec<MinecraftPathCandidate> {
+	let mut candidates = vec![];
+	if let Some(path) = override_var() {
+		candidates.push((path, MinecraftInstall::Override));
+	}
+	candidates.push((platform_default(), MinecraftInstall::Default));
+
+	if cfg!(target_os = "windows") {
+		if let Some(local_appdata) = std::env::var_os("LOCALAPPDATA") {
+			let package = Path::new(&local_appdata).join("Packages").join("Microsoft.4297127D64EC6_8wekyb3d8bbwe");
+			candidates.push((package.join("LocalCache").join("Roaming").join(".minecraft"), MinecraftInstall::MicrosoftStore));
+		}
+	} else if cfg!(target_os = "linux") {
+		if let Some(home) = std::env::var_os("HOME") {
+			let home = Path::new(&home);
+			candidates.push((home.join(".var").join("app").join("com.mojang.Minecraft").join(".minecraft"), MinecraftInstall::Flatpak));
+			candidates.push((home.join("snap").join("mc-installer").join("current").join(".minecraft"), MinecraftInstall::Snap));
+		}
+	}
+
+	candidates.into_iter().map(|(path, install)| MinecraftPathCandidate {
+		exists: path.is_dir(),
+		path,
+		install
+	}).collect()
+}
+
+fn override_var() -> Option<PathBuf> {
+	std::env::var_os(MINECRAFT_PATH_VAR).filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+fn platform_default() -> PathBuf {
 	if cfg!(target_os = "windows") {
 		let appdata = std::env::var("APPDATA").unwrap();
-		let appdata_path = std::path::Path::new(&appdata);
+		let appdata_path = Path::new(&appdata);
 		appdata_path.join(".minecraft")
 	} else if cfg!(target_os = "macos") {
 		let home = std::env::var("HOME").unwrap();
-		let home_path = std::path::Path::new(&home);
+		let home_path = Path::new(&home);
 		home_path.join("Library")
 			.join("Application Support")
 			.join("minecraft")
 	} else {
 		let home = std::env::var("HOME").unwrap();
-		let home_path = std::path::Path::new(&home);
+		let home_path = Path::new(&home);
 		home_path.join(".minecraft")
 	}
 }
+
+/// # Description
+/// Gets the minecraft path, preferring `override_path`, then [MINECRAFT_PATH_VAR], then the platform default.
+/// Only `override_path` is checked, it has to be an existing directory.
+pub fn minecraft_path_with_override(override_path: Option<&Path>) -> Result<PathBuf, MinecraftPathError> {
+	let Some(path) = override_path else {
+		return Ok(minecraft_path());
+	};
+
+	match std::fs::metadata(path) {
+		Ok(metadata) if metadata.is_dir() => Ok(path.to_owned()),
+		Ok(_) => Err(MinecraftPathError::NotADirectory(path.to_owned())),
+		Err(error) => Err(MinecraftPathError::Inaccessible {
+			path: path.to_owned(),
+			error
+		})
+	}
+}
+
+/// Why the path given to [minecraft_path_with_override] can't be used.
+#[derive(Debug)]
+pub enum MinecraftPathError {
+	/// It doesn't exist or can't be inspected.
+	Inaccessible {
+		path: PathBuf,
+		error: std::io::Error
+	},
+	/// It exists but isn't a directory.
+	NotADirectory(PathBuf)
+}
+
+impl std::fmt::Display for MinecraftPathError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MinecraftPathError::Inaccessible { path, error } => write!(f, "cannot use {} as the minecraft path: {error}", path.display()),
+			MinecraftPathError::NotADirectory(path) => write!(f, "cannot use {} as the minecraft path: not a directory", path.display())
+		}
+	}
+}
+
+impl std::error::Error for MinecraftPathError {}
+
+/// A Java runtime found by [find_java_installations].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaInstall {
+	/// The `java` executable (`java.exe` on Windows).
+	pub path: PathBuf,
+	/// Like `17.0.2` or `1.8.0_392`, [None] when it couldn't be found out.
+	pub version: Option<String>,
+	/// Like `x86_64` or `aarch64`, as the runtime reports it.
+	pub arch: Option<String>,
+	/// Like `Eclipse Adoptium`.
+	pub vendor: Option<String>
+}
+
+/// # Description
+/// Looks for installed Java runtimes: `JAVA_HOME`, the usual install folders of the platform and the runtimes the launcher downloaded into [minecraft_path].
+/// The version, architecture and vendor come from the `release` file of the runtime, or from running it when that file is missing.
+/// Broken installs are skipped and runtimes found in several places are only listed once.
+/// # Warning
+/// This blocks while it reads the filesystem and runs the runtimes, use `spawn_blocking` from async code.
+pub fn find_java_installations() -> Vec<JavaInstall> {
+	let mut homes = vec![];
+	if let Some(java_home) = std::env::var_os("JAVA_HOME").filter(|path| !path.is_empty()) {
+		homes.push(PathBuf::from(java_home));
+	}
+
+	let subfolders = |folder: PathBuf| std::fs::read_dir(folder).into_iter().flatten().flatten().map(|entry| entry.path());
+	if cfg!(target_os = "windows") {
+		for program_files in ["ProgramFiles", "ProgramW6432", "ProgramFiles(x86)"].into_iter().filter_map(std::env::var_os) {
+			for vendor in ["Java", "Eclipse Adoptium", "Zulu", "Microsoft", "Amazon Corretto"] {
+				homes.extend(subfolders(Path::new(&program_files).join(vendor)));
+			}
+		}
+	} else if cfg!(target_os = "macos") {
+		homes.extend(subfolders(PathBuf::from("/Library/Java/JavaVirtualMachines")).map(|bundle| bundle.join("Contents").join("Home")));
+	} else {
+		homes.extend(subfolders(PathBuf::from("/usr/lib/jvm")));
+	}
+
+	// runtime/<component>/<platform>/<component>, with a Contents/Home bundle inside on MacOS
+	for component in subfolders(minecraft_path().join("runtime")) {
+		for platform in subfolders(component) {
+			for home in subfolders(platform) {
+				homes.push(home.join("jre.bundle").join("Contents").join("Home"));
+				homes.push(home);
+			}
+		}
+	}
+
+	let mut seen = std::collections::HashSet::new();
+	homes.into_iter().filter_map(|home| {
+		let path = std::fs::canonicalize(home.join("bin").join(JAVA_EXECUTABLE)).ok().filter(|path| path.is_file())?;
+		seen.insert(path.clone()).then(|| java_install(&home, path))
+	}).collect()
+}
+
+const JAVA_EXECUTABLE: &str = if cfg!(target_os = "windows") { "java.exe" } else { "java" };
+
+/// Describes the runtime in `home`, from its `release` file or by asking it.
+fn java_install(home: &Path, path: PathBuf) -> JavaInstall {
+	let release = std::fs::read_to_string(home.join("release")).unwrap_or_default();
+	let release_value = |key: &str| release.lines().find_map(|line| {
+		let value = line.strip_prefix(key)?.strip_prefix('=')?.trim().trim_matches('"');
+		(!value.is_empty()).then(|| value.to_owned())
+	});
+
+	let mut install = JavaInstall {
+		version: release_value("JAVA_VERSION"),
+		arch: release_value("OS_ARCH"),
+		vendor: release_value("IMPLEMENTOR"),
+		path
+	};
+
+	if install.version.is_none() {
+		// every runtime since Java 7 lists its properties on stderr with this
+		let output = std::process::Command::new(&install.path)
+			.args(["-XshowSettings:properties", "-version"])
+			.stdin(std::process::Stdio::null())
+			.output();
+		let properties = output.map(|output| String::from_utf8_lossy(&output.stderr).into_owned()).unwrap_or_default();
+		let property = |key: &str| properties.lines().find_map(|line| {
+			let value = line.trim().strip_prefix(key)?.trim_start().strip_prefix('=')?.trim();
+			(!value.is_empty()).then(|| value.to_owned())
+		});
+
+		install.version = property("java.version");
+		install.arch = install.arch.or_else(|| property("os.arch"));
+		install.vendor = install.vendor.or_else(|| property("java.vendor"));
+	}
+
+	install
+}
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+	"CON", "PRN", "AUX", "NUL",
+	"COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+	"LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
+
+/// # Description
+/// Turns any string into a name that's safe as a single file or folder name on every platform.
+/// - path separators, NULs, control characters and the characters Windows doesn't allow (`<>:"/\|?*`) become `_`
+/// - trailing dots and spaces are removed
+/// - names Windows reserves for devices (`CON`, `nul.txt`, `COM1`...) get a `_` in front
+/// - a name that would end up empty (or be `.` or `..`) becomes `_`
+///
+/// Nothing else changes and nothing depends on the platform, so every machine turns the same pack name into the same folder.
+pub fn sanitize_file_name(name: &str) -> String {
+	let mut sanitized: String = name.chars().map(|character| match character {
+		'<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+		character if character.is_control() => '_',
+		character => character
+	}).collect();
+
+	sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+	if sanitized.is_empty() {
+		return "_".to_owned();
+	}
+
+	let stem = sanitized.split('.').next().unwrap().trim_end_matches(' ');
+	if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+		sanitized.insert(0, '_');
+	}
+
+	sanitized
+}
+
+/// # Description
+/// Same as [sanitize_file_name], but the result is at most `max_bytes` bytes of UTF-8 (at least 1).
+/// Longer names are cut short and end with `-` and 8 hex digits of the sha256 of `name`, so names that only differ after the cut still get different results.
+pub fn sanitize_file_name_max(name: &str, max_bytes: usize) -> String {
+	let sanitized = sanitize_file_name(name);
+	if sanitized.len() <= max_bytes {
+		return sanitized;
+	}
+
+	let hash = crate::hash::Sha256Hash::digest(name.as_bytes()).to_string();
+	let suffix = &hash[..8];
+	let Some(keep) = max_bytes.checked_sub(suffix.len() + 1).filter(|keep| *keep > 0) else {
+		return hash[..max_bytes.clamp(1, hash.len())].to_owned();
+	};
+
+	let mut end = keep;
+	while !sanitized.is_char_boundary(end) {
+		end -= 1;
+	}
+	let prefix = sanitized[..end].trim_end_matches(['.', ' ']);
+	match prefix.is_empty() {
+		true => hash[..max_bytes.min(hash.len())].to_owned(),
+		false => format!("{prefix}-{suffix}")
+	}
+}
+
+/// # Description
+/// The folder a pack is installed to in `instances`, a launcher's folder of instances.
+/// It's named after `folder_name` when the pack suggests one (see [crate::packs::ManifestMetadata::suggested_folder_name]) and after `pack_key` otherwise, made safe with [sanitize_file_name].
+pub fn instance_path(instances: &Path, pack_key: &str, folder_name: Option<&str>) -> PathBuf {
+	let name = folder_name.filter(|name| !name.trim().is_empty()).unwrap_or(pack_key);
+	instances.join(sanitize_file_name(name))
+}
+
+/// # Description
+/// Appends a `/` separated relative path to `base`, percent-encoding every segment of it with [encode_path_segment].
+/// `base` is used as it is, only a `/` is added when it doesn't end with one, so a base that's already encoded isn't encoded twice.
+///
+/// A file called `50% done #1.png` in `mods` of `https://example.com/pack` is at `https://example.com/pack/mods/50%25%20done%20%231.png`.
+/// Urls in manifests are downloaded as they are, so an encoded url keeps pointing at the same file.
+pub fn join_url(base: &str, relative_path: &str) -> String {
+	let mut url = base.trim_end_matches('/').to_owned();
+	for segment in relative_path.split('/').filter(|segment| !segment.is_empty()) {
+		url.push('/');
+		url.push_str(&encode_path_segment(segment));
+	}
+	url
+}
+
+/// # Description
+/// Percent-encodes a single path segment of a url, every byte of its UTF-8 except ASCII letters, digits and `-._~` is encoded.
+/// That includes `/`, `%`, `#`, `?` and spaces, so the segment can't be read as anything but a name.
+pub fn encode_path_segment(segment: &str) -> String {
+	let mut encoded = String::with_capacity(segment.len());
+	for byte in segment.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+			byte => encoded.push_str(&format!("%{byte:02X}"))
+		}
+	}
+	encoded
+}
@@ -0,0 +1,327 @@
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+use crate::executor::Executor;
+use crate::hash_cache;
+use crate::upgrade::LocalHasher;
+use crate::{Directory, File, Mismatch, Progress, SeparatorPolicy, Side, UpgradeOptions, UpgradeStatus, UpgradeWarning, VerificationReport, VerifyOutcome};
+
+/// How many of the matching files of a report are looked at again before a repair goes by it.
+const SPOT_CHECKS: usize = 64;
+
+/// What [verify] saw of the folder, so a repair can tell whether its report still holds, see [Directory::repair_game_folder].
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+	taken: Instant,
+	path: PathBuf,
+	manifest: [u8; 32],
+	side: Side,
+	filtered: bool,
+	/// The modification times of the nested folders that were listed for unknown entries, [None] for ones that didn't exist.
+	folders: Vec<(PathBuf, Option<SystemTime>)>,
+	/// The size and modification time of the files that matched, from before they were hashed.
+	files: Vec<(PathBuf, u64, Option<SystemTime>)>
+}
+
+/// Compares `path` to `directory` without changing anything, local files are hashed by a bounded number of blocking jobs on the executor of `options`.
+pub(crate) async fn verify(directory: &Directory, path: &Path, options: &UpgradeOptions, progress: Progress) -> VerificationReport {
+	// of the manifest as it was passed in, like a repair compares it
+	let manifest = directory.content_hash();
+	// with SeparatorPolicy::Error an upgrade wouldn't have written anything, so the keys are checked as they are
+	let separated = match options.separators {
+		SeparatorPolicy::Split => crate::normalize::separated(directory, SeparatorPolicy::Split).ok(),
+		SeparatorPolicy::Error => None
+	};
+	let directory = separated.as_deref().unwrap_or(directory);
+	let executor = Executor::of(options);
+	let (hasher, max_hashing) = LocalHasher::new(path, options).await;
+	let subtrees = crate::subtrees::Subtrees::load(path, options).await;
+	let mut report = VerificationReport {
+		not_installed: !executor.metadata(path).await.is_ok_and(|metadata| metadata.is_dir()),
+		snapshot: Some(Snapshot {
+			taken: Instant::now(),
+			path: path.to_owned(),
+			manifest,
+			side: options.side.clone(),
+			filtered: options.filter.is_some(),
+			folders: vec![],
+			files: vec![]
+		}),
+		..Default::default()
+	};
+	let mut hashing = FuturesUnordered::new();
+	let mut stack = vec![(directory, path.to_owned(), PathBuf::new())];
+
+	while let Some((directory, path, relative)) = stack.pop() {
+		if !relative.as_os_str().is_empty() && subtrees.unchanged(directory, &relative) && executor.metadata(&path).await.is_ok_and(|metadata| metadata.is_dir()) {
+			unchanged(directory, &relative, options, &mut report, &progress).await;
+			continue;
+		}
+
+		// like the upgrade, the top level isn't checked for unknown entries
+		if !relative.as_os_str().is_empty() {
+			// before listing it, so whatever changes after that changes its modification time too
+			let modified = executor.symlink_metadata(&path).await.ok().and_then(|metadata| metadata.modified().ok());
+			if let Some(snapshot) = &mut report.snapshot {
+				snapshot.folders.push((relative.clone(), modified));
+			}
+			unknown(directory, &path, &relative, options, &executor, &mut report).await;
+		}
+
+		for (name, file) in directory.files.iter().filter(|(_, file)| file.side.includes(&options.side)) {
+			let (local_path, relative) = (path.join(name), relative.join(name));
+			if !file.applies_here() {
+				progress.emit(UpgradeStatus::NotApplicable(relative.clone())).await;
+				report.not_applicable.push(relative);
+				continue;
+			}
+			if options.filter.as_ref().is_some_and(|filter| !filter.includes(&relative, file)) {
+				report.filtered.push(relative);
+				continue;
+			}
+			match executor.symlink_metadata(&local_path).await {
+				Ok(metadata) if metadata.is_file() => {},
+				_ => {
+					report.missing.push(relative);
+					continue;
+				}
+			}
+
+			while hashing.len() >= max_hashing {
+				checked(hashing.next().await.unwrap(), &mut report, &progress).await;
+			}
+
+			// a single blocking job, so it goes on while nothing polls it
+			let (hasher, file) = (hasher.clone(), file.clone());
+			hashing.push(async move {
+				let job_hasher = hasher.clone();
+				hasher.run(move || {
+					let metadata = std::fs::metadata(&local_path).ok();
+					let local_sha = job_hasher.local_sha_now(&local_path, &relative);
+					let mut checked = Checked {
+						relative,
+						file,
+						local_sha,
+						metadata,
+						damaged_chunks: vec![]
+					};
+					if checked.local_sha.as_ref().is_ok_and(|found| *found != checked.file.sha) {
+						// a file that changed since it was hashed just doesn't get its chunks pinpointed
+						if let Some(chunks) = &checked.file.chunks {
+							checked.damaged_chunks = crate::chunks::damaged(&local_path, chunks).ok().flatten().unwrap_or_default();
+						}
+					}
+					checked
+				}).await
+			});
+		}
+
+		for (name, target) in &directory.links {
+			let target: PathBuf = target.split('/').filter(|component| !component.is_empty() && *component != ".").collect();
+			match executor.read_link(&path.join(name)).await {
+				Ok(existing) if existing.components().eq(target.components()) => report.matched.push(relative.join(name)),
+				_ => report.wrong_links.push(relative.join(name))
+			}
+		}
+
+		for (name, child) in directory.children.iter().filter(|(_, child)| child.side.includes(&options.side)) {
+			stack.push((child, path.join(name), relative.join(name)));
+		}
+	}
+
+	while let Some(result) = hashing.next().await {
+		checked(result, &mut report, &progress).await;
+	}
+	hasher.save(path).await;
+
+	report.matched.sort();
+	report.mismatched.sort_by(|a, b| a.path.cmp(&b.path));
+	report.missing.sort();
+	report.unknown.sort();
+	report.kept_hidden.sort();
+	report.protected.sort();
+	report.wrong_links.sort();
+	report.filtered.sort();
+	report.not_applicable.sort();
+	report
+}
+
+/// Reports a child that was already applied as matching, without looking at the folder.
+async fn unchanged(directory: &Directory, relative: &Path, options: &UpgradeOptions, report: &mut VerificationReport, progress: &Progress) {
+	let mut stack = vec![(directory, relative.to_owned())];
+	while let Some((directory, relative)) = stack.pop() {
+		for (name, file) in directory.files.iter().filter(|(_, file)| file.side.includes(&options.side)) {
+			let relative = relative.join(name);
+			if !file.applies_here() {
+				progress.emit(UpgradeStatus::NotApplicable(relative.clone())).await;
+				report.not_applicable.push(relative);
+			} else if options.filter.as_ref().is_some_and(|filter| !filter.includes(&relative, file)) {
+				report.filtered.push(relative);
+			} else {
+				progress.emit(UpgradeStatus::Skipped(relative.clone())).await;
+				report.matched.push(relative);
+			}
+		}
+		report.matched.extend(directory.links.keys().map(|name| relative.join(name)));
+		for (name, child) in directory.children.iter().filter(|(_, child)| child.side.includes(&options.side)) {
+			stack.push((child, relative.join(name)));
+		}
+	}
+}
+
+/// A local file that was hashed, with what a mismatch is reported with.
+struct Checked {
+	relative: PathBuf,
+	file: File,
+	local_sha: std::io::Result<crate::hash::Sha256Hash>,
+	/// From before it was hashed.
+	metadata: Option<std::fs::Metadata>,
+	damaged_chunks: Vec<usize>
+}
+
+async fn checked(Checked { relative, file, local_sha, metadata, damaged_chunks }: Checked, report: &mut VerificationReport, progress: &Progress) {
+	match local_sha {
+		Ok(found) if found == file.sha => {
+			progress.emit(UpgradeStatus::Skipped(relative.clone())).await;
+			if let (Some(snapshot), Some(metadata)) = (&mut report.snapshot, &metadata) {
+				snapshot.files.push((relative.clone(), metadata.len(), metadata.modified().ok()));
+			}
+			report.matched.push(relative);
+		},
+		Ok(found) => report.mismatched.push(Mismatch {
+			path: relative,
+			expected: file.sha,
+			found,
+			size: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+			mtime: metadata.as_ref().and_then(crate::mtime::of),
+			damaged_chunks
+		}),
+		Err(error) => {
+			progress.emit(UpgradeStatus::Warning {
+				path: relative.clone(),
+				reason: error.to_string()
+			}).await;
+			report.unreadable.push(UpgradeWarning {
+				path: relative,
+				error
+			});
+		}
+	}
+}
+
+/// Lists the entries of a folder that an upgrade would delete.
+async fn unknown(directory: &Directory, path: &Path, relative: &Path, options: &UpgradeOptions, executor: &Executor, report: &mut VerificationReport) {
+	let Ok(entries) = executor.read_dir(path).await else {
+		return;
+	};
+
+	for entry in entries {
+		let name = entry.name.to_string_lossy();
+
+		if relative.as_os_str().is_empty() && name == hash_cache::STATE_DIR {
+			continue;
+		}
+
+		let known = match &entry.file_type {
+			Ok(file_type) if file_type.is_symlink() => directory.links.contains_key(name.as_ref()),
+			Ok(file_type) if file_type.is_dir() => directory.children.contains_key(name.as_ref()),
+			Ok(_) => directory.files.contains_key(name.as_ref()),
+			Err(_) => true
+		};
+
+		match known {
+			true => {},
+			false if options.protect.as_ref().is_some_and(|protect| protect.protects(&relative.join(name.as_ref()))) => report.protected.push(relative.join(name.as_ref())),
+			false if options.hidden.keeps(&name, &entry.path, executor).await => report.kept_hidden.push(relative.join(name.as_ref())),
+			false => report.unknown.push(relative.join(name.as_ref()))
+		}
+	}
+}
+
+/// Compares a single local file to `file`, see [File::verify].
+pub(crate) async fn verify_file(file: &File, path: &Path) -> std::io::Result<VerifyOutcome> {
+	let metadata = match tokio::fs::symlink_metadata(path).await {
+		Ok(metadata) if metadata.is_file() => metadata,
+		Ok(_) => return Ok(VerifyOutcome::Missing),
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(VerifyOutcome::Missing),
+		Err(error) => return Err(error)
+	};
+	if file.size.is_some_and(|size| size != metadata.len()) {
+		return Ok(VerifyOutcome::Mismatch {
+			found_sha: None,
+			size: metadata.len(),
+			mtime: crate::mtime::of(&metadata)
+		});
+	}
+
+	// without the hash cache of the folder, the file is always hashed
+	let hasher = LocalHasher::new(path, &UpgradeOptions::default()).await.0;
+	let found = hasher.local_sha(path, Path::new("")).await?;
+	Ok(match found == file.sha {
+		true => VerifyOutcome::Match,
+		false => VerifyOutcome::Mismatch {
+			found_sha: Some(found),
+			size: metadata.len(),
+			mtime: crate::mtime::of(&metadata)
+		}
+	})
+}
+
+/// What a repair does instead of scanning, built from a [VerificationReport] of the folder, see [Directory::repair_game_folder].
+pub(crate) struct Repair {
+	/// The files the report didn't find in place, they're downloaded without being hashed.
+	pub(crate) fix: HashSet<PathBuf>,
+	/// The entries the report found unknown, they're deleted like a scan would.
+	pub(crate) unknown: HashSet<PathBuf>,
+	/// The folders with unknown entries, the others aren't listed at all.
+	pub(crate) listed: HashSet<PathBuf>,
+	folders: Vec<(PathBuf, Option<SystemTime>)>,
+	sample: Vec<(PathBuf, u64, Option<SystemTime>)>
+}
+
+impl Repair {
+	/// [None] when `report` isn't a fresh one of `path` for `directory` with the side and filtering of `options`.
+	pub(crate) fn new(report: &VerificationReport, directory: &Directory, path: &Path, options: &UpgradeOptions) -> Option<Self> {
+		let snapshot = report.snapshot.as_ref()?;
+		let fresh = snapshot.taken.elapsed() <= crate::MAX_VERIFICATION_AGE
+			&& snapshot.path == path
+			&& snapshot.side == options.side
+			&& snapshot.filtered == options.filter.is_some()
+			&& snapshot.manifest == directory.content_hash();
+		if !fresh {
+			return None;
+		}
+
+		let fix = report.missing.iter()
+			.chain(report.mismatched.iter().map(|mismatch| &mismatch.path))
+			.chain(report.unreadable.iter().map(|warning| &warning.path))
+			.cloned()
+			.collect();
+		let step = snapshot.files.len().div_ceil(SPOT_CHECKS).max(1);
+		Some(Self {
+			fix,
+			unknown: report.unknown.iter().cloned().collect(),
+			listed: report.unknown.iter().filter_map(|path| path.parent()).map(Path::to_owned).collect(),
+			folders: snapshot.folders.clone(),
+			sample: snapshot.files.iter().step_by(step).cloned().collect()
+		})
+	}
+
+	/// Whether the folders and the sampled files under `path` still have the modification times and sizes the report saw.
+	pub(crate) async fn holds(&self, path: &Path) -> bool {
+		for (relative, modified) in &self.folders {
+			let now = tokio::fs::symlink_metadata(path.join(relative)).await.ok().and_then(|metadata| metadata.modified().ok());
+			if now != *modified {
+				return false;
+			}
+		}
+		for (relative, size, modified) in &self.sample {
+			match tokio::fs::metadata(path.join(relative)).await {
+				Ok(metadata) if metadata.len() == *size && metadata.modified().ok() == *modified => {},
+				_ => return false
+			}
+		}
+		true
+	}
+}
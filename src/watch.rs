@@ -0,0 +1,90 @@
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use crate::fetch::{self, Conditional, Validators};
+use crate::manifest::Manifest;
+use crate::{http, ManifestError, NoRuntime, WatchOptions, Watcher};
+
+/// Polls `url` until the [Watcher] is dropped, sending what `change` makes of every manifest that differs from the one before.
+/// `change` gets [None] as the previous manifest the first time.
+/// Outside a tokio runtime nothing is polled, [ManifestError::NoRuntime] is all the watcher hands out.
+pub(crate) fn watch<T, C, F>(url: String, interval: Duration, options: &WatchOptions, change: F) -> Watcher<C>
+where
+	T: Manifest + Send + 'static,
+	C: Send + 'static,
+	F: Fn(Option<&T>, &T) -> Option<C> + Send + 'static
+{
+	let http = options.http.as_ref().map(|config| http::Http::new(config.clone()).map(std::sync::Arc::new));
+	let (jitter, max_backoff) = (options.jitter, options.max_backoff.max(interval));
+	let (tx, receiver) = tokio::sync::mpsc::channel(1);
+	if NoRuntime::check().is_err() {
+		let _ = tx.try_send(Err(ManifestError::NoRuntime));
+		return Watcher {
+			receiver,
+			task: None
+		};
+	}
+
+	let task = tokio::spawn(async move {
+		let http = match http {
+			Some(Ok(http)) => http,
+			// without a client nothing can be polled, so the error is all the watcher hands out
+			Some(Err(error)) => {
+				let _ = tx.send(Err(ManifestError::Request(error))).await;
+				return;
+			},
+			None => http::shared()
+		};
+		let mut validators = Validators::default();
+		let mut current: Option<T> = None;
+		let mut failures = 0;
+
+		loop {
+			let event = match fetch::manifest_if_changed::<T>(&http, &url, &validators).await {
+				Ok(Conditional::NotModified) => {
+					failures = 0;
+					None
+				},
+				Ok(Conditional::Modified(manifest, new_validators)) => {
+					failures = 0;
+					validators = new_validators;
+					let event = change(current.as_ref(), &manifest).map(Ok);
+					current = Some(manifest);
+					event
+				},
+				Err(error) => {
+					failures += 1;
+					Some(Err(error))
+				}
+			};
+			if let Some(event) = event {
+				if tx.send(event).await.is_err() {
+					return;
+				}
+			}
+
+			tokio::time::sleep(jittered(backoff(interval, max_backoff, failures), jitter)).await;
+		}
+	});
+
+	Watcher {
+		receiver,
+		task: Some(task)
+	}
+}
+
+/// The interval, doubled for every failure in a row up to `max`.
+fn backoff(interval: Duration, max: Duration, failures: u32) -> Duration {
+	let factor = 2u32.saturating_pow(failures.min(16));
+	interval.saturating_mul(factor).min(max)
+}
+
+/// Moves `wait` by up to `jitter` times itself either way, so watchers started together don't keep polling together.
+fn jittered(wait: Duration, jitter: f64) -> Duration {
+	// random enough for spreading requests, without a dependency for it
+	let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+	hasher.write_u64(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos() as u64);
+	let unit = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+
+	let jitter = jitter.clamp(0.0, 1.0);
+	wait.mul_f64(1.0 - jitter + 2.0 * jitter * unit)
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::UpgradeOptions;
+
+fn contents(seed: u8, length: usize) -> Vec<u8> {
+	(0..length).map(|index| (index as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+}
+
+#[test]
+fn downloads_stay_within_the_buffer_budget() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = (0..8).fold(DirectoryBuilder::new(), |builder, index| builder.file(&format!("mods/{index}.jar"), contents(index, 2 << 20)));
+		let directory = server.serve("pack", &builder);
+		let folder = TempDir::new("buffer-budget");
+		let budget = 1 << 20;
+		let options = UpgradeOptions {
+			buffer_budget: Some(budget),
+			..Default::default()
+		};
+
+		let report = directory.upgrade_game_folder_with_options(&folder.0, &options, None).await.unwrap();
+		for index in 0..8 {
+			assert!(std::fs::read(folder.0.join(format!("mods/{index}.jar"))).unwrap() == contents(index, 2 << 20));
+		}
+		assert!(report.peak_buffered_bytes <= budget, "{} bytes were buffered at once", report.peak_buffered_bytes);
+	});
+}
+
+#[test]
+fn a_budget_smaller_than_a_download_admits_one_at_a_time() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = (0..4).fold(DirectoryBuilder::new(), |builder, index| builder.file(&format!("mods/{index}.jar"), contents(index, 1 << 20)));
+		let directory = server.serve("pack", &builder);
+		let folder = TempDir::new("buffer-budget-alone");
+		let options = UpgradeOptions {
+			buffer_budget: Some(1),
+			..Default::default()
+		};
+
+		let report = directory.upgrade_game_folder_with_options(&folder.0, &options, None).await.unwrap();
+		for index in 0..4 {
+			assert!(std::fs::read(folder.0.join(format!("mods/{index}.jar"))).unwrap() == contents(index, 1 << 20));
+		}
+		// a single download at a time holds no more than the chunk of the response it's writing
+		assert!(report.peak_buffered_bytes <= 512 << 10, "{} bytes were buffered at once", report.peak_buffered_bytes);
+	});
+}
@@ -0,0 +1,50 @@
+#![allow(dead_code)]
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Runs a test on a runtime of its own, the crate doesn't turn on tokio's macros.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+	tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(future)
+}
+
+struct Unpark(std::thread::Thread);
+
+impl Wake for Unpark {
+	fn wake(self: Arc<Self>) {
+		self.0.unpark();
+	}
+}
+
+/// Polls a future on this thread like an executor that isn't tokio would.
+pub fn block_on_without_runtime<F: Future>(future: F) -> F::Output {
+	let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+	let mut context = Context::from_waker(&waker);
+	let mut future = std::pin::pin!(future);
+	loop {
+		match future.as_mut().poll(&mut context) {
+			Poll::Ready(output) => return output,
+			Poll::Pending => std::thread::park()
+		}
+	}
+}
+
+/// An empty folder for a single test, removed when it's dropped.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+	pub fn new(name: &str) -> Self {
+		let path = std::env::temp_dir().join(format!("green-lib-test-{name}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		std::fs::create_dir_all(&path).unwrap();
+		Self(path)
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.0);
+	}
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::UpgradeError;
+
+/// `sodium-fabric-0.5.8\n` four times, gzipped without a timestamp.
+const GZIPPED: [u8; 43] = [31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 43, 206, 79, 201, 44, 205, 213, 77, 75, 76, 42, 202, 76, 214, 53, 208, 51, 213, 179, 224, 42, 166, 64, 12, 0, 125, 15, 94, 137, 80, 0, 0, 0];
+
+fn encoding_gzip(server: &FixtureServer, path: &str) {
+	server.fail(path, Failure::ContentEncoding("gzip".to_owned()));
+}
+
+// the dev-dependencies turn on reqwest's gzip feature for these
+#[test]
+fn shas_are_over_the_bytes_as_they_are_stored() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("logs/latest.log.gz", GZIPPED.to_vec()));
+		encoding_gzip(&server, "pack/logs/latest.log.gz");
+		encoding_gzip(&server, "pack/options.txt");
+
+		let folder = TempDir::new("content-encoding-stored");
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("logs/latest.log.gz")).unwrap(), GZIPPED);
+		let headers = server.request_headers("pack/logs/latest.log.gz").unwrap();
+		assert!(headers.contains(&("accept-encoding".to_owned(), "identity".to_owned())), "{headers:?}");
+	});
+}
+
+#[test]
+fn encoded_bodies_arent_decoded() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let decoded = "sodium-fabric-0.5.8\n".repeat(4);
+		// a server that encodes whatever the request accepts
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("mods/sodium.jar", decoded));
+		server.insert("pack/mods/sodium.jar", GZIPPED.to_vec());
+		encoding_gzip(&server, "pack/mods/sodium.jar");
+
+		let folder = TempDir::new("content-encoding-decoded");
+		match directory.upgrade_game_folder(&folder.0, None).await {
+			Err(UpgradeError::Download { path, .. }) => assert!(path.ends_with("mods/sodium.jar"), "{path:?}"),
+			other => panic!("expected the encoded body not to match, got {other:?}")
+		}
+		assert!(!folder.0.join("mods/sodium.jar").exists());
+	});
+}
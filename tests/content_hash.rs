@@ -0,0 +1,68 @@
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::Directory;
+
+const FILES: [(&str, &str); 5] = [
+	("options.txt", "options"),
+	("mods/sodium.jar", "sodium"),
+	("mods/lithium.jar", "lithium"),
+	("config/sodium-options.json", "{}"),
+	("resourcepacks/faithful/pack.mcmeta", "mcmeta")
+];
+
+fn pack<'a>(files: impl IntoIterator<Item = &'a (&'a str, &'a str)>) -> Directory {
+	files.into_iter().fold(DirectoryBuilder::new(), |builder, (path, contents)| builder.file(path, contents.to_string())).build("https://example.com/pack")
+}
+
+#[test]
+fn equal_directories_hash_equal() {
+	let first = pack(&FILES);
+	let reversed = pack(FILES.iter().rev());
+	assert_eq!(first, reversed);
+	assert_eq!(first.content_hash(), reversed.content_hash());
+	assert_eq!(first.content_hash(), first.clone().content_hash());
+
+	// what's downloaded from where doesn't change what ends up on disk
+	let mut moved = pack(&FILES);
+	moved.files.get_mut("options.txt").unwrap().url = Some("https://mirror.example.com/options.txt".to_owned());
+	moved.children.get_mut("mods").unwrap().files.get_mut("sodium.jar").unwrap().size = None;
+	assert_ne!(first, moved);
+	assert_eq!(first.content_hash(), moved.content_hash());
+	let inline = FILES.iter().fold(DirectoryBuilder::new(), |builder, (path, contents)| builder.inline_file(path, *contents)).build("https://example.com/pack");
+	assert_eq!(first.content_hash(), inline.content_hash());
+}
+
+#[test]
+fn changing_any_file_changes_the_hash() {
+	let original = pack(&FILES).content_hash();
+	for (index, (path, _)) in FILES.iter().enumerate() {
+		let mut files = FILES;
+		files[index] = (path, "changed");
+		assert_ne!(pack(&files).content_hash(), original, "{path}");
+
+		// and so does removing it
+		let fewer: Vec<_> = FILES.iter().filter(|(other, _)| other != path).collect();
+		assert_ne!(pack(fewer).content_hash(), original, "{path}");
+	}
+
+	let mut more = FILES.to_vec();
+	more.push(("mods/iris.jar", "iris"));
+	assert_ne!(pack(&more).content_hash(), original);
+	// the same file somewhere else
+	let mut renamed = FILES;
+	renamed[1] = ("config/sodium.jar", "sodium");
+	assert_ne!(pack(&renamed).content_hash(), original);
+
+	let mut linked = pack(&FILES);
+	linked.links.insert("latest.jar".to_owned(), "mods/sodium.jar".to_owned());
+	assert_ne!(linked.content_hash(), original);
+	let mut empty_folder = pack(&FILES);
+	empty_folder.children.insert("saves".to_owned(), Directory::default());
+	assert_ne!(empty_folder.content_hash(), original);
+}
+
+#[test]
+fn the_hash_stays_the_same_across_versions() {
+	// changing this is a breaking change for everything that keys on it
+	let hex: String = pack(&FILES).content_hash().iter().map(|byte| format!("{byte:02x}")).collect();
+	assert_eq!(hex, "c7f40c05e4766b549ebae2b2d499b9b78429b90f21509d9e1d2da7af6c68548c");
+}
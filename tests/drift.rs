@@ -0,0 +1,104 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::{Directory, DriftEvent, DriftOptions, FolderWatcher, FromPathOptions};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+fn write(folder: &TempDir, path: &str, contents: &str) {
+	let path = folder.0.join(path);
+	std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+	std::fs::write(path, contents).unwrap();
+}
+
+/// A folder and the manifest made of it, watched.
+async fn watched(name: &str) -> (TempDir, Directory, FolderWatcher) {
+	let folder = TempDir::new(name);
+	for path in ["mods/a.jar", "mods/b.jar", "config/c.toml", "config/deep/d.toml"] {
+		write(&folder, path, path);
+	}
+	let directory = Directory::from_path(&folder.0, &FromPathOptions {
+		base_url: "https://example.com/pack".to_owned(),
+		..Default::default()
+	}).await.unwrap();
+	write(&folder, "saves/world/level.dat", "level");
+
+	let watcher = directory.watch_folder(&folder.0, &DriftOptions {
+		debounce: DEBOUNCE
+	}).unwrap();
+	(folder, directory, watcher)
+}
+
+/// The inotify instances this process has open.
+#[cfg(target_os = "linux")]
+fn inotify_handles() -> usize {
+	std::fs::read_dir("/proc/self/fd").unwrap().filter_map(|entry| std::fs::read_link(entry.ok()?.path()).ok()).filter(|target| target.to_string_lossy().contains("inotify")).count()
+}
+
+/// Every event until none came for a while, sorted.
+async fn events(watcher: &mut FolderWatcher) -> Vec<DriftEvent> {
+	let mut events = vec![];
+	while let Ok(Some(event)) = tokio::time::timeout(DEBOUNCE * 5, watcher.next()).await {
+		events.push(event);
+	}
+	events.sort_by_key(|event| format!("{event:?}"));
+	events
+}
+
+#[test]
+fn changes_are_classified_once_they_settle() {
+	block_on(async {
+		let (folder, _directory, mut watcher) = watched("drift").await;
+		#[cfg(target_os = "linux")]
+		assert!(inotify_handles() > 0);
+
+		// a burst of writes is one change, and the file is only hashed once it settled
+		for index in 0..20 {
+			write(&folder, "mods/a.jar", &format!("edit {index}"));
+		}
+		// written again with what it had, its sha still matches
+		write(&folder, "mods/b.jar", "mods/b.jar");
+		std::fs::remove_file(folder.0.join("config/c.toml")).unwrap();
+		write(&folder, "mods/unknown.jar", "unknown");
+		write(&folder, "config/new/e.toml", "e");
+		// upgrades don't look at the top level
+		write(&folder, "saves/world/level.dat", "saved");
+
+		assert_eq!(events(&mut watcher).await, [
+			DriftEvent::Appeared(PathBuf::from("config/new")),
+			DriftEvent::Appeared(PathBuf::from("mods/unknown.jar")),
+			DriftEvent::Deleted(PathBuf::from("config/c.toml")),
+			DriftEvent::Modified(PathBuf::from("mods/a.jar"))
+		]);
+
+		// a directory moved away as a whole takes its files with it
+		std::fs::rename(folder.0.join("config/deep"), folder.0.join("saves/deep")).unwrap();
+		assert_eq!(events(&mut watcher).await, [DriftEvent::Deleted(PathBuf::from("config/deep/d.toml"))]);
+
+		// the only test that watches anything, so nothing else opens or closes one meanwhile
+		#[cfg(target_os = "linux")]
+		{
+			let open = inotify_handles();
+			drop(watcher);
+			// the thread reading the events closes it on its way out
+			for _ in 0..100 {
+				if inotify_handles() < open {
+					break;
+				}
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+			assert_eq!(inotify_handles(), open - 1);
+		}
+	});
+}
+
+#[test]
+fn folders_that_cant_be_watched_fail() {
+	block_on(async {
+		let folder = TempDir::new("drift-missing");
+		let error = Directory::default().watch_folder(&folder.0.join("missing"), &DriftOptions::default()).unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+	});
+}
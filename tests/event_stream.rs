@@ -0,0 +1,85 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{UpgradeStatus, UPGRADE_STATUS_SCHEMA_VERSION};
+
+/// The events of the upgrade in [a_full_upgrade_streams_these_events] as JSON lines, in one of the orders they can come in.
+/// Changing how any of them looks means raising [UPGRADE_STATUS_SCHEMA_VERSION].
+const STREAM: &str = r#"{"type":"entering_directory","data":"mods"}
+{"type":"entering_directory","data":"config"}
+{"type":"leaving_directory","data":"mods"}
+{"type":"deleted","data":"config/unknown.toml"}
+{"type":"leaving_directory","data":"config"}
+{"type":"started","data":"options.txt"}
+{"type":"started","data":"mods/retried.jar"}
+{"type":"started","data":"mods/a.jar"}
+{"type":"started","data":"config/inline.toml"}
+{"type":"progressed","data":["config/inline.toml",6]}
+{"type":"finished","data":"config/inline.toml"}
+{"type":"tick"}
+{"type":"skipped","data":"config/kept.toml"}
+{"type":"length","data":4}
+{"type":"progressed","data":["options.txt",7]}
+{"type":"finished","data":"options.txt"}
+{"type":"tick"}
+{"type":"retrying","data":{"path":"mods/retried.jar","attempt":2,"max_attempts":5,"delay":{"secs":0,"nanos":250000000},"reason":{"server_error":503}}}
+{"type":"progressed","data":["mods/a.jar",1]}
+{"type":"finished","data":"mods/a.jar"}
+{"type":"tick"}
+{"type":"progressed","data":["mods/retried.jar",7]}
+{"type":"finished","data":"mods/retried.jar"}
+{"type":"tick"}"#;
+
+fn sorted<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+	let mut lines: Vec<_> = lines.into_iter().collect();
+	lines.sort();
+	lines
+}
+
+#[test]
+fn a_full_upgrade_streams_these_events() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		// the check whether the network is up goes to options.txt, which is the only file at the top with a url
+		let directory = server.serve("pack", &DirectoryBuilder::new()
+			.file("options.txt", "options")
+			.file("mods/a.jar", "a")
+			.file("mods/retried.jar", "retried")
+			.inline_file("config/inline.toml", "inline")
+			.file("config/kept.toml", "kept"));
+		server.fail("pack/mods/retried.jar", Failure::Status {
+			status: 503,
+			count: 1
+		});
+		let folder = TempDir::new("event-stream");
+		std::fs::create_dir_all(folder.0.join("config")).unwrap();
+		std::fs::write(folder.0.join("config/kept.toml"), "kept").unwrap();
+		std::fs::write(folder.0.join("config/unknown.toml"), "unknown").unwrap();
+
+		let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+		let received = tokio::spawn(async move {
+			let mut lines = vec![];
+			while let Some(event) = rx.recv().await {
+				lines.push(serde_json::to_string(&event).unwrap());
+			}
+			lines
+		});
+		directory.upgrade_game_folder(&folder.0, Some(tx)).await.unwrap();
+		let lines = received.await.unwrap();
+
+		assert_eq!(sorted(lines.iter().map(String::as_str)), sorted(STREAM.lines()));
+	});
+}
+
+#[test]
+fn streamed_events_read_back_the_same() {
+	assert_eq!(UPGRADE_STATUS_SCHEMA_VERSION, 1);
+	for line in STREAM.lines() {
+		let event: UpgradeStatus = serde_json::from_str(line).unwrap();
+		assert_eq!(serde_json::to_string(&event).unwrap(), line);
+	}
+
+	// events of a newer schema fail to parse, readers skip them
+	assert!(serde_json::from_str::<UpgradeStatus>(r#"{"type":"from_a_newer_version","data":"mods/a.jar"}"#).is_err());
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::packs::PacksListManifest;
+use green_lib::{Directory, DownloadError, ManifestError, UpgradeError, UpgradeOptions};
+
+#[test]
+fn served_manifests_fetch_as_they_were_built() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = DirectoryBuilder::new().file("mods/a.jar", "a").inline_file("config/b.toml", "b");
+		let directory = server.serve("pack", &builder);
+
+		assert_eq!(Directory::try_from_url(server.url("pack/manifest.json")).await.unwrap(), directory);
+		assert_eq!(directory, builder.build(&server.url("pack")));
+		assert_eq!(directory.file("mods/a.jar".as_ref()).unwrap().url.as_deref(), Some(server.url("pack/mods/a.jar").as_str()));
+
+		match Directory::try_from_url(server.url("pack/missing.json")).await {
+			Err(ManifestError::Status(404)) => {},
+			other => panic!("expected a 404, got {other:?}")
+		}
+	});
+}
+
+#[test]
+fn served_packs_lists_point_at_their_manifest() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = DirectoryBuilder::new().file("mods/a.jar", "a");
+		let directory = server.serve("pack", &builder);
+		let list = builder.packs_list("pack", &server.url("pack"), &server.url("pack/manifest.json"));
+		server.insert("packs.json", list.to_string_canonical().unwrap());
+
+		let list = PacksListManifest::try_from_url(server.url("packs.json")).await.unwrap();
+		let pack = list.get_featured_pack_metadata().unwrap();
+		assert_eq!(pack.try_to_directory().await.unwrap(), directory);
+	});
+}
+
+#[test]
+fn upgrades_download_once_and_retry_failures() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("mods/b.jar", "b"));
+		// one of them might go to the check whether the network is up, which doesn't retry
+		server.fail("pack/mods/b.jar", Failure::Status {
+			status: 503,
+			count: 2
+		});
+		let folder = TempDir::new("fixture-upgrade");
+
+		let report = directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+		assert_eq!(std::fs::read(folder.0.join("mods/b.jar")).unwrap(), b"b");
+		assert!(matches!(report.metrics.retries.server_error, 1 | 2), "{:?}", report.metrics.retries);
+		let requests = (server.requests("pack/mods/a.jar"), server.requests("pack/mods/b.jar"));
+
+		// nothing changed, so nothing is requested again
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!((server.requests("pack/mods/a.jar"), server.requests("pack/mods/b.jar")), requests);
+	});
+}
+
+#[test]
+fn upgrades_fail_on_bytes_that_never_match() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a"));
+		server.fail("pack/mods/a.jar", Failure::WrongBytes("b".into()));
+		let folder = TempDir::new("fixture-mismatch");
+
+		match directory.upgrade_game_folder(&folder.0, None).await {
+			Err(UpgradeError::Download { error: DownloadError::ShaMismatch { .. }, .. }) => {},
+			other => panic!("expected a sha mismatch, got {other:?}")
+		}
+		assert!(!folder.0.join("mods/a.jar").exists());
+	});
+}
+
+#[test]
+fn upgrades_reuse_connections() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = (0..300).fold(DirectoryBuilder::new(), |builder, index| builder.file(&format!("scripts/{index}.js"), format!("script {index}")));
+		let directory = server.serve("pack", &builder);
+		let folder = TempDir::new("fixture-connections");
+
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("scripts/299.js")).unwrap(), b"script 299");
+		// about one for every download running at once, not one for every file
+		assert!(server.connections() <= 2 * green_lib::DEFAULT_CONCURRENT_DOWNLOADS, "{} connections", server.connections());
+	});
+}
+
+#[test]
+fn single_files_download_to_any_path() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("mods/b.jar", "b"));
+		let folder = TempDir::new("fixture-single");
+
+		let dest = folder.0.join("elsewhere/a.jar");
+		directory.file("mods/a.jar".as_ref()).unwrap().download_to(&dest, &UpgradeOptions::default(), None).await.unwrap();
+		assert_eq!(std::fs::read(dest).unwrap(), b"a");
+
+		directory.download_single("mods/b.jar", &folder.0, &UpgradeOptions::default(), None).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("mods/b.jar")).unwrap(), b"b");
+		assert!(!folder.0.join("mods/a.jar").exists());
+		match directory.download_single("mods/c.jar", &folder.0, &UpgradeOptions::default(), None).await {
+			Err(UpgradeError::NotInManifest(_)) => {},
+			other => panic!("expected UpgradeError::NotInManifest, got {other:?}")
+		}
+	});
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::{Directory, ExcludeSource, Excluded, FromPathOptions, IGNORE_FILE};
+use std::path::PathBuf;
+
+const IGNORED: &str = "# editor and backup files
+*.bak
+*.swp
+!mods/keep.bak
+
+.git/
+/build/
+";
+
+fn write(folder: &TempDir, path: &str) {
+	let path = folder.0.join(path);
+	std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+	std::fs::write(path, "contents").unwrap();
+}
+
+fn excluded(path: &str, pattern: &str, source: ExcludeSource) -> Excluded {
+	Excluded {
+		path: PathBuf::from(path),
+		pattern: pattern.to_owned(),
+		source
+	}
+}
+
+/// Every file of a manifest by its `/` separated path, sorted.
+fn paths(directory: &Directory) -> Vec<String> {
+	fn walk(directory: &Directory, prefix: &str, paths: &mut Vec<String>) {
+		paths.extend(directory.files.keys().map(|name| format!("{prefix}{name}")));
+		for (name, child) in &directory.children {
+			walk(child, &format!("{prefix}{name}/"), paths);
+		}
+	}
+
+	let mut paths = vec![];
+	walk(directory, "", &mut paths);
+	paths.sort();
+	paths
+}
+
+#[test]
+fn options_come_after_the_ignore_file() {
+	block_on(async {
+		let folder = TempDir::new("greenignore");
+		std::fs::write(folder.0.join(IGNORE_FILE), IGNORED).unwrap();
+		for path in [".git/config", ".options.txt.swp", "build/pack.sh", "config/a.toml", "config/build/b.toml", "config/restored.bak", "config/dropped.bak", "mods/a.jar", "mods/a.jar.bak", "mods/keep.bak", "mods/also.bak"] {
+			write(&folder, path);
+		}
+		let options = FromPathOptions {
+			base_url: "https://example.com/pack".to_owned(),
+			// brings back what the file leaves out, and leaves out what it brings back
+			exclude: vec!["!config/restored.bak".to_owned(), "mods/a*.bak".to_owned()],
+			..Default::default()
+		};
+
+		let (directory, report) = Directory::from_path_with_report(&folder.0, &options).await.unwrap();
+		assert_eq!(paths(&directory), ["config/a.toml", "config/build/b.toml", "config/restored.bak", "mods/a.jar", "mods/keep.bak"]);
+		assert_eq!(report.excluded, [
+			excluded(".git", ".git/", ExcludeSource::IgnoreFile { line: 6 }),
+			excluded(".options.txt.swp", "*.swp", ExcludeSource::IgnoreFile { line: 3 }),
+			excluded("build", "/build/", ExcludeSource::IgnoreFile { line: 7 }),
+			excluded("config/dropped.bak", "*.bak", ExcludeSource::IgnoreFile { line: 2 }),
+			excluded("mods/a.jar.bak", "mods/a*.bak", ExcludeSource::Options),
+			excluded("mods/also.bak", "mods/a*.bak", ExcludeSource::Options)
+		]);
+
+		// without the file only the options apply
+		std::fs::remove_file(folder.0.join(IGNORE_FILE)).unwrap();
+		let (directory, report) = Directory::from_path_with_report(&folder.0, &options).await.unwrap();
+		assert_eq!(directory.files.len(), 1, "{:?}", directory.files.keys());
+		assert!(directory.children.contains_key(".git") && directory.children.contains_key("build"));
+		assert_eq!(report.excluded.len(), 2, "{:?}", report.excluded);
+	});
+}
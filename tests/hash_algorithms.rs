@@ -0,0 +1,90 @@
+mod common;
+
+use common::block_on;
+use green_lib::hash::HashAlgorithm;
+use green_lib::packs::{PacksListBuilder, PacksListManifest};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::ManifestError;
+
+#[test]
+fn sha256_digests_have_no_prefix() {
+	let digest = HashAlgorithm::Sha256.digest("hello");
+	assert_eq!(digest, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+	assert_eq!(HashAlgorithm::split(&digest).unwrap(), (HashAlgorithm::Sha256, digest.as_str()));
+}
+
+#[test]
+fn other_digests_are_prefixed() {
+	let digest = HashAlgorithm::Sha512.digest("hello");
+	let (algorithm, hex) = HashAlgorithm::split(&digest).unwrap();
+	assert_eq!(algorithm, HashAlgorithm::Sha512);
+	assert_eq!(hex.len(), 128);
+	assert!(digest.starts_with("sha512:"));
+}
+
+#[test]
+fn unknown_algorithms_are_unsupported() {
+	let error = HashAlgorithm::split("md5:00").unwrap_err();
+	assert_eq!(error.algorithm, "md5");
+	assert!(error.to_string().contains("newer green-lib"));
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn blake3_digests() {
+	assert_eq!(HashAlgorithm::Blake3.digest(""), "blake3:af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+	assert_eq!(HashAlgorithm::split("BLAKE3:00").unwrap().0, HashAlgorithm::Blake3);
+}
+
+/// Serves a pack and lists it with its manifest hashed by `algorithm`.
+async fn served(server: &FixtureServer, algorithm: HashAlgorithm) -> PacksListManifest {
+	let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a"));
+	PacksListBuilder::new()
+		.hash_algorithm(algorithm)
+		.add_pack("pack", "Pack", &server.url("pack/manifest.json"), &directory)
+		.build()
+		.unwrap()
+}
+
+#[test]
+fn manifests_are_checked_with_their_algorithm() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		#[cfg_attr(not(feature = "blake3"), allow(unused_mut))]
+		let mut algorithms = vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+		#[cfg(feature = "blake3")]
+		algorithms.push(HashAlgorithm::Blake3);
+
+		for algorithm in algorithms {
+			let list = served(&server, algorithm).await;
+			let directory = list.packs["pack"].to_directory_with(&green_lib::http::config()).await.unwrap();
+			assert!(directory.files.is_empty());
+			assert!(directory.children["mods"].files.contains_key("a.jar"));
+		}
+	});
+}
+
+#[test]
+fn tampered_manifests_are_rejected() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let list = served(&server, HashAlgorithm::Sha512).await;
+		let tampered = DirectoryBuilder::new().file("mods/b.jar", "b").build(&server.url("pack"));
+		server.insert("pack/manifest.json", tampered.to_string_canonical().unwrap());
+		assert!(list.packs["pack"].to_directory_with(&green_lib::http::config()).await.is_err());
+	});
+}
+
+#[test]
+fn unknown_algorithms_need_a_newer_green_lib() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		server.serve("pack", &DirectoryBuilder::new().file("a", "a"));
+		let list = format!(r#"{{"packs": {{"pack": {{"display_name": "Pack", "manifest_url": "{}", "manifest_sha": "md5:0123456789abcdef"}}}}}}"#, server.url("pack/manifest.json"));
+		let list = PacksListManifest::from_slice(list.as_bytes()).unwrap();
+		match list.packs["pack"].to_directory_with(&green_lib::http::config()).await {
+			Err(ManifestError::UnsupportedHash(error)) => assert_eq!(error.algorithm, "md5"),
+			other => panic!("expected UnsupportedHash, got {other:?}")
+		}
+	});
+}
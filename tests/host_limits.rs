@@ -0,0 +1,68 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{Directory, DownloadLimiter, HostLimits, UpgradeOptions};
+use std::time::Duration;
+
+const FILES: usize = 6;
+
+/// A pack with files from the same server under two names, `127.0.0.1` for `cdn` and `localhost` for `mirror`, which take a while to be answered so the downloads overlap.
+fn serve(server: &FixtureServer) -> Directory {
+	let builder = (0..FILES).fold(DirectoryBuilder::new(), |builder, index| builder.file(&format!("cdn/{index}.jar"), format!("cdn {index}")).file(&format!("mirror/{index}.jar"), format!("mirror {index}")));
+	for (path, contents) in builder.blobs() {
+		server.fail(&path, Failure::Slow(Duration::from_millis(100)));
+		server.insert(&path, contents);
+	}
+
+	let mut directory = builder.build(&server.url(""));
+	for file in directory.children.get_mut("mirror").unwrap().files.values_mut() {
+		file.url = file.url.as_ref().map(|url| url.replacen("127.0.0.1", "localhost", 1));
+	}
+	directory
+}
+
+async fn upgrade(directory: &Directory, limiter: DownloadLimiter, name: &str) {
+	let folder = TempDir::new(name);
+	let options = UpgradeOptions {
+		download_limiter: Some(limiter),
+		..Default::default()
+	};
+	let report = directory.upgrade_game_folder_with_options(&folder.0, &options, None).await.unwrap();
+	assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+	for index in 0..FILES {
+		assert!(folder.0.join(format!("mirror/{index}.jar")).exists());
+	}
+}
+
+#[test]
+fn hosts_are_capped_on_their_own() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let limiter = DownloadLimiter::with_host_limits(8, HostLimits {
+			hosts: [("LocalHost".to_owned(), 2)].into(),
+			..Default::default()
+		});
+		upgrade(&serve(&server), limiter, "host-limits-override").await;
+
+		assert_eq!(server.most_concurrent_requests_to("localhost"), 2);
+		// the others are only capped by the whole limiter
+		assert!(server.most_concurrent_requests_to("127.0.0.1") > 2, "{}", server.most_concurrent_requests_to("127.0.0.1"));
+		assert!(server.most_concurrent_requests() <= 8, "{}", server.most_concurrent_requests());
+	});
+}
+
+#[test]
+fn overrides_come_before_the_cap_for_every_host() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let limiter = DownloadLimiter::with_host_limits(8, HostLimits {
+			per_host: Some(3),
+			hosts: [("localhost".to_owned(), 1)].into()
+		});
+		upgrade(&serve(&server), limiter, "host-limits-default").await;
+
+		assert_eq!(server.most_concurrent_requests_to("localhost"), 1);
+		assert_eq!(server.most_concurrent_requests_to("127.0.0.1"), 3);
+	});
+}
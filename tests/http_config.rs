@@ -0,0 +1,44 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{UpgradeError, UpgradeOptions};
+
+/// Settings reqwest can't build a client from.
+fn invalid() -> green_lib::http::HttpConfig {
+	let mut config = green_lib::http::config();
+	config.proxy.all = Some("not a proxy url".to_owned());
+	config
+}
+
+#[test]
+fn invalid_http_settings_fail_the_upgrade() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a"));
+		let folder = TempDir::new("invalid-http-settings");
+		let options = UpgradeOptions {
+			http: Some(invalid()),
+			..Default::default()
+		};
+		match directory.upgrade_game_folder_with_options(&folder.0, &options, None).await {
+			Err(UpgradeError::Client(_)) => {},
+			other => panic!("expected UpgradeError::Client, got {other:?}")
+		}
+		assert!(!folder.0.join("mods").exists());
+	});
+}
+
+#[test]
+fn invalid_http_settings_fail_single_downloads() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("a", "a"));
+		let folder = TempDir::new("invalid-http-settings-single");
+		let options = UpgradeOptions {
+			http: Some(invalid()),
+			..Default::default()
+		};
+		assert!(matches!(directory.download_single("a", &folder.0, &options, None).await, Err(UpgradeError::Client(_))));
+	});
+}
@@ -0,0 +1,96 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::UpgradeError;
+use std::io::BufRead;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// Killed when dropped, so a failing test doesn't leave it behind, along with what's left of its output.
+struct Running(Child, #[allow(dead_code)] Option<std::io::BufReader<std::process::ChildStdout>>);
+
+impl Drop for Running {
+	fn drop(&mut self) {
+		let _ = self.0.kill();
+		let _ = self.0.wait();
+	}
+}
+
+/// Upgrades a folder that already has `prepare` done to it, returns why that failed.
+fn upgrade(name: &str, prepare: impl FnOnce(&Path) -> Running) -> Option<UpgradeError> {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a"));
+		let folder = TempDir::new(name);
+		let _running = prepare(&folder.0);
+		directory.upgrade_game_folder(&folder.0, None).await.err()
+	})
+}
+
+#[test]
+fn a_process_working_in_the_folder_isnt_a_game() {
+	let result = upgrade("in-use-cwd", |folder| Running(Command::new("sleep").arg("30").current_dir(folder).spawn().unwrap(), None));
+	assert!(result.is_none(), "{result:?}");
+}
+
+#[test]
+fn an_open_file_means_the_folder_is_in_use() {
+	let result = upgrade("in-use-open", |folder| {
+		std::fs::create_dir_all(folder.join("logs")).unwrap();
+		let log = std::fs::File::create(folder.join("logs/latest.log")).unwrap();
+		Running(Command::new("sleep").arg("30").stdout(log).spawn().unwrap(), None)
+	});
+	assert!(matches!(result, Some(UpgradeError::InUse { .. })), "{result:?}");
+}
+
+#[test]
+fn a_locked_world_means_the_folder_is_in_use() {
+	let result = upgrade("in-use-locked", |folder| {
+		// locked through a hardlink outside of the folder, /proc doesn't show the holder having anything in it open
+		let lock = folder.with_extension("session.lock");
+		std::fs::write(&lock, "").unwrap();
+		std::fs::create_dir_all(folder.join("saves/world")).unwrap();
+		std::fs::hard_link(&lock, folder.join("saves/world/session.lock")).unwrap();
+
+		let mut child = Command::new(std::env::current_exe().unwrap())
+			.args(["--exact", "hold_session_lock", "--ignored", "--nocapture"])
+			.env("GREEN_LIB_TEST_LOCK", &lock)
+			.stdout(Stdio::piped())
+			.spawn()
+			.unwrap();
+		// after the test harness announced the test on the same line
+		let mut output = std::io::BufReader::new(child.stdout.take().unwrap());
+		let mut line = String::new();
+		while !line.trim_end().ends_with("locked") {
+			line.clear();
+			assert_ne!(output.read_line(&mut line).unwrap(), 0, "the lock holder stopped");
+		}
+		// the hardlink in the folder keeps the locked file around
+		std::fs::remove_file(&lock).unwrap();
+		Running(child, Some(output))
+	});
+	assert!(matches!(result, Some(UpgradeError::InUse { .. })), "{result:?}");
+}
+
+/// Run by [a_locked_world_means_the_folder_is_in_use] in a process of its own, fcntl locks of the test process itself aren't reported to it.
+#[test]
+#[ignore]
+fn hold_session_lock() {
+	use std::os::fd::AsRawFd;
+
+	let Some(path) = std::env::var_os("GREEN_LIB_TEST_LOCK") else {
+		return;
+	};
+	let file = std::fs::OpenOptions::new().read(true).write(true).open(path).unwrap();
+	// SAFETY: flock is plain data, all zeroes is a valid value for it
+	let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+	lock.l_type = libc::F_WRLCK as libc::c_short;
+	lock.l_whence = libc::SEEK_SET as libc::c_short;
+	// SAFETY: the descriptor stays open until the file is dropped and F_SETLK only reads `lock`
+	assert_eq!(unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &lock) }, 0);
+	println!("locked");
+	std::thread::sleep(std::time::Duration::from_secs(30));
+}
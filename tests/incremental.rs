@@ -0,0 +1,90 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::{Directory, FromPathOptions, ScanCache};
+use std::time::{Duration, SystemTime};
+
+fn write(folder: &TempDir, path: &str, contents: &[u8]) {
+	let path = folder.0.join(path);
+	std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+	std::fs::write(path, contents).unwrap();
+}
+
+fn set_mtime(folder: &TempDir, path: &str, mtime: SystemTime) {
+	std::fs::File::options().write(true).open(folder.0.join(path)).unwrap().set_modified(mtime).unwrap();
+}
+
+fn options() -> FromPathOptions {
+	FromPathOptions {
+		base_url: "https://example.com/pack".to_owned(),
+		chunks_above: Some(1024),
+		chunk_size: Some(1024),
+		subtree_hashes: true,
+		..Default::default()
+	}
+}
+
+#[test]
+fn incremental_scans_match_full_ones() {
+	block_on(async {
+		let folder = TempDir::new("incremental-folder");
+		let caches = TempDir::new("incremental-caches");
+		let big: Vec<u8> = (0..5000).map(|index| (index % 253) as u8).collect();
+		write(&folder, "options.txt", b"options");
+		write(&folder, "mods/a.jar", b"a");
+		write(&folder, "mods/big.jar", &big);
+		write(&folder, "config/a.toml", b"gone soon");
+		let options = options();
+
+		let (first, report, cache) = Directory::from_path_incremental(&folder.0, &options, &Directory::default(), &ScanCache::default()).await.unwrap();
+		assert_eq!(report.reused, 0);
+		assert_eq!(first, Directory::from_path(&folder.0, &options).await.unwrap());
+		cache.save(&caches.0.join("scan")).await.unwrap();
+
+		// nothing changed, nothing is read
+		let cache = ScanCache::load(&caches.0.join("scan")).await;
+		let (unchanged, report, _) = Directory::from_path_incremental(&folder.0, &options, &first, &cache).await.unwrap();
+		assert_eq!(report.reused, 4);
+		assert_eq!(unchanged, first);
+
+		// a new file, a removed one, one that grew and one with the same size and a new modification time
+		write(&folder, "mods/new.jar", b"new");
+		std::fs::remove_file(folder.0.join("config/a.toml")).unwrap();
+		write(&folder, "options.txt", b"options, changed");
+		let mut changed = big.clone();
+		changed[4000] ^= 0xff;
+		write(&folder, "mods/big.jar", &changed);
+		set_mtime(&folder, "mods/big.jar", SystemTime::now() + Duration::from_secs(60));
+		let (incremental, report, cache) = Directory::from_path_incremental(&folder.0, &options, &unchanged, &cache).await.unwrap();
+		assert_eq!(report.reused, 1);
+		assert_eq!(incremental, Directory::from_path(&folder.0, &options).await.unwrap());
+		assert_ne!(incremental, first);
+
+		// and that cache is the one for the folder as it is now
+		let (again, report, _) = Directory::from_path_incremental(&folder.0, &options, &incremental, &cache).await.unwrap();
+		assert_eq!(report.reused, 4);
+		assert_eq!(again, incremental);
+	});
+}
+
+#[test]
+fn an_empty_cache_hashes_everything_again() {
+	block_on(async {
+		let folder = TempDir::new("incremental-paranoid");
+		write(&folder, "mods/a.jar", b"a");
+		let options = options();
+		let (first, _, cache) = Directory::from_path_incremental(&folder.0, &options, &Directory::default(), &ScanCache::default()).await.unwrap();
+
+		// a change that lies about its modification time
+		let mtime = std::fs::metadata(folder.0.join("mods/a.jar")).unwrap().modified().unwrap();
+		write(&folder, "mods/a.jar", b"b");
+		set_mtime(&folder, "mods/a.jar", mtime);
+		let (trusting, _, _) = Directory::from_path_incremental(&folder.0, &options, &first, &cache).await.unwrap();
+		assert_eq!(trusting, first);
+
+		let (paranoid, report, _) = Directory::from_path_incremental(&folder.0, &options, &first, &ScanCache::default()).await.unwrap();
+		assert_eq!(report.reused, 0);
+		assert_eq!(paranoid, Directory::from_path(&folder.0, &options).await.unwrap());
+		assert_ne!(paranoid, first);
+	});
+}
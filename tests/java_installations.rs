@@ -0,0 +1,64 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::TempDir;
+use green_lib::util::{find_java_installations, JavaInstall, MINECRAFT_PATH_VAR};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Fakes a runtime in `home`, with a `release` file when it's given and a `java` that only knows how to list its properties otherwise.
+fn fake_runtime(home: &Path, release: Option<&str>) -> PathBuf {
+	std::fs::create_dir_all(home.join("bin")).unwrap();
+	let java = home.join("bin/java");
+	match release {
+		Some(release) => {
+			std::fs::write(home.join("release"), release).unwrap();
+			std::fs::write(&java, "#!/bin/sh\nexit 1\n").unwrap();
+		},
+		None => std::fs::write(&java, "#!/bin/sh\necho 'Property settings:' >&2\necho '    java.vendor = Microsoft' >&2\necho '    java.version = 21.0.1' >&2\necho '    os.arch = aarch64' >&2\n").unwrap()
+	}
+	std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+	std::fs::canonicalize(java).unwrap()
+}
+
+// a single test, the environment is shared by every test of a file
+#[test]
+fn runtimes_are_found_in_java_home_and_the_minecraft_folder() {
+	let temp = TempDir::new("java-installations");
+	let java_home = temp.0.join("jdk-17");
+	let jdk = fake_runtime(&java_home, Some("IMPLEMENTOR=\"Eclipse Adoptium\"\nJAVA_VERSION=\"17.0.2\"\nOS_ARCH=\"x86_64\"\n"));
+
+	// runtime/<component>/<platform>/<component> like the launcher downloads them
+	let minecraft = temp.0.join(".minecraft");
+	let runtimes = minecraft.join("runtime");
+	let gamma = fake_runtime(&runtimes.join("java-runtime-gamma/linux/java-runtime-gamma"), None);
+	// one without its executable, and one that's the runtime of JAVA_HOME again
+	std::fs::create_dir_all(runtimes.join("jre-legacy/linux/jre-legacy/lib")).unwrap();
+	std::fs::create_dir_all(runtimes.join("java-runtime-alpha/linux")).unwrap();
+	std::os::unix::fs::symlink(&java_home, runtimes.join("java-runtime-alpha/linux/java-runtime-alpha")).unwrap();
+
+	std::env::set_var("JAVA_HOME", &java_home);
+	std::env::set_var(MINECRAFT_PATH_VAR, &minecraft);
+	let root = std::fs::canonicalize(&temp.0).unwrap();
+	// the ones really installed on this machine don't matter
+	let mut found: Vec<_> = find_java_installations().into_iter().filter(|install| install.path.starts_with(&root)).collect();
+	found.sort_by(|a, b| a.path.cmp(&b.path));
+
+	let mut expected = vec![
+		JavaInstall {
+			path: jdk,
+			version: Some("17.0.2".to_owned()),
+			arch: Some("x86_64".to_owned()),
+			vendor: Some("Eclipse Adoptium".to_owned())
+		},
+		JavaInstall {
+			path: gamma,
+			version: Some("21.0.1".to_owned()),
+			arch: Some("aarch64".to_owned()),
+			vendor: Some("Microsoft".to_owned())
+		}
+	];
+	expected.sort_by(|a, b| a.path.cmp(&b.path));
+	assert_eq!(found, expected);
+}
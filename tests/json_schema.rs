@@ -0,0 +1,117 @@
+use green_lib::packs::{InstallHint, InstallLocation, PacksListBuilder, PacksListManifest};
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::{Directory, DirectoryRef, Side};
+use jsonschema::JSONSchema;
+use serde_json::{json, Value};
+
+fn compile(schema: schemars::schema::RootSchema) -> JSONSchema {
+	JSONSchema::compile(&serde_json::to_value(schema).unwrap()).unwrap()
+}
+
+fn assert_valid(schema: &JSONSchema, instance: &Value) {
+	if let Err(errors) = schema.validate(instance) {
+		let errors: Vec<_> = errors.map(|error| format!("{} at {}", error, error.instance_path)).collect();
+		panic!("{errors:#?} in {instance:#}");
+	}
+}
+
+/// A manifest with as many of the optional fields as possible set.
+fn sample_manifest() -> Directory {
+	let mut directory = DirectoryBuilder::new()
+		.file("options.txt", "options")
+		.file("mods/big.jar", vec![7; 3000])
+		.inline_file("config/inline.toml", "inline")
+		.chunk_size(1024)
+		.build("https://example.com/pack");
+	directory.min_green_lib = Some("0.7.0".to_owned());
+	directory.links.insert("latest.jar".to_owned(), "mods/big.jar".to_owned());
+	let mods = directory.children.get_mut("mods").unwrap();
+	mods.side = Side::Client;
+	let big = mods.files.get_mut("big.jar").unwrap();
+	big.mtime = Some(1_700_000_000);
+	big.side = Side::Server;
+	big.os = vec!["linux".to_owned()];
+	big.arch = vec!["x86_64".to_owned()];
+	big.headers.insert("x-pack".to_owned(), "example".to_owned());
+	big.query = vec![("token".to_owned(), "abc".to_owned())];
+	directory.children.insert("templated".to_owned(), DirectoryBuilder::new().file("a.zip", "a").build("https://example.com/pack/templated"));
+	let templated = directory.children.get_mut("templated").unwrap();
+	templated.url_template = Some("https://cdn.example.com/{sha:0..2}/{sha}".to_owned());
+	templated.files.get_mut("a.zip").unwrap().url = None;
+	directory.children.insert("shaderpacks".to_owned(), Directory {
+		reference: Some(DirectoryRef {
+			url: "https://example.com/pack/shaderpacks.json".to_owned(),
+			sha: "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb".to_owned(),
+			extra: Default::default()
+		}),
+		..Default::default()
+	});
+	directory.set_subtree_hashes();
+	directory.extra.insert("x-generator".to_owned(), json!("packer"));
+	directory.validate().unwrap();
+	directory
+}
+
+/// A packs list with as many of the optional fields as possible set, as the crate writes it.
+fn sample_list() -> Value {
+	let manifest = sample_manifest();
+	let list = PacksListBuilder::new()
+		.add_pack("pack", "Pack", "https://example.com/pack/manifest.json", &manifest)
+		.add_pack("bare", "Bare", "https://example.com/bare/manifest.json", &Directory::default())
+		.edit_pack("pack", |metadata| {
+			metadata.description = Some("A pack".to_owned());
+			metadata.display_name_localized.insert("de".to_owned(), "Paket".to_owned());
+			metadata.description_localized.insert("de".to_owned(), "Ein Paket".to_owned());
+			metadata.icon_url = Some("https://example.com/pack/icon.png".to_owned());
+			metadata.tags = vec!["performance".to_owned()];
+			metadata.sort_weight = Some(-1);
+			metadata.channel = Some("beta".to_owned());
+			metadata.notice = Some("Read this".to_owned());
+			metadata.requires_acceptance = true;
+			metadata.min_launcher_version = Some("1.2.0".to_owned());
+			metadata.install_hint = Some(InstallHint {
+				location: InstallLocation::MinecraftFolder,
+				folder_name: Some("Pack".to_owned()),
+				extra: Default::default()
+			});
+			metadata.successor = Some("bare".to_owned());
+		})
+		.featured("pack")
+		.build()
+		.unwrap();
+
+	// versions and bootstraps can only be read, so they go through the parser like everything else
+	let mut value: Value = serde_json::from_str(&list.to_string_canonical().unwrap()).unwrap();
+	let pack = &mut value["packs"]["pack"];
+	pack["versions"] = json!([{"version": "1.0.0", "manifest_url": "https://example.com/pack/1.0.0.json", "manifest_sha": pack["manifest_sha"], "released_at": 1_700_000_000}]);
+	pack["bootstrap"] = json!({"url": "https://example.com/pack/bootstrap.zip", "sha": "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb", "revision": "1"});
+	let list = PacksListManifest::from_slice(value.to_string().as_bytes()).unwrap();
+	serde_json::from_str(&list.to_string_canonical().unwrap()).unwrap()
+}
+
+#[test]
+fn serialized_manifests_match_their_schema() {
+	let schema = compile(Directory::json_schema());
+	for directory in [Directory::default(), sample_manifest()] {
+		assert_valid(&schema, &serde_json::from_str(&directory.to_string_canonical().unwrap()).unwrap());
+	}
+
+	let mut wrong: Value = serde_json::from_str(&sample_manifest().to_string_canonical().unwrap()).unwrap();
+	wrong["files"]["options.txt"]["size"] = json!("seven");
+	assert!(!schema.is_valid(&wrong));
+	wrong["files"]["options.txt"] = json!({"url": "https://example.com/pack/options.txt"});
+	assert!(!schema.is_valid(&wrong));
+}
+
+#[test]
+fn serialized_packs_lists_match_their_schema() {
+	let schema = compile(PacksListManifest::json_schema());
+	let list = sample_list();
+	assert!(list["packs"]["pack"]["versions"].is_array() && list["packs"]["pack"]["bootstrap"].is_object(), "{list:#}");
+	assert_valid(&schema, &list);
+	assert_valid(&schema, &serde_json::from_str(&PacksListManifest::default().to_string_canonical().unwrap()).unwrap());
+
+	let mut wrong = list.clone();
+	wrong["packs"]["pack"]["display_name"] = json!(null);
+	assert!(!schema.is_valid(&wrong));
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::TempDir;
+use green_lib::util::{minecraft_path, minecraft_path_candidates, minecraft_path_with_override, MinecraftInstall, MinecraftPathError, MINECRAFT_PATH_VAR};
+use std::path::PathBuf;
+
+// a single test, the environment is shared by every test of a file
+#[test]
+fn explicit_paths_come_before_the_variable_and_the_variable_before_the_default() {
+	let home = TempDir::new("minecraft-path-home");
+	let explicit = TempDir::new("minecraft-path-explicit");
+	std::env::set_var("HOME", &home.0);
+	std::env::set_var("APPDATA", &home.0);
+	let default = minecraft_path();
+	assert!(default.starts_with(&home.0), "{default:?}");
+	if cfg!(target_os = "linux") {
+		assert_eq!(default, home.0.join(".minecraft"));
+	}
+
+	// returned as it is, without checking it or adding anything to it
+	let portable = PathBuf::from("portable/launcher/.minecraft");
+	std::env::set_var(MINECRAFT_PATH_VAR, &portable);
+	assert_eq!(minecraft_path(), portable);
+	assert_eq!(minecraft_path_with_override(None).unwrap(), portable);
+	assert_eq!(minecraft_path_with_override(Some(&explicit.0)).unwrap(), explicit.0);
+	let candidates = minecraft_path_candidates();
+	assert_eq!((&candidates[0].path, candidates[0].install, candidates[0].exists), (&portable, MinecraftInstall::Override, false));
+	assert_eq!(candidates[1].path, default);
+
+	// an empty variable is the same as none
+	std::env::set_var(MINECRAFT_PATH_VAR, "");
+	assert_eq!(minecraft_path(), default);
+	assert_eq!(minecraft_path_with_override(None).unwrap(), default);
+	std::env::remove_var(MINECRAFT_PATH_VAR);
+	assert_eq!(minecraft_path_with_override(None).unwrap(), default);
+	assert_eq!(minecraft_path_candidates()[0].install, MinecraftInstall::Default);
+
+	// only explicit paths are checked
+	match minecraft_path_with_override(Some(&explicit.0.join("missing"))) {
+		Err(MinecraftPathError::Inaccessible { path, error }) => {
+			assert_eq!(path, explicit.0.join("missing"));
+			assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+		},
+		other => panic!("expected MinecraftPathError::Inaccessible, got {other:?}")
+	}
+	std::fs::write(explicit.0.join("options.txt"), "").unwrap();
+	match minecraft_path_with_override(Some(&explicit.0.join("options.txt"))) {
+		Err(MinecraftPathError::NotADirectory(path)) => assert_eq!(path, explicit.0.join("options.txt")),
+		other => panic!("expected MinecraftPathError::NotADirectory, got {other:?}")
+	}
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{Directory, DownloadError, MergeError, MergePrecedence, UpgradeError, UpgradeOptions, UpgradeStatus};
+use std::path::{Path, PathBuf};
+
+/// A base pack and an add-on with a different `mods/shared.jar`, served by the same server.
+async fn packs() -> (FixtureServer, Directory, Directory) {
+	let server = FixtureServer::start().await.unwrap();
+	// the check whether the network is up goes to options.txt, which is the only file at the top with a url
+	let base = server.serve("base", &DirectoryBuilder::new()
+		.file("options.txt", "options")
+		.file("mods/a.jar", "a")
+		.file("mods/shared.jar", "shared by the base")
+		.file("config/base.toml", "base"));
+	let addon = server.serve("addon", &DirectoryBuilder::new()
+		.file("mods/b.jar", "b")
+		.file("mods/shared.jar", "shared by the add-on")
+		.inline_file("config/addon.toml", "addon"));
+	(server, base, addon)
+}
+
+/// A folder with a stale file of the base, a file of the add-on that's up to date, entries neither of them knows about and a world.
+fn folder(name: &str) -> TempDir {
+	let folder = TempDir::new(name);
+	for (path, contents) in [("mods/a.jar", "stale"), ("config/addon.toml", "addon"), ("mods/unknown.jar", "unknown"), ("config/old/old.toml", "old"), ("saves/world/level.dat", "level")] {
+		let path = folder.0.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, contents).unwrap();
+	}
+	folder
+}
+
+/// Every file under `root` with what it contains, by its `/` separated path, sorted.
+fn tree(root: &Path) -> Vec<(String, Vec<u8>)> {
+	fn walk(root: &Path, path: &Path, files: &mut Vec<(String, Vec<u8>)>) {
+		for entry in std::fs::read_dir(path).unwrap() {
+			let path = entry.unwrap().path();
+			match path.is_dir() {
+				true => walk(root, &path, files),
+				false => files.push((path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"), std::fs::read(&path).unwrap()))
+			}
+		}
+	}
+
+	let mut files = vec![];
+	walk(root, root, &mut files);
+	files.sort();
+	files
+}
+
+/// What the events of an upgrade say was deleted, downloaded and skipped, each sorted.
+#[derive(Debug, Default, PartialEq)]
+struct Work {
+	deleted: Vec<PathBuf>,
+	downloaded: Vec<PathBuf>,
+	skipped: Vec<PathBuf>
+}
+
+fn collect() -> (tokio::sync::mpsc::Sender<UpgradeStatus>, tokio::task::JoinHandle<Work>) {
+	let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+	let work = tokio::spawn(async move {
+		let mut work = Work::default();
+		while let Some(event) = rx.recv().await {
+			match event {
+				UpgradeStatus::Deleted(path) => work.deleted.push(path),
+				UpgradeStatus::Finished(path) => work.downloaded.push(path),
+				UpgradeStatus::Skipped(path) => work.skipped.push(path),
+				_ => {}
+			}
+		}
+		work.deleted.sort();
+		work.downloaded.sort();
+		work.skipped.sort();
+		work
+	});
+	(tx, work)
+}
+
+#[test]
+fn upgrading_to_several_manifests_is_upgrading_to_their_merge() {
+	block_on(async {
+		let (_server, base, addon) = packs().await;
+
+		let multi = folder("multi-upgrade-multi");
+		let (tx, work) = collect();
+		let report = Directory::upgrade_game_folder_multi(&[&base, &addon], &multi.0, &UpgradeOptions::default(), Some(tx)).await.unwrap();
+		let multi_work = work.await.unwrap();
+
+		let merged = folder("multi-upgrade-merged");
+		let (tx, work) = collect();
+		Directory::merge(&[&base, &addon], MergePrecedence::LaterWins).unwrap().upgrade_game_folder(&merged.0, Some(tx)).await.unwrap();
+		let merged_work = work.await.unwrap();
+
+		assert_eq!(tree(&multi.0), tree(&merged.0));
+		assert_eq!(multi_work, merged_work);
+		// nothing of either pack was deleted as unknown to the other, and the add-on's shared.jar won
+		assert_eq!(multi_work.deleted, [PathBuf::from("config/old"), PathBuf::from("mods/unknown.jar")]);
+		assert!(multi.0.join("saves/world/level.dat").exists());
+		assert_eq!(multi_work.downloaded, ["config/base.toml", "mods/a.jar", "mods/b.jar", "mods/shared.jar", "options.txt"].map(PathBuf::from));
+		assert_eq!(multi_work.skipped, [PathBuf::from("config/addon.toml")]);
+		assert_eq!(std::fs::read(multi.0.join("mods/shared.jar")).unwrap(), b"shared by the add-on");
+
+		assert_eq!(report.sources[0].downloaded, ["config/base.toml", "mods/a.jar", "options.txt"].map(PathBuf::from));
+		assert_eq!(report.sources[0].overridden, [PathBuf::from("mods/shared.jar")]);
+		assert_eq!(report.sources[1].downloaded, ["mods/b.jar", "mods/shared.jar"].map(PathBuf::from));
+		assert_eq!(report.sources[1].skipped, [PathBuf::from("config/addon.toml")]);
+	});
+}
+
+#[test]
+fn downloads_are_verified_the_same_way() {
+	block_on(async {
+		let (server, base, addon) = packs().await;
+		// as long as the file is supposed to be, so it only fails at its sha
+		server.insert("addon/mods/b.jar", "c");
+
+		let mismatch = |result: Result<(), UpgradeError>| match result {
+			Err(UpgradeError::Download { path, error: DownloadError::ShaMismatch { expected, found, .. }, .. }) => (path, expected, found),
+			other => panic!("expected mods/b.jar not to match its sha, got {other:?}")
+		};
+		let multi = folder("multi-upgrade-tampered-multi");
+		let multi_error = mismatch(Directory::upgrade_game_folder_multi(&[&base, &addon], &multi.0, &UpgradeOptions::default(), None).await.map(drop));
+		let merged = folder("multi-upgrade-tampered-merged");
+		let merged_error = mismatch(Directory::merge(&[&base, &addon], MergePrecedence::LaterWins).unwrap().upgrade_game_folder(&merged.0, None).await.map(drop));
+
+		assert_eq!(multi_error, merged_error);
+		assert_eq!(multi_error.0, Path::new("mods/b.jar"));
+		assert_eq!(tree(&multi.0), tree(&merged.0));
+		assert!(!multi.0.join("mods/b.jar").exists());
+	});
+}
+
+#[test]
+fn conflicts_fail_before_anything_changes() {
+	block_on(async {
+		let (server, base, addon) = packs().await;
+		let folder = folder("multi-upgrade-conflict");
+		let before = tree(&folder.0);
+		let options = UpgradeOptions {
+			precedence: MergePrecedence::Error,
+			..Default::default()
+		};
+
+		match Directory::upgrade_game_folder_multi(&[&base, &addon], &folder.0, &options, None).await {
+			Err(UpgradeError::Merge(MergeError::Conflict { path, first: 0, second: 1 })) => assert_eq!(path, "mods/shared.jar"),
+			other => panic!("expected mods/shared.jar to conflict, got {other:?}")
+		}
+		assert!(Directory::merge(&[&base, &addon], MergePrecedence::Error).is_err());
+		assert_eq!(tree(&folder.0), before);
+		assert_eq!(server.requests("addon/mods/b.jar"), 0);
+	});
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{block_on_without_runtime, TempDir};
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::{Directory, FromPathOptions, ManifestError, NoRuntime, UpgradeError, UpgradeOptions, WatchOptions};
+
+fn is_no_runtime(error: &std::io::Error) -> bool {
+	error.get_ref().is_some_and(|inner| inner.is::<NoRuntime>())
+}
+
+#[test]
+fn fetching_fails_outside_a_runtime() {
+	match block_on_without_runtime(Directory::try_from_url("http://127.0.0.1:9/manifest.json")) {
+		Err(ManifestError::NoRuntime) => {},
+		other => panic!("expected ManifestError::NoRuntime, got {other:?}")
+	}
+	assert!(block_on_without_runtime(Directory::from_url("http://127.0.0.1:9/manifest.json")).is_none());
+
+	let mut watcher = Directory::watch_url("http://127.0.0.1:9/manifest.json", std::time::Duration::from_secs(1), &WatchOptions::default());
+	match block_on_without_runtime(watcher.next()) {
+		Some(Err(ManifestError::NoRuntime)) => {},
+		other => panic!("expected ManifestError::NoRuntime, got {other:?}")
+	}
+	assert!(block_on_without_runtime(watcher.next()).is_none());
+}
+
+#[test]
+fn folders_are_left_alone_outside_a_runtime() {
+	let folder = TempDir::new("no-runtime");
+	std::fs::write(folder.0.join("a.txt"), "a").unwrap();
+	let directory = DirectoryBuilder::new().file("mods/a.jar", "a").build("http://127.0.0.1:9/");
+
+	let error = block_on_without_runtime(Directory::from_path(&folder.0, &FromPathOptions::default())).unwrap_err();
+	assert!(is_no_runtime(&error), "{error:?}");
+	let error = block_on_without_runtime(directory.to_file(&folder.0.join("manifest.json"), false)).unwrap_err();
+	assert!(is_no_runtime(&error), "{error:?}");
+
+	match block_on_without_runtime(directory.upgrade_game_folder(&folder.0, None)) {
+		Err(UpgradeError::NoRuntime) => {},
+		other => panic!("expected UpgradeError::NoRuntime, got {other:?}")
+	}
+	let report = block_on_without_runtime(directory.verify_folder(&folder.0, &UpgradeOptions::default(), None));
+	assert_eq!(report.unreadable.len(), 1);
+	assert!(is_no_runtime(&report.unreadable[0].error));
+	assert!(!folder.0.join("mods").exists());
+}
+
+#[test]
+fn upgrades_run_on_the_given_runtime() {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let folder = TempDir::new("given-runtime");
+	let directory = DirectoryBuilder::new().inline_file("config/a.toml", "a").build("http://127.0.0.1:9/");
+	let options = UpgradeOptions {
+		runtime: Some(runtime.handle().clone()),
+		..Default::default()
+	};
+
+	block_on_without_runtime(directory.upgrade_game_folder_with_options(&folder.0, &options, None)).unwrap();
+	assert_eq!(std::fs::read(folder.0.join("config/a.toml")).unwrap(), b"a");
+	let report = block_on_without_runtime(directory.verify_folder(&folder.0, &options, None));
+	assert_eq!(report.matched, vec![std::path::PathBuf::from("config/a.toml")]);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{Directory, UpgradeError, UpgradeObserver, UpgradeStatus};
+use std::sync::{Arc, Mutex};
+
+/// Keeps every event, optionally panicking or taking its time over each of them.
+#[derive(Default)]
+struct Recorder {
+	events: Mutex<Vec<UpgradeStatus>>,
+	panics: bool,
+	slow: bool
+}
+
+impl UpgradeObserver for Recorder {
+	fn status(&self, status: &UpgradeStatus) {
+		self.events.lock().unwrap().push(status.clone());
+		if self.slow {
+			std::thread::sleep(std::time::Duration::from_millis(5));
+		}
+		if self.panics {
+			panic!("observer panicked on {status:?}");
+		}
+	}
+}
+
+/// A pack and a folder it gets everything out of: a download, a retried one, a failed one, a file that's already there and an unknown one.
+async fn setup(name: &str) -> (FixtureServer, Directory, TempDir) {
+	let server = FixtureServer::start().await.unwrap();
+	// the check whether the network is up goes to the first file at the top
+	let directory = server.serve("pack", &DirectoryBuilder::new()
+		.file("options.txt", "options")
+		.file("mods/a.jar", "a")
+		.file("mods/retried.jar", "retried")
+		.file("mods/failed.jar", "failed")
+		.file("config/kept.toml", "kept"));
+	server.fail("pack/mods/retried.jar", Failure::Status {
+		status: 503,
+		count: 1
+	});
+	server.fail("pack/mods/failed.jar", Failure::Status {
+		status: 404,
+		count: usize::MAX
+	});
+
+	let folder = TempDir::new(name);
+	std::fs::create_dir_all(folder.0.join("config")).unwrap();
+	std::fs::write(folder.0.join("config/kept.toml"), "kept").unwrap();
+	std::fs::write(folder.0.join("config/unknown.toml"), "unknown").unwrap();
+	(server, directory, folder)
+}
+
+/// The events without how many bytes each progress event had, which depends on how the body arrived, in a stable order.
+fn normalized(events: Vec<UpgradeStatus>) -> Vec<String> {
+	let mut events: Vec<_> = events.into_iter().map(|event| match event {
+		UpgradeStatus::Progressed(path, _) => format!("Progressed({path:?})"),
+		UpgradeStatus::Failed { path, .. } => format!("Failed({path:?})"),
+		UpgradeStatus::Retrying { path, .. } => format!("Retrying({path:?})"),
+		event => format!("{event:?}")
+	}).collect();
+	events.sort();
+	events.dedup_by(|a, b| a.starts_with("Progressed") && a == b);
+	events
+}
+
+fn assert_failed(result: Result<green_lib::UpgradeReport, UpgradeError>) {
+	match result {
+		Err(UpgradeError::Download { path, .. }) => assert!(path.ends_with("mods/failed.jar"), "{path:?}"),
+		other => panic!("expected mods/failed.jar to fail, got {other:?}")
+	}
+}
+
+#[test]
+fn channels_and_observers_get_the_same_events() {
+	block_on(async {
+		let (_server, directory, folder) = setup("observer-channel").await;
+		let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+		let received = tokio::spawn(async move {
+			let mut events = vec![];
+			while let Some(event) = rx.recv().await {
+				events.push(event);
+			}
+			events
+		});
+		assert_failed(directory.upgrade_game_folder(&folder.0, Some(tx)).await);
+		let from_channel = normalized(received.await.unwrap());
+
+		let (_server, directory, folder) = setup("observer-observer").await;
+		let recorder = Arc::new(Recorder::default());
+		assert_failed(directory.upgrade_game_folder_with_observer(&folder.0, recorder.clone()).await);
+		let from_observer = normalized(std::mem::take(&mut *recorder.events.lock().unwrap()));
+
+		assert_eq!(from_channel, from_observer);
+		for expected in [r#"Finished("mods/a.jar")"#, r#"Retrying("mods/retried.jar")"#, r#"Finished("mods/retried.jar")"#, r#"Failed("mods/failed.jar")"#, r#"Skipped("config/kept.toml")"#, r#"Deleted("config/unknown.toml")"#] {
+			assert!(from_observer.iter().any(|event| event == expected), "no {expected} in {from_observer:#?}");
+		}
+	});
+}
+
+#[test]
+fn panicking_and_slow_observers_dont_break_upgrades() {
+	block_on(async {
+		let (_server, directory, folder) = setup("observer-panics").await;
+		let recorder = Arc::new(Recorder {
+			panics: true,
+			slow: true,
+			..Default::default()
+		});
+		assert_failed(directory.upgrade_game_folder_with_observer(&folder.0, recorder.clone()).await);
+
+		assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+		assert_eq!(std::fs::read(folder.0.join("mods/retried.jar")).unwrap(), b"retried");
+		assert!(!folder.0.join("config/unknown.toml").exists());
+		// every event still reached it after the ones it panicked on
+		assert!(recorder.events.lock().unwrap().iter().any(|event| matches!(event, UpgradeStatus::Finished(path) if path.ends_with("mods/retried.jar"))));
+	});
+}
+
+#[test]
+fn dropping_the_receiver_doesnt_break_upgrades() {
+	block_on(async {
+		let (_server, directory, folder) = setup("observer-dropped").await;
+		let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+		let received = tokio::spawn(async move {
+			// the receiver goes away after the first event
+			rx.recv().await
+		});
+		assert_failed(directory.upgrade_game_folder(&folder.0, Some(tx)).await);
+		assert!(received.await.unwrap().is_some());
+
+		assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+		assert_eq!(std::fs::read(folder.0.join("mods/retried.jar")).unwrap(), b"retried");
+		assert!(!folder.0.join("config/unknown.toml").exists());
+	});
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::UpgradeError;
+
+#[test]
+fn offline_upgrades_only_fail_when_something_is_downloaded() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("config/b.toml", "b"));
+		let folder = TempDir::new("offline-upgrade");
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+
+		drop(server);
+		tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+		// already up to date, nothing needs the network
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+
+		// unknown entries aren't deleted while the network is down
+		std::fs::write(folder.0.join("mods/extra.jar"), "extra").unwrap();
+		let report = directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!(report.kept, vec![std::path::PathBuf::from("mods/extra.jar")]);
+		assert!(folder.0.join("mods/extra.jar").exists());
+
+		std::fs::remove_file(folder.0.join("mods/a.jar")).unwrap();
+		match directory.upgrade_game_folder(&folder.0, None).await {
+			Err(UpgradeError::Offline { not_attempted: 1, .. }) => {},
+			other => panic!("expected UpgradeError::Offline, got {other:?}")
+		}
+		assert!(folder.0.join("mods/extra.jar").exists());
+		assert_eq!(std::fs::read(folder.0.join("config/b.toml")).unwrap(), b"b");
+	});
+}
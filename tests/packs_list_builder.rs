@@ -0,0 +1,82 @@
+mod common;
+
+use common::block_on;
+use green_lib::hash::HashAlgorithm;
+use green_lib::packs::{PacksListBuildError, PacksListBuilder, PacksListManifest};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::ManifestError;
+
+/// The list as a launcher gets it, after it was published.
+fn published(list: &PacksListManifest) -> PacksListManifest {
+	PacksListManifest::from_slice(list.to_string_canonical().unwrap().as_bytes()).unwrap()
+}
+
+#[test]
+fn built_lists_match_the_served_manifests() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let first = server.serve("first", &DirectoryBuilder::new().file("mods/a.jar", "a"));
+		let second = server.serve("second", &DirectoryBuilder::new().file("mods/b.jar", "b").inline_file("options.txt", "options"));
+		let list = PacksListBuilder::new()
+			.add_pack("first", "First", &server.url("first/manifest.json"), &first)
+			.hash_algorithm(HashAlgorithm::Sha512)
+			// relative to the base url
+			.add_pack("second", "Second", "second/manifest.json", &second)
+			.base_url(&server.url(""))
+			.featured("second")
+			.featured("first")
+			.build()
+			.unwrap();
+		// and once it's served next to the packs, relative to where it came from
+		server.insert("packs.json", list.to_string_canonical().unwrap());
+		let served = PacksListManifest::try_from_url(server.url("packs.json")).await.unwrap();
+
+		for list in [list, served] {
+			assert_eq!(list.featured_pack.as_deref(), Some("second"));
+			assert_eq!(list.get_featured_pack_metadata().unwrap().display_name, "Second");
+			assert_eq!(list.packs["first"].try_to_directory().await.unwrap(), first);
+			assert_eq!(list.packs["second"].try_to_directory().await.unwrap(), second);
+			assert_eq!(list.packs["second"].size_mismatch(&second), None);
+		}
+	});
+}
+
+#[test]
+fn manifests_written_some_other_way_keep_their_bytes() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = DirectoryBuilder::new().file("mods/a.jar", "a").build(&server.url("pack"));
+		// post-processed, so not what to_string_canonical writes
+		let pretty = serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(&directory.to_string_canonical().unwrap()).unwrap()).unwrap();
+		server.insert("pack/manifest.json", pretty.clone());
+
+		let from_bytes = published(&PacksListBuilder::new().add_pack_bytes("pack", "Pack", &server.url("pack/manifest.json"), pretty.as_bytes()).build().unwrap());
+		assert_eq!(from_bytes.packs["pack"].try_to_directory().await.unwrap(), directory);
+
+		// which the sha of the canonical manifest doesn't match
+		let from_directory = published(&PacksListBuilder::new().add_pack("pack", "Pack", &server.url("pack/manifest.json"), &directory).build().unwrap());
+		match from_directory.packs["pack"].try_to_directory().await {
+			Err(ManifestError::IntegrityMismatch { .. }) => {},
+			other => panic!("expected ManifestError::IntegrityMismatch, got {other:?}")
+		}
+	});
+}
+
+#[test]
+fn mistakes_fail_the_build() {
+	let directory = DirectoryBuilder::new().file("mods/a.jar", "a").build("https://example.com/pack");
+	let url = "https://example.com/pack/manifest.json";
+
+	match PacksListBuilder::new().add_pack("pack", "Pack", url, &directory).add_pack("pack", "Again", url, &directory).build() {
+		Err(PacksListBuildError::Duplicate { key }) => assert_eq!(key, "pack"),
+		other => panic!("expected PacksListBuildError::Duplicate, got {other:?}")
+	}
+	match PacksListBuilder::new().add_pack("pack", "Pack", url, &directory).featured("other").build() {
+		Err(PacksListBuildError::UnknownFeatured { key }) => assert_eq!(key, "other"),
+		other => panic!("expected PacksListBuildError::UnknownFeatured, got {other:?}")
+	}
+	match PacksListBuilder::new().add_pack_bytes("pack", "Pack", url, b"{\"files\": ").build() {
+		Err(PacksListBuildError::InvalidManifest { key, error: ManifestError::Parse(_) }) => assert_eq!(key, "pack"),
+		other => panic!("expected PacksListBuildError::InvalidManifest, got {other:?}")
+	}
+}
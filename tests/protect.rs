@@ -0,0 +1,64 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{HiddenPolicy, ProtectFilter, UpgradeOptions};
+use std::path::{Path, PathBuf};
+
+fn write(folder: &TempDir, path: &str) {
+	let path = folder.0.join(path);
+	std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+	std::fs::write(path, "local").unwrap();
+}
+
+fn options(hidden: HiddenPolicy) -> UpgradeOptions {
+	UpgradeOptions {
+		hidden,
+		protect: Some(ProtectFilter::new(|relative| relative == Path::new("mods/local.jar") || relative == Path::new("mods/keep"))),
+		..Default::default()
+	}
+}
+
+#[test]
+fn protected_paths_are_kept_and_the_same_names_elsewhere_arent() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("config/b.toml", "b"));
+		let folder = TempDir::new("protect");
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		for path in ["mods/local.jar", "config/local.jar", "mods/keep/notes.txt", "config/keep/notes.txt", "mods/.cache"] {
+			write(&folder, path);
+		}
+
+		let report = directory.verify_folder(&folder.0, &options(HiddenPolicy::Delete), None).await;
+		assert_eq!(report.protected, [PathBuf::from("mods/keep"), PathBuf::from("mods/local.jar")]);
+		assert_eq!(report.unknown, [PathBuf::from("config/keep"), PathBuf::from("config/local.jar"), PathBuf::from("mods/.cache")]);
+
+		let report = directory.upgrade_game_folder_with_options(&folder.0, &options(HiddenPolicy::Delete), None).await.unwrap();
+		assert_eq!(report.kept, [PathBuf::from("mods/keep"), PathBuf::from("mods/local.jar")]);
+		assert!(folder.0.join("mods/local.jar").exists());
+		assert!(folder.0.join("mods/keep/notes.txt").exists());
+		// only the path is protected, not the name
+		assert!(!folder.0.join("config/local.jar").exists());
+		assert!(!folder.0.join("config/keep").exists());
+		// protecting something doesn't change what happens to hidden entries
+		assert!(!folder.0.join("mods/.cache").exists());
+	});
+}
+
+#[test]
+fn protecting_leaves_the_hidden_policy_alone() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a"));
+		let folder = TempDir::new("protect-hidden");
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		for path in ["mods/local.jar", "mods/.cache", "mods/other.jar"] {
+			write(&folder, path);
+		}
+
+		let report = directory.upgrade_game_folder_with_options(&folder.0, &options(HiddenPolicy::Keep), None).await.unwrap();
+		assert_eq!(report.kept, [PathBuf::from("mods/.cache"), PathBuf::from("mods/local.jar")]);
+		assert!(!folder.0.join("mods/other.jar").exists());
+	});
+}
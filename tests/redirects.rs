@@ -0,0 +1,70 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::http::{self, ScopedHeaders};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::UpgradeOptions;
+
+fn config(forward_auth: bool) -> http::HttpConfig {
+	let mut config = http::config();
+	config.headers = vec![
+		ScopedHeaders::from_pairs([("authorization", "Bearer secret"), ("x-client", "launcher")]).unwrap(),
+		ScopedHeaders::bearer("scoped", ["127.0.0.1"]).unwrap()
+	];
+	config.redirects.forward_auth = forward_auth;
+	config
+}
+
+/// Upgrades a folder to a pack served by `from` whose only file redirects to `path` on `to`, returning the headers `to` got.
+fn headers_after_redirect(from: &FixtureServer, to: &FixtureServer, path: &str, forward_auth: bool, name: &str) -> Vec<(String, String)> {
+	let builder = DirectoryBuilder::new().file("mods/a.jar", "a");
+	let directory = from.serve("pack", &builder);
+	to.insert(path, "a");
+	from.fail("pack/mods/a.jar", Failure::Redirect {
+		location: to.url(path),
+		count: usize::MAX
+	});
+
+	let folder = TempDir::new(name);
+	let options = UpgradeOptions {
+		http: Some(config(forward_auth)),
+		..Default::default()
+	};
+	block_on(directory.upgrade_game_folder_with_options(&folder.0, &options, None)).unwrap();
+	assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+	to.request_headers(path).unwrap()
+}
+
+fn has(headers: &[(String, String)], name: &str) -> bool {
+	headers.iter().any(|(header, _)| header == name)
+}
+
+#[test]
+fn same_origin_redirects_keep_credentials() {
+	let runtime = tokio::runtime::Runtime::new().unwrap();
+	let server = runtime.block_on(FixtureServer::start()).unwrap();
+	let _guard = runtime.enter();
+	let headers = headers_after_redirect(&server, &server, "elsewhere/a.jar", false, "same-origin");
+	assert!(has(&headers, "authorization"));
+}
+
+#[test]
+fn other_ports_dont_get_credentials() {
+	let runtime = tokio::runtime::Runtime::new().unwrap();
+	let (from, to) = runtime.block_on(async { (FixtureServer::start().await.unwrap(), FixtureServer::start().await.unwrap()) });
+	let _guard = runtime.enter();
+	// same host, only the port differs
+	let headers = headers_after_redirect(&from, &to, "cdn/a.jar", false, "other-port");
+	// neither the header for every host nor the one scoped to 127.0.0.1, which the other port is a different service of
+	assert!(!has(&headers, "authorization"));
+	assert!(has(&headers, "x-client"));
+}
+
+#[test]
+fn forward_auth_sends_credentials_across_origins() {
+	let runtime = tokio::runtime::Runtime::new().unwrap();
+	let (from, to) = runtime.block_on(async { (FixtureServer::start().await.unwrap(), FixtureServer::start().await.unwrap()) });
+	let _guard = runtime.enter();
+	let headers = headers_after_redirect(&from, &to, "cdn/a.jar", true, "forward-auth");
+	assert!(has(&headers, "authorization"));
+}
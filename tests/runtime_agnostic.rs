@@ -0,0 +1,80 @@
+mod common;
+
+use common::{block_on_without_runtime, TempDir};
+use green_lib::{Directory, FromPathOptions, HiddenPolicy, Spawner, UpgradeOptions, UpgradeStatus};
+use std::path::PathBuf;
+
+fn write(folder: &TempDir, path: &str, contents: &str) {
+	let path = folder.0.join(path);
+	std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+	std::fs::write(path, contents).unwrap();
+}
+
+/// A folder with a few files and the manifest scanned from it without a tokio runtime.
+fn scanned(name: &str) -> (TempDir, Directory) {
+	let folder = TempDir::new(name);
+	for path in ["mods/a.jar", "mods/b.jar", "config/c.toml", "config/deep/d.toml"] {
+		write(&folder, path, path);
+	}
+	write(&folder, ".greenignore", "*.bak\n");
+	write(&folder, "config/old.bak", "old");
+
+	let directory = block_on_without_runtime(Directory::from_path(&folder.0, &FromPathOptions {
+		base_url: "https://example.com/pack".to_owned(),
+		spawner: Some(Spawner::threads()),
+		..Default::default()
+	})).unwrap();
+	(folder, directory)
+}
+
+#[test]
+fn folders_are_scanned_on_the_spawner() {
+	let (folder, directory) = scanned("agnostic-scan");
+	assert_eq!(directory.children["mods"].files.len(), 2);
+	assert!(!directory.children["config"].files.contains_key("old.bak"));
+	assert_eq!(directory.children["config"].children["deep"].files["d.toml"].url.as_deref(), Some("https://example.com/pack/config/deep/d.toml"));
+
+	// the same as on tokio
+	let on_tokio = common::block_on(Directory::from_path(&folder.0, &FromPathOptions {
+		base_url: "https://example.com/pack".to_owned(),
+		..Default::default()
+	})).unwrap();
+	assert_eq!(directory, on_tokio);
+}
+
+#[test]
+fn folders_are_verified_on_the_spawner() {
+	let (folder, directory) = scanned("agnostic-verify");
+	write(&folder, "mods/a.jar", "edited");
+	// left out of the manifest, so an upgrade would delete it
+	std::fs::remove_file(folder.0.join("config/old.bak")).unwrap();
+	std::fs::remove_file(folder.0.join("config/c.toml")).unwrap();
+	write(&folder, "mods/unknown.jar", "unknown");
+	write(&folder, "mods/.cache", "hidden");
+
+	let options = UpgradeOptions {
+		hash_cache: true,
+		hidden: HiddenPolicy::Keep,
+		spawner: Some(Spawner::threads()),
+		..Default::default()
+	};
+	let (tx, mut rx) = futures_channel::mpsc::channel(16);
+	let report = block_on_without_runtime(directory.verify_folder(&folder.0, &options, tx));
+	assert!(report.unreadable.is_empty(), "{:?}", report.unreadable);
+	assert_eq!(report.matched, [PathBuf::from("config/deep/d.toml"), PathBuf::from("mods/b.jar")]);
+	assert_eq!(report.mismatched.iter().map(|mismatch| &mismatch.path).collect::<Vec<_>>(), [&PathBuf::from("mods/a.jar")]);
+	assert_eq!(report.missing, [PathBuf::from("config/c.toml")]);
+	assert_eq!(report.unknown, [PathBuf::from("mods/unknown.jar")]);
+	assert_eq!(report.kept_hidden, [PathBuf::from("mods/.cache")]);
+
+	let mut skipped = vec![];
+	while let Ok(status) = rx.try_recv() {
+		if let UpgradeStatus::Skipped(path) = status {
+			skipped.push(path);
+		}
+	}
+	skipped.sort();
+	assert_eq!(skipped, report.matched);
+	// the hash cache was written through the spawner as well
+	assert!(folder.0.join(".green-lib/hashcache.bin").exists());
+}
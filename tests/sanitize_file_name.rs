@@ -0,0 +1,58 @@
+use green_lib::util::{sanitize_file_name, sanitize_file_name_max};
+
+#[test]
+fn unsafe_characters_become_underscores() {
+	assert_eq!(sanitize_file_name("Fabulously Optimized"), "Fabulously Optimized");
+	assert_eq!(sanitize_file_name("../../.ssh"), ".._.._.ssh");
+	assert_eq!(sanitize_file_name("a/b\\c"), "a_b_c");
+	assert_eq!(sanitize_file_name("<pack>: \"1|2\"?*"), "_pack__ _1_2___");
+	assert_eq!(sanitize_file_name("nul\0tab\tnewline\n"), "nul_tab_newline_");
+	assert_eq!(sanitize_file_name("Création 日本"), "Création 日本");
+}
+
+#[test]
+fn trailing_dots_and_spaces_are_removed() {
+	assert_eq!(sanitize_file_name("pack. . "), "pack");
+	assert_eq!(sanitize_file_name("  pack"), "  pack");
+	assert_eq!(sanitize_file_name("v1.2"), "v1.2");
+}
+
+#[test]
+fn reserved_device_names_get_an_underscore() {
+	for name in ["CON", "con", "Nul.txt", "com1", "LPT9.tar.gz", "aux "] {
+		let sanitized = sanitize_file_name(name);
+		assert!(sanitized.starts_with('_'), "{name:?} became {sanitized:?}");
+	}
+	assert_eq!(sanitize_file_name("nul.txt"), "_nul.txt");
+	for name in ["CONSOLE", "com10", "lpt", "my con"] {
+		assert_eq!(sanitize_file_name(name), name);
+	}
+}
+
+#[test]
+fn results_are_never_empty() {
+	for name in ["", ".", "..", " ", "...", ". ."] {
+		assert_eq!(sanitize_file_name(name), "_", "{name:?}");
+	}
+}
+
+#[test]
+fn long_names_stay_apart_with_a_hash() {
+	let long = "A really long display name for a pack ".repeat(8);
+	let first = sanitize_file_name_max(&format!("{long}1"), 64);
+	let second = sanitize_file_name_max(&format!("{long}2"), 64);
+	assert!(first.len() <= 64 && second.len() <= 64, "{first:?} {second:?}");
+	assert_ne!(first, second);
+	assert!(first.starts_with("A really long display name"), "{first:?}");
+	// the same name gives the same result every time
+	assert_eq!(first, sanitize_file_name_max(&format!("{long}1"), 64));
+
+	assert_eq!(sanitize_file_name_max("short", 64), "short");
+	// cut on a character, not in the middle of one
+	let cut = sanitize_file_name_max(&"日本語".repeat(20), 20);
+	assert!(cut.len() <= 20, "{cut:?}");
+	for max_bytes in [0, 1, 5, 9] {
+		let cut = sanitize_file_name_max(&long, max_bytes);
+		assert!(!cut.is_empty() && cut.len() <= max_bytes.max(1), "{max_bytes}: {cut:?}");
+	}
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{Directory, NormalizeError, NormalizeOptions, RemoveOptions, SeparatorPolicy, UpgradeError, UpgradeOptions, ValidationError};
+use std::path::{Path, PathBuf};
+
+/// A pack as it's meant to be, and the same pack as Windows tooling writes it with its nested files at the top.
+fn setup(server: &FixtureServer) -> (Directory, Directory) {
+	let nested = server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("config/mymod/settings.json", "{}").file("mods/a.jar", "a"));
+	let mut flat = nested.clone();
+	let settings = flat.children.remove("config").unwrap().children["mymod"].files["settings.json"].clone();
+	flat.files.insert("config\\mymod\\settings.json".to_owned(), settings);
+	let a = flat.children.remove("mods").unwrap().files["a.jar"].clone();
+	flat.files.insert("mods/a.jar".to_owned(), a);
+	(nested, flat)
+}
+
+/// A folder with an unknown file where the split keys go.
+fn folder(name: &str) -> TempDir {
+	let folder = TempDir::new(name);
+	std::fs::create_dir_all(folder.0.join("config/mymod")).unwrap();
+	std::fs::write(folder.0.join("config/mymod/other.json"), "other").unwrap();
+	folder
+}
+
+fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+	paths.sort();
+	paths
+}
+
+#[test]
+fn split_keys_are_the_same_tree_everywhere() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let (nested, flat) = setup(&server);
+		flat.validate().unwrap();
+		assert!(flat.diff(&nested).is_empty(), "{:?}", flat.diff(&nested));
+
+		let mut normalized = flat.clone();
+		let report = normalized.normalize().unwrap();
+		assert_eq!(normalized, nested);
+		assert_eq!(report.renamed, vec![
+			("config\\mymod\\settings.json".to_owned(), "config/mymod/settings.json".to_owned()),
+			("mods/a.jar".to_owned(), "mods/a.jar".to_owned())
+		]);
+
+		let folder = folder("separators-split");
+		let report = flat.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+		assert_eq!(std::fs::read(folder.0.join("config/mymod/settings.json")).unwrap(), b"{}");
+		assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+		// deleted as unknown, and nothing with a separator in its name
+		assert!(!folder.0.join("config/mymod/other.json").exists());
+		let mut names: Vec<_> = std::fs::read_dir(&folder.0).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).filter(|name| !name.starts_with('.')).collect();
+		names.sort();
+		assert_eq!(names, ["config", "mods", "options.txt"]);
+
+		let verification = flat.verify_folder(&folder.0, &UpgradeOptions::default(), None).await;
+		assert!(verification.missing.is_empty() && verification.unknown.is_empty(), "{verification:?}");
+		assert_eq!(sorted(verification.matched), [Path::new("config/mymod/settings.json"), Path::new("mods/a.jar"), Path::new("options.txt")]);
+
+		let removed = flat.remove_from_folder(&folder.0, &RemoveOptions::default(), None).await.unwrap();
+		assert_eq!(sorted(removed.removed), [Path::new("config/mymod/settings.json"), Path::new("mods/a.jar"), Path::new("options.txt")]);
+	});
+}
+
+#[test]
+fn keys_with_separators_can_be_refused() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let (nested, flat) = setup(&server);
+		nested.validate_with(SeparatorPolicy::Error).unwrap();
+		match flat.validate_with(SeparatorPolicy::Error) {
+			Err(ValidationError::SeparatorInKey { path }) => assert!(path == Path::new("config\\mymod\\settings.json") || path == Path::new("mods/a.jar"), "{path:?}"),
+			other => panic!("expected ValidationError::SeparatorInKey, got {other:?}")
+		}
+
+		let mut normalized = flat.clone();
+		let refuse = NormalizeOptions {
+			separators: SeparatorPolicy::Error,
+			..Default::default()
+		};
+		match normalized.normalize_with_options(&refuse) {
+			Err(NormalizeError::Separator { path }) => assert!(path == "config\\mymod\\settings.json" || path == "mods/a.jar", "{path:?}"),
+			other => panic!("expected NormalizeError::Separator, got {other:?}")
+		}
+		assert_eq!(normalized, flat);
+
+		let folder = folder("separators-error");
+		let refuse = UpgradeOptions {
+			separators: SeparatorPolicy::Error,
+			..Default::default()
+		};
+		match flat.upgrade_game_folder_with_options(&folder.0, &refuse, None).await {
+			Err(UpgradeError::Normalize(NormalizeError::Separator { .. })) => {},
+			other => panic!("expected UpgradeError::Normalize, got {other:?}")
+		}
+		// before changing anything
+		assert!(folder.0.join("config/mymod/other.json").exists());
+		assert!(!folder.0.join("options.txt").exists());
+		assert_eq!(server.requests("pack/options.txt"), 0);
+	});
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::packs::PacksListManifest;
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{Directory, ManifestError, ValidationError};
+
+#[test]
+fn uppercase_shas_dont_download_again() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("config/b.toml", "b"));
+		// the same manifest as another tool would write it
+		let manifest = directory.to_string_canonical().unwrap();
+		let shouting = ["mods/a.jar", "config/b.toml"].iter().fold(manifest.clone(), |manifest, path| {
+			let sha = directory.file(path.as_ref()).unwrap().sha.to_string();
+			manifest.replace(&sha, &sha.to_uppercase())
+		});
+		assert_ne!(shouting, manifest);
+		server.insert("pack/shouting.json", shouting);
+
+		let directory = Directory::try_from_url(server.url("pack/shouting.json")).await.unwrap();
+		let folder = TempDir::new("sha-case");
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		let requests = (server.requests("pack/mods/a.jar"), server.requests("pack/config/b.toml"));
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!((server.requests("pack/mods/a.jar"), server.requests("pack/config/b.toml")), requests);
+	});
+}
+
+#[test]
+fn malformed_shas_are_rejected_with_their_path() {
+	let error = Directory::from_slice(br#"{"files":{},"children":{"mods":{"files":{"a.jar":{"sha":"xyz","url":"https://example.com/a.jar"}},"children":{}}}}"#).unwrap_err();
+	assert!(matches!(error, ManifestError::Parse(_)), "{error:?}");
+	assert!(error.to_string().contains("children/mods/files/a.jar/sha"), "{error}");
+
+	let sha = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+	assert_eq!(Directory::from_slice(format!(r#"{{"files":{{"a.jar":{{"sha":"{}","url":"https://example.com/a.jar"}}}},"children":{{}}}}"#, sha.to_uppercase()).as_bytes()).unwrap().files["a.jar"].sha.to_string(), sha);
+
+	let list = |sha: &str| PacksListManifest::from_slice(format!(r#"{{"packs":{{"p":{{"display_name":"p","manifest_url":"https://example.com/m.json","manifest_sha":"{sha}"}}}}}}"#).as_bytes());
+	assert!(matches!(list("abc"), Err(ManifestError::Invalid(ValidationError::InvalidManifestSha { .. }))));
+	assert!(list(&sha.to_uppercase()).is_ok());
+}
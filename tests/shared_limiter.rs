@@ -0,0 +1,65 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{Directory, DownloadLimiter, UpgradeOptions};
+use std::time::Duration;
+
+const FILES: usize = 8;
+
+/// Two packs on the same server whose files take a while to be answered, so the downloads of both upgrades overlap.
+fn serve_packs(server: &FixtureServer) -> [Directory; 2] {
+	["first", "second"].map(|pack| {
+		let builder = (0..FILES).fold(DirectoryBuilder::new(), |builder, index| builder.file(&format!("mods/{index}.jar"), format!("{pack} {index}")));
+		for index in 0..FILES {
+			server.fail(&format!("{pack}/mods/{index}.jar"), Failure::Slow(Duration::from_millis(100)));
+		}
+		server.serve(pack, &builder)
+	})
+}
+
+/// Upgrades a folder of its own for each directory at the same time, each with its options.
+async fn upgrade_both(directories: [Directory; 2], options: [UpgradeOptions; 2], name: &str) {
+	let upgrades: Vec<_> = directories.into_iter().zip(options).enumerate().map(|(index, (directory, options))| {
+		let folder = TempDir::new(&format!("{name}-{index}"));
+		tokio::spawn(async move {
+			directory.upgrade_game_folder_with_options(&folder.0, &options, None).await.unwrap();
+			for index in 0..FILES {
+				assert!(folder.0.join(format!("mods/{index}.jar")).exists());
+			}
+		})
+	}).collect();
+	for upgrade in upgrades {
+		upgrade.await.unwrap();
+	}
+}
+
+fn with_limiter(limiter: &DownloadLimiter) -> UpgradeOptions {
+	UpgradeOptions {
+		download_limiter: Some(limiter.clone()),
+		..Default::default()
+	}
+}
+
+#[test]
+fn a_shared_limiter_caps_every_upgrade_together() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let limiter = DownloadLimiter::new(3);
+		upgrade_both(serve_packs(&server), [with_limiter(&limiter), with_limiter(&limiter)], "shared-limiter").await;
+
+		assert_eq!(server.most_concurrent_requests(), 3);
+	});
+}
+
+#[test]
+fn limiters_of_their_own_cap_upgrades_separately() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		upgrade_both(serve_packs(&server), [with_limiter(&DownloadLimiter::new(3)), with_limiter(&DownloadLimiter::new(3))], "own-limiters").await;
+
+		// the server does see more than one limiter allows
+		assert!(server.most_concurrent_requests() > 3, "{}", server.most_concurrent_requests());
+		assert!(server.most_concurrent_requests() <= 6, "{}", server.most_concurrent_requests());
+	});
+}
@@ -0,0 +1,57 @@
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::{Directory, Side};
+
+/// A pack with a mod for both sides, client-only shaders and a server-only plugins folder, with `version` in what changes between versions.
+fn pack(version: &str, minimap: bool) -> Directory {
+	let builder = DirectoryBuilder::new()
+		.file("mods/a.jar", "a")
+		.file("mods/shaders.jar", format!("shaders {version}"))
+		.file("plugins/admin.jar", format!("admin {version}"));
+	let builder = match minimap {
+		true => builder.file("mods/minimap.jar", "minimap"),
+		false => builder
+	};
+
+	let mut directory = builder.build("https://example.com/pack");
+	let mods = directory.children.get_mut("mods").unwrap();
+	mods.files.get_mut("shaders.jar").unwrap().side = Side::Client;
+	if let Some(minimap) = mods.files.get_mut("minimap.jar") {
+		minimap.side = Side::Client;
+	}
+	directory.children.get_mut("plugins").unwrap().side = Side::Server;
+	directory
+}
+
+#[test]
+fn diffs_leave_out_the_other_side() {
+	let (old, new) = (pack("1", false), pack("2", true));
+
+	let server = old.diff_for(&new, &Side::Server);
+	assert!(server.added.is_empty() && server.removed.is_empty(), "{server:?}");
+	assert_eq!(server.changed, ["plugins/admin.jar"]);
+
+	let client = old.diff_for(&new, &Side::Client);
+	assert_eq!(client.added, ["mods/minimap.jar"]);
+	assert_eq!(client.changed, ["mods/shaders.jar"]);
+
+	assert_eq!(old.diff_for(&new, &Side::Both), old.diff(&new));
+	assert_eq!(old.diff(&new).changed, ["mods/shaders.jar", "plugins/admin.jar"]);
+}
+
+#[test]
+fn stats_leave_out_the_other_side() {
+	let directory = pack("1", true);
+
+	let server = directory.stats_for(&Side::Server);
+	assert_eq!(server.total.files, 2);
+	assert_eq!(server.total.size, ("a".len() + "admin 1".len()) as u64);
+	assert_eq!(server.children["mods"].files, 1);
+	assert!(server.largest.iter().all(|file| !file.path.contains("shaders") && !file.path.contains("minimap")), "{:?}", server.largest);
+
+	let client = directory.stats_for(&Side::Client);
+	assert_eq!(client.total.files, 3);
+	assert!(!client.children.contains_key("plugins"));
+
+	assert_eq!(directory.stats_for(&Side::Both), directory.stats());
+	assert_eq!(directory.stats().total.files, 4);
+}
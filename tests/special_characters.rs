@@ -0,0 +1,45 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::FixtureServer;
+use green_lib::{Directory, FromPathOptions};
+
+const FILES: [(&str, &str); 3] = [
+	("screenshots/50% done #1.png", "png"),
+	("config/what?.toml", "what"),
+	("Création/日本 語.txt", "unicode")
+];
+
+#[test]
+fn special_characters_round_trip_through_a_manifest() {
+	block_on(async {
+		let source = TempDir::new("special-characters-source");
+		for (path, contents) in FILES {
+			let path = source.0.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		// the server decodes the paths of requests, and the base is already encoded
+		let server = FixtureServer::start().await.unwrap();
+		for (path, contents) in FILES {
+			server.insert(&format!("my pack/{path}"), contents);
+		}
+		let options = FromPathOptions {
+			base_url: server.url("my%20pack"),
+			..Default::default()
+		};
+		let directory = Directory::from_path(&source.0, &options).await.unwrap();
+		let url = directory.children["screenshots"].files["50% done #1.png"].url.clone().unwrap();
+		assert_eq!(url, server.url("my%20pack/screenshots/50%25%20done%20%231.png"));
+
+		// through the manifest as it's published
+		let directory = Directory::from_slice(directory.to_string_canonical().unwrap().as_bytes()).unwrap();
+		let folder = TempDir::new("special-characters-folder");
+		let report = directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+		for (path, contents) in FILES {
+			assert_eq!(std::fs::read_to_string(folder.0.join(path)).unwrap(), contents, "{path}");
+		}
+	});
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{Directory, DownloadError, RetryReason, UpgradeError, UpgradeOptions, UpgradeStatus};
+use std::time::Duration;
+
+/// A pack with a file that's worth resuming, whose server sends half of it and then nothing for the first `count` requests.
+async fn setup(count: usize) -> (FixtureServer, Directory, Vec<u8>) {
+	let server = FixtureServer::start().await.unwrap();
+	let contents: Vec<u8> = (0..64 * 1024).map(|index| (index % 251) as u8).collect();
+	// the check whether the network is up goes to the first file at the top
+	let directory = server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("mods/big.jar", contents.clone()));
+	server.fail("pack/mods/big.jar", Failure::Stall {
+		after: contents.len() / 2,
+		count
+	});
+	(server, directory, contents)
+}
+
+fn stalling_after(timeout: Duration) -> UpgradeOptions {
+	UpgradeOptions {
+		stall_timeout: Some(timeout),
+		..Default::default()
+	}
+}
+
+#[test]
+fn stalled_downloads_are_retried() {
+	block_on(async {
+		let (server, directory, contents) = setup(1).await;
+		let folder = TempDir::new("stalls-retried");
+		let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+		let received = tokio::spawn(async move {
+			let mut events = vec![];
+			while let Some(event) = rx.recv().await {
+				events.push(event);
+			}
+			events
+		});
+
+		let report = directory.upgrade_game_folder_with_options(&folder.0, &stalling_after(Duration::from_millis(300)), Some(tx)).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("mods/big.jar")).unwrap(), contents);
+		assert_eq!(report.metrics.retries.stalled, 1);
+		assert_eq!(server.requests("pack/mods/big.jar"), 2);
+		let events = received.await.unwrap();
+		assert!(events.iter().any(|event| matches!(event, UpgradeStatus::Retrying { path, reason: RetryReason::Stalled, .. } if path.ends_with("mods/big.jar"))), "{events:#?}");
+	});
+}
+
+#[test]
+fn downloads_that_keep_stalling_fail() {
+	block_on(async {
+		let (_server, directory, _) = setup(usize::MAX).await;
+		let folder = TempDir::new("stalls-failed");
+
+		match directory.upgrade_game_folder_with_options(&folder.0, &stalling_after(Duration::from_millis(100)), None).await {
+			Err(UpgradeError::Download { path, error: DownloadError::Exhausted(RetryReason::Stalled), .. }) => assert!(path.ends_with("mods/big.jar"), "{path:?}"),
+			other => panic!("expected the download to stall every time, got {other:?}")
+		}
+	});
+}
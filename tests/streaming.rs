@@ -0,0 +1,112 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{DownloadError, UpgradeError, UpgradeOptions};
+use std::time::Duration;
+
+/// Bytes that don't repeat in a way a chunk could match somewhere else.
+fn noise(length: usize) -> Vec<u8> {
+	let mut state = 0x2545f4914f6cdd1du64;
+	(0..length).map(|_| {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		state as u8
+	}).collect()
+}
+
+/// Files in `path` that a download left behind.
+fn temp_files(path: &std::path::Path) -> Vec<String> {
+	std::fs::read_dir(path).unwrap()
+		.map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+		.filter(|name| name.ends_with(".tmp"))
+		.collect()
+}
+
+#[test]
+fn large_files_are_streamed_to_disk() {
+	block_on(async {
+		let large = noise(16 << 20);
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new()
+			.file("mods/large.jar", large.clone())
+			.file("config/tiny.toml", "tiny")
+			.inline_file("options.txt", "inline"));
+		let folder = TempDir::new("streamed");
+
+		let report = directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert!(std::fs::read(folder.0.join("mods/large.jar")).unwrap() == large);
+		assert_eq!(std::fs::read(folder.0.join("config/tiny.toml")).unwrap(), b"tiny");
+		assert_eq!(std::fs::read(folder.0.join("options.txt")).unwrap(), b"inline");
+		// buffered in full the peak would be the whole 16 MiB
+		assert!(report.peak_buffered_bytes < 1 << 20, "{} bytes were buffered at once", report.peak_buffered_bytes);
+		assert!(temp_files(&folder.0.join("mods")).is_empty());
+	});
+}
+
+#[test]
+fn stalled_chunked_downloads_continue_after_the_last_verified_chunk() {
+	block_on(async {
+		let contents = noise(1 << 20);
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().chunk_size(64 << 10).file("mods/a.jar", contents.clone()));
+		// the first request is the check whether the network is up
+		server.fail("pack/mods/a.jar", Failure::Stall {
+			after: 300_000,
+			count: 2
+		});
+		let folder = TempDir::new("streamed-resume");
+		let options = UpgradeOptions {
+			stall_timeout: Some(Duration::from_millis(300)),
+			..Default::default()
+		};
+
+		directory.upgrade_game_folder_with_options(&folder.0, &options, None).await.unwrap();
+		assert!(std::fs::read(folder.0.join("mods/a.jar")).unwrap() == contents);
+		assert_eq!(server.requests("pack/mods/a.jar"), 3);
+		let headers = server.request_headers("pack/mods/a.jar").unwrap();
+		// 300000 bytes arrived, the last full chunk of them ends at 4 * 64 KiB
+		assert!(headers.contains(&("range".to_owned(), "bytes=262144-".to_owned())), "{headers:?}");
+		assert!(temp_files(&folder.0.join("mods")).is_empty());
+	});
+}
+
+#[test]
+fn mismatched_bodies_leave_no_temp_files() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", noise(256 << 10)));
+		server.fail("pack/mods/a.jar", Failure::WrongBytes(noise(255 << 10).into()));
+		let folder = TempDir::new("streamed-mismatch");
+
+		match directory.upgrade_game_folder(&folder.0, None).await {
+			Err(UpgradeError::Download { error: DownloadError::ShaMismatch { bytes, .. }, .. }) => assert_eq!(bytes, 255 << 10),
+			other => panic!("expected a sha mismatch, got {other:?}")
+		}
+		assert!(!folder.0.join("mods/a.jar").exists());
+		assert!(temp_files(&folder.0.join("mods")).is_empty());
+	});
+}
+
+#[test]
+fn damaged_chunks_are_repaired_without_downloading_the_rest() {
+	block_on(async {
+		let contents = noise(1 << 20);
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().chunk_size(64 << 10).file("mods/a.jar", contents.clone()));
+		let folder = TempDir::new("streamed-repair");
+		directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+
+		let mut damaged = contents.clone();
+		damaged[70_000] ^= 0xff;
+		std::fs::write(folder.0.join("mods/a.jar"), &damaged).unwrap();
+
+		let report = directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert!(std::fs::read(folder.0.join("mods/a.jar")).unwrap() == contents);
+		let headers = server.request_headers("pack/mods/a.jar").unwrap();
+		assert!(headers.contains(&("range".to_owned(), "bytes=65536-131071".to_owned())), "{headers:?}");
+		assert!(report.peak_buffered_bytes <= 64 << 10);
+		assert!(temp_files(&folder.0.join("mods")).is_empty());
+	});
+}
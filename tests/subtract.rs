@@ -0,0 +1,83 @@
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::{Directory, MergePrecedence};
+use std::collections::BTreeMap;
+
+const PATHS: [&str; 7] = ["options.txt", "mods/a.jar", "mods/b.jar", "config/a.toml", "config/nested/b.toml", "shaderpacks/a.zip", "resourcepacks/a.zip"];
+
+/// A xorshift, so every run checks the same manifests.
+struct Random(u64);
+
+impl Random {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+}
+
+/// Some of the paths with one of two contents each, so two manifests share some files, differ on others and have some of their own.
+fn random_directory(random: &mut Random) -> Directory {
+	PATHS.iter().fold(DirectoryBuilder::new(), |builder, path| match random.next() % 3 {
+		0 => builder,
+		contents => builder.file(path, format!("{path} {contents}"))
+	}).build("https://example.com/pack")
+}
+
+/// Every file by its `/` separated path, with its sha.
+fn files(directory: &Directory) -> BTreeMap<String, String> {
+	fn walk(directory: &Directory, prefix: &str, files: &mut BTreeMap<String, String>) {
+		for (name, file) in &directory.files {
+			files.insert(format!("{prefix}{name}"), file.sha.to_string());
+		}
+		for (name, child) in &directory.children {
+			assert!(!child.files.is_empty() || !child.children.is_empty(), "{prefix}{name} was left empty");
+			walk(child, &format!("{prefix}{name}/"), files);
+		}
+	}
+
+	let mut files = BTreeMap::new();
+	walk(directory, "", &mut files);
+	files
+}
+
+fn pairs() -> impl Iterator<Item = (Directory, Directory)> {
+	let mut random = Random(0x9e3779b97f4a7c15);
+	(0..200).map(move |_| (random_directory(&mut random), random_directory(&mut random)))
+}
+
+#[test]
+fn subtracting_keeps_exactly_the_paths_the_other_doesnt_have() {
+	for (a, b) in pairs() {
+		let (a_files, b_files) = (files(&a), files(&b));
+		let expected: BTreeMap<_, _> = a_files.iter().filter(|(path, _)| !b_files.contains_key(*path)).map(|(path, sha)| (path.clone(), sha.clone())).collect();
+		assert_eq!(files(&a.subtract(&b)), expected);
+
+		let changed: BTreeMap<_, _> = a_files.iter().filter(|(path, sha)| b_files.get(*path) != Some(sha)).map(|(path, sha)| (path.clone(), sha.clone())).collect();
+		assert_eq!(files(&a.subtract_changed(&b)), changed);
+
+		assert!(files(&a.subtract(&a)).is_empty());
+		assert_eq!(a.subtract(&Directory::default()), a);
+	}
+}
+
+#[test]
+fn subtract_merge_and_diff_agree() {
+	for (base, addon) in pairs() {
+		let merged = Directory::merge(&[&base, &addon], MergePrecedence::LaterWins).unwrap();
+		// the add-on only contributes what it doesn't share with the base
+		let contributed = addon.subtract_changed(&base);
+		let from_contributed = Directory::merge(&[&base, &contributed], MergePrecedence::LaterWins).unwrap();
+		assert!(merged.diff(&from_contributed).is_empty(), "{:?}", merged.diff(&from_contributed));
+
+		// what's added on top of the base is what subtracting gives, what's changed is what only subtract_changed keeps
+		let diff = base.diff(&merged);
+		assert!(diff.removed.is_empty(), "{diff:?}");
+		assert_eq!(diff.added, files(&addon.subtract(&base)).into_keys().collect::<Vec<_>>());
+		let only_changed: Vec<_> = files(&contributed).into_keys().filter(|path| !diff.added.contains(path)).collect();
+		assert_eq!(diff.changed, only_changed);
+
+		// and taking the base away again leaves the add-on's own files
+		assert_eq!(files(&merged.subtract(&base)), files(&addon.subtract(&base)));
+	}
+}
@@ -0,0 +1,92 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{Directory, SymlinkPolicy, UpgradeError, UpgradeOptions};
+use std::path::Path;
+
+/// A folder with links pointing at a folder outside of it, two of them where an upgrade deletes things.
+struct Setup {
+	_server: FixtureServer,
+	directory: Directory,
+	folder: TempDir,
+	outside: TempDir
+}
+
+async fn setup(name: &str) -> Setup {
+	let server = FixtureServer::start().await.unwrap();
+	let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("config/sodium.toml", "sodium"));
+	let folder = TempDir::new(&format!("symlinks-{name}"));
+	let outside = TempDir::new(&format!("symlinks-{name}-outside"));
+	std::fs::write(outside.0.join("world.dat"), "world").unwrap();
+
+	std::fs::create_dir_all(folder.0.join("mods")).unwrap();
+	std::fs::create_dir_all(folder.0.join("config/unknown")).unwrap();
+	// an unknown link, a link in an unknown folder and a link in place of a manifest entry
+	std::os::unix::fs::symlink(&outside.0, folder.0.join("mods/saves")).unwrap();
+	std::os::unix::fs::symlink(&outside.0, folder.0.join("config/unknown/saves")).unwrap();
+	std::os::unix::fs::symlink(outside.0.join("world.dat"), folder.0.join("config/sodium.toml")).unwrap();
+	Setup {
+		_server: server,
+		directory,
+		folder,
+		outside
+	}
+}
+
+fn upgrade_with(symlinks: SymlinkPolicy) -> UpgradeOptions {
+	UpgradeOptions {
+		symlinks,
+		..Default::default()
+	}
+}
+
+fn assert_outside_untouched(outside: &Path) {
+	assert_eq!(std::fs::read(outside.join("world.dat")).unwrap(), b"world");
+	assert_eq!(std::fs::read_dir(outside).unwrap().count(), 1);
+}
+
+#[test]
+fn ignored_links_are_left_alone() {
+	block_on(async {
+		let setup = setup("ignore").await;
+		setup.directory.upgrade_game_folder_with_options(&setup.folder.0, &upgrade_with(SymlinkPolicy::Ignore), None).await.unwrap();
+
+		assert!(setup.folder.0.join("mods/saves").symlink_metadata().unwrap().is_symlink());
+		assert!(setup.folder.0.join("config/sodium.toml").symlink_metadata().unwrap().is_symlink());
+		// the unknown folder goes, without following the link in it
+		assert!(!setup.folder.0.join("config/unknown").exists());
+		assert_eq!(std::fs::read(setup.folder.0.join("mods/a.jar")).unwrap(), b"a");
+		assert_outside_untouched(&setup.outside.0);
+	});
+}
+
+#[test]
+fn deleted_links_are_removed_without_following_them() {
+	block_on(async {
+		let setup = setup("delete").await;
+		setup.directory.upgrade_game_folder_with_options(&setup.folder.0, &upgrade_with(SymlinkPolicy::Delete), None).await.unwrap();
+
+		assert!(setup.folder.0.join("mods/saves").symlink_metadata().is_err());
+		assert!(!setup.folder.0.join("config/unknown").exists());
+		let sodium = setup.folder.0.join("config/sodium.toml");
+		assert!(sodium.symlink_metadata().unwrap().is_file());
+		assert_eq!(std::fs::read(sodium).unwrap(), b"sodium");
+		assert_outside_untouched(&setup.outside.0);
+	});
+}
+
+#[test]
+fn links_fail_the_upgrade_with_error() {
+	block_on(async {
+		let setup = setup("error").await;
+		match setup.directory.upgrade_game_folder_with_options(&setup.folder.0, &upgrade_with(SymlinkPolicy::Error), None).await {
+			Err(UpgradeError::Symlink { path }) => assert!(path == Path::new("mods/saves") || path == Path::new("config/sodium.toml"), "{path:?}"),
+			other => panic!("expected UpgradeError::Symlink, got {other:?}")
+		}
+		assert!(setup.folder.0.join("mods/saves").symlink_metadata().unwrap().is_symlink());
+		assert_outside_untouched(&setup.outside.0);
+	});
+}
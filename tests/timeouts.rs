@@ -0,0 +1,61 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::http::HttpConfig;
+use green_lib::test_util::DirectoryBuilder;
+use green_lib::{DownloadError, ManifestError, RetryReason, UpgradeError, UpgradeOptions};
+use std::time::Duration;
+
+/// Accepts connections and never answers them, returning its url.
+async fn silent_server() -> String {
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let url = format!("http://{}/", listener.local_addr().unwrap());
+	tokio::spawn(async move {
+		let mut connections = vec![];
+		while let Ok((stream, _)) = listener.accept().await {
+			connections.push(stream);
+		}
+	});
+	url
+}
+
+fn impatient() -> HttpConfig {
+	HttpConfig {
+		read_timeout: Some(Duration::from_millis(200)),
+		manifest_attempts: 2,
+		..Default::default()
+	}
+}
+
+#[test]
+fn downloads_from_a_silent_server_time_out() {
+	block_on(async {
+		let url = silent_server().await;
+		let directory = DirectoryBuilder::new().file("mods/a.jar", "a").build(&url);
+		let folder = TempDir::new("timeouts-download");
+		let options = UpgradeOptions {
+			http: Some(impatient()),
+			..Default::default()
+		};
+
+		let upgrade = directory.upgrade_game_folder_with_options(&folder.0, &options, None);
+		match tokio::time::timeout(Duration::from_secs(30), upgrade).await.expect("the upgrade hung") {
+			Err(UpgradeError::Download { error: DownloadError::Exhausted(RetryReason::Timeout), attempts, .. }) => assert!(attempts > 1),
+			other => panic!("expected the download to time out, got {other:?}")
+		}
+	});
+}
+
+#[test]
+fn manifests_from_a_silent_server_time_out() {
+	block_on(async {
+		let url = silent_server().await;
+		let list = DirectoryBuilder::new().file("mods/a.jar", "a").packs_list("pack", &url, &format!("{url}manifest.json"));
+		let pack = list.get_featured_pack_metadata().unwrap();
+
+		match tokio::time::timeout(Duration::from_secs(30), pack.to_directory_with(&impatient())).await.expect("the fetch hung") {
+			Err(ManifestError::Exhausted { attempts: 2, error }) => assert!(matches!(*error, ManifestError::Request(ref error) if error.is_timeout()), "{error:?}"),
+			other => panic!("expected the fetch to time out, got {other:?}")
+		}
+	});
+}
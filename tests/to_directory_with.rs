@@ -0,0 +1,56 @@
+mod common;
+
+use common::block_on;
+use green_lib::http::{HttpConfig, ScopedHeaders};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::ManifestError;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+
+fn with_token() -> HttpConfig {
+	let mut headers = HeaderMap::new();
+	headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer private-pack"));
+	HttpConfig {
+		headers: vec![ScopedHeaders::new(headers)],
+		..Default::default()
+	}
+}
+
+#[test]
+fn own_clients_fetch_the_same_manifest_with_their_headers() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = DirectoryBuilder::new().file("mods/a.jar", "a");
+		let directory = server.serve("pack", &builder);
+		let list = builder.packs_list("pack", &server.url("pack"), &server.url("pack/manifest.json"));
+		let pack = list.get_featured_pack_metadata().unwrap();
+
+		assert_eq!(pack.to_directory_with(&with_token()).await.unwrap(), directory);
+		let headers = server.request_headers("pack/manifest.json").unwrap();
+		assert!(headers.contains(&("authorization".to_owned(), "Bearer private-pack".to_owned())), "{headers:?}");
+
+		assert_eq!(pack.try_to_directory().await.unwrap(), directory);
+		let headers = server.request_headers("pack/manifest.json").unwrap();
+		assert!(!headers.iter().any(|(name, _)| name == "authorization"), "{headers:?}");
+	});
+}
+
+#[test]
+fn own_clients_check_the_manifest_sha_the_same_way() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let builder = DirectoryBuilder::new().file("mods/a.jar", "a");
+		server.serve("pack", &builder);
+		let list = builder.packs_list("pack", &server.url("pack"), &server.url("pack/manifest.json"));
+		// the pack changed without the list being updated
+		server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "b"));
+		let pack = list.get_featured_pack_metadata().unwrap();
+
+		let (shared, own) = (pack.try_to_directory().await, pack.to_directory_with(&with_token()).await);
+		match (&shared, &own) {
+			(Err(ManifestError::IntegrityMismatch { expected, found }), Err(ManifestError::IntegrityMismatch { expected: own_expected, found: own_found })) => {
+				assert_eq!((expected, found), (own_expected, own_found));
+			},
+			_ => panic!("expected both to fail the integrity check, got {shared:?} and {own:?}")
+		}
+	});
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::http::{HttpConfig, TokenProvider};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{Directory, DownloadError, RetryCounts, UpgradeError, UpgradeOptions};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Gives `token-1`, `token-2`... and counts how often it was asked.
+fn counting_provider() -> (TokenProvider, Arc<AtomicUsize>) {
+	let asked = Arc::new(AtomicUsize::new(0));
+	let provider = TokenProvider::new({
+		let asked = asked.clone();
+		move || {
+			let token = asked.fetch_add(1, Ordering::SeqCst) + 1;
+			async move { Ok(format!("token-{token}")) }
+		}
+	});
+	(provider, asked)
+}
+
+fn with_token(token: TokenProvider) -> UpgradeOptions {
+	UpgradeOptions {
+		http: Some(HttpConfig {
+			token: Some(token),
+			..Default::default()
+		}),
+		..Default::default()
+	}
+}
+
+fn authorization(server: &FixtureServer, path: &str) -> Option<String> {
+	server.request_headers(path).unwrap().into_iter().find(|(name, _)| name == "authorization").map(|(_, value)| value)
+}
+
+fn serve(server: &FixtureServer) -> Directory {
+	server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("mods/a.jar", "a").file("mods/b.jar", "b").file("mods/expiring.jar", "expiring"))
+}
+
+#[test]
+fn expired_tokens_are_refreshed_mid_upgrade() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = serve(&server);
+		// the token expires by the time this one is requested
+		server.fail("pack/mods/expiring.jar", Failure::Status {
+			status: 401,
+			count: 1
+		});
+		let (provider, asked) = counting_provider();
+
+		let folder = TempDir::new("token-refreshed");
+		let report = directory.upgrade_game_folder_with_options(&folder.0, &with_token(provider), None).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("mods/expiring.jar")).unwrap(), b"expiring");
+		assert_eq!(asked.load(Ordering::SeqCst), 2);
+		assert_eq!(authorization(&server, "pack/mods/expiring.jar").as_deref(), Some("Bearer token-2"));
+		assert_eq!(server.requests("pack/mods/expiring.jar"), 2);
+		for path in ["pack/mods/a.jar", "pack/mods/b.jar"] {
+			assert!(authorization(&server, path).is_some_and(|token| token.starts_with("Bearer token-")), "{path}");
+		}
+		// without it counting as a retry
+		assert_eq!(report.metrics.retries, RetryCounts::default());
+	});
+}
+
+#[test]
+fn refused_fresh_tokens_fail_the_download() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = serve(&server);
+		server.fail("pack/mods/expiring.jar", Failure::Status {
+			status: 401,
+			count: usize::MAX
+		});
+		let (provider, asked) = counting_provider();
+
+		let folder = TempDir::new("token-refused");
+		let error = directory.upgrade_game_folder_with_options(&folder.0, &with_token(provider), None).await.unwrap_err();
+		match &error {
+			UpgradeError::Download { path, error: DownloadError::Status(401), .. } => assert!(path.ends_with("mods/expiring.jar"), "{path:?}"),
+			other => panic!("expected a 401 for mods/expiring.jar, got {other:?}")
+		}
+		// asked again once, not for every attempt
+		assert_eq!(asked.load(Ordering::SeqCst), 2);
+		for shown in [error.to_string(), format!("{error:?}")] {
+			assert!(!shown.contains("token-"), "{shown}");
+		}
+	});
+}
+
+#[test]
+fn providers_that_fail_fail_the_download() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = serve(&server);
+		let provider = TokenProvider::new(|| async { Err("the refresh token was revoked".into()) });
+
+		let folder = TempDir::new("token-failed");
+		match directory.upgrade_game_folder_with_options(&folder.0, &with_token(provider), None).await {
+			Err(UpgradeError::Download { error: DownloadError::Token(reason), .. }) => assert_eq!(reason, "the refresh token was revoked"),
+			other => panic!("expected DownloadError::Token, got {other:?}")
+		}
+	});
+}
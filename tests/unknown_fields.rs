@@ -0,0 +1,69 @@
+use green_lib::packs::PacksListManifest;
+use green_lib::{Directory, ManifestError};
+use serde_json::{json, Value};
+
+const SHA: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+
+/// A manifest from a newer version or another tool, with fields green-lib doesn't know at every level.
+fn manifest() -> Value {
+	json!({
+		"x-generator": {"name": "packer", "version": [1, 2]},
+		"files": {
+			"options.txt": {"sha": SHA, "url": "https://example.com/options.txt", "x-note": "keep me"}
+		},
+		"children": {
+			"mods": {
+				"x-loader": "fabric",
+				"files": {
+					"a.jar": {"sha": SHA, "url": "https://example.com/a.jar", "x-modrinth": {"project": "AANobbMI", "tags": ["optimization", null]}}
+				},
+				"children": {}
+			}
+		}
+	})
+}
+
+#[test]
+fn unknown_manifest_fields_survive_a_round_trip() {
+	let mut directory = Directory::from_slice(manifest().to_string().as_bytes()).unwrap();
+	directory.children.get_mut("mods").unwrap().files.get_mut("a.jar").unwrap().url = Some("https://mirror.example.com/a.jar".to_owned());
+
+	let written: Value = serde_json::from_str(&directory.to_string_canonical().unwrap()).unwrap();
+	let original = manifest();
+	for pointer in ["/x-generator", "/files/options.txt/x-note", "/children/mods/x-loader", "/children/mods/files/a.jar/x-modrinth"] {
+		assert_eq!(written.pointer(pointer), original.pointer(pointer), "{pointer}");
+	}
+	assert_eq!(written.pointer("/children/mods/files/a.jar/url"), Some(&json!("https://mirror.example.com/a.jar")));
+
+	// and once more, they're not only kept the first time
+	assert_eq!(Directory::from_slice(written.to_string().as_bytes()).unwrap(), directory);
+}
+
+#[test]
+fn strict_parsing_rejects_unknown_manifest_fields() {
+	match Directory::from_slice_strict(manifest().to_string().as_bytes()) {
+		Err(ManifestError::UnknownField { key, .. }) => assert!(key.starts_with("x-"), "{key}"),
+		other => panic!("expected ManifestError::UnknownField, got {other:?}")
+	}
+}
+
+#[test]
+fn unknown_packs_list_fields_survive_a_round_trip() {
+	let original = json!({
+		"x-publisher": "example",
+		"packs": {
+			"pack": {
+				"display_name": "Pack",
+				"manifest_url": "https://example.com/manifest.json",
+				"manifest_sha": SHA,
+				"x-discord": {"invite": "abc"}
+			}
+		}
+	});
+	let list = PacksListManifest::from_slice(original.to_string().as_bytes()).unwrap();
+
+	let written: Value = serde_json::from_str(&list.to_string_canonical().unwrap()).unwrap();
+	for pointer in ["/x-publisher", "/packs/pack/x-discord"] {
+		assert_eq!(written.pointer(pointer), original.pointer(pointer), "{pointer}");
+	}
+}
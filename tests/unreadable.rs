@@ -0,0 +1,67 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, FixtureServer};
+use green_lib::{UpgradeError, UpgradeOptions};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Makes `file` impossible to delete until it's dropped: its folder is made read only, which root ignores, so for root the file is made immutable instead.
+struct Undeletable(PathBuf);
+
+impl Undeletable {
+	fn new(file: &Path) -> Option<Self> {
+		// SAFETY: geteuid has no preconditions
+		match unsafe { libc::geteuid() } {
+			0 => {
+				Command::new("chattr").arg("+i").arg(file).status().ok().filter(|status| status.success())?;
+			},
+			_ => std::fs::set_permissions(file.parent().unwrap(), std::fs::Permissions::from_mode(0o555)).unwrap()
+		}
+		Some(Self(file.to_owned()))
+	}
+}
+
+impl Drop for Undeletable {
+	fn drop(&mut self) {
+		let _ = Command::new("chattr").arg("-i").arg(&self.0).status();
+		let _ = std::fs::set_permissions(self.0.parent().unwrap(), std::fs::Permissions::from_mode(0o755));
+	}
+}
+
+#[test]
+fn entries_that_cant_be_deleted_are_warnings() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let directory = server.serve("pack", &DirectoryBuilder::new().file("mods/a.jar", "a").file("config/b.toml", "b"));
+		let folder = TempDir::new("unreadable");
+		std::fs::create_dir_all(folder.0.join("config")).unwrap();
+		std::fs::write(folder.0.join("config/b.toml"), "b").unwrap();
+		std::fs::write(folder.0.join("config/extra.toml"), "extra").unwrap();
+		let Some(_undeletable) = Undeletable::new(&folder.0.join("config/extra.toml")) else {
+			eprintln!("skipped, chattr isn't available to make a file root can't delete");
+			return;
+		};
+
+		let strict = UpgradeOptions {
+			strict: true,
+			..Default::default()
+		};
+		match directory.upgrade_game_folder_with_options(&folder.0, &strict, None).await {
+			Err(UpgradeError::Local { path, error }) => {
+				assert_eq!(path, Path::new("config/extra.toml"));
+				assert!(matches!(error.kind(), std::io::ErrorKind::PermissionDenied), "{error:?}");
+			},
+			other => panic!("expected UpgradeError::Local, got {other:?}")
+		}
+
+		let report = directory.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+		assert_eq!(report.warnings[0].path, Path::new("config/extra.toml"));
+		assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+		assert!(folder.0.join("config/extra.toml").exists());
+	});
+}
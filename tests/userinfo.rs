@@ -0,0 +1,80 @@
+mod common;
+
+use common::{block_on, TempDir};
+use green_lib::test_util::{DirectoryBuilder, Failure, FixtureServer};
+use green_lib::{Directory, UpgradeError};
+
+/// `user:p@ss word` signs in, `%40` and `%20` are how a url spells it.
+const BASIC: &str = "Basic dXNlcjpwQHNzIHdvcmQ=";
+
+fn with_userinfo(url: &str) -> String {
+	url.replacen("http://", "http://user:p%40ss%20word@", 1)
+}
+
+fn authorization(server: &FixtureServer, path: &str) -> Option<String> {
+	server.request_headers(path).unwrap().into_iter().find(|(name, _)| name == "authorization").map(|(_, value)| value)
+}
+
+#[test]
+fn userinfo_becomes_a_basic_authorization_header() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let mut directory = server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("mods/a.jar", "a"));
+		let a = directory.children.get_mut("mods").unwrap().files.get_mut("a.jar").unwrap();
+		a.url = Some(with_userinfo(a.url.as_deref().unwrap()));
+		server.insert("pack/manifest.json", directory.to_string_canonical().unwrap());
+
+		let fetched = Directory::try_from_url(with_userinfo(&server.url("pack/manifest.json"))).await.unwrap();
+		assert_eq!(fetched, directory);
+		assert_eq!(authorization(&server, "pack/manifest.json").as_deref(), Some(BASIC));
+
+		let folder = TempDir::new("userinfo-header");
+		fetched.upgrade_game_folder(&folder.0, None).await.unwrap();
+		assert_eq!(std::fs::read(folder.0.join("mods/a.jar")).unwrap(), b"a");
+		assert_eq!(authorization(&server, "pack/mods/a.jar").as_deref(), Some(BASIC));
+		// only for the urls that have it
+		assert_eq!(authorization(&server, "pack/options.txt"), None);
+	});
+}
+
+#[test]
+fn userinfo_is_left_out_of_errors_and_events() {
+	block_on(async {
+		let server = FixtureServer::start().await.unwrap();
+		let mut directory = server.serve("pack", &DirectoryBuilder::new().file("options.txt", "options").file("mods/a.jar", "a"));
+		let a = directory.children.get_mut("mods").unwrap().files.get_mut("a.jar").unwrap();
+		a.url = Some(with_userinfo(a.url.as_deref().unwrap()));
+		server.fail("pack/mods/a.jar", Failure::Status {
+			status: 404,
+			count: usize::MAX
+		});
+
+		let folder = TempDir::new("userinfo-redacted");
+		let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+		let received = tokio::spawn(async move {
+			let mut events = vec![];
+			while let Some(event) = rx.recv().await {
+				events.push(format!("{event:?}"));
+			}
+			events
+		});
+		let error = directory.upgrade_game_folder(&folder.0, Some(tx)).await.unwrap_err();
+		let UpgradeError::Download { url, .. } = &error else {
+			panic!("expected UpgradeError::Download, got {error:?}");
+		};
+		assert_eq!(url.as_deref(), Some(server.url("pack/mods/a.jar").as_str()));
+		let shown = error.to_string();
+		assert!(shown.contains(&server.url("pack/mods/a.jar")), "{shown}");
+		for shown in [shown, format!("{error:?}")].into_iter().chain(received.await.unwrap()) {
+			assert!(!shown.contains("p%40ss") && !shown.contains("user:"), "{shown}");
+		}
+
+		// nothing listens on the port of a server that's gone
+		let gone = with_userinfo(&server.url("pack/manifest.json"));
+		drop(server);
+		let error = Directory::try_from_url(&gone).await.unwrap_err();
+		for shown in [error.to_string(), format!("{error:?}")] {
+			assert!(!shown.contains("p%40ss") && !shown.contains("user:"), "{shown}");
+		}
+	});
+}